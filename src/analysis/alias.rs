@@ -0,0 +1,129 @@
+//! Conservative alias analysis of memory addresses.
+//!
+//! An address is traced back through `getelemptr`/`getptr` to the object it
+//! points into: a local `alloc`, a global, or the array a pointer parameter
+//! was passed (loaded from the parameter's slot). Addresses into different
+//! objects never overlap, except that a pointer parameter may point into any
+//! global or into another parameter's array. Within one object, two paths of
+//! the same shape whose constant indices differ somewhere are disjoint, as
+//! SysY array accesses stay within their bounds.
+//!
+//! A call may write to globals and to whatever the pointers it is passed
+//! reach, which includes the locals whose address is passed to some call.
+
+use koopa::ir::{FunctionData, TypeKind, Value, ValueKind};
+
+/// The object an address points into
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Base {
+    Local(Value),
+    Global(Value),
+    /// The array a pointer parameter points to, by the loaded pointer
+    Pointer(Value),
+    Unknown,
+}
+
+/// One address computation on the way from the object to the address, with
+/// its index if it is a constant
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Step {
+    GetPtr(Option<i32>),
+    GetElemPtr(Option<i32>),
+}
+
+/// The object `addr` points into and the steps leading there from its start
+fn trace(func: &FunctionData, mut addr: Value) -> (Base, Vec<Step>) {
+    let constant = |index: Value| match func.dfg().value(index).kind() {
+        ValueKind::Integer(int) => Some(int.value()),
+        _ => None,
+    };
+    let mut path = Vec::new();
+    let base = loop {
+        if addr.is_global() {
+            break Base::Global(addr);
+        }
+        match func.dfg().value(addr).kind() {
+            ValueKind::GetElemPtr(get_elem_ptr) => {
+                path.push(Step::GetElemPtr(constant(get_elem_ptr.index())));
+                addr = get_elem_ptr.src();
+            }
+            ValueKind::GetPtr(get_ptr) => {
+                path.push(Step::GetPtr(constant(get_ptr.index())));
+                addr = get_ptr.src();
+            }
+            ValueKind::Alloc(_) => break Base::Local(addr),
+            ValueKind::Load(load) if is_param_slot(func, load.src()) => break Base::Pointer(addr),
+            ValueKind::FuncArgRef(_) => break Base::Pointer(addr),
+            _ => break Base::Unknown,
+        }
+    };
+    path.reverse();
+    (base, path)
+}
+
+/// Whether `slot` is a local holding a pointer, which only parameter slots do
+fn is_param_slot(func: &FunctionData, slot: Value) -> bool {
+    if slot.is_global() {
+        return false;
+    }
+    let data = func.dfg().value(slot);
+    let TypeKind::Pointer(ty) = data.ty().kind() else {
+        return false;
+    };
+    matches!(data.kind(), ValueKind::Alloc(_)) && matches!(ty.kind(), TypeKind::Pointer(_))
+}
+
+/// Whether the addresses `a` and `b` may refer to overlapping memory
+pub fn may_alias(func: &FunctionData, a: Value, b: Value) -> bool {
+    if a == b {
+        return true;
+    }
+    let ((a_base, a_path), (b_base, b_path)) = (trace(func, a), trace(func, b));
+    let same_object = match (a_base, b_base) {
+        (Base::Unknown, _) | (_, Base::Unknown) => return true,
+        // A pointer parameter may point into a global or into the same array
+        // as another one
+        (Base::Pointer(_), Base::Pointer(_) | Base::Global(_)) | (Base::Global(_), Base::Pointer(_))
+            if a_base != b_base =>
+        {
+            return true;
+        }
+        // and never into the frame of its own call
+        _ => a_base == b_base,
+    };
+    if !same_object {
+        return false;
+    }
+    let same_shape = a_path.len() == b_path.len()
+        && a_path.iter().zip(&b_path).all(|(x, y)| std::mem::discriminant(x) == std::mem::discriminant(y));
+    let differs = a_path.iter().zip(&b_path).any(|pair| {
+        matches!(pair, (Step::GetPtr(Some(x)), Step::GetPtr(Some(y)))
+            | (Step::GetElemPtr(Some(x)), Step::GetElemPtr(Some(y))) if x != y)
+    });
+    !(same_shape && differs)
+}
+
+/// Whether a call may write to the memory at `addr`
+pub fn call_may_write(func: &FunctionData, addr: Value) -> bool {
+    match trace(func, addr).0 {
+        Base::Local(alloc) => escapes(func, alloc),
+        _ => true,
+    }
+}
+
+/// Whether the address of the local `alloc`, or of a part of it, is passed to
+/// a call or stored anywhere
+fn escapes(func: &FunctionData, alloc: Value) -> bool {
+    let mut work = vec![alloc];
+    while let Some(addr) = work.pop() {
+        for &user in func.dfg().value(addr).used_by() {
+            match func.dfg().value(user).kind() {
+                ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_) => work.push(user),
+                ValueKind::Call(_) => return true,
+                ValueKind::Store(store) if store.value() == addr => return true,
+                _ => {}
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,145 @@
+//! The control flow graph of a function.
+//!
+//! Edges are read off the terminator of each block once, so passes can ask
+//! for the predecessors of a block or walk the blocks in reverse postorder
+//! without inspecting terminators themselves. The graph is a snapshot: a pass
+//! that adds, removes or retargets a terminator must build it again.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::{BasicBlock, FunctionData};
+
+/// Which part of a terminator an edge comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// The target of a `jump`
+    Jump,
+    /// The target of a `br` taken when the condition is nonzero
+    True,
+    /// The target of a `br` taken when the condition is zero
+    False,
+}
+
+pub struct Cfg {
+    entry: Option<BasicBlock>,
+    /// All blocks, in layout order
+    blocks: Vec<BasicBlock>,
+    /// Successors of each block, in the order its terminator names them
+    succs: HashMap<BasicBlock, Vec<BasicBlock>>,
+    /// Predecessors of each block, reachable or not, once per edge
+    preds: HashMap<BasicBlock, Vec<BasicBlock>>,
+    /// Blocks reachable from the entry, in reverse postorder
+    order: Vec<BasicBlock>,
+    reachable: HashSet<BasicBlock>,
+    /// Edges `(from, to)` to a block still on the depth-first search stack,
+    /// which close a cycle
+    back_edges: HashSet<(BasicBlock, BasicBlock)>,
+}
+
+impl Cfg {
+    pub fn new(func: &FunctionData) -> Self {
+        let blocks: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+        let succs: HashMap<BasicBlock, Vec<BasicBlock>> = func
+            .layout()
+            .bbs()
+            .iter()
+            .map(|(&bb, node)| {
+                let succs = match node.insts().back_key() {
+                    Some(&last) => func.dfg().value(last).kind().bb_uses().collect(),
+                    None => Vec::new(),
+                };
+                (bb, succs)
+            })
+            .collect();
+        let mut preds: HashMap<BasicBlock, Vec<BasicBlock>> = blocks.iter().map(|&bb| (bb, Vec::new())).collect();
+        for &bb in &blocks {
+            for &succ in &succs[&bb] {
+                preds.get_mut(&succ).unwrap().push(bb);
+            }
+        }
+
+        // Depth-first search from the entry, visiting successors in order
+        let entry = func.layout().entry_bb();
+        let mut postorder = Vec::new();
+        let mut back_edges = HashSet::new();
+        if let Some(entry) = entry {
+            let mut visited = HashSet::from([entry]);
+            let mut on_stack = HashSet::from([entry]);
+            let mut stack = vec![(entry, 0)];
+            while let Some((bb, i)) = stack.last_mut() {
+                let bb = *bb;
+                match succs[&bb].get(*i) {
+                    Some(&succ) => {
+                        *i += 1;
+                        if on_stack.contains(&succ) {
+                            back_edges.insert((bb, succ));
+                        } else if visited.insert(succ) {
+                            on_stack.insert(succ);
+                            stack.push((succ, 0));
+                        }
+                    }
+                    None => {
+                        on_stack.remove(&bb);
+                        postorder.push(bb);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        postorder.reverse();
+        let reachable = postorder.iter().copied().collect();
+        Self { entry, blocks, succs, preds, order: postorder, reachable, back_edges }
+    }
+
+    /// The entry block, `None` for a declaration
+    pub fn entry(&self) -> Option<BasicBlock> {
+        self.entry
+    }
+
+    /// All blocks, in layout order
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// Successors of `bb`, the true target of a branch first
+    pub fn succs(&self, bb: BasicBlock) -> &[BasicBlock] {
+        &self.succs[&bb]
+    }
+
+    /// Predecessors of `bb`, including unreachable ones; a block branching to
+    /// `bb` on both sides appears twice
+    pub fn preds(&self, bb: BasicBlock) -> &[BasicBlock] {
+        &self.preds[&bb]
+    }
+
+    /// The outgoing edges of `bb`, with the part of the terminator each one
+    /// comes from
+    pub fn edges(&self, bb: BasicBlock) -> impl Iterator<Item = (BasicBlock, EdgeKind)> + '_ {
+        let kinds: &[EdgeKind] = match self.succs[&bb].len() {
+            1 => &[EdgeKind::Jump],
+            _ => &[EdgeKind::True, EdgeKind::False],
+        };
+        self.succs[&bb].iter().copied().zip(kinds.iter().copied())
+    }
+
+    /// Blocks reachable from the entry, in reverse postorder: each block comes
+    /// before its successors, except along back edges
+    pub fn reverse_postorder(&self) -> &[BasicBlock] {
+        &self.order
+    }
+
+    pub fn is_reachable(&self, bb: BasicBlock) -> bool {
+        self.reachable.contains(&bb)
+    }
+
+    /// Whether the edge from `from` to `to` closes a cycle, going back to a
+    /// block the search from the entry was still inside of. In a loop this is
+    /// the edge from the latch to the header.
+    pub fn is_back_edge(&self, from: BasicBlock, to: BasicBlock) -> bool {
+        self.back_edges.contains(&(from, to))
+    }
+
+    pub fn back_edges(&self) -> impl Iterator<Item = (BasicBlock, BasicBlock)> + '_ {
+        self.back_edges.iter().copied()
+    }
+}
@@ -0,0 +1,156 @@
+//! Dominator tree and dominance frontiers of a function's control flow graph.
+//!
+//! Block `a` dominates block `b` when every path from the entry to `b` goes
+//! through `a`. The immediate dominators are found with the iterative
+//! algorithm of Cooper, Harvey and Kennedy ("A Simple, Fast Dominance
+//! Algorithm"), which refines them over the blocks in reverse postorder until
+//! nothing changes. Blocks unreachable from the entry are left out: they have
+//! no dominators, dominate nothing and have no frontier.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::BasicBlock;
+
+use super::Cfg;
+
+pub struct DomTree {
+    entry: BasicBlock,
+    /// Immediate dominator of each reachable block except the entry
+    idom: HashMap<BasicBlock, BasicBlock>,
+    children: HashMap<BasicBlock, Vec<BasicBlock>>,
+    frontiers: HashMap<BasicBlock, HashSet<BasicBlock>>,
+    /// Preorder and postorder numbers in the tree, for dominance queries
+    numbers: HashMap<BasicBlock, (usize, usize)>,
+}
+
+impl DomTree {
+    /// Compute the dominator tree of the graph `cfg`, or `None` for a
+    /// declaration
+    pub fn new(cfg: &Cfg) -> Option<Self> {
+        let entry = cfg.entry()?;
+        let order = cfg.reverse_postorder();
+        let rank: HashMap<BasicBlock, usize> = order.iter().enumerate().map(|(i, &bb)| (bb, i)).collect();
+
+        // The entry is its own dominator while iterating
+        let mut idom = HashMap::from([(entry, entry)]);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &bb in &order[1..] {
+                let mut new_idom = None;
+                for &pred in cfg.preds(bb) {
+                    if !idom.contains_key(&pred) {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => pred,
+                        Some(other) => intersect(&idom, &rank, pred, other),
+                    });
+                }
+                let new_idom = new_idom.expect("reachable block without a processed predecessor");
+                if idom.insert(bb, new_idom) != Some(new_idom) {
+                    changed = true;
+                }
+            }
+        }
+
+        let mut children: HashMap<BasicBlock, Vec<BasicBlock>> = HashMap::new();
+        for &bb in &order[1..] {
+            children.entry(idom[&bb]).or_default().push(bb);
+        }
+        let mut frontiers: HashMap<BasicBlock, HashSet<BasicBlock>> = HashMap::new();
+        for &bb in order {
+            let preds: Vec<BasicBlock> =
+                cfg.preds(bb).iter().copied().filter(|&pred| cfg.is_reachable(pred)).collect();
+            if preds.len() < 2 {
+                continue;
+            }
+            for mut runner in preds {
+                while runner != idom[&bb] {
+                    frontiers.entry(runner).or_default().insert(bb);
+                    runner = idom[&runner];
+                }
+            }
+        }
+        idom.remove(&entry);
+
+        let mut numbers = HashMap::new();
+        let (mut pre, mut post) = (0, 0);
+        let mut stack = vec![(entry, 0)];
+        while let Some((bb, next_child)) = stack.pop() {
+            if next_child == 0 {
+                numbers.insert(bb, (pre, 0));
+                pre += 1;
+            }
+            match children.get(&bb).and_then(|children| children.get(next_child)) {
+                Some(&child) => {
+                    stack.push((bb, next_child + 1));
+                    stack.push((child, 0));
+                }
+                None => {
+                    numbers.get_mut(&bb).unwrap().1 = post;
+                    post += 1;
+                }
+            }
+        }
+
+        Some(Self { entry, idom, children, frontiers, numbers })
+    }
+
+    pub fn entry(&self) -> BasicBlock {
+        self.entry
+    }
+
+    pub fn is_reachable(&self, bb: BasicBlock) -> bool {
+        self.numbers.contains_key(&bb)
+    }
+
+    /// Immediate dominator of `bb`, `None` for the entry and unreachable blocks
+    pub fn idom(&self, bb: BasicBlock) -> Option<BasicBlock> {
+        self.idom.get(&bb).copied()
+    }
+
+    /// Blocks immediately dominated by `bb`
+    pub fn children(&self, bb: BasicBlock) -> &[BasicBlock] {
+        self.children.get(&bb).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether every path from the entry to `b` goes through `a` (so each
+    /// reachable block dominates itself)
+    pub fn dominates(&self, a: BasicBlock, b: BasicBlock) -> bool {
+        match (self.numbers.get(&a), self.numbers.get(&b)) {
+            (Some(&(a_pre, a_post)), Some(&(b_pre, b_post))) => a_pre <= b_pre && b_post <= a_post,
+            _ => false,
+        }
+    }
+
+    pub fn strictly_dominates(&self, a: BasicBlock, b: BasicBlock) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// Dominance frontier of `bb`: the blocks where its dominance ends, which
+    /// have a predecessor dominated by `bb` without being strictly dominated
+    /// by it themselves
+    pub fn frontier(&self, bb: BasicBlock) -> impl Iterator<Item = BasicBlock> + '_ {
+        self.frontiers.get(&bb).into_iter().flatten().copied()
+    }
+}
+
+/// Common dominator of `a` and `b`, walking up from whichever comes later in
+/// reverse postorder
+fn intersect(
+    idom: &HashMap<BasicBlock, BasicBlock>,
+    rank: &HashMap<BasicBlock, usize>,
+    mut a: BasicBlock,
+    mut b: BasicBlock,
+) -> BasicBlock {
+    while a != b {
+        while rank[&a] > rank[&b] {
+            a = idom[&a];
+        }
+        while rank[&b] > rank[&a] {
+            b = idom[&b];
+        }
+    }
+    a
+}
@@ -0,0 +1,134 @@
+//! Induction variables of simple loops in SSA form.
+//!
+//! A simple loop has a header entered by exactly two `jump`s: one from a
+//! preheader outside the loop, and one from a latch inside it, closing the
+//! only back edge. This is the shape `mem2reg` leaves rotated loops in, with
+//! the values carried around the loop as parameters of the header.
+//!
+//! A basic induction variable is a header parameter that the latch passes
+//! back as itself plus or minus a constant: it starts at whatever the
+//! preheader passes, and steps by that constant every iteration.
+//!
+//! ```text
+//!   %pre:   jump %loop(0)
+//!   %loop(%i: i32):
+//!           ...
+//!   %latch: %next = add %i, 1
+//!           jump %loop(%next)
+//! ```
+
+use std::collections::HashSet;
+
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Type, Value, ValueKind};
+
+use super::{Cfg, DomTree};
+
+/// A simple loop (see the module documentation)
+pub struct Loop {
+    pub header: BasicBlock,
+    pub preheader: BasicBlock,
+    pub latch: BasicBlock,
+    /// The blocks of the loop, header included
+    pub blocks: HashSet<BasicBlock>,
+}
+
+/// A header parameter stepping by a constant every iteration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InductionVar {
+    pub param: Value,
+    /// Position of the parameter in the header's parameter list
+    pub index: usize,
+    /// The value it starts at, passed by the preheader
+    pub init: Value,
+    /// The value the latch passes back, `param + step`
+    pub update: Value,
+    /// What it changes by every iteration; may wrap around
+    pub step: i32,
+}
+
+/// The simple loops of `func`, inner loops before the loops containing them
+pub fn simple_loops(func: &FunctionData, cfg: &Cfg, dom_tree: &DomTree) -> Vec<Loop> {
+    let mut loops: Vec<Loop> = cfg
+        .blocks()
+        .iter()
+        .filter_map(|&header| {
+            let [a, b] = cfg.preds(header)[..] else {
+                return None;
+            };
+            let (preheader, latch) = match (dom_tree.dominates(header, a), dom_tree.dominates(header, b)) {
+                (false, true) => (a, b),
+                (true, false) => (b, a),
+                _ => return None,
+            };
+            let plain_jump = |bb: BasicBlock| {
+                let node = func.layout().bbs().node(&bb).unwrap();
+                node.insts().back_key().is_some_and(|&last| matches!(func.dfg().value(last).kind(), ValueKind::Jump(_)))
+            };
+            if latch == header || !dom_tree.is_reachable(preheader) || !plain_jump(preheader) || !plain_jump(latch) {
+                return None;
+            }
+            Some(Loop { header, preheader, latch, blocks: loop_blocks(cfg, header, latch) })
+        })
+        .collect();
+    loops.sort_by_key(|found| found.blocks.len());
+    loops
+}
+
+/// The blocks that reach `latch` without going through `header`, and the
+/// header itself
+fn loop_blocks(cfg: &Cfg, header: BasicBlock, latch: BasicBlock) -> HashSet<BasicBlock> {
+    let mut blocks = HashSet::from([header, latch]);
+    let mut work = vec![latch];
+    while let Some(bb) = work.pop() {
+        for &pred in cfg.preds(bb) {
+            if cfg.is_reachable(pred) && blocks.insert(pred) {
+                work.push(pred);
+            }
+        }
+    }
+    blocks
+}
+
+/// The arguments of the `jump` ending `bb`
+pub fn jump_args(func: &FunctionData, bb: BasicBlock) -> Vec<Value> {
+    let &last = func.layout().bbs().node(&bb).unwrap().insts().back_key().unwrap();
+    match func.dfg().value(last).kind() {
+        ValueKind::Jump(jump) => jump.args().to_vec(),
+        kind => unreachable!("simple loop edge from a block ending in {:?}", kind),
+    }
+}
+
+/// The constant `value` is, if it is one
+pub fn integer(func: &FunctionData, value: Value) -> Option<i32> {
+    match func.dfg().value(value).kind() {
+        ValueKind::Integer(int) => Some(int.value()),
+        _ => None,
+    }
+}
+
+/// The basic induction variables of `found`, in parameter order
+pub fn induction_vars(func: &FunctionData, found: &Loop) -> Vec<InductionVar> {
+    let params = func.dfg().bb(found.header).params();
+    let (inits, updates) = (jump_args(func, found.preheader), jump_args(func, found.latch));
+    let mut vars = Vec::new();
+    for (index, &param) in params.iter().enumerate() {
+        if func.dfg().value(param).ty() != &Type::get_i32() {
+            continue;
+        }
+        let update = updates[index];
+        let ValueKind::Binary(binary) = func.dfg().value(update).kind() else {
+            continue;
+        };
+        let step = match (binary.op(), binary.lhs(), binary.rhs()) {
+            (BinaryOp::Add, lhs, rhs) if lhs == param => integer(func, rhs),
+            (BinaryOp::Add, lhs, rhs) if rhs == param => integer(func, lhs),
+            (BinaryOp::Sub, lhs, rhs) if lhs == param => integer(func, rhs).map(i32::wrapping_neg),
+            _ => None,
+        };
+        let Some(step) = step else {
+            continue;
+        };
+        vars.push(InductionVar { param, index, init: inits[index], update, step });
+    }
+    vars
+}
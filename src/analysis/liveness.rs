@@ -0,0 +1,134 @@
+//! Liveness of values within a function.
+//!
+//! A value is live at a point if some path from there reaches a use of it
+//! without passing its definition. Block parameters are defined on entry to
+//! their block, and the arguments a terminator passes are uses at the end of
+//! its block.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::{BasicBlock, FunctionData, Value};
+
+use super::Cfg;
+
+/// The values live at the start and at the end of each basic block
+pub struct Liveness {
+    pub live_in: HashMap<BasicBlock, HashSet<Value>>,
+    pub live_out: HashMap<BasicBlock, HashSet<Value>>,
+    /// The tracked values defined or used in the function
+    tracked: HashSet<Value>,
+}
+
+/// The positions a value is live over, in the numbering of the instructions
+/// in layout order; both ends are inclusive. Holes, such as the blocks placed
+/// in the middle of a loop that the value is not live in, are not recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Interval {
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    fn cover(&mut self, pos: usize) {
+        self.start = self.start.min(pos);
+        self.end = self.end.max(pos);
+    }
+}
+
+impl Liveness {
+    /// Compute which of the values selected by `tracked` are live at the start
+    /// and end of each block, by iterating the usual backward dataflow
+    /// equations to a fixed point
+    pub fn analyze(func: &FunctionData, tracked: impl Fn(Value) -> bool) -> Self {
+        let cfg = Cfg::new(func);
+        let bbs = cfg.blocks();
+
+        // Upward-exposed uses and definitions of each block
+        let mut uses = HashMap::new();
+        let mut defs = HashMap::new();
+        let mut tracked_values = HashSet::new();
+        for (&bb, node) in func.layout().bbs() {
+            let mut bb_uses = HashSet::new();
+            // Block parameters are defined on entry to the block
+            let mut bb_defs: HashSet<Value> =
+                func.dfg().bb(bb).params().iter().copied().filter(|&param| tracked(param)).collect();
+            for &inst in node.insts().keys() {
+                for used in func.dfg().value(inst).kind().value_uses() {
+                    if tracked(used) && !bb_defs.contains(&used) {
+                        bb_uses.insert(used);
+                    }
+                }
+                if tracked(inst) {
+                    bb_defs.insert(inst);
+                }
+            }
+            tracked_values.extend(bb_uses.iter().chain(&bb_defs).copied());
+            uses.insert(bb, bb_uses);
+            defs.insert(bb, bb_defs);
+        }
+
+        let mut live_in: HashMap<BasicBlock, HashSet<Value>> =
+            bbs.iter().map(|&bb| (bb, HashSet::new())).collect();
+        let mut live_out = live_in.clone();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &bb in bbs.iter().rev() {
+                let out: HashSet<Value> = cfg
+                    .succs(bb)
+                    .iter()
+                    .flat_map(|succ| live_in[succ].iter().copied())
+                    .collect();
+                let in_set: HashSet<Value> = uses[&bb]
+                    .iter()
+                    .copied()
+                    .chain(out.iter().copied().filter(|value| !defs[&bb].contains(value)))
+                    .collect();
+                if in_set != live_in[&bb] {
+                    live_in.insert(bb, in_set);
+                    changed = true;
+                }
+                live_out.insert(bb, out);
+            }
+        }
+        Self { live_in, live_out, tracked: tracked_values }
+    }
+
+    /// The interval of each tracked value that is defined or used, numbering
+    /// the instructions of all blocks in layout order. Block parameters start
+    /// at the first instruction of their block, and a value live into or out
+    /// of a block covers the whole start or end of it.
+    pub fn intervals(&self, func: &FunctionData) -> HashMap<Value, Interval> {
+        let mut intervals: HashMap<Value, Interval> = HashMap::new();
+        let mut cover = |value: Value, pos: usize| {
+            if !self.tracked.contains(&value) {
+                return;
+            }
+            intervals.entry(value).or_insert(Interval { start: pos, end: pos }).cover(pos);
+        };
+        let mut pos = 0;
+        for (&bb, node) in func.layout().bbs() {
+            let first = pos;
+            let last = first + node.insts().len().max(1) - 1;
+            for &value in func.dfg().bb(bb).params().iter().chain(&self.live_in[&bb]) {
+                cover(value, first);
+            }
+            for &inst in node.insts().keys() {
+                for used in func.dfg().value(inst).kind().value_uses() {
+                    cover(used, pos);
+                }
+                cover(inst, pos);
+                pos += 1;
+            }
+            for &value in &self.live_out[&bb] {
+                cover(value, last);
+            }
+            pos = last + 1;
+        }
+        intervals
+    }
+}
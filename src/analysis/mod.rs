@@ -0,0 +1,12 @@
+//! Analyses of Koopa IR functions shared by the optimization passes and the
+//! backend.
+
+pub mod alias;
+pub mod cfg;
+pub mod dom_tree;
+pub mod induction;
+pub mod liveness;
+
+pub use cfg::{Cfg, EdgeKind};
+pub use dom_tree::DomTree;
+pub use liveness::Liveness;
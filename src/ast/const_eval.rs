@@ -0,0 +1,304 @@
+//! Compile-time evaluation of constant expressions: array dimensions and the
+//! initializers of constants and global variables.
+
+use std::fmt::{self, Display};
+use std::rc::Rc;
+
+use super::*;
+
+/// A scalar computed at compile time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scalar {
+    Int(i32),
+    Float(f32),
+}
+
+impl Scalar {
+    /// The value converted to `btype`, as it is stored in an object of that
+    /// type: a `float` converted to an integer type drops its fractional part
+    pub fn convert(self, btype: BType) -> Self {
+        match (self, btype) {
+            (Self::Int(value), BType::Float) => Self::Float(value as f32),
+            (Self::Float(value), BType::Float) => Self::Float(value),
+            (Self::Int(value), _) => Self::Int(btype.narrow(value)),
+            (Self::Float(value), _) => Self::Int(btype.narrow(value as i32)),
+        }
+    }
+
+    /// The `i32` standing for the value in the IR: a `float` is its IEEE 754
+    /// bits (see `crate::float`)
+    pub fn bits(self) -> i32 {
+        match self {
+            Self::Int(value) => value,
+            Self::Float(value) => value.to_bits() as i32,
+        }
+    }
+
+    fn as_float(self) -> f32 {
+        match self {
+            Self::Int(value) => value as f32,
+            Self::Float(value) => value,
+        }
+    }
+
+    /// Whether the value is nonzero, as C tests a condition
+    fn is_true(self) -> bool {
+        match self {
+            Self::Int(value) => value != 0,
+            Self::Float(value) => value != 0.0,
+        }
+    }
+}
+
+/// Elements of a constant array, flattened in memory order
+#[derive(Debug)]
+pub struct ConstArray {
+    /// Dimensions, outermost first
+    pub dims: Vec<usize>,
+    /// Elements, converted to the element type, so they are all `Float`s in
+    /// a `float` array and all `Int`s otherwise
+    pub elems: Vec<Scalar>,
+}
+
+impl ConstArray {
+    /// Element at the given (complete) list of indices, if it is in bounds
+    pub fn get(&self, indices: &[i32]) -> Option<Scalar> {
+        let mut pos = 0;
+        for (&index, &dim) in indices.iter().zip(&self.dims) {
+            let index = usize::try_from(index).ok().filter(|&index| index < dim)?;
+            pos = pos * dim + index;
+        }
+        Some(self.elems[pos])
+    }
+
+    /// Whether the elements are `float`s
+    pub fn is_float(&self) -> bool {
+        matches!(self.elems.first(), Some(Scalar::Float(_)))
+    }
+}
+
+/// Value of a constant
+#[derive(Debug, Clone)]
+pub enum ConstValue {
+    Int(i32),
+    Float(f32),
+    Array(Rc<ConstArray>),
+}
+
+/// Why an expression could not be evaluated at compile time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConstError {
+    /// The name is a variable (or undefined), not a constant
+    NotConst(Ident),
+    Call(Ident),
+    DivisionByZero,
+    /// A scalar constant is indexed
+    NotArray(Ident),
+    /// A constant array is used with too few indices
+    NotScalar(Ident),
+    IndexOutOfBounds(Ident),
+    /// An array dimension is zero or negative
+    BadDimension(Ident, i32),
+    /// A `float` where only an `int` will do, or an operand of `%` or a
+    /// bitwise operator
+    Float,
+}
+
+impl Display for ConstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConst(ident) => write!(f, "Variable in constant expression: {}", ident),
+            Self::Call(ident) => write!(f, "Function call in constant expression: {}", ident),
+            Self::DivisionByZero => write!(f, "Division by zero in constant expression"),
+            Self::NotArray(ident) => write!(f, "Subscripted value is not an array: {}", ident),
+            Self::NotScalar(ident) => write!(f, "Array used as a value in constant expression: {}", ident),
+            Self::IndexOutOfBounds(ident) => write!(f, "Array index out of bounds in constant expression: {}", ident),
+            Self::BadDimension(ident, len) => write!(f, "Array size must be positive: {}[{}]", ident, len),
+            Self::Float => write!(f, "Floating-point value in an integer constant expression"),
+        }
+    }
+}
+
+impl std::error::Error for ConstError {}
+
+/// The constants visible where an expression is evaluated
+pub trait ConstScope {
+    /// Value of the constant named `ident`, or `None` if the name does not
+    /// refer to a constant
+    fn lookup_const(&self, ident: &Ident) -> Option<ConstValue>;
+}
+
+/// Evaluate an integer constant expression, such as an array size
+pub fn eval(exprs: &ExprArena, expr: ExprId, scope: &impl ConstScope) -> Result<i32, ConstError> {
+    match eval_scalar(exprs, expr, scope)? {
+        Scalar::Int(value) => Ok(value),
+        Scalar::Float(_) => Err(ConstError::Float),
+    }
+}
+
+/// Evaluate a constant expression of either type. An operation with a `float`
+/// operand converts the other one and computes in single precision, as C does.
+pub fn eval_scalar(exprs: &ExprArena, expr: ExprId, scope: &impl ConstScope) -> Result<Scalar, ConstError> {
+    let int = |expr: ExprId| eval(exprs, expr, scope);
+    match &exprs[expr] {
+        Expr::Number(number) => Ok(Scalar::Int(*number)),
+        Expr::Char(c) => Ok(Scalar::Int(*c as i32)),
+        Expr::Float(value) => Ok(Scalar::Float(*value)),
+        Expr::LVal(lval) => eval_lval(exprs, lval, scope),
+        Expr::Call(ident, _) => Err(ConstError::Call(ident.clone())),
+        Expr::Str(_) => unreachable!("String literals only appear as call arguments"),
+        Expr::Bitwise(lhs, op, rhs, _) => {
+            let (l, r) = (int(*lhs)?, int(*rhs)?);
+            // Shift counts wrap like on RV32, which only uses their low 5 bits
+            Ok(Scalar::Int(match op {
+                BitOp::And => l & r,
+                BitOp::Or => l | r,
+                BitOp::Xor => l ^ r,
+                BitOp::Shl => l.wrapping_shl(r as u32),
+                BitOp::Shr => l.wrapping_shr(r as u32),
+            }))
+        }
+        // Like `&&` and `||`, the arm not taken must still be constant
+        Expr::Ternary(cond, then_expr, else_expr, _) => {
+            let c = eval_scalar(exprs, *cond, scope)?;
+            let (t, e) = (eval_scalar(exprs, *then_expr, scope)?, eval_scalar(exprs, *else_expr, scope)?);
+            let value = if c.is_true() { t } else { e };
+            Ok(match (t, e) {
+                (Scalar::Int(_), Scalar::Int(_)) => value,
+                _ => Scalar::Float(value.as_float()),
+            })
+        }
+        Expr::Unary(op, expr) => {
+            let value = eval_scalar(exprs, *expr, scope)?;
+            Ok(match (op, value) {
+                (UnaryOp::Plus, _) => value,
+                (UnaryOp::Minus, Scalar::Int(value)) => Scalar::Int(value.wrapping_neg()),
+                (UnaryOp::Minus, Scalar::Float(value)) => Scalar::Float(-value),
+                (UnaryOp::Not, _) => Scalar::Int(!value.is_true() as i32),
+            })
+        }
+        Expr::Binary(lhs, op, rhs) => {
+            let (l, r) = (eval_scalar(exprs, *lhs, scope)?, eval_scalar(exprs, *rhs, scope)?);
+            let (Scalar::Int(l), Scalar::Int(r)) = (l, r) else {
+                return eval_float_binary(l, *op, r);
+            };
+            Ok(Scalar::Int(match op {
+                BinaryOp::Add => l.wrapping_add(r),
+                BinaryOp::Sub => l.wrapping_sub(r),
+                BinaryOp::Mul => l.wrapping_mul(r),
+                BinaryOp::Div => l.checked_div(r).ok_or(ConstError::DivisionByZero)?,
+                BinaryOp::Mod => l.checked_rem(r).ok_or(ConstError::DivisionByZero)?,
+                BinaryOp::Or => (l != 0 || r != 0) as i32,
+                BinaryOp::And => (l != 0 && r != 0) as i32,
+                BinaryOp::Eq => (l == r) as i32,
+                BinaryOp::Ne => (l != r) as i32,
+                BinaryOp::Lt => (l < r) as i32,
+                BinaryOp::Gt => (l > r) as i32,
+                BinaryOp::Le => (l <= r) as i32,
+                BinaryOp::Ge => (l >= r) as i32,
+            }))
+        }
+    }
+}
+
+/// Evaluate a binary operation with at least one `float` operand. Division by
+/// zero gives an infinity or a NaN, as it does at run time.
+fn eval_float_binary(l: Scalar, op: BinaryOp, r: Scalar) -> Result<Scalar, ConstError> {
+    let truth = |value: bool| Ok(Scalar::Int(value as i32));
+    let (l, r) = match op {
+        BinaryOp::Or => return truth(l.is_true() || r.is_true()),
+        BinaryOp::And => return truth(l.is_true() && r.is_true()),
+        _ => (l.as_float(), r.as_float()),
+    };
+    match op {
+        BinaryOp::Add => Ok(Scalar::Float(l + r)),
+        BinaryOp::Sub => Ok(Scalar::Float(l - r)),
+        BinaryOp::Mul => Ok(Scalar::Float(l * r)),
+        BinaryOp::Div => Ok(Scalar::Float(l / r)),
+        BinaryOp::Mod => Err(ConstError::Float),
+        BinaryOp::Eq => truth(l == r),
+        BinaryOp::Ne => truth(l != r),
+        BinaryOp::Lt => truth(l < r),
+        BinaryOp::Gt => truth(l > r),
+        BinaryOp::Le => truth(l <= r),
+        BinaryOp::Ge => truth(l >= r),
+        BinaryOp::Or | BinaryOp::And => unreachable!("logical operators are evaluated above"),
+    }
+}
+
+/// Evaluate a constant, or an element of a constant array with constant indices
+pub fn eval_lval(exprs: &ExprArena, lval: &LVal, scope: &impl ConstScope) -> Result<Scalar, ConstError> {
+    let ident = &lval.ident;
+    let scalar = match scope.lookup_const(ident) {
+        None => return Err(ConstError::NotConst(ident.clone())),
+        Some(ConstValue::Int(value)) => Scalar::Int(value),
+        Some(ConstValue::Float(value)) => Scalar::Float(value),
+        Some(ConstValue::Array(array)) => {
+            if lval.indices.len() != array.dims.len() {
+                return Err(ConstError::NotScalar(ident.clone()));
+            }
+            let indices = lval.indices
+                .iter()
+                .map(|&index| eval(exprs, index, scope))
+                .collect::<Result<Vec<_>, _>>()?;
+            return array.get(&indices).ok_or_else(|| ConstError::IndexOutOfBounds(ident.clone()));
+        }
+    };
+    if !lval.indices.is_empty() {
+        return Err(ConstError::NotArray(ident.clone()));
+    }
+    Ok(scalar)
+}
+
+/// Evaluate the dimensions of an array named `ident`, outermost first
+pub fn eval_dims(
+    exprs: &ExprArena,
+    dims: &[ConstExpr],
+    ident: &Ident,
+    scope: &impl ConstScope,
+) -> Result<Vec<usize>, ConstError> {
+    dims.iter()
+        .map(|dim| match eval(exprs, dim.expr, scope)? {
+            len if len > 0 => Ok(len as usize),
+            len => Err(ConstError::BadDimension(ident.clone(), len)),
+        })
+        .collect()
+}
+
+/// Value of an enumerator: its explicit value, or `next` (one more than the
+/// previous enumerator, or 0 for the first)
+pub fn eval_enumerator(
+    exprs: &ExprArena,
+    enumerator: &Enumerator,
+    next: i32,
+    scope: &impl ConstScope,
+) -> Result<i32, ConstError> {
+    match &enumerator.value {
+        Some(value) => eval(exprs, value.expr, scope),
+        None => Ok(next),
+    }
+}
+
+/// Evaluate the value of a constant definition
+pub fn eval_def(
+    exprs: &ExprArena,
+    btype: BType,
+    def: &ConstDef,
+    scope: &impl ConstScope,
+) -> Result<ConstValue, ConstError> {
+    let dims = eval_dims(exprs, &def.dims, &def.id, scope)?;
+    let elems = def.init
+        .flatten(&dims, &def.id)
+        .into_iter()
+        .map(|elem| elem.map_or(Ok(Scalar::Int(0)), |expr| eval_scalar(exprs, expr, scope)))
+        .map(|elem| elem.map(|elem| elem.convert(btype)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if dims.is_empty() {
+        Ok(match elems[0] {
+            Scalar::Int(value) => ConstValue::Int(value),
+            Scalar::Float(value) => ConstValue::Float(value),
+        })
+    } else {
+        Ok(ConstValue::Array(Rc::new(ConstArray { dims, elems })))
+    }
+}
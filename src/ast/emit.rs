@@ -1,42 +1,347 @@
 use super::*;
+use super::const_eval::{self, ConstArray, ConstScope, ConstValue, Scalar};
+use crate::diag::Diagnostic;
+use crate::float::{FloatOp, FloatSignature};
+use crate::semant::SymbolTable;
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
-use koopa::ir::{BasicBlock, FunctionData, Program, Type, Value};
+use koopa::ir::{BasicBlock, Function, FunctionData, Program, Type, TypeKind, Value, ValueKind};
 use koopa::ir::builder_traits::*;
 
 
 
-pub struct EmitContext {
-    const_table: HashMap<Ident, ConstExpr>,
-    var_table: HashMap<Ident, Value>,
+/// What an identifier refers to during emission
+#[derive(Clone)]
+enum Symbol {
+    /// A constant, folded to its value at its declaration
+    Const(Scalar),
+    /// A constant array: the address of its `alloc` or `global_alloc`, for
+    /// indexing at run time, and its elements, for constant expressions
+    ConstArray(Value, Rc<ConstArray>),
+    /// A variable, represented by the address of its `alloc` or `global_alloc`,
+    /// its number of array dimensions (0 for a scalar) and its element type
+    Var(Value, usize, BType),
+    /// An array parameter, represented by the address of the slot holding the
+    /// pointer it decays to, its number of dimensions (including the omitted
+    /// first one) and its element type
+    Ptr(Value, usize, BType),
 }
 
-impl EmitContext {
-    pub fn new() -> Self {
+/// Source spans of the emitted values, for diagnostics and debug info about
+/// the IR to point back at the source
+///
+/// Each value computed for an expression maps to the span of the innermost
+/// expression it was emitted for. Values created later by the passes have no
+/// span.
+#[derive(Debug, Clone, Default)]
+pub struct Locations {
+    spans: HashMap<Value, Span>,
+}
+
+impl Locations {
+    /// The span of the expression `value` was emitted for
+    pub fn span(&self, value: Value) -> Option<Span> {
+        self.spans.get(&value).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Record the span of `value`, unless a subexpression gave it one already
+    fn record(&mut self, value: Value, span: Span) {
+        self.spans.entry(value).or_insert(span);
+    }
+}
+
+/// Options that change the emitted IR
+#[derive(Debug, Clone, Default)]
+pub struct EmitOptions {
+    /// Store 0 into every local declared without an initializer, so reads of
+    /// uninitialized locals are deterministic (a debugging aid)
+    pub zero_init_locals: bool,
+    /// If non-empty, only these functions are lowered; all others are
+    /// emitted as declarations without a body
+    pub only_functions: Vec<String>,
+    /// Functions emitted as declarations without a body
+    pub skip_functions: Vec<String>,
+}
+
+impl EmitOptions {
+    /// Whether the body of function `name` is lowered
+    fn lowers(&self, name: &str) -> bool {
+        (self.only_functions.is_empty() || self.only_functions.iter().any(|f| f == name))
+            && !self.skip_functions.iter().any(|f| f == name)
+    }
+}
+
+pub struct EmitContext<'a> {
+    options: EmitOptions,
+    /// Expressions of the unit being emitted
+    exprs: &'a ExprArena,
+    /// Functions defined so far, by name
+    functions: HashMap<Ident, Function>,
+    /// Parameter counts of the variadic runtime functions (`putf`), which are
+    /// declared with as many parameters as the longest call passes
+    varargs: HashMap<Ident, usize>,
+    /// Globals holding the string literals, by the expression they appear in
+    strings: HashMap<ExprId, Value>,
+    /// Globals holding the static locals of the function being emitted, by
+    /// the span of their name
+    statics: HashMap<Span, Value>,
+    /// Globals holding the static locals of every function
+    static_locals: HashSet<Value>,
+    /// Names of the global values, which the values of a function do not reuse
+    globals: HashMap<Value, String>,
+    /// Names of the functions and globals of the program, including the
+    /// file-scope declarations not emitted yet, which generated names avoid
+    taken_names: HashSet<String>,
+    /// What the names in scope refer to
+    symbols: SymbolTable<Symbol>,
+    /// Enclosing loops and `switch`es, innermost last, as (continue target,
+    /// break target); a `switch` has no continue target of its own
+    loops: Vec<(Option<BasicBlock>, BasicBlock)>,
+    /// Spans of the values emitted so far; expressions are emitted through a
+    /// shared context, so this is filled in through a `RefCell`
+    locations: RefCell<Locations>,
+    /// Errors found while lowering, like `locations` filled in through a
+    /// `RefCell`
+    errors: RefCell<Vec<Diagnostic>>,
+    /// Which parameters and results are `float`s, for the functions with any
+    float_signatures: HashMap<Ident, FloatSignature>,
+    /// The functions standing for the operations on `float`s
+    float_ops: HashMap<FloatOp, Function>,
+    /// The operations on `float`s emitted so far, like `locations` filled in
+    /// through a `RefCell`; the functions of the others are removed once the
+    /// program is lowered
+    used_float_ops: RefCell<HashSet<FloatOp>>,
+    /// Whether the function being emitted returns a `float`
+    returns_float: bool,
+}
+
+impl<'a> EmitContext<'a> {
+    pub fn new(exprs: &'a ExprArena) -> Self {
+        Self::with_options(exprs, EmitOptions::default())
+    }
+
+    pub fn with_options(exprs: &'a ExprArena, options: EmitOptions) -> Self {
         Self {
-            const_table: HashMap::new(),
-            var_table: HashMap::new(),
+            options,
+            exprs,
+            functions: HashMap::new(),
+            varargs: HashMap::new(),
+            strings: HashMap::new(),
+            statics: HashMap::new(),
+            static_locals: HashSet::new(),
+            globals: HashMap::new(),
+            taken_names: HashSet::new(),
+            symbols: SymbolTable::new(),
+            loops: Vec::new(),
+            locations: RefCell::default(),
+            errors: RefCell::default(),
+            float_signatures: HashMap::new(),
+            float_ops: HashMap::new(),
+            used_float_ops: RefCell::default(),
+            returns_float: false,
+        }
+    }
+
+    fn enter_scope(&mut self) {
+        self.symbols.enter_scope();
+    }
+
+    fn exit_scope(&mut self) {
+        self.symbols.exit_scope();
+    }
+
+    fn declare(&mut self, ident: &Ident, symbol: Symbol) {
+        self.symbols.declare(ident, symbol);
+    }
+
+    /// What the name of `lval` refers to. Semantic checks reject undeclared
+    /// names before lowering; one that gets here anyway is reported as an
+    /// error, and the caller lowers the use as best it can so that lowering
+    /// can go on to report the others.
+    fn lookup(&self, lval: &LVal) -> Option<Symbol> {
+        let symbol = self.symbols.lookup(&lval.ident).cloned();
+        if symbol.is_none() {
+            let message = format!("use of undeclared identifier '{}'", lval.ident);
+            self.errors.borrow_mut().push(Diagnostic::error(lval.span, message));
+        }
+        symbol
+    }
+
+    fn name_global(&mut self, program: &mut Program, global: Value, name: String) {
+        program.set_value_name(global, Some(name.clone()));
+        self.taken_names.insert(name.clone());
+        self.globals.insert(global, name);
+    }
+
+    /// Whether `expr` is a `float`, by the typing rules of the semantic
+    /// checks: arithmetic with a `float` operand and `?:` with a `float` arm
+    /// are `float`s, comparisons and logical operators are `int`s
+    fn is_float(&self, expr: ExprId) -> bool {
+        match &self.exprs[expr] {
+            Expr::Float(_) => true,
+            Expr::Number(_) | Expr::Char(_) | Expr::Str(_) | Expr::Bitwise(..) => false,
+            Expr::Unary(op, operand) => !matches!(op, UnaryOp::Not) && self.is_float(*operand),
+            Expr::Binary(lhs, op, rhs) => match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
+                    self.is_float(*lhs) || self.is_float(*rhs)
+                }
+                _ => false,
+            },
+            Expr::Ternary(_, then_expr, else_expr, _) => self.is_float(*then_expr) || self.is_float(*else_expr),
+            Expr::Call(ident, _) => self.float_signatures.get(ident).is_some_and(|signature| signature.ret),
+            Expr::LVal(lval) => match self.symbols.lookup(&lval.ident) {
+                Some(Symbol::Const(scalar)) => matches!(scalar, Scalar::Float(_)),
+                Some(Symbol::ConstArray(_, array)) => array.is_float() && lval.indices.len() == array.dims.len(),
+                Some(Symbol::Var(_, rank, btype) | Symbol::Ptr(_, rank, btype)) => {
+                    *btype == BType::Float && lval.indices.len() == *rank
+                }
+                None => false,
+            },
+        }
+    }
+
+    /// Emit the operation `op` on `float`s at the end of `bb`
+    fn float_op(&self, func: &mut FunctionData, bb: BasicBlock, op: FloatOp, args: Vec<Value>) -> Value {
+        self.used_float_ops.borrow_mut().insert(op);
+        let call = func.dfg_mut().new_value().call(self.float_ops[&op], args);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(call).unwrap();
+        call
+    }
+
+    /// Convert `value` from `int` to `float` or back at the end of `bb`, if
+    /// `from_float` and `to_float` differ. Constants are converted at compile
+    /// time.
+    fn convert(
+        &self,
+        func: &mut FunctionData,
+        bb: BasicBlock,
+        value: Value,
+        from_float: bool,
+        to_float: bool,
+    ) -> Value {
+        if from_float == to_float {
+            return value;
+        }
+        if let ValueKind::Integer(int) = func.dfg().value(value).kind() {
+            let scalar =
+                if from_float { Scalar::Float(f32::from_bits(int.value() as u32)) } else { Scalar::Int(int.value()) };
+            let btype = if to_float { BType::Float } else { BType::Int };
+            return func.dfg_mut().new_value().integer(scalar.convert(btype).bits());
         }
+        let op = if to_float { FloatOp::FromInt } else { FloatOp::ToInt };
+        self.float_op(func, bb, op, vec![value])
+    }
+
+    /// Emit `expr` converted to `btype` before it is stored in an object of
+    /// that type
+    fn emit_as(&self, expr: ExprId, btype: BType, func: &mut FunctionData, bb: &mut BasicBlock) -> Value {
+        let value = expr.emit(func, bb, self);
+        let value = self.convert(func, *bb, value, self.is_float(expr), btype == BType::Float);
+        narrow(func, *bb, btype, value)
+    }
+
+    /// Convert `value`, emitted for `expr`, to a truth value at the end of
+    /// `bb`; a `float` is true unless it equals 0.0 (or -0.0), as
+    /// `eq feq(value, 0.0), 0`
+    fn truth(&self, func: &mut FunctionData, bb: BasicBlock, expr: ExprId, value: Value) -> Value {
+        if !self.is_float(expr) {
+            return to_bool(func, bb, value);
+        }
+        if let ValueKind::Integer(int) = func.dfg().value(value).kind() {
+            let truth = (f32::from_bits(int.value() as u32) != 0.0) as i32;
+            return func.dfg_mut().new_value().integer(truth);
+        }
+        let zero = func.dfg_mut().new_value().integer(0);
+        let is_zero = self.float_op(func, bb, FloatOp::Eq, vec![value, zero]);
+        let zero = func.dfg_mut().new_value().integer(0);
+        let truth = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::Eq, is_zero, zero);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(truth).unwrap();
+        truth
+    }
+
+    /// Emit `lhs op rhs` on two `float`s at the end of `bb`: arithmetic gives
+    /// a `float`, a comparison 0 or 1
+    fn float_binary(
+        &self,
+        func: &mut FunctionData,
+        bb: BasicBlock,
+        op: BinaryOp,
+        lhs: Value,
+        rhs: Value,
+    ) -> Value {
+        let (float_op, args) = match op {
+            BinaryOp::Add => (FloatOp::Add, vec![lhs, rhs]),
+            BinaryOp::Sub => (FloatOp::Sub, vec![lhs, rhs]),
+            BinaryOp::Mul => (FloatOp::Mul, vec![lhs, rhs]),
+            BinaryOp::Div => (FloatOp::Div, vec![lhs, rhs]),
+            BinaryOp::Eq | BinaryOp::Ne => (FloatOp::Eq, vec![lhs, rhs]),
+            // a > b => b < a, a >= b => b <= a
+            BinaryOp::Lt => (FloatOp::Lt, vec![lhs, rhs]),
+            BinaryOp::Gt => (FloatOp::Lt, vec![rhs, lhs]),
+            BinaryOp::Le => (FloatOp::Le, vec![lhs, rhs]),
+            BinaryOp::Ge => (FloatOp::Le, vec![rhs, lhs]),
+            BinaryOp::Mod | BinaryOp::And | BinaryOp::Or => unreachable!("'{}' is not a float operation", op),
+        };
+        let value = self.float_op(func, bb, float_op, args);
+        if !matches!(op, BinaryOp::Ne) {
+            return value;
+        }
+        // a != b => feq(a, b) == 0
+        let zero = func.dfg_mut().new_value().integer(0);
+        let ne = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::Eq, value, zero);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(ne).unwrap();
+        ne
     }
 }
 
-impl Default for EmitContext {
-    fn default() -> Self {
-        Self::new()
+impl ConstScope for EmitContext<'_> {
+    fn lookup_const(&self, ident: &Ident) -> Option<ConstValue> {
+        match self.symbols.lookup(ident)? {
+            Symbol::Const(Scalar::Int(value)) => Some(ConstValue::Int(*value)),
+            Symbol::Const(Scalar::Float(value)) => Some(ConstValue::Float(*value)),
+            Symbol::ConstArray(_, array) => Some(ConstValue::Array(Rc::clone(array))),
+            Symbol::Var(..) | Symbol::Ptr(..) => None,
+        }
     }
 }
 
+
 impl Ident {
     pub fn emit(&self) -> String {
         format!("@{}", self.value)
     }
 }
 
-impl FuncType {
+impl BType {
     pub fn emit(&self) -> Type {
         match self {
             Self::Int => Type::get_i32(),
+            // The Koopa IR has no floating-point type: a `float` is the word
+            // holding its bits (see `crate::float`)
+            Self::Float => Type::get_i32(),
+            // Koopa has no 8-bit type: a `char` takes a word, and is kept in
+            // range by narrowing every value stored into it
+            Self::Char => Type::get_i32(),
+        }
+    }
+}
+
+impl FuncType {
+    pub fn emit(&self) -> Type {
+        match self {
+            Self::Int | Self::Float => Type::get_i32(),
+            Self::Void => Type::get_unit(),
         }
     }
 }
@@ -47,9 +352,257 @@ impl FuncType {
 
 impl CompUnit {
     pub fn emit(&self) -> Program {
+        self.emit_with_options(EmitOptions::default())
+    }
+
+    pub fn emit_with_options(&self, options: EmitOptions) -> Program {
+        self.emit_with_locations(options).0
+    }
+
+    /// Emit the program along with the source spans of its values
+    pub fn emit_with_locations(&self, options: EmitOptions) -> (Program, Locations) {
+        let Emitted { program, locations, .. } = self.emit_all(options);
+        (program, locations)
+    }
+
+    /// Emit the program along with what the IR does not record about it
+    pub fn emit_all(&self, options: EmitOptions) -> Emitted {
         let mut prgm = Program::new();
-        self.func_def.emit(&mut prgm);
-        prgm
+        let mut context = EmitContext::with_options(&self.exprs, options);
+        declare_runtime(&mut prgm, &mut context);
+        declare_float_ops(&mut prgm, &mut context);
+        reserve_names(&prgm, &mut context, &self.items);
+        declare_strings(&mut prgm, &mut context);
+        for item in &self.items {
+            match item {
+                GlobalItem::Decl(decl) => decl.emit_global(&mut prgm, &mut context),
+                GlobalItem::FuncDef(func_def) => func_def.emit(&mut prgm, &mut context),
+            }
+        }
+        let used_float_ops = context.used_float_ops.into_inner();
+        for (op, &func) in &context.float_ops {
+            if !used_float_ops.contains(op) {
+                prgm.remove_func(func);
+            }
+        }
+        Emitted {
+            program: prgm,
+            locations: context.locations.into_inner(),
+            string_literals: context.strings.values().copied().collect(),
+            static_locals: context.static_locals,
+            float_signatures: context
+                .float_signatures
+                .into_iter()
+                .map(|(ident, signature)| (ident.value, signature))
+                .collect(),
+            errors: context.errors.into_inner(),
+        }
+    }
+}
+
+/// A lowered compilation unit
+pub struct Emitted {
+    pub program: Program,
+    pub locations: Locations,
+    /// The globals holding string literals, which the backend places in
+    /// read-only data as packed bytes (see `AsmGenerator::with_string_literals`)
+    pub string_literals: HashSet<Value>,
+    /// The globals holding static locals, which the backend does not export
+    /// (see `AsmGenerator::with_static_locals`)
+    pub static_locals: HashSet<Value>,
+    /// Which parameters and results are `float`s, by function name (without
+    /// the `@`), for the functions with any; the backend passes them in the
+    /// floating-point registers (see `AsmGenerator::with_float_signatures`)
+    pub float_signatures: HashMap<String, FloatSignature>,
+    /// Errors semantic checks would have caught, such as undeclared names;
+    /// the program is incomplete if there are any
+    pub errors: Vec<Diagnostic>,
+}
+
+/// Declare the functions of the SysY runtime library, which every program
+/// may call without declaring them
+fn declare_runtime(program: &mut Program, context: &mut EmitContext) {
+    let int = Type::get_i32;
+    let ptr = || Type::get_pointer(Type::get_i32());
+    let unit = Type::get_unit;
+    let runtime = [
+        ("getint", vec![], int()),
+        ("getch", vec![], int()),
+        ("getfloat", vec![], int()),
+        ("getarray", vec![ptr()], int()),
+        ("getfarray", vec![ptr()], int()),
+        ("putint", vec![int()], unit()),
+        ("putch", vec![int()], unit()),
+        ("putfloat", vec![int()], unit()),
+        ("putarray", vec![int(), ptr()], unit()),
+        ("putfarray", vec![int(), ptr()], unit()),
+        ("starttime", vec![], unit()),
+        ("stoptime", vec![], unit()),
+    ];
+    for (name, params, ret) in runtime {
+        let func = program.new_func(FunctionData::new_decl(format!("@{}", name), params, ret));
+        context.functions.insert(Ident::from(name.to_string()), func);
+    }
+    let floats = [
+        ("getfloat", FloatSignature { params: vec![], ret: true }),
+        ("putfloat", FloatSignature { params: vec![true], ret: false }),
+    ];
+    for (name, signature) in floats {
+        context.float_signatures.insert(Ident::from(name.to_string()), signature);
+    }
+
+    // Koopa has no variadic functions, so `putf` gets a parameter for every
+    // argument of its longest call; the RISC-V calling convention passes
+    // variadic ints like fixed ones, and shorter calls pass zeros
+    let putf = Ident::from("putf".to_string());
+    let arity = context
+        .exprs
+        .iter()
+        .filter_map(|(_, expr)| match expr {
+            Expr::Call(ident, args) if *ident == putf => Some(args.len()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let params = std::iter::once(ptr()).chain(std::iter::repeat_with(int).take(arity - 1)).collect();
+    let func = program.new_func(FunctionData::new_decl("@putf".to_string(), params, unit()));
+    context.functions.insert(putf.clone(), func);
+    context.varargs.insert(putf, arity);
+}
+
+/// Declare the functions standing for the operations on `float`s (see
+/// `crate::float`); those the program does not use are removed after lowering
+fn declare_float_ops(program: &mut Program, context: &mut EmitContext) {
+    for op in FloatOp::ALL {
+        let params = vec![Type::get_i32(); op.arity()];
+        let func = program.new_func(FunctionData::new_decl(format!("@{}", op.name()), params, Type::get_i32()));
+        context.float_ops.insert(op, func);
+    }
+}
+
+/// Reserve the names of the functions declared so far and of the file-scope
+/// declarations of `items`, so that the globals of string literals and static
+/// locals are not given one of them
+fn reserve_names(program: &Program, context: &mut EmitContext, items: &[GlobalItem]) {
+    let taken = &mut context.taken_names;
+    taken.extend(program.funcs().values().map(|func| func.name().to_string()));
+    for item in items {
+        match item {
+            GlobalItem::FuncDef(func_def) => {
+                taken.insert(func_def.id.emit());
+            }
+            GlobalItem::Decl(Decl::Var(decl)) => taken.extend(decl.defs.iter().map(|def| def.id.emit())),
+            GlobalItem::Decl(Decl::Const(decl)) => taken.extend(decl.defs.iter().map(|def| def.id.emit())),
+            GlobalItem::Decl(_) => {}
+        }
+    }
+}
+
+/// Place every string literal in a global, as the bytes of the NUL-terminated
+/// string packed little-endian into words. The globals are named `@__str_<n>`,
+/// skipping the names already taken.
+fn declare_strings(program: &mut Program, context: &mut EmitContext) {
+    let mut indices = 0..;
+    let literals: Vec<(ExprId, &String)> = context
+        .exprs
+        .iter()
+        .filter_map(|(id, expr)| match expr {
+            Expr::Str(string) => Some((id, string)),
+            _ => None,
+        })
+        .collect();
+    for (id, string) in literals {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.push(0);
+        let words = bytes
+            .chunks(4)
+            .map(|chunk| {
+                let mut word = [0; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                program.new_value().integer(i32::from_le_bytes(word))
+            })
+            .collect();
+        let init = program.new_value().aggregate(words);
+        let global = program.new_value().global_alloc(init);
+        let name = indices
+            .by_ref()
+            .map(|i| format!("@__str_{}", i))
+            .find(|name| !context.taken_names.contains(name))
+            .unwrap();
+        context.name_global(program, global, name);
+        context.strings.insert(id, global);
+    }
+}
+
+impl Decl {
+    /// Emit a file-scope declaration; initializers must be constant expressions
+    pub fn emit_global(&self, program: &mut Program, context: &mut EmitContext) {
+        match self {
+            Self::Const(const_decl) => const_decl.emit_global(program, context),
+            Self::Var(var_decl) => {
+                for def in &var_decl.defs {
+                    let alloc = def.emit_global(var_decl.btype, program, context);
+                    context.name_global(program, alloc, def.id.emit());
+                    context.declare(&def.id, Symbol::Var(alloc, def.dims.len(), var_decl.btype));
+                }
+            }
+            Self::Enum(enum_decl) => enum_decl.emit(context),
+        }
+    }
+}
+
+impl VarDef {
+    /// Emit the `global_alloc` of a file-scope or static variable, whose
+    /// initializer must be constant
+    fn emit_global(&self, btype: BType, program: &mut Program, context: &EmitContext) -> Value {
+        let ty = self.ty(&btype, context);
+        let init = match &self.init {
+            Some(init) => {
+                let elems: Vec<i32> = init
+                    .flatten(&self.dims(context), &self.id)
+                    .into_iter()
+                    .map(|elem| elem.map_or(0, |expr| expr.eval_scalar(context).convert(btype).bits()))
+                    .collect();
+                global_init(program, &elems, &ty)
+            }
+            None => program.new_value().zero_init(ty),
+        };
+        program.new_value().global_alloc(init)
+    }
+}
+
+/// Collect the static local declarations in `block` and its nested statements
+fn static_decls<'b>(block: &'b Block, decls: &mut Vec<&'b VarDecl>) {
+    for item in &block.items {
+        match item {
+            BlockItem::Decl(Decl::Var(decl)) if decl.static_span.is_some() => decls.push(decl),
+            BlockItem::Decl(_) => {}
+            BlockItem::Stmt(stmt) => stmt_static_decls(stmt, decls),
+        }
+    }
+}
+
+fn stmt_static_decls<'b>(stmt: &'b Stmt, decls: &mut Vec<&'b VarDecl>) {
+    match stmt {
+        Stmt::Block(block) => static_decls(block, decls),
+        Stmt::If(if_stmt) => {
+            stmt_static_decls(&if_stmt.then_stmt, decls);
+            if let Some(else_stmt) = &if_stmt.else_stmt {
+                stmt_static_decls(else_stmt, decls);
+            }
+        }
+        Stmt::While(while_stmt) => stmt_static_decls(&while_stmt.body, decls),
+        Stmt::DoWhile(do_while) => stmt_static_decls(&do_while.body, decls),
+        Stmt::For(for_stmt) => {
+            match &for_stmt.init {
+                Some(ForInit::Decl(decl)) if decl.static_span.is_some() => decls.push(decl),
+                _ => {}
+            }
+            stmt_static_decls(&for_stmt.body, decls);
+        }
+        Stmt::Switch(switch) => static_decls(&switch.body, decls),
+        Stmt::Return(_) | Stmt::Assign(_) | Stmt::Expr(_) | Stmt::Break(_) | Stmt::Continue(_) => {}
     }
 }
 
@@ -57,31 +610,207 @@ impl CompUnit {
 
 
 impl FuncDef {
-    pub fn emit(&self, program: &mut Program) {
-        let func = FunctionData::new(
+    pub fn emit(&self, program: &mut Program, context: &mut EmitContext) {
+        let signature = FloatSignature {
+            params: self.params.iter().map(|param| param.dims.is_none() && param.btype == BType::Float).collect(),
+            ret: matches!(self.func_type, FuncType::Float),
+        };
+        if signature.has_floats() {
+            context.float_signatures.insert(self.id.clone(), signature);
+        }
+        // A function that is filtered out is still declared, so calls to it
+        // can be emitted
+        if !context.options.lowers(&self.id.value) {
+            let params = self.params.iter().map(|param| param.ty(context)).collect();
+            let func = FunctionData::new_decl(self.id.emit(), params, self.func_type.emit());
+            let func = program.new_func(func);
+            context.functions.insert(self.id.clone(), func);
+            return;
+        }
+        let params = self.params
+            .iter()
+            .map(|param| (Some(format!("%{}", param.id)), param.ty(context)))
+            .collect();
+        let func = FunctionData::with_param_names(
             self.id.emit(),
-            vec![],
+            params,
             self.func_type.emit(),
         );
-        let mut context = EmitContext::new();
-        let func =program.new_func(func);
+        let func = program.new_func(func);
+        // Register the function before its body so it can call itself
+        context.functions.insert(self.id.clone(), func);
+
+        // Static locals live in globals, which cannot be created while the
+        // function is being lowered. They are named `@__<function>_<name>`,
+        // with a suffix if another function or global already has that name
+        // (static `x_y` of `f` and static `y` of `f_x`, say).
+        let mut statics = Vec::new();
+        static_decls(&self.block, &mut statics);
+        for decl in statics {
+            for def in &decl.defs {
+                let global = def.emit_global(decl.btype, program, context);
+                let base = format!("@__{}_{}", self.id, def.id);
+                let name = std::iter::once(base.clone())
+                    .chain((1..).map(|i| format!("{}_{}", base, i)))
+                    .find(|name| !context.taken_names.contains(name))
+                    .unwrap();
+                context.name_global(program, global, name);
+                context.statics.insert(def.span, global);
+                context.static_locals.insert(global);
+            }
+        }
         let func = program.func_mut(func);
-        self.block.emit(func, &mut context);
+        let entry = func.dfg_mut().new_bb().basic_block(Some("%entry".into()));
+        func.layout_mut().bbs_mut().push_key_back(entry).unwrap();
+
+        // Parameters live in the scope enclosing the body; copy each incoming
+        // value into its own slot so the body can treat it like a local
+        context.enter_scope();
+        let param_values = func.params().to_vec();
+        for (param, value) in self.params.iter().zip(param_values) {
+            let alloc = func.dfg_mut().new_value().alloc(param.ty(context));
+            func.dfg_mut().set_value_name(alloc, Some(param.id.emit()));
+            func.layout_mut().bb_mut(entry).insts_mut().push_key_back(alloc).unwrap();
+            let symbol = match &param.dims {
+                Some(dims) => Symbol::Ptr(alloc, dims.len() + 1, param.btype),
+                None => Symbol::Var(alloc, 0, param.btype),
+            };
+            let value = match symbol {
+                Symbol::Var(..) => narrow(func, entry, param.btype, value),
+                _ => value,
+            };
+            let store = func.dfg_mut().new_value().store(value, alloc);
+            func.layout_mut().bb_mut(entry).insts_mut().push_key_back(store).unwrap();
+            context.declare(&param.id, symbol);
+        }
+
+        // Control reaching the end of the body returns, with 0 from a
+        // function that should have returned a value, as C compilers do
+        context.returns_float = matches!(self.func_type, FuncType::Float);
+        let end = self.block.emit(func, entry, context);
+        if !is_terminated(func, end) {
+            let value = match self.func_type {
+                FuncType::Void => None,
+                _ => Some(func.dfg_mut().new_value().integer(0)),
+            };
+            let ret = func.dfg_mut().new_value().ret(value);
+            func.layout_mut().bb_mut(end).insts_mut().push_key_back(ret).unwrap();
+        }
+        context.exit_scope();
+        hoist_allocs(func, entry);
+        name_values(func, context);
+    }
+}
+
+/// Move every `alloc` to the start of the entry block, keeping their order.
+/// Locals are allocated where they are declared, but their slots exist for
+/// the whole call, and passes expect to find them all in the entry block.
+fn hoist_allocs(func: &mut FunctionData, entry: BasicBlock) {
+    let is_alloc = |func: &FunctionData, inst: &Value| matches!(func.dfg().value(*inst).kind(), ValueKind::Alloc(_));
+    let entry_insts: Vec<Value> = func.layout().bbs().node(&entry).unwrap().insts().keys().copied().collect();
+    let (mut allocs, rest): (Vec<Value>, Vec<Value>) =
+        entry_insts.iter().copied().partition(|inst| is_alloc(func, inst));
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().filter(|&bb| bb != entry).collect();
+    for bb in bbs {
+        let insts = func.layout().bbs().node(&bb).unwrap().insts().keys();
+        let bb_allocs: Vec<Value> = insts.filter(|inst| is_alloc(func, inst)).copied().collect();
+        for inst in bb_allocs {
+            func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+            allocs.push(inst);
+        }
+    }
+    for inst in entry_insts {
+        func.layout_mut().bb_mut(entry).insts_mut().remove(&inst);
+    }
+    for inst in allocs.into_iter().chain(rest) {
+        func.layout_mut().bb_mut(entry).insts_mut().push_key_back(inst).unwrap();
+    }
+}
+
+/// Give the values of a function readable names, so the printed IR can be
+/// followed against the source: locals and parameters keep the names they
+/// were emitted with, loads are named after the variable they read (`%x` for
+/// a load of any `x`) and branch conditions `%cond`. A name already taken in
+/// the function, or by a global or function, gets the first free suffix `_1`,
+/// `_2`, ... in layout order, so shadowed locals read `@x`, `@x_1`, ...
+fn name_values(func: &mut FunctionData, context: &EmitContext) {
+    let mut taken: HashSet<String> = context.globals
+        .values()
+        .cloned()
+        .chain(context.functions.keys().map(Ident::emit))
+        .collect();
+    let mut suffixes: HashMap<String, usize> = HashMap::new();
+    let mut unique = |base: String| {
+        let next = suffixes.entry(base.clone()).or_insert(0);
+        loop {
+            let name = if *next == 0 { base.clone() } else { format!("{}_{}", base, next) };
+            *next += 1;
+            if taken.insert(name.clone()) {
+                return name;
+            }
+        }
+    };
+
+    let insts = func.layout().bbs().iter().flat_map(|(_, node)| node.insts().keys().copied());
+    let values: Vec<Value> = func.params().iter().copied().chain(insts).collect();
+    let mut names: HashMap<Value, String> = HashMap::new();
+    for value in values {
+        let data = func.dfg().value(value);
+        if let Some(name) = data.name() {
+            names.insert(value, unique(name.clone()));
+        }
+        match data.kind() {
+            ValueKind::Load(load) if data.name().is_none() => {
+                let src = match load.src() {
+                    src if src.is_global() => context.globals.get(&src),
+                    src => func.dfg().value(src).name().as_ref(),
+                };
+                if let Some(src) = src {
+                    let name = unique(format!("%{}", &src[1..]));
+                    names.insert(value, name);
+                }
+            }
+            ValueKind::Branch(branch) => {
+                let cond = branch.cond();
+                let unnamed = !cond.is_global() && !names.contains_key(&cond);
+                if unnamed && !matches!(func.dfg().value(cond).kind(), ValueKind::Integer(_)) {
+                    names.insert(cond, unique("%cond".to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    for (value, name) in names {
+        func.dfg_mut().set_value_name(value, Some(name));
+    }
+}
+
+impl FuncFParam {
+    /// Type of the parameter; an array parameter decays to a pointer to its
+    /// first element
+    fn ty(&self, context: &EmitContext) -> Type {
+        let Some(dims) = &self.dims else {
+            return self.btype.emit();
+        };
+        let dims = const_eval::eval_dims(context.exprs, dims, &self.id, context).unwrap_or_else(|e| panic!("{}", e));
+        Type::get_pointer(array_ty(self.btype.emit(), &dims))
     }
 }
 
 impl Block {
-    pub fn emit(&self, func: &mut FunctionData, context: &mut EmitContext) {
-        let entry = func.dfg_mut().new_bb().basic_block(Some("%entry".into()));
-        func.layout_mut().bbs_mut().push_key_back(entry).unwrap();
+    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        context.enter_scope();
+        let mut bb = bb;
         for item in &self.items {
-            item.emit(func, entry, context);
+            bb = item.emit(func, open_bb(func, bb), context);
         }
+        context.exit_scope();
+        bb
     }
 }
 
 impl BlockItem {
-    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) {
+    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
         match self {
             Self::Stmt(stmt) => stmt.emit(func, bb, context),
             Self::Decl(decl) => decl.emit(func, bb, context),
@@ -90,93 +819,628 @@ impl BlockItem {
 }
 
 impl Decl {
-    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) {
+    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
         match self {
-            Self::Const(const_decl) => const_decl.emit(context),
+            Self::Const(const_decl) => {
+                const_decl.emit(func, bb, context);
+                bb
+            }
             Self::Var(var_decl) => var_decl.emit(func, bb, context),
+            Self::Enum(enum_decl) => {
+                enum_decl.emit(context);
+                bb
+            }
         }
     }
 }
 
-impl ConstDecl {
+impl EnumDecl {
+    /// Declare the enumerators as constants; no code or data is emitted
     pub fn emit(&self, context: &mut EmitContext) {
+        let mut next = 0;
+        for enumerator in &self.enumerators {
+            let value = const_eval::eval_enumerator(context.exprs, enumerator, next, context)
+                .unwrap_or_else(|e| panic!("{}", e));
+            context.declare(&enumerator.id, Symbol::Const(Scalar::Int(value)));
+            next = value.wrapping_add(1);
+        }
+    }
+}
+
+impl ConstDecl {
+    /// Emit a file-scope constant. Scalars are only folded; arrays are also
+    /// stored in a global, since they may be indexed at run time.
+    pub fn emit_global(&self, program: &mut Program, context: &mut EmitContext) {
         for def in &self.defs {
-            context.const_table.insert(def.id.clone(), def.init.const_expr.clone());
+            let symbol = match def.eval(self.btype, context) {
+                ConstValue::Int(value) => Symbol::Const(Scalar::Int(value)),
+                ConstValue::Float(value) => Symbol::Const(Scalar::Float(value)),
+                ConstValue::Array(array) => {
+                    let ty = array_ty(self.btype.emit(), &array.dims);
+                    let elems: Vec<i32> = array.elems.iter().map(|elem| elem.bits()).collect();
+                    let init = global_init(program, &elems, &ty);
+                    let alloc = program.new_value().global_alloc(init);
+                    context.name_global(program, alloc, def.id.emit());
+                    Symbol::ConstArray(alloc, array)
+                }
+            };
+            context.declare(&def.id, symbol);
         }
     }
+
+    /// Emit a local constant; arrays are stored in a local like variables
+    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) {
+        for def in &self.defs {
+            let symbol = match def.eval(self.btype, context) {
+                ConstValue::Int(value) => Symbol::Const(Scalar::Int(value)),
+                ConstValue::Float(value) => Symbol::Const(Scalar::Float(value)),
+                ConstValue::Array(array) => {
+                    let alloc = func.dfg_mut().new_value().alloc(array_ty(self.btype.emit(), &array.dims));
+                    func.dfg_mut().set_value_name(alloc, Some(def.id.emit()));
+                    func.layout_mut().bb_mut(bb).insts_mut().push_key_back(alloc).unwrap();
+                    for (i, &elem) in array.elems.iter().enumerate() {
+                        let value = func.dfg_mut().new_value().integer(elem.bits());
+                        let ptr = element_ptr(func, bb, alloc, &array.dims, i);
+                        let store = func.dfg_mut().new_value().store(value, ptr);
+                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(store).unwrap();
+                    }
+                    Symbol::ConstArray(alloc, array)
+                }
+            };
+            context.declare(&def.id, symbol);
+        }
+    }
+}
+
+impl ConstDef {
+    fn eval(&self, btype: BType, context: &EmitContext) -> ConstValue {
+        const_eval::eval_def(context.exprs, btype, self, context).unwrap_or_else(|e| panic!("{}", e))
+    }
 }
 
 impl VarDecl {
-    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) {
+    /// Returns the block where code continues, as initializers may branch
+    pub fn emit(&self, func: &mut FunctionData, mut bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        // A static local refers to its global, initialized once before the program starts
+        if self.static_span.is_some() {
+            for def in &self.defs {
+                let global = context.statics[&def.span];
+                context.declare(&def.id, Symbol::Var(global, def.dims.len(), self.btype));
+            }
+            return bb;
+        }
         for def in &self.defs {
-            // alloc i32
-            let alloc = func.dfg_mut().new_value().alloc(Type::get_i32());
+            // alloc i32 (or an array of it)
+            let ty = def.ty(&self.btype, context);
+            let alloc = func.dfg_mut().new_value().alloc(ty.clone());
             func.dfg_mut().set_value_name(alloc, Some(def.id.emit()));
             func.layout_mut().bb_mut(bb).insts_mut().push_key_back(alloc).unwrap();
 
-            // store to var_table
-            context.var_table.insert(def.id.clone(), alloc);
+            // store to the innermost scope
+            context.declare(&def.id, Symbol::Var(alloc, def.dims.len(), self.btype));
+
+            // if has init (or locals are zero-initialized), generate store
+            // Elements without an initializer in a list are set to zero
+            match &def.init {
+                Some(init) => {
+                    let dims = def.dims(context);
+                    for (i, elem) in init.flatten(&dims, &def.id).into_iter().enumerate() {
+                        let value = match elem {
+                            Some(expr) => context.emit_as(expr, self.btype, func, &mut bb),
+                            None => func.dfg_mut().new_value().integer(0),
+                        };
+                        let ptr = element_ptr(func, bb, alloc, &dims, i);
+                        let store = func.dfg_mut().new_value().store(value, ptr);
+                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(store).unwrap();
+                    }
+                }
+                None if context.options.zero_init_locals => zero_fill(func, bb, alloc, &ty),
+                None => {}
+            }
+        }
+        bb
+    }
+}
+
+impl VarDef {
+    /// Array dimensions, outermost first
+    fn dims(&self, context: &EmitContext) -> Vec<usize> {
+        const_eval::eval_dims(context.exprs, &self.dims, &self.id, context).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Type of the defined variable: the base type wrapped in its array dimensions
+    fn ty(&self, btype: &BType, context: &EmitContext) -> Type {
+        array_ty(btype.emit(), &self.dims(context))
+    }
+}
+
+impl InitVal {
+    /// Flatten the initializer of an object with dimensions `dims` into one
+    /// entry per scalar element, in memory order; `None` marks an element
+    /// that is implicitly zero.
+    ///
+    /// A nested list initializes the largest sub-array that starts at the
+    /// current element, e.g. for `int a[2][3]`, `{1, {2}}` is invalid (`{2}`
+    /// would start mid-row) while `{{1, 2}, 3}` sets `a[0][0..2]` and `a[1][0]`.
+    pub fn flatten(&self, dims: &[usize], id: &Ident) -> Vec<Option<ExprId>> {
+        self.try_flatten(dims, id).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`InitVal::flatten`], but returning an error if the shape of the
+    /// initializer does not fit `dims`
+    pub fn try_flatten(&self, dims: &[usize], id: &Ident) -> Result<Vec<Option<ExprId>>, String> {
+        match self {
+            Self::Expr(expr) if dims.is_empty() => Ok(vec![Some(*expr)]),
+            Self::Expr(_) => Err(format!("array initializer must be a list: {}", id)),
+            Self::List(_) if dims.is_empty() => Err(format!("braces around scalar initializer: {}", id)),
+            Self::List(items) => {
+                let mut elems = Vec::new();
+                Self::flatten_list(items, dims, id, &mut elems)?;
+                Ok(elems)
+            }
+        }
+    }
+
+    fn flatten_list(
+        items: &[InitVal],
+        dims: &[usize],
+        id: &Ident,
+        elems: &mut Vec<Option<ExprId>>,
+    ) -> Result<(), String> {
+        let start = elems.len();
+        let total: usize = dims.iter().product();
+        for item in items {
+            match item {
+                Self::Expr(expr) => elems.push(Some(*expr)),
+                Self::List(sub_items) => {
+                    let offset = elems.len() - start;
+                    let sub_dims = (1..dims.len())
+                        .map(|k| &dims[k..])
+                        .find(|sub_dims| offset.is_multiple_of(sub_dims.iter().product::<usize>()))
+                        .ok_or_else(|| format!("misaligned nested initializer list: {}", id))?;
+                    Self::flatten_list(sub_items, sub_dims, id, elems)?;
+                }
+            }
+            if elems.len() - start > total {
+                return Err(format!("excess elements in array initializer: {}", id));
+            }
+        }
+        elems.resize(start + total, None);
+        Ok(())
+    }
+}
+
+/// `base` wrapped in the array dimensions `dims` (outermost first)
+fn array_ty(base: Type, dims: &[usize]) -> Type {
+    dims.iter().rev().fold(base, |ty, &len| Type::get_array(ty, len))
+}
+
+/// Build the constant initializer of a global of type `ty` from its flattened
+/// elements, using `zeroinit` for all-zero sub-arrays
+fn global_init(program: &mut Program, elems: &[i32], ty: &Type) -> Value {
+    match ty.kind() {
+        TypeKind::Array(..) if elems.iter().all(|&elem| elem == 0) => program.new_value().zero_init(ty.clone()),
+        TypeKind::Array(base, len) => {
+            let values = elems
+                .chunks(elems.len() / len)
+                .map(|chunk| global_init(program, chunk, base))
+                .collect();
+            program.new_value().aggregate(values)
+        }
+        _ => program.new_value().integer(elems[0]),
+    }
+}
+
+/// Pointer to the `index`-th scalar element (in memory order) of the object
+/// with dimensions `dims` at `ptr`
+fn element_ptr(func: &mut FunctionData, bb: BasicBlock, ptr: Value, dims: &[usize], index: usize) -> Value {
+    let mut ptr = ptr;
+    let mut stride: usize = dims.iter().product();
+    for &dim in dims {
+        stride /= dim;
+        let i = func.dfg_mut().new_value().integer((index / stride % dim) as i32);
+        ptr = func.dfg_mut().new_value().get_elem_ptr(ptr, i);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(ptr).unwrap();
+    }
+    ptr
+}
+
+/// Convert `value` to `btype` before it is stored: a `char` keeps its low 8
+/// bits, sign-extended, as a shift left and arithmetic shift right by 24
+fn narrow(func: &mut FunctionData, bb: BasicBlock, btype: BType, value: Value) -> Value {
+    if btype != BType::Char {
+        return value;
+    }
+    if let ValueKind::Integer(int) = func.dfg().value(value).kind() {
+        let narrowed = btype.narrow(int.value());
+        return func.dfg_mut().new_value().integer(narrowed);
+    }
+    let shift = func.dfg_mut().new_value().integer(24);
+    let shl = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::Shl, value, shift);
+    func.layout_mut().bb_mut(bb).insts_mut().push_key_back(shl).unwrap();
+    let shift = func.dfg_mut().new_value().integer(24);
+    let sar = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::Sar, shl, shift);
+    func.layout_mut().bb_mut(bb).insts_mut().push_key_back(sar).unwrap();
+    sar
+}
 
-            // if has init, generate store
-            if let Some(init) = &def.init {
-                let value = init.expr.emit(func, bb, context);
-                let store = func.dfg_mut().new_value().store(value, alloc);
-                func.layout_mut().bb_mut(bb).insts_mut().push_key_back(store).unwrap();
+/// Store 0 into every element of the object of type `ty` at `ptr`
+fn zero_fill(func: &mut FunctionData, bb: BasicBlock, ptr: Value, ty: &Type) {
+    match ty.kind() {
+        TypeKind::Array(base, len) => {
+            for i in 0..*len {
+                let index = func.dfg_mut().new_value().integer(i as i32);
+                let elem = func.dfg_mut().new_value().get_elem_ptr(ptr, index);
+                func.layout_mut().bb_mut(bb).insts_mut().push_key_back(elem).unwrap();
+                zero_fill(func, bb, elem, base);
             }
         }
+        _ => {
+            let zero = func.dfg_mut().new_value().integer(0);
+            let store = func.dfg_mut().new_value().store(zero, ptr);
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(store).unwrap();
+        }
+    }
+}
+
+/// Create a new basic block and append it to the function layout
+fn new_bb(func: &mut FunctionData, name: &str) -> BasicBlock {
+    let bb = func.dfg_mut().new_bb().basic_block(Some(name.into()));
+    func.layout_mut().bbs_mut().push_key_back(bb).unwrap();
+    bb
+}
+
+/// Whether the last instruction of a basic block is a terminator
+fn is_terminated(func: &FunctionData, bb: BasicBlock) -> bool {
+    func.layout().bbs().node(&bb).unwrap().insts().back_key().is_some_and(|&inst| {
+        matches!(
+            func.dfg().value(inst).kind(),
+            ValueKind::Return(_) | ValueKind::Jump(_) | ValueKind::Branch(_)
+        )
+    })
+}
+
+/// The block to emit the next statement in: `bb`, or a new block that nothing
+/// jumps to if `bb` already ends with a terminator: code after a `return`,
+/// `break` or `continue` is unreachable, but must not add instructions after
+/// the terminator
+fn open_bb(func: &mut FunctionData, bb: BasicBlock) -> BasicBlock {
+    if is_terminated(func, bb) { new_bb(func, "%unreachable") } else { bb }
+}
+
+/// Whether `value` can only be 0 or 1: a comparison, the constant 0 or 1,
+/// or the `and`/`or` of two such values
+fn is_bool(func: &FunctionData, value: Value) -> bool {
+    use koopa::ir::BinaryOp;
+    match func.dfg().value(value).kind() {
+        ValueKind::Integer(int) => matches!(int.value(), 0 | 1),
+        ValueKind::Binary(binary) => match binary.op() {
+            BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => true,
+            BinaryOp::And | BinaryOp::Or => is_bool(func, binary.lhs()) && is_bool(func, binary.rhs()),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Convert `value` to a truth value at the end of `bb`: `ne value, 0`, unless
+/// it already is 0 or 1. Constants are converted at compile time.
+fn to_bool(func: &mut FunctionData, bb: BasicBlock, value: Value) -> Value {
+    if is_bool(func, value) {
+        return value;
+    }
+    if let ValueKind::Integer(int) = func.dfg().value(value).kind() {
+        let truth = (int.value() != 0) as i32;
+        return func.dfg_mut().new_value().integer(truth);
+    }
+    let zero = func.dfg_mut().new_value().integer(0);
+    let ne = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::NotEq, value, zero);
+    func.layout_mut().bb_mut(bb).insts_mut().push_key_back(ne).unwrap();
+    ne
+}
+
+/// Emit `jump target` at the end of `bb`, unless `bb` already ends with a terminator
+fn jump_if_open(func: &mut FunctionData, bb: BasicBlock, target: BasicBlock) {
+    if !is_terminated(func, bb) {
+        let jump = func.dfg_mut().new_value().jump(target);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(jump).unwrap();
     }
 }
 
 impl Stmt {
-    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) {
+    /// Emit the statement starting in `bb`; returns the block where control continues
+    pub fn emit(&self, func: &mut FunctionData, mut bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
         match self {
-            Self::Return(return_stmt) => return_stmt.emit(func, bb, context),
-            Self::Assign(assign_stmt) => assign_stmt.emit(func, bb, context),
+            Self::Return(return_stmt) => return return_stmt.emit(func, bb, context),
+            Self::Assign(assign_stmt) => return assign_stmt.emit(func, bb, context),
+            Self::Block(block) => return block.emit(func, bb, context),
+            Self::Expr(expr) => {
+                if let Some(expr) = expr {
+                    expr.emit(func, &mut bb, context);
+                }
+            }
+            Self::If(if_stmt) => return if_stmt.emit(func, bb, context),
+            Self::While(while_stmt) => return while_stmt.emit(func, bb, context),
+            Self::For(for_stmt) => return for_stmt.emit(func, bb, context),
+            Self::DoWhile(do_while) => return do_while.emit(func, bb, context),
+            Self::Switch(switch) => return switch.emit(func, bb, context),
+            Self::Break(_) => {
+                let (_, end) = *context.loops.last().expect("break outside of a loop");
+                jump_if_open(func, bb, end);
+            }
+            Self::Continue(_) => {
+                let cond = context.loops.iter().rev().find_map(|&(cond, _)| cond);
+                jump_if_open(func, bb, cond.expect("continue outside of a loop"));
+            }
         }
+        bb
     }
 }
 
 impl ReturnStmt {
-    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) {
-        let value = self.expr.emit(func, bb, context);
-        let ret_stmt = func.dfg_mut().new_value().ret(Some(value));
+    pub fn emit(&self, func: &mut FunctionData, mut bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        let value = self.expr.map(|expr| {
+            let value = expr.emit(func, &mut bb, context);
+            context.convert(func, bb, value, context.is_float(expr), context.returns_float)
+        });
+        let ret_stmt = func.dfg_mut().new_value().ret(value);
         func.layout_mut().bb_mut(bb).insts_mut().push_key_back(ret_stmt).unwrap();
+        bb
     }
 }
 
 impl AssignStmt {
-    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) {
-        let addr = *context.var_table.get(&self.lval.ident).unwrap();
-        let value = self.expr.emit(func, bb, context);
+    pub fn emit(&self, func: &mut FunctionData, mut bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        let Some(symbol) = context.lookup(&self.lval) else {
+            self.expr.emit(func, &mut bb, context);
+            return bb;
+        };
+        let btype = match &symbol {
+            Symbol::ConstArray(..) => panic!("Cannot assign to constant: {}", self.lval.ident),
+            Symbol::Var(_, _, btype) | Symbol::Ptr(_, _, btype) => *btype,
+            Symbol::Const(_) => BType::Int,
+        };
+        let addr = self.lval.emit_addr(&symbol, func, &mut bb, context);
+        let value = context.emit_as(self.expr, btype, func, &mut bb);
         let store = func.dfg_mut().new_value().store(value, addr);
         func.layout_mut().bb_mut(bb).insts_mut().push_key_back(store).unwrap();
+        bb
+    }
+}
+
+impl IfStmt {
+    pub fn emit(&self, func: &mut FunctionData, mut bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        let cond = self.cond.emit_cond(func, &mut bb, context);
+        let then_bb = new_bb(func, "%then");
+        let else_bb = self.else_stmt.as_ref().map(|_| new_bb(func, "%else"));
+        let end_bb = new_bb(func, "%end");
+
+        let branch = func.dfg_mut().new_value().branch(cond, then_bb, else_bb.unwrap_or(end_bb));
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(branch).unwrap();
+
+        let then_end = self.then_stmt.emit(func, then_bb, context);
+        jump_if_open(func, then_end, end_bb);
+
+        if let (Some(else_stmt), Some(else_bb)) = (&self.else_stmt, else_bb) {
+            let else_end = else_stmt.emit(func, else_bb, context);
+            jump_if_open(func, else_end, end_bb);
+        }
+
+        // keep the layout in source order: the join block goes after both arms
+        func.layout_mut().bbs_mut().remove(&end_bb);
+        func.layout_mut().bbs_mut().push_key_back(end_bb).unwrap();
+        end_bb
+    }
+}
+
+impl WhileStmt {
+    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        let cond_bb = new_bb(func, "%while_cond");
+        jump_if_open(func, bb, cond_bb);
+
+        let mut cond_end = cond_bb;
+        let cond = self.cond.emit_cond(func, &mut cond_end, context);
+        let body_bb = new_bb(func, "%while_body");
+        let end_bb = new_bb(func, "%while_end");
+        let branch = func.dfg_mut().new_value().branch(cond, body_bb, end_bb);
+        func.layout_mut().bb_mut(cond_end).insts_mut().push_key_back(branch).unwrap();
+
+        context.loops.push((Some(cond_bb), end_bb));
+        let body_end = self.body.emit(func, body_bb, context);
+        context.loops.pop();
+        jump_if_open(func, body_end, cond_bb);
+
+        func.layout_mut().bbs_mut().remove(&end_bb);
+        func.layout_mut().bbs_mut().push_key_back(end_bb).unwrap();
+        end_bb
+    }
+}
+
+impl DoWhileStmt {
+    /// The body comes first and the condition is checked at its end, which
+    /// is where `continue` goes
+    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        let body_bb = new_bb(func, "%do_body");
+        jump_if_open(func, bb, body_bb);
+        let cond_bb = new_bb(func, "%do_cond");
+        let end_bb = new_bb(func, "%do_end");
+
+        context.loops.push((Some(cond_bb), end_bb));
+        let body_end = self.body.emit(func, body_bb, context);
+        context.loops.pop();
+        jump_if_open(func, body_end, cond_bb);
+
+        let mut cond_end = cond_bb;
+        let cond = self.cond.emit_cond(func, &mut cond_end, context);
+        let branch = func.dfg_mut().new_value().branch(cond, body_bb, end_bb);
+        func.layout_mut().bb_mut(cond_end).insts_mut().push_key_back(branch).unwrap();
+
+        // keep the layout in source order: the check and the exit after the body
+        for bb in [cond_bb, end_bb] {
+            func.layout_mut().bbs_mut().remove(&bb);
+            func.layout_mut().bbs_mut().push_key_back(bb).unwrap();
+        }
+        end_bb
+    }
+}
+
+impl SwitchStmt {
+    /// Lowered to a chain of comparisons, one per `case` in source order,
+    /// that jumps to the block of the matching label (or to `default`). Each
+    /// label starts a new block, which the code before it falls through to.
+    pub fn emit(&self, func: &mut FunctionData, mut bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        let value = self.expr.emit(func, &mut bb, context);
+        // The label blocks are laid out when their code is emitted, to keep
+        // the layout in source order
+        let detached_bb = |func: &mut FunctionData, name: &str| func.dfg_mut().new_bb().basic_block(Some(name.into()));
+        let label_bbs: Vec<BasicBlock> = self
+            .labels
+            .iter()
+            .map(|label| detached_bb(func, if label.value.is_some() { "%case" } else { "%default" }))
+            .collect();
+        let end_bb = detached_bb(func, "%switch_end");
+
+        let mut cases = HashSet::new();
+        let mut default = None;
+        for (label, &label_bb) in self.labels.iter().zip(&label_bbs) {
+            let Some(case) = label.value else {
+                assert!(default.replace(label_bb).is_none(), "Multiple default labels in switch");
+                continue;
+            };
+            let case = case.eval_const(context);
+            assert!(cases.insert(case), "Duplicate case value in switch: {}", case);
+            let case = func.dfg_mut().new_value().integer(case);
+            let eq = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::Eq, value, case);
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(eq).unwrap();
+            let next_bb = new_bb(func, "%switch_next");
+            let branch = func.dfg_mut().new_value().branch(eq, label_bb, next_bb);
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(branch).unwrap();
+            bb = next_bb;
+        }
+        jump_if_open(func, bb, default.unwrap_or(end_bb));
+
+        // Code before the first label is never executed, but still needs a
+        // block to go in
+        if self.labels.first().is_none_or(|label| label.position > 0) {
+            bb = new_bb(func, "%switch_body");
+        }
+        context.loops.push((None, end_bb));
+        context.enter_scope();
+        let mut labels = self.labels.iter().map(|label| label.position).zip(label_bbs).peekable();
+        for i in 0..=self.body.items.len() {
+            while let Some((_, label_bb)) = labels.next_if(|&(position, _)| position == i) {
+                jump_if_open(func, bb, label_bb);
+                func.layout_mut().bbs_mut().push_key_back(label_bb).unwrap();
+                bb = label_bb;
+            }
+            if let Some(item) = self.body.items.get(i) {
+                bb = item.emit(func, open_bb(func, bb), context);
+            }
+        }
+        context.exit_scope();
+        context.loops.pop();
+
+        jump_if_open(func, bb, end_bb);
+        func.layout_mut().bbs_mut().push_key_back(end_bb).unwrap();
+        end_bb
+    }
+}
+
+impl ForStmt {
+    /// Lowered like `while`, with the step in its own block, which is where
+    /// `continue` goes
+    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        context.enter_scope();
+        let bb = match &self.init {
+            Some(ForInit::Decl(decl)) => decl.emit(func, bb, context),
+            Some(ForInit::Step(step)) => step.emit(func, bb, context),
+            None => bb,
+        };
+
+        let cond_bb = new_bb(func, "%for_cond");
+        jump_if_open(func, bb, cond_bb);
+        let body_bb = new_bb(func, "%for_body");
+        let step_bb = new_bb(func, "%for_step");
+        let end_bb = new_bb(func, "%for_end");
+        match self.cond {
+            Some(cond) => {
+                let mut cond_end = cond_bb;
+                let cond = cond.emit_cond(func, &mut cond_end, context);
+                let branch = func.dfg_mut().new_value().branch(cond, body_bb, end_bb);
+                func.layout_mut().bb_mut(cond_end).insts_mut().push_key_back(branch).unwrap();
+            }
+            None => jump_if_open(func, cond_bb, body_bb),
+        }
+
+        context.loops.push((Some(step_bb), end_bb));
+        let body_end = self.body.emit(func, body_bb, context);
+        context.loops.pop();
+        jump_if_open(func, body_end, step_bb);
+
+        let step_end = match &self.step {
+            Some(step) => step.emit(func, step_bb, context),
+            None => step_bb,
+        };
+        jump_if_open(func, step_end, cond_bb);
+        context.exit_scope();
+
+        // keep the layout in source order: the step and the exit after the body
+        for bb in [step_bb, end_bb] {
+            func.layout_mut().bbs_mut().remove(&bb);
+            func.layout_mut().bbs_mut().push_key_back(bb).unwrap();
+        }
+        end_bb
+    }
+}
+
+impl ForStep {
+    pub fn emit(&self, func: &mut FunctionData, mut bb: BasicBlock, context: &mut EmitContext) -> BasicBlock {
+        match self {
+            Self::Assign(assign) => assign.emit(func, bb, context),
+            Self::Expr(expr) => {
+                expr.emit(func, &mut bb, context);
+                bb
+            }
+        }
     }
 }
 
 impl Expr {
-    pub fn emit(&self, func: &mut FunctionData, bb: BasicBlock, context: &EmitContext) -> Value {
+    pub fn emit(&self, func: &mut FunctionData, bb: &mut BasicBlock, context: &EmitContext) -> Value {
         match self {
             Self::Number(number) => func.dfg_mut().new_value().integer(*number),
+            Self::Char(c) => func.dfg_mut().new_value().integer(*c as i32),
+            Self::Float(value) => func.dfg_mut().new_value().integer(value.to_bits() as i32),
             Self::Unary(unary_op, expr) => {
                 let value = expr.emit(func, bb, context);
+                let float = context.is_float(*expr);
 
                 match unary_op {
                     // +x => x
                     UnaryOp::Plus => value,
 
+                    // -x => fneg(x) for a float
+                    UnaryOp::Minus if float => context.float_op(func, *bb, FloatOp::Neg, vec![value]),
+
                     // -x => 0 - x
                     UnaryOp::Minus => {
                         let zero = func.dfg_mut().new_value().integer(0);
                         let sub = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::Sub, zero, value);
-                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(sub).unwrap();
+                        func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(sub).unwrap();
                         sub
                     }
 
+                    // !x => feq(x, 0.0) for a float
+                    UnaryOp::Not if float => {
+                        let zero = func.dfg_mut().new_value().integer(0);
+                        context.float_op(func, *bb, FloatOp::Eq, vec![value, zero])
+                    }
+
                     // !x => x == 0
                     UnaryOp::Not => {
                         let zero = func.dfg_mut().new_value().integer(0);
                         let eq = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::Eq, value, zero);
-                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(eq).unwrap();
+                        func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(eq).unwrap();
                         eq
                     }
                 }
@@ -184,49 +1448,232 @@ impl Expr {
             Self::Binary(lhs, op, rhs) => {
                 let lhs_val = lhs.emit(func, bb, context);
                 let rhs_val = rhs.emit(func, bb, context);
+                let (lhs_float, rhs_float) = (context.is_float(*lhs), context.is_float(*rhs));
 
                 // Special handling for logical operators (Koopa IR only has bitwise Or/And)
                 match op {
                     BinaryOp::Or => {
-                        // a || b => (a | b) != 0
+                        // a || b => (a | b) != 0, where the comparison is
+                        // only needed if a or b may be neither 0 nor 1; a
+                        // float operand is compared against 0.0 first
+                        let lhs_val = if lhs_float { context.truth(func, *bb, *lhs, lhs_val) } else { lhs_val };
+                        let rhs_val = if rhs_float { context.truth(func, *bb, *rhs, rhs_val) } else { rhs_val };
                         let or_val = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::Or, lhs_val, rhs_val);
-                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(or_val).unwrap();
-                        let zero = func.dfg_mut().new_value().integer(0);
-                        let result = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::NotEq, or_val, zero);
-                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(result).unwrap();
-                        result
+                        func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(or_val).unwrap();
+                        to_bool(func, *bb, or_val)
                     }
                     BinaryOp::And => {
                         // a && b => (a != 0) & (b != 0)
-                        let zero = func.dfg_mut().new_value().integer(0);
-                        let lhs_bool = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::NotEq, lhs_val, zero);
-                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(lhs_bool).unwrap();
-                        let zero2 = func.dfg_mut().new_value().integer(0);
-                        let rhs_bool = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::NotEq, rhs_val, zero2);
-                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(rhs_bool).unwrap();
+                        let lhs_bool = context.truth(func, *bb, *lhs, lhs_val);
+                        let rhs_bool = context.truth(func, *bb, *rhs, rhs_val);
                         let result = func.dfg_mut().new_value().binary(koopa::ir::BinaryOp::And, lhs_bool, rhs_bool);
-                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(result).unwrap();
+                        func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(result).unwrap();
                         result
                     }
+                    // With a float operand, both are converted to float
+                    _ if lhs_float || rhs_float => {
+                        let lhs_val = context.convert(func, *bb, lhs_val, lhs_float, true);
+                        let rhs_val = context.convert(func, *bb, rhs_val, rhs_float, true);
+                        context.float_binary(func, *bb, *op, lhs_val, rhs_val)
+                    }
                     _ => {
                         let ir_op = op.emit();
                         let value = func.dfg_mut().new_value().binary(ir_op, lhs_val, rhs_val);
-                        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(value).unwrap();
+                        func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(value).unwrap();
                         value
                     }
                 }
             }
-            Self::LVal(lval) => {
-                // first check const_table
-                if let Some(expr) = context.const_table.get(&lval.ident) {
-                    return expr.expr.emit(func, bb, context);
-                }
-                // then check var_table
-                let addr = *context.var_table.get(&lval.ident).unwrap();
-                let load = func.dfg_mut().new_value().load(addr);
-                func.layout_mut().bb_mut(bb).insts_mut().push_key_back(load).unwrap();
+            Self::Call(ident, args) => {
+                let callee = *context.functions
+                    .get(ident)
+                    .unwrap_or_else(|| panic!("Undefined function: {}", ident));
+                // Arguments are converted to the types of float parameters and back
+                let signature = context.float_signatures.get(ident);
+                let mut args: Vec<Value> = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &arg)| {
+                        let value = arg.emit(func, bb, context);
+                        let to_float = signature.is_some_and(|signature| signature.params.get(i) == Some(&true));
+                        context.convert(func, *bb, value, context.is_float(arg), to_float)
+                    })
+                    .collect();
+                if let Some(&arity) = context.varargs.get(ident) {
+                    while args.len() < arity {
+                        args.push(func.dfg_mut().new_value().integer(0));
+                    }
+                }
+                let call = func.dfg_mut().new_value().call(callee, args);
+                func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(call).unwrap();
+                call
+            }
+            Self::Bitwise(lhs, op, rhs, _) => {
+                let lhs = lhs.emit(func, bb, context);
+                let rhs = rhs.emit(func, bb, context);
+                let value = func.dfg_mut().new_value().binary(op.emit(), lhs, rhs);
+                func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(value).unwrap();
+                value
+            }
+            Self::Ternary(cond, then_expr, else_expr, _) => {
+                let cond = cond.emit_cond(func, bb, context);
+                let then_bb = new_bb(func, "%cond_then");
+                let else_bb = new_bb(func, "%cond_else");
+                let end_bb = new_bb(func, "%cond_end");
+                let branch = func.dfg_mut().new_value().branch(cond, then_bb, else_bb);
+                func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(branch).unwrap();
+
+                // Only the taken arm is evaluated; it leaves its value in a
+                // temporary, which is typed after the arms (an array arm
+                // decays to a pointer) and allocated in the entry block. If
+                // either arm is a float, both are converted to float.
+                let float = context.is_float(*then_expr) || context.is_float(*else_expr);
+                let mut result = None;
+                for (arm, arm_bb) in [(then_expr, then_bb), (else_expr, else_bb)] {
+                    let mut arm_end = arm_bb;
+                    let value = arm.emit(func, &mut arm_end, context);
+                    let value = context.convert(func, arm_end, value, context.is_float(*arm), float);
+                    let slot = *result.get_or_insert_with(|| {
+                        let ty = func.dfg().value(value).ty().clone();
+                        let slot = func.dfg_mut().new_value().alloc(ty);
+                        let entry = func.layout().entry_bb().unwrap();
+                        func.layout_mut().bb_mut(entry).insts_mut().push_key_front(slot).unwrap();
+                        slot
+                    });
+                    let store = func.dfg_mut().new_value().store(value, slot);
+                    func.layout_mut().bb_mut(arm_end).insts_mut().push_key_back(store).unwrap();
+                    jump_if_open(func, arm_end, end_bb);
+                }
+
+                func.layout_mut().bbs_mut().remove(&end_bb);
+                func.layout_mut().bbs_mut().push_key_back(end_bb).unwrap();
+                *bb = end_bb;
+                let load = func.dfg_mut().new_value().load(result.unwrap());
+                func.layout_mut().bb_mut(end_bb).insts_mut().push_key_back(load).unwrap();
                 load
             }
+            Self::Str(_) => unreachable!("String literals are emitted through their ExprId"),
+            Self::LVal(lval) => {
+                // Constants, and constant arrays indexed by constants, fold to their value
+                if let Ok(value) = const_eval::eval_lval(context.exprs, lval, context) {
+                    return func.dfg_mut().new_value().integer(value.bits());
+                }
+                let Some(symbol) = context.lookup(lval) else {
+                    return func.dfg_mut().new_value().integer(0);
+                };
+                let rank = match &symbol {
+                    Symbol::Const(_) => panic!("Cannot index a scalar constant: {}", lval.ident),
+                    Symbol::ConstArray(_, array) => array.dims.len(),
+                    Symbol::Var(_, rank, _) | Symbol::Ptr(_, rank, _) => *rank,
+                };
+                if lval.indices.len() == rank {
+                    let addr = lval.emit_addr(&symbol, func, bb, context);
+                    let load = func.dfg_mut().new_value().load(addr);
+                    func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(load).unwrap();
+                    load
+                } else if matches!(symbol, Symbol::Ptr(..)) && lval.indices.is_empty() {
+                    // An unindexed array parameter already is a pointer
+                    lval.emit_addr(&symbol, func, bb, context)
+                } else {
+                    // A partially indexed array decays to a pointer to its first element
+                    let addr = lval.emit_addr(&symbol, func, bb, context);
+                    let zero = func.dfg_mut().new_value().integer(0);
+                    let ptr = func.dfg_mut().new_value().get_elem_ptr(addr, zero);
+                    func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(ptr).unwrap();
+                    ptr
+                }
+            }
+        }
+    }
+}
+
+impl ExprId {
+    pub fn emit(self, func: &mut FunctionData, bb: &mut BasicBlock, context: &EmitContext) -> Value {
+        let value = match &context.exprs[self] {
+            // A string literal is the address of its first word
+            Expr::Str(_) => {
+                let zero = func.dfg_mut().new_value().integer(0);
+                let ptr = func.dfg_mut().new_value().get_elem_ptr(context.strings[&self], zero);
+                func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(ptr).unwrap();
+                ptr
+            }
+            // Constant subexpressions, such as `2 * 3 + 4`, `-1` or `N - 1`
+            // for a constant `N`, fold to a single integer (a float to its
+            // bits). Anything the evaluator rejects, such as a division by
+            // zero, is left to run.
+            expr => match const_eval::eval_scalar(context.exprs, self, context) {
+                Ok(value) => func.dfg_mut().new_value().integer(value.bits()),
+                Err(_) => expr.emit(func, bb, context),
+            },
+        };
+        context.locations.borrow_mut().record(value, context.exprs.span(self));
+        value
+    }
+
+    /// Emit a branch condition as a truth value, 0 or 1, as C tests a
+    /// scalar: `if (x)` branches on `ne x, 0`
+    pub fn emit_cond(self, func: &mut FunctionData, bb: &mut BasicBlock, context: &EmitContext) -> Value {
+        let value = self.emit(func, bb, context);
+        context.truth(func, *bb, self, value)
+    }
+
+    /// Evaluate a constant expression at compile time
+    pub fn eval_const(self, context: &EmitContext) -> i32 {
+        const_eval::eval(context.exprs, self, context).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Evaluate a constant expression of either type at compile time
+    pub fn eval_scalar(self, context: &EmitContext) -> Scalar {
+        const_eval::eval_scalar(context.exprs, self, context).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl LVal {
+    /// Emit the address the lvalue refers to, given what its name refers to,
+    /// indexing into arrays with `getelemptr`, and into array parameters with
+    /// `getptr` for the first index
+    fn emit_addr(
+        &self,
+        symbol: &Symbol,
+        func: &mut FunctionData,
+        bb: &mut BasicBlock,
+        context: &EmitContext,
+    ) -> Value {
+        let mut indices = self.indices.iter();
+        let mut addr = match *symbol {
+            Symbol::Var(addr, ..) | Symbol::ConstArray(addr, _) => addr,
+            Symbol::Ptr(slot, ..) => {
+                let ptr = func.dfg_mut().new_value().load(slot);
+                func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(ptr).unwrap();
+                match indices.next() {
+                    Some(index) => {
+                        let index = index.emit(func, bb, context);
+                        let ptr = func.dfg_mut().new_value().get_ptr(ptr, index);
+                        func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(ptr).unwrap();
+                        ptr
+                    }
+                    None => ptr,
+                }
+            }
+            Symbol::Const(_) => panic!("Cannot assign to constant: {}", self.ident),
+        };
+        for index in indices {
+            let index = index.emit(func, bb, context);
+            addr = func.dfg_mut().new_value().get_elem_ptr(addr, index);
+            func.layout_mut().bb_mut(*bb).insts_mut().push_key_back(addr).unwrap();
+        }
+        addr
+    }
+}
+
+impl BitOp {
+    pub fn emit(&self) -> koopa::ir::BinaryOp {
+        match self {
+            Self::And => koopa::ir::BinaryOp::And,
+            Self::Or => koopa::ir::BinaryOp::Or,
+            Self::Xor => koopa::ir::BinaryOp::Xor,
+            Self::Shl => koopa::ir::BinaryOp::Shl,
+            Self::Shr => koopa::ir::BinaryOp::Sar,
         }
     }
 }
@@ -1,15 +1,148 @@
+pub mod const_eval;
 pub mod emit;
 
 use std::fmt::{self, Display};
+use std::ops::{Index, IndexMut};
+
+/// Byte range `[start, end)` of a syntax node in the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+/// Text the lexer cannot read, or C syntax that the parser recognizes only to
+/// reject it, with the reason; returned as the `ParseError::User` error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub span: Span,
+    pub message: &'static str,
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
 
 #[derive(Debug)]
 pub struct CompUnit {
-    pub func_def: FuncDef,
+    pub items: Vec<GlobalItem>,
+    /// All expressions of the unit; the items refer to them by `ExprId`
+    pub exprs: ExprArena,
 }
 
 impl Display for CompUnit {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.func_def)
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", self.exprs.show(item))?;
+        }
+        Ok(())
+    }
+}
+
+/// Index of an expression in the `ExprArena` of its compilation unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(u32);
+
+/// Storage for the expressions of a compilation unit.
+///
+/// Expressions are allocated bottom-up by the parser, so the operands of an
+/// expression always come before it.
+#[derive(Debug, Default)]
+pub struct ExprArena {
+    exprs: Vec<Expr>,
+    /// Source span of each expression, by `ExprId`
+    spans: Vec<Span>,
+}
+
+impl ExprArena {
+    pub fn alloc(&mut self, expr: Expr, span: Span) -> ExprId {
+        let id = ExprId(self.exprs.len() as u32);
+        self.exprs.push(expr);
+        self.spans.push(span);
+        id
+    }
+
+    /// The source text the expression was parsed from (without enclosing
+    /// parentheses)
+    pub fn span(&self, id: ExprId) -> Span {
+        self.spans[id.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.exprs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exprs.is_empty()
+    }
+
+    /// All expressions, in allocation order
+    pub fn iter(&self) -> impl Iterator<Item = (ExprId, &Expr)> {
+        self.exprs.iter().enumerate().map(|(i, expr)| (ExprId(i as u32), expr))
+    }
+
+    /// Display an AST node, looking up the expressions it refers to here
+    pub fn show<'a, T: Show + ?Sized>(&'a self, node: &'a T) -> Shown<'a, T> {
+        Shown { node, exprs: self }
+    }
+}
+
+impl Index<ExprId> for ExprArena {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        &self.exprs[id.0 as usize]
+    }
+}
+
+impl IndexMut<ExprId> for ExprArena {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.exprs[id.0 as usize]
+    }
+}
+
+/// Formatting of AST nodes that contain expressions, which can only be
+/// printed together with the arena they live in
+pub trait Show {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+/// An AST node paired with its expression arena, see `ExprArena::show`
+pub struct Shown<'a, T: ?Sized> {
+    node: &'a T,
+    exprs: &'a ExprArena,
+}
+
+impl<T: Show + ?Sized> Display for Shown<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.node.show(self.exprs, f)
+    }
+}
+
+/// A top-level item of a compilation unit
+#[derive(Debug)]
+pub enum GlobalItem {
+    Decl(Decl),
+    FuncDef(FuncDef),
+}
+
+impl Show for GlobalItem {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decl(decl) => decl.show(exprs, f),
+            Self::FuncDef(func_def) => func_def.show(exprs, f),
+        }
     }
 }
 
@@ -17,12 +150,47 @@ impl Display for CompUnit {
 pub struct FuncDef {
     pub func_type: FuncType,
     pub id: Ident,
+    pub params: Vec<FuncFParam>,
     pub block: Block,
+    /// Span of the function header, from the return type to the `)`
+    pub span: Span,
+    /// Span of the function name
+    pub name_span: Span,
 }
 
-impl Display for FuncDef {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} {}() {}", self.func_type, self.id, self.block)
+impl Show for FuncDef {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}(", self.func_type, self.id)?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            param.show(exprs, f)?;
+        }
+        write!(f, ") {}", exprs.show(&self.block))
+    }
+}
+
+#[derive(Debug)]
+pub struct FuncFParam {
+    pub btype: BType,
+    pub id: Ident,
+    /// For an array parameter, the dimensions after the omitted first one
+    pub dims: Option<Vec<ConstExpr>>,
+    /// Span of the parameter name
+    pub span: Span,
+}
+
+impl Show for FuncFParam {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.btype, self.id)?;
+        if let Some(dims) = &self.dims {
+            write!(f, "[]")?;
+            for dim in dims {
+                write!(f, "[{}]", exprs.show(dim))?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -30,12 +198,16 @@ impl Display for FuncDef {
 #[derive(Debug)]
 pub enum FuncType {
     Int,
+    Float,
+    Void,
 }
 
 impl Display for FuncType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Int => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::Void => write!(f, "void"),
         }
     }
 }
@@ -45,11 +217,11 @@ pub struct Block {
     pub items: Vec<BlockItem>,
 }
 
-impl Display for Block {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Show for Block {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{{")?;
         for item in &self.items {
-            writeln!(f, "{}", item)?;
+            writeln!(f, "{}", exprs.show(item))?;
         }
         write!(f, "}}")
     }
@@ -62,37 +234,209 @@ impl Display for Block {
 pub enum Stmt {
     Return(ReturnStmt),
     Assign(AssignStmt),
-}
-
-impl Display for Stmt {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    Block(Block),
+    /// Expression statement; `None` for the empty statement `;`
+    Expr(Option<ExprId>),
+    If(IfStmt),
+    While(WhileStmt),
+    For(ForStmt),
+    DoWhile(DoWhileStmt),
+    Switch(SwitchStmt),
+    /// The span is that of the keyword
+    Break(Span),
+    Continue(Span),
+}
+
+impl Show for Stmt {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Return(return_stmt) => write!(f, "{}", return_stmt),
-            Self::Assign(assign_stmt) => write!(f, "{}", assign_stmt),
+            Self::Return(return_stmt) => return_stmt.show(exprs, f),
+            Self::Assign(assign_stmt) => assign_stmt.show(exprs, f),
+            Self::Block(block) => block.show(exprs, f),
+            Self::Expr(Some(expr)) => write!(f, "{};", exprs.show(expr)),
+            Self::Expr(None) => write!(f, ";"),
+            Self::If(if_stmt) => if_stmt.show(exprs, f),
+            Self::While(while_stmt) => while_stmt.show(exprs, f),
+            Self::For(for_stmt) => for_stmt.show(exprs, f),
+            Self::DoWhile(do_while) => do_while.show(exprs, f),
+            Self::Switch(switch) => switch.show(exprs, f),
+            Self::Break(_) => write!(f, "break;"),
+            Self::Continue(_) => write!(f, "continue;"),
         }
     }
 }
 
 #[derive(Debug)]
 pub struct ReturnStmt {
-    pub expr: Expr,
+    /// `None` for `return;` in a `void` function
+    pub expr: Option<ExprId>,
+    /// Span of the `return` keyword
+    pub span: Span,
 }
 
-impl Display for ReturnStmt {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "return {};", self.expr)
+impl Show for ReturnStmt {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.expr {
+            Some(expr) => write!(f, "return {};", exprs.show(expr)),
+            None => write!(f, "return;"),
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct AssignStmt {
     pub lval: LVal,
-    pub expr: Expr,
+    pub expr: ExprId,
 }
 
-impl Display for AssignStmt {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} = {};", self.lval, self.expr)
+impl Show for AssignStmt {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {};", exprs.show(&self.lval), exprs.show(&self.expr))
+    }
+}
+
+#[derive(Debug)]
+pub struct IfStmt {
+    pub cond: ExprId,
+    pub cond_span: Span,
+    pub then_stmt: Box<Stmt>,
+    pub else_stmt: Option<Box<Stmt>>,
+}
+
+impl Show for IfStmt {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "if ({}) {}", exprs.show(&self.cond), exprs.show(&*self.then_stmt))?;
+        if let Some(else_stmt) = &self.else_stmt {
+            write!(f, " else {}", exprs.show(&**else_stmt))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct WhileStmt {
+    pub cond: ExprId,
+    pub cond_span: Span,
+    pub body: Box<Stmt>,
+}
+
+impl Show for WhileStmt {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "while ({}) {}", exprs.show(&self.cond), exprs.show(&*self.body))
+    }
+}
+
+/// `do body while (cond);`, an extension enabled with `--ext=do-while`
+#[derive(Debug)]
+pub struct DoWhileStmt {
+    pub body: Box<Stmt>,
+    pub cond: ExprId,
+    pub cond_span: Span,
+    /// Span of the `do` keyword
+    pub span: Span,
+}
+
+impl Show for DoWhileStmt {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "do {} while ({});", exprs.show(&*self.body), exprs.show(&self.cond))
+    }
+}
+
+/// `switch (expr) { ... }`, an extension enabled with `--ext=switch`. The
+/// body is a block whose items may be preceded by `case`/`default` labels
+#[derive(Debug)]
+pub struct SwitchStmt {
+    pub expr: ExprId,
+    /// The body, without the labels
+    pub body: Block,
+    /// The labels in source order
+    pub labels: Vec<CaseLabel>,
+    /// Span of the `switch` keyword
+    pub span: Span,
+}
+
+impl Show for SwitchStmt {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "switch ({}) {{", exprs.show(&self.expr))?;
+        let mut labels = self.labels.iter().peekable();
+        for i in 0..=self.body.items.len() {
+            while let Some(label) = labels.next_if(|label| label.position == i) {
+                match &label.value {
+                    Some(value) => writeln!(f, "case {}:", exprs.show(value))?,
+                    None => writeln!(f, "default:")?,
+                }
+            }
+            if let Some(item) = self.body.items.get(i) {
+                writeln!(f, "{}", exprs.show(item))?;
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+/// A `case value:` or `default:` label in a `switch`
+#[derive(Debug)]
+pub struct CaseLabel {
+    /// The constant expression of a `case`; `None` for `default`
+    pub value: Option<ExprId>,
+    /// Index of the item of the body that the label precedes (the number of
+    /// items for a label at the end)
+    pub position: usize,
+    /// Span of the whole label
+    pub span: Span,
+}
+
+/// `for (init; cond; step) body`, an extension enabled with `--ext=for`
+#[derive(Debug)]
+pub struct ForStmt {
+    pub init: Option<ForInit>,
+    /// `None` if omitted: the loop only exits through `break` or `return`
+    pub cond: Option<ExprId>,
+    pub step: Option<ForStep>,
+    pub body: Box<Stmt>,
+    /// Span of the `for` keyword
+    pub span: Span,
+}
+
+impl Show for ForStmt {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "for (")?;
+        match &self.init {
+            Some(ForInit::Decl(decl)) => decl.show(exprs, f)?,
+            Some(ForInit::Step(step)) => write!(f, "{};", exprs.show(step))?,
+            None => write!(f, ";")?,
+        }
+        if let Some(cond) = &self.cond {
+            write!(f, " {}", exprs.show(cond))?;
+        }
+        write!(f, ";")?;
+        if let Some(step) = &self.step {
+            write!(f, " {}", exprs.show(step))?;
+        }
+        write!(f, ") {}", exprs.show(&*self.body))
+    }
+}
+
+/// The first clause of a `for` loop; a declaration is scoped to the loop
+#[derive(Debug)]
+pub enum ForInit {
+    Decl(VarDecl),
+    Step(ForStep),
+}
+
+/// An assignment or expression in the header of a `for` loop (without `;`)
+#[derive(Debug)]
+pub enum ForStep {
+    Assign(AssignStmt),
+    Expr(ExprId),
+}
+
+impl Show for ForStep {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Assign(assign) => write!(f, "{} = {}", exprs.show(&assign.lval), exprs.show(&assign.expr)),
+            Self::Expr(expr) => write!(f, "{}", exprs.show(expr)),
+        }
     }
 }
 
@@ -107,6 +451,40 @@ impl From<String> for Ident {
     }
 }
 
+/// Resolve the escape sequences of a string literal (without its quotes)
+pub fn unescape(literal: &str) -> String {
+    let mut string = String::new();
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some('t') => string.push('\t'),
+            Some('r') => string.push('\r'),
+            Some('0') => string.push('\0'),
+            // `\\`, `\"`, `\'` and unknown escapes stand for the character itself
+            Some(c) => string.push(c),
+            None => string.push('\\'),
+        }
+    }
+    string
+}
+
+/// Value of a hexadecimal floating-point literal such as `0x1.8p3`
+pub fn parse_hex_float(literal: &str) -> f32 {
+    let (mantissa, exp) = literal[2..]
+        .split_once(['p', 'P'])
+        .expect("Hexadecimal float literal without an exponent");
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = int.chars().chain(frac.chars());
+    let value = digits.fold(0.0, |value: f64, digit| value * 16.0 + digit.to_digit(16).unwrap() as f64);
+    let exp: i32 = exp.parse().unwrap();
+    (value * 2f64.powi(exp - 4 * frac.len() as i32)) as f32
+}
+
 impl Display for Ident {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.value)
@@ -116,52 +494,81 @@ impl Display for Ident {
 #[derive(Debug, Clone)]
 pub struct LVal {
     pub ident: Ident,
-    pub indices: Vec<Expr>,
+    pub indices: Vec<ExprId>,
+    /// Span of the name and its indices
+    pub span: Span,
 }
 
-impl Display for LVal {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Show for LVal {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.ident)?;
         for idx in &self.indices {
-            write!(f, "[{}]", idx)?;
+            write!(f, "[{}]", exprs.show(idx))?;
         }
         Ok(())
     }
 }
 
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Expr {
     Number(i32),
-    Unary(UnaryOp, Box<Expr>),
-    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    Float(f32),
+    Unary(UnaryOp, ExprId),
+    Binary(ExprId, BinaryOp, ExprId),
     LVal(LVal),
-}
-
-impl Clone for Expr {
-    fn clone(&self) -> Self {
-        match self {
-            Self::Number(number) => Self::Number(*number),
-            Self::Unary(unary_op, expr) => Self::Unary(*unary_op, expr.clone()),
-            Self::Binary(lhs, op, rhs) => Self::Binary(lhs.clone(), *op, rhs.clone()),
-            Self::LVal(lval) => Self::LVal(lval.clone()),
-        }
-    }
-}
-
-impl Display for Expr {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    Call(Ident, Vec<ExprId>),
+    /// A bitwise operator, an extension enabled with `--ext=bitwise`; the
+    /// span is that of the operator
+    Bitwise(ExprId, BitOp, ExprId, Span),
+    /// `cond ? then : else`, an extension enabled with `--ext=ternary`; the
+    /// span is that of the `?`
+    Ternary(ExprId, ExprId, ExprId, Span),
+    /// A string literal, with escapes already resolved; only allowed as the
+    /// format argument of `putf`
+    Str(String),
+    /// A character literal such as `'a'` or `'\n'`, an extension enabled
+    /// with `--ext=char`; its value is an `int`
+    Char(u8),
+}
+
+impl Show for Expr {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Number(number) => write!(f, "{}", number),
-            Self::Unary(unary_op, expr) => write!(f, "{}{}", unary_op, expr),
-            Self::Binary(lhs, op, rhs) => write!(f, "({} {} {})", lhs, op, rhs),
+            Self::Float(value) => write!(f, "{:?}", value),
+            Self::Unary(unary_op, expr) => write!(f, "{}{}", unary_op, exprs.show(expr)),
+            Self::Binary(lhs, op, rhs) => write!(f, "({} {} {})", exprs.show(lhs), op, exprs.show(rhs)),
             // TODO: we don't know the precedence of binary operations and we are lazy
             // so that a pair of parentheses is added.
-            Self::LVal(lval) => write!(f, "{}", lval),
+            Self::LVal(lval) => lval.show(exprs, f),
+            Self::Call(ident, args) => {
+                write!(f, "{}(", ident)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", exprs.show(arg))?;
+                }
+                write!(f, ")")
+            }
+            Self::Bitwise(lhs, op, rhs, _) => write!(f, "({} {} {})", exprs.show(lhs), op, exprs.show(rhs)),
+            Self::Ternary(cond, then_expr, else_expr, _) => {
+                write!(f, "({} ? {} : {})", exprs.show(cond), exprs.show(then_expr), exprs.show(else_expr))
+            }
+            Self::Str(string) => write!(f, "{:?}", string),
+            Self::Char(b'\0') => write!(f, "'\\0'"),
+            Self::Char(c) => write!(f, "'{}'", c.escape_ascii()),
         }
     }
 }
 
+impl Show for ExprId {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        exprs[*self].show(exprs, f)
+    }
+}
+
 
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy)]
@@ -219,16 +626,55 @@ impl Display for BinaryOp {
     }
 }
 
+/// The operators of `--ext=bitwise`; `>>` is arithmetic, as for a signed
+/// `int` in C
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+}
+
+impl Display for BitOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::And => write!(f, "&"),
+            Self::Or => write!(f, "|"),
+            Self::Xor => write!(f, "^"),
+            Self::Shl => write!(f, "<<"),
+            Self::Shr => write!(f, ">>"),
+        }
+    }
+}
+
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BType {
     Int,
+    Float,
+    /// An 8-bit signed integer, an extension enabled with `--ext=char`
+    Char,
+}
+
+impl BType {
+    /// Convert an `int` value to this type: a `char` keeps the low 8 bits,
+    /// sign-extended
+    pub fn narrow(self, value: i32) -> i32 {
+        match self {
+            Self::Char => value as i8 as i32,
+            Self::Int | Self::Float => value,
+        }
+    }
 }
 
 impl Display for BType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Int => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::Char => write!(f, "char"),
         }
     }
 }
@@ -239,11 +685,11 @@ pub enum BlockItem {
     Decl(Decl),
 }
 
-impl Display for BlockItem {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Show for BlockItem {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Stmt(stmt) => write!(f, "{}", stmt),
-            Self::Decl(decl) => write!(f, "{}", decl),
+            Self::Stmt(stmt) => stmt.show(exprs, f),
+            Self::Decl(decl) => decl.show(exprs, f),
         }
     }
 }
@@ -253,31 +699,73 @@ impl Display for BlockItem {
 pub enum Decl {
     Const(ConstDecl),
     Var(VarDecl),
+    Enum(EnumDecl),
 }
 
-impl Display for Decl {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Show for Decl {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Const(const_decl) => write!(f, "{}", const_decl),
-            Self::Var(var_decl) => write!(f, "{}", var_decl),
+            Self::Const(const_decl) => const_decl.show(exprs, f),
+            Self::Var(var_decl) => var_decl.show(exprs, f),
+            Self::Enum(enum_decl) => enum_decl.show(exprs, f),
+        }
+    }
+}
+
+/// `enum [Tag] { A, B = 5, C };`, an extension enabled with `--ext=enum`.
+/// The enumerators are `int` constants; the tag is not a type.
+#[derive(Debug)]
+pub struct EnumDecl {
+    pub tag: Option<Ident>,
+    pub enumerators: Vec<Enumerator>,
+    /// Span of the `enum` keyword
+    pub span: Span,
+}
+
+impl Show for EnumDecl {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "enum ")?;
+        if let Some(tag) = &self.tag {
+            write!(f, "{} ", tag)?;
+        }
+        write!(f, "{{ ")?;
+        for (i, enumerator) in self.enumerators.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", enumerator.id)?;
+            if let Some(value) = &enumerator.value {
+                write!(f, " = {}", exprs.show(&value.expr))?;
+            }
         }
+        write!(f, " }};")
     }
 }
 
+#[derive(Debug)]
+pub struct Enumerator {
+    pub id: Ident,
+    /// The explicit value; otherwise one more than the previous enumerator,
+    /// or 0 for the first
+    pub value: Option<ConstExpr>,
+    /// Span of the enumerator name
+    pub span: Span,
+}
+
 #[derive(Debug)]
 pub struct ConstDecl {
     pub btype: BType,
     pub defs: Vec<ConstDef>,
 }
 
-impl Display for ConstDecl {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Show for ConstDecl {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "const {} ", self.btype)?;
         for (i, def) in self.defs.iter().enumerate() {
             if i > 0 {
                 write!(f, ", ")?;
             }
-            write!(f, "{}", def)?;
+            def.show(exprs, f)?;
         }
         write!(f, ";")?;
         Ok(())
@@ -288,16 +776,22 @@ impl Display for ConstDecl {
 pub struct VarDecl {
     pub btype: BType,
     pub defs: Vec<VarDef>,
+    /// Span of the `static` keyword of a static local, an extension enabled
+    /// with `--ext=static`
+    pub static_span: Option<Span>,
 }
 
-impl Display for VarDecl {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Show for VarDecl {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.static_span.is_some() {
+            write!(f, "static ")?;
+        }
         write!(f, "{} ", self.btype)?;
         for (i, def) in self.defs.iter().enumerate() {
             if i > 0 {
                 write!(f, ", ")?;
             }
-            write!(f, "{}", def)?;
+            def.show(exprs, f)?;
         }
         write!(f, ";")?;
         Ok(())
@@ -307,60 +801,79 @@ impl Display for VarDecl {
 #[derive(Debug)]
 pub struct ConstDef {
     pub id: Ident,
-    pub init: ConstInit,
+    /// Array dimensions, outermost first; empty for a scalar
+    pub dims: Vec<ConstExpr>,
+    pub init: InitVal,
+    /// Span of the defined identifier
+    pub span: Span,
 }
 
-impl Display for ConstDef {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} = {}", self.id, self.init)
+impl Show for ConstDef {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        for dim in &self.dims {
+            write!(f, "[{}]", exprs.show(dim))?;
+        }
+        write!(f, " = {}", exprs.show(&self.init))
     }
 }
 
 #[derive(Debug)]
 pub struct VarDef {
     pub id: Ident,
-    pub init: Option<VarInit>,
+    /// Array dimensions, outermost first; empty for a scalar
+    pub dims: Vec<ConstExpr>,
+    pub init: Option<InitVal>,
+    /// Span of the defined identifier
+    pub span: Span,
 }
 
-impl Display for VarDef {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Show for VarDef {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.id)?;
+        for dim in &self.dims {
+            write!(f, "[{}]", exprs.show(dim))?;
+        }
         if let Some(init) = &self.init {
-            write!(f, " = {}", init)?;
+            write!(f, " = {}", exprs.show(init))?;
         }
         Ok(())
     }
 }
 
+/// Initializer of a variable or constant: an expression, or a braced list
+/// for arrays
 #[derive(Debug, Clone)]
-pub struct ConstInit {
-    pub const_expr: ConstExpr,
+pub enum InitVal {
+    Expr(ExprId),
+    List(Vec<InitVal>),
 }
 
-impl Display for ConstInit {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.const_expr)
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct VarInit {
-    pub expr: Expr,
-}
-
-impl Display for VarInit {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.expr)
+impl Show for InitVal {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Expr(expr) => expr.show(exprs, f),
+            Self::List(items) => {
+                write!(f, "{{")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.show(exprs, f)?;
+                }
+                write!(f, "}}")
+            }
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ConstExpr {
-    pub expr: Expr,
+    pub expr: ExprId,
 }
 
-impl Display for ConstExpr {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.expr)
+impl Show for ConstExpr {
+    fn show(&self, exprs: &ExprArena, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.expr.show(exprs, f)
     }
-}
\ No newline at end of file
+}
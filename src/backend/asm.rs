@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::str::FromStr;
 
 pub enum AsmLine {
     Directive(Directive),
@@ -30,18 +31,335 @@ pub enum Instruction {
     Rem { rd: Reg, rs1: Reg, rs2: Reg },
     And { rd: Reg, rs1: Reg, rs2: Reg },
     Or { rd: Reg, rs1: Reg, rs2: Reg },
+    Xor { rd: Reg, rs1: Reg, rs2: Reg },
     Xori { rd: Reg, rs: Reg, imm: i32 },
+    Andi { rd: Reg, rs: Reg, imm: i32 },
+    Slli { rd: Reg, rs: Reg, imm: i32 },
+    Sll { rd: Reg, rs1: Reg, rs2: Reg },
+    Srl { rd: Reg, rs1: Reg, rs2: Reg },  // shift right logical
+    Sra { rd: Reg, rs1: Reg, rs2: Reg },  // shift right arithmetic
     Slt { rd: Reg, rs1: Reg, rs2: Reg },  // set less than (signed)
+    Sgt { rd: Reg, rs1: Reg, rs2: Reg },  // set greater than (signed, pseudo)
+    // 32-bit arithmetic on RV64, sign-extending the result
+    Addw { rd: Reg, rs1: Reg, rs2: Reg },
+    Subw { rd: Reg, rs1: Reg, rs2: Reg },
+    Mulw { rd: Reg, rs1: Reg, rs2: Reg },
+    Divw { rd: Reg, rs1: Reg, rs2: Reg },
+    Remw { rd: Reg, rs1: Reg, rs2: Reg },
+    Sllw { rd: Reg, rs1: Reg, rs2: Reg },
+    Srlw { rd: Reg, rs1: Reg, rs2: Reg },
+    Sraw { rd: Reg, rs1: Reg, rs2: Reg },
     // Logical
     Seqz { rd: Reg, rs: Reg },  // set if equal to zero
     Snez { rd: Reg, rs: Reg },  // set if not equal to zero
     // Move
     Mv { rd: Reg, rs: Reg },
     // Memory
+    La { rd: Reg, symbol: String },  // load address of a symbol (pseudo)
     Lw { rd: Reg, offset: i32, base: Reg },
     Sw { rs: Reg, offset: i32, base: Reg },
+    Ld { rd: Reg, offset: i32, base: Reg },  // RV64 only
+    Sd { rs: Reg, offset: i32, base: Reg },  // RV64 only
+    // Single-precision floating point (F extension)
+    Flw { rd: FReg, offset: i32, base: Reg },
+    Fsw { rs: FReg, offset: i32, base: Reg },
+    FmvWX { rd: FReg, rs: Reg },  // move the bits of an int register
+    FmvXW { rd: Reg, rs: FReg },  // move the bits to an int register, sign-extended
+    FaddS { rd: FReg, rs1: FReg, rs2: FReg },
+    FsubS { rd: FReg, rs1: FReg, rs2: FReg },
+    FmulS { rd: FReg, rs1: FReg, rs2: FReg },
+    FdivS { rd: FReg, rs1: FReg, rs2: FReg },
+    FnegS { rd: FReg, rs: FReg },
+    FeqS { rd: Reg, rs1: FReg, rs2: FReg },
+    FltS { rd: Reg, rs1: FReg, rs2: FReg },
+    FleS { rd: Reg, rs1: FReg, rs2: FReg },
+    FcvtSW { rd: FReg, rs: Reg },  // int to float
+    FcvtWS { rd: Reg, rs: FReg },  // float to int, rounding towards zero
+    // Control flow
+    Beqz { rs: Reg, label: String },
+    Bnez { rs: Reg, label: String },
+    J { label: String },
+    Call { func: String },
 
     Ret,
+    /// Trap into the debugger (or the emulator)
+    Ebreak,
+}
+
+impl Instruction {
+    /// The integer register the instruction writes, if any (calls clobber all
+    /// caller-saved registers and are not described by this)
+    pub fn dest(&self) -> Option<Reg> {
+        match self {
+            Self::Li { reg: rd, .. }
+            | Self::Add { rd, .. }
+            | Self::Addi { rd, .. }
+            | Self::Sub { rd, .. }
+            | Self::Mul { rd, .. }
+            | Self::Div { rd, .. }
+            | Self::Rem { rd, .. }
+            | Self::And { rd, .. }
+            | Self::Or { rd, .. }
+            | Self::Xor { rd, .. }
+            | Self::Xori { rd, .. }
+            | Self::Andi { rd, .. }
+            | Self::Slli { rd, .. }
+            | Self::Sll { rd, .. }
+            | Self::Srl { rd, .. }
+            | Self::Sra { rd, .. }
+            | Self::Slt { rd, .. }
+            | Self::Sgt { rd, .. }
+            | Self::Addw { rd, .. }
+            | Self::Subw { rd, .. }
+            | Self::Mulw { rd, .. }
+            | Self::Divw { rd, .. }
+            | Self::Remw { rd, .. }
+            | Self::Sllw { rd, .. }
+            | Self::Srlw { rd, .. }
+            | Self::Sraw { rd, .. }
+            | Self::Seqz { rd, .. }
+            | Self::Snez { rd, .. }
+            | Self::Mv { rd, .. }
+            | Self::La { rd, .. }
+            | Self::Lw { rd, .. }
+            | Self::Ld { rd, .. }
+            | Self::FmvXW { rd, .. }
+            | Self::FeqS { rd, .. }
+            | Self::FltS { rd, .. }
+            | Self::FleS { rd, .. }
+            | Self::FcvtWS { rd, .. } => Some(*rd),
+            Self::Sw { .. }
+            | Self::Sd { .. }
+            | Self::Flw { .. }
+            | Self::Fsw { .. }
+            | Self::FmvWX { .. }
+            | Self::FaddS { .. }
+            | Self::FsubS { .. }
+            | Self::FmulS { .. }
+            | Self::FdivS { .. }
+            | Self::FnegS { .. }
+            | Self::FcvtSW { .. }
+            | Self::Beqz { .. }
+            | Self::Bnez { .. }
+            | Self::J { .. }
+            | Self::Call { .. }
+            | Self::Ret
+            | Self::Ebreak => None,
+        }
+    }
+
+    /// Mutable access to the integer register the instruction writes, if any
+    pub fn dest_mut(&mut self) -> Option<&mut Reg> {
+        match self {
+            Self::Li { reg: rd, .. }
+            | Self::Add { rd, .. }
+            | Self::Addi { rd, .. }
+            | Self::Sub { rd, .. }
+            | Self::Mul { rd, .. }
+            | Self::Div { rd, .. }
+            | Self::Rem { rd, .. }
+            | Self::And { rd, .. }
+            | Self::Or { rd, .. }
+            | Self::Xor { rd, .. }
+            | Self::Xori { rd, .. }
+            | Self::Andi { rd, .. }
+            | Self::Slli { rd, .. }
+            | Self::Sll { rd, .. }
+            | Self::Srl { rd, .. }
+            | Self::Sra { rd, .. }
+            | Self::Slt { rd, .. }
+            | Self::Sgt { rd, .. }
+            | Self::Addw { rd, .. }
+            | Self::Subw { rd, .. }
+            | Self::Mulw { rd, .. }
+            | Self::Divw { rd, .. }
+            | Self::Remw { rd, .. }
+            | Self::Sllw { rd, .. }
+            | Self::Srlw { rd, .. }
+            | Self::Sraw { rd, .. }
+            | Self::Seqz { rd, .. }
+            | Self::Snez { rd, .. }
+            | Self::Mv { rd, .. }
+            | Self::La { rd, .. }
+            | Self::Lw { rd, .. }
+            | Self::Ld { rd, .. }
+            | Self::FmvXW { rd, .. }
+            | Self::FeqS { rd, .. }
+            | Self::FltS { rd, .. }
+            | Self::FleS { rd, .. }
+            | Self::FcvtWS { rd, .. } => Some(rd),
+            Self::Sw { .. }
+            | Self::Sd { .. }
+            | Self::Flw { .. }
+            | Self::Fsw { .. }
+            | Self::FmvWX { .. }
+            | Self::FaddS { .. }
+            | Self::FsubS { .. }
+            | Self::FmulS { .. }
+            | Self::FdivS { .. }
+            | Self::FnegS { .. }
+            | Self::FcvtSW { .. }
+            | Self::Beqz { .. }
+            | Self::Bnez { .. }
+            | Self::J { .. }
+            | Self::Call { .. }
+            | Self::Ret
+            | Self::Ebreak => None,
+        }
+    }
+
+    /// The integer registers the instruction reads
+    pub fn uses(&self) -> Vec<Reg> {
+        match self {
+            Self::Add { rs1, rs2, .. }
+            | Self::Sub { rs1, rs2, .. }
+            | Self::Mul { rs1, rs2, .. }
+            | Self::Div { rs1, rs2, .. }
+            | Self::Rem { rs1, rs2, .. }
+            | Self::And { rs1, rs2, .. }
+            | Self::Or { rs1, rs2, .. }
+            | Self::Xor { rs1, rs2, .. }
+            | Self::Sll { rs1, rs2, .. }
+            | Self::Srl { rs1, rs2, .. }
+            | Self::Sra { rs1, rs2, .. }
+            | Self::Slt { rs1, rs2, .. }
+            | Self::Sgt { rs1, rs2, .. }
+            | Self::Addw { rs1, rs2, .. }
+            | Self::Subw { rs1, rs2, .. }
+            | Self::Mulw { rs1, rs2, .. }
+            | Self::Divw { rs1, rs2, .. }
+            | Self::Remw { rs1, rs2, .. }
+            | Self::Sllw { rs1, rs2, .. }
+            | Self::Srlw { rs1, rs2, .. }
+            | Self::Sraw { rs1, rs2, .. } => vec![*rs1, *rs2],
+            Self::Addi { rs, .. }
+            | Self::Xori { rs, .. }
+            | Self::Andi { rs, .. }
+            | Self::Slli { rs, .. }
+            | Self::Seqz { rs, .. }
+            | Self::Snez { rs, .. }
+            | Self::Mv { rs, .. }
+            | Self::Beqz { rs, .. }
+            | Self::Bnez { rs, .. }
+            | Self::FmvWX { rs, .. }
+            | Self::FcvtSW { rs, .. } => vec![*rs],
+            Self::Lw { base, .. } | Self::Ld { base, .. } | Self::Flw { base, .. } | Self::Fsw { base, .. } => {
+                vec![*base]
+            }
+            Self::Sw { rs, base, .. } | Self::Sd { rs, base, .. } => vec![*rs, *base],
+            Self::Call { .. } => Reg::ARGS.to_vec(),
+            Self::Ret => vec![Reg::A0, Reg::Ra],
+            Self::Li { .. }
+            | Self::La { .. }
+            | Self::J { .. }
+            | Self::Ebreak
+            | Self::FmvXW { .. }
+            | Self::FaddS { .. }
+            | Self::FsubS { .. }
+            | Self::FmulS { .. }
+            | Self::FdivS { .. }
+            | Self::FnegS { .. }
+            | Self::FeqS { .. }
+            | Self::FltS { .. }
+            | Self::FleS { .. }
+            | Self::FcvtWS { .. } => vec![],
+        }
+    }
+}
+
+/// Split the text of an instruction into its mnemonic and operands, e.g.
+/// `lw t0, 4(sp)` into `lw`, `t0`, `4`, `sp`
+pub fn tokenize(text: &str) -> Vec<&str> {
+    text.split(|c: char| c.is_whitespace() || matches!(c, ',' | '(' | ')'))
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+impl Instruction {
+    /// Build an instruction from its mnemonic and operands as split by
+    /// [`tokenize`]
+    pub fn from_tokens(tokens: &[&str]) -> Option<Self> {
+        let reg = |i: usize| tokens.get(i)?.parse::<Reg>().ok();
+        let freg = |i: usize| tokens.get(i)?.parse::<FReg>().ok();
+        let imm = |i: usize| tokens.get(i)?.parse::<i32>().ok();
+        let sym = |i: usize| tokens.get(i).map(|token| token.to_string());
+        let arity = match *tokens.first()? {
+            "ret" | "ebreak" => 1,
+            "j" | "call" => 2,
+            "li" | "seqz" | "snez" | "mv" | "la" | "beqz" | "bnez" => 3,
+            "fmv.w.x" | "fmv.x.w" | "fneg.s" | "fcvt.s.w" => 3,
+            _ => 4,
+        };
+        if tokens.len() != arity {
+            return None;
+        }
+        Some(match tokens[0] {
+            "li" => Self::Li { reg: reg(1)?, imm: imm(2)? },
+            "add" => Self::Add { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "addi" => Self::Addi { rd: reg(1)?, rs: reg(2)?, imm: imm(3)? },
+            "sub" => Self::Sub { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "mul" => Self::Mul { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "div" => Self::Div { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "rem" => Self::Rem { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "and" => Self::And { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "or" => Self::Or { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "xor" => Self::Xor { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "xori" => Self::Xori { rd: reg(1)?, rs: reg(2)?, imm: imm(3)? },
+            "andi" => Self::Andi { rd: reg(1)?, rs: reg(2)?, imm: imm(3)? },
+            "slli" => Self::Slli { rd: reg(1)?, rs: reg(2)?, imm: imm(3)? },
+            "sll" => Self::Sll { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "srl" => Self::Srl { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "sra" => Self::Sra { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "slt" => Self::Slt { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "sgt" => Self::Sgt { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "addw" => Self::Addw { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "subw" => Self::Subw { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "mulw" => Self::Mulw { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "divw" => Self::Divw { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "remw" => Self::Remw { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "sllw" => Self::Sllw { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "srlw" => Self::Srlw { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "sraw" => Self::Sraw { rd: reg(1)?, rs1: reg(2)?, rs2: reg(3)? },
+            "seqz" => Self::Seqz { rd: reg(1)?, rs: reg(2)? },
+            "snez" => Self::Snez { rd: reg(1)?, rs: reg(2)? },
+            "mv" => Self::Mv { rd: reg(1)?, rs: reg(2)? },
+            "la" => Self::La { rd: reg(1)?, symbol: sym(2)? },
+            "lw" => Self::Lw { rd: reg(1)?, offset: imm(2)?, base: reg(3)? },
+            "sw" => Self::Sw { rs: reg(1)?, offset: imm(2)?, base: reg(3)? },
+            "ld" => Self::Ld { rd: reg(1)?, offset: imm(2)?, base: reg(3)? },
+            "sd" => Self::Sd { rs: reg(1)?, offset: imm(2)?, base: reg(3)? },
+            "flw" => Self::Flw { rd: freg(1)?, offset: imm(2)?, base: reg(3)? },
+            "fsw" => Self::Fsw { rs: freg(1)?, offset: imm(2)?, base: reg(3)? },
+            "fmv.w.x" => Self::FmvWX { rd: freg(1)?, rs: reg(2)? },
+            "fmv.x.w" => Self::FmvXW { rd: reg(1)?, rs: freg(2)? },
+            "fadd.s" => Self::FaddS { rd: freg(1)?, rs1: freg(2)?, rs2: freg(3)? },
+            "fsub.s" => Self::FsubS { rd: freg(1)?, rs1: freg(2)?, rs2: freg(3)? },
+            "fmul.s" => Self::FmulS { rd: freg(1)?, rs1: freg(2)?, rs2: freg(3)? },
+            "fdiv.s" => Self::FdivS { rd: freg(1)?, rs1: freg(2)?, rs2: freg(3)? },
+            "fneg.s" => Self::FnegS { rd: freg(1)?, rs: freg(2)? },
+            "feq.s" => Self::FeqS { rd: reg(1)?, rs1: freg(2)?, rs2: freg(3)? },
+            "flt.s" => Self::FltS { rd: reg(1)?, rs1: freg(2)?, rs2: freg(3)? },
+            "fle.s" => Self::FleS { rd: reg(1)?, rs1: freg(2)?, rs2: freg(3)? },
+            "fcvt.s.w" => Self::FcvtSW { rd: freg(1)?, rs: reg(2)? },
+            // Only the rounding mode C conversions use is supported
+            "fcvt.w.s" if tokens[3] == "rtz" => Self::FcvtWS { rd: reg(1)?, rs: freg(2)? },
+            "beqz" => Self::Beqz { rs: reg(1)?, label: sym(2)? },
+            "bnez" => Self::Bnez { rs: reg(1)?, label: sym(2)? },
+            "j" => Self::J { label: sym(1)? },
+            "call" => Self::Call { func: sym(1)? },
+            "ret" => Self::Ret,
+            "ebreak" => Self::Ebreak,
+            _ => return None,
+        })
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::from_tokens(&tokenize(text)).ok_or_else(|| format!("Invalid instruction: {}", text.trim()))
+    }
 }
 
 impl Display for Instruction {
@@ -56,14 +374,51 @@ impl Display for Instruction {
             Self::Rem { rd, rs1, rs2 } => write!(f, "  rem {}, {}, {}", rd, rs1, rs2),
             Self::And { rd, rs1, rs2 } => write!(f, "  and {}, {}, {}", rd, rs1, rs2),
             Self::Or { rd, rs1, rs2 } => write!(f, "  or {}, {}, {}", rd, rs1, rs2),
+            Self::Xor { rd, rs1, rs2 } => write!(f, "  xor {}, {}, {}", rd, rs1, rs2),
+            Self::Slli { rd, rs, imm } => write!(f, "  slli {}, {}, {}", rd, rs, imm),
+            Self::Sll { rd, rs1, rs2 } => write!(f, "  sll {}, {}, {}", rd, rs1, rs2),
+            Self::Srl { rd, rs1, rs2 } => write!(f, "  srl {}, {}, {}", rd, rs1, rs2),
+            Self::Sra { rd, rs1, rs2 } => write!(f, "  sra {}, {}, {}", rd, rs1, rs2),
             Self::Xori { rd, rs, imm } => write!(f, "  xori {}, {}, {}", rd, rs, imm),
+            Self::Andi { rd, rs, imm } => write!(f, "  andi {}, {}, {}", rd, rs, imm),
             Self::Slt { rd, rs1, rs2 } => write!(f, "  slt {}, {}, {}", rd, rs1, rs2),
+            Self::Sgt { rd, rs1, rs2 } => write!(f, "  sgt {}, {}, {}", rd, rs1, rs2),
+            Self::Addw { rd, rs1, rs2 } => write!(f, "  addw {}, {}, {}", rd, rs1, rs2),
+            Self::Subw { rd, rs1, rs2 } => write!(f, "  subw {}, {}, {}", rd, rs1, rs2),
+            Self::Mulw { rd, rs1, rs2 } => write!(f, "  mulw {}, {}, {}", rd, rs1, rs2),
+            Self::Divw { rd, rs1, rs2 } => write!(f, "  divw {}, {}, {}", rd, rs1, rs2),
+            Self::Remw { rd, rs1, rs2 } => write!(f, "  remw {}, {}, {}", rd, rs1, rs2),
+            Self::Sllw { rd, rs1, rs2 } => write!(f, "  sllw {}, {}, {}", rd, rs1, rs2),
+            Self::Srlw { rd, rs1, rs2 } => write!(f, "  srlw {}, {}, {}", rd, rs1, rs2),
+            Self::Sraw { rd, rs1, rs2 } => write!(f, "  sraw {}, {}, {}", rd, rs1, rs2),
             Self::Seqz { rd, rs } => write!(f, "  seqz {}, {}", rd, rs),
             Self::Snez { rd, rs } => write!(f, "  snez {}, {}", rd, rs),
             Self::Mv { rd, rs } => write!(f, "  mv {}, {}", rd, rs),
+            Self::La { rd, symbol } => write!(f, "  la {}, {}", rd, symbol),
             Self::Lw { rd, offset, base } => write!(f, "  lw {}, {}({})", rd, offset, base),
             Self::Sw { rs, offset, base } => write!(f, "  sw {}, {}({})", rs, offset, base),
+            Self::Ld { rd, offset, base } => write!(f, "  ld {}, {}({})", rd, offset, base),
+            Self::Sd { rs, offset, base } => write!(f, "  sd {}, {}({})", rs, offset, base),
+            Self::Flw { rd, offset, base } => write!(f, "  flw {}, {}({})", rd, offset, base),
+            Self::Fsw { rs, offset, base } => write!(f, "  fsw {}, {}({})", rs, offset, base),
+            Self::FmvWX { rd, rs } => write!(f, "  fmv.w.x {}, {}", rd, rs),
+            Self::FmvXW { rd, rs } => write!(f, "  fmv.x.w {}, {}", rd, rs),
+            Self::FaddS { rd, rs1, rs2 } => write!(f, "  fadd.s {}, {}, {}", rd, rs1, rs2),
+            Self::FsubS { rd, rs1, rs2 } => write!(f, "  fsub.s {}, {}, {}", rd, rs1, rs2),
+            Self::FmulS { rd, rs1, rs2 } => write!(f, "  fmul.s {}, {}, {}", rd, rs1, rs2),
+            Self::FdivS { rd, rs1, rs2 } => write!(f, "  fdiv.s {}, {}, {}", rd, rs1, rs2),
+            Self::FnegS { rd, rs } => write!(f, "  fneg.s {}, {}", rd, rs),
+            Self::FeqS { rd, rs1, rs2 } => write!(f, "  feq.s {}, {}, {}", rd, rs1, rs2),
+            Self::FltS { rd, rs1, rs2 } => write!(f, "  flt.s {}, {}, {}", rd, rs1, rs2),
+            Self::FleS { rd, rs1, rs2 } => write!(f, "  fle.s {}, {}, {}", rd, rs1, rs2),
+            Self::FcvtSW { rd, rs } => write!(f, "  fcvt.s.w {}, {}", rd, rs),
+            Self::FcvtWS { rd, rs } => write!(f, "  fcvt.w.s {}, {}, rtz", rd, rs),
+            Self::Beqz { rs, label } => write!(f, "  beqz {}, {}", rs, label),
+            Self::Bnez { rs, label } => write!(f, "  bnez {}, {}", rs, label),
+            Self::J { label } => write!(f, "  j {}", label),
+            Self::Call { func } => write!(f, "  call {}", func),
             Self::Ret => write!(f, "  ret"),
+            Self::Ebreak => write!(f, "  ebreak"),
         }
     }
 }
@@ -88,6 +443,33 @@ pub enum Reg {
     A0, A1, A2, A3, A4, A5, A6, A7,
 }
 
+impl Reg {
+    /// Registers used to pass the first eight arguments, in order
+    pub const ARGS: [Reg; 8] = [
+        Reg::A0, Reg::A1, Reg::A2, Reg::A3, Reg::A4, Reg::A5, Reg::A6, Reg::A7,
+    ];
+
+    /// All registers
+    pub const ALL: [Reg; 32] = [
+        Reg::Zero, Reg::Ra, Reg::Sp, Reg::Gp, Reg::Tp,
+        Reg::T0, Reg::T1, Reg::T2, Reg::T3, Reg::T4, Reg::T5, Reg::T6,
+        Reg::S0, Reg::S1, Reg::S2, Reg::S3, Reg::S4, Reg::S5, Reg::S6, Reg::S7,
+        Reg::S8, Reg::S9, Reg::S10, Reg::S11,
+        Reg::A0, Reg::A1, Reg::A2, Reg::A3, Reg::A4, Reg::A5, Reg::A6, Reg::A7,
+    ];
+}
+
+impl FromStr for Reg {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Reg::ALL
+            .into_iter()
+            .find(|reg| reg.to_string() == name)
+            .ok_or_else(|| format!("Unknown register: {}", name))
+    }
+}
+
 impl Display for Reg {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -127,10 +509,74 @@ impl Display for Reg {
     }
 }
 
+/// Floating-point registers; only the temporaries the generator computes in
+/// and the argument registers are used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FReg {
+    // Temporaries
+    Ft0, Ft1, Ft2,
+    // Function arguments / return values
+    Fa0, Fa1, Fa2, Fa3, Fa4, Fa5, Fa6, Fa7,
+}
+
+impl FReg {
+    /// Registers used to pass the first eight `float` arguments, in order
+    pub const ARGS: [FReg; 8] = [
+        FReg::Fa0, FReg::Fa1, FReg::Fa2, FReg::Fa3, FReg::Fa4, FReg::Fa5, FReg::Fa6, FReg::Fa7,
+    ];
+
+    /// All registers
+    pub const ALL: [FReg; 11] = [
+        FReg::Ft0, FReg::Ft1, FReg::Ft2,
+        FReg::Fa0, FReg::Fa1, FReg::Fa2, FReg::Fa3, FReg::Fa4, FReg::Fa5, FReg::Fa6, FReg::Fa7,
+    ];
+}
+
+impl FromStr for FReg {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        FReg::ALL
+            .into_iter()
+            .find(|reg| reg.to_string() == name)
+            .ok_or_else(|| format!("Unknown register: {}", name))
+    }
+}
+
+impl Display for FReg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ft0 => write!(f, "ft0"),
+            Self::Ft1 => write!(f, "ft1"),
+            Self::Ft2 => write!(f, "ft2"),
+            Self::Fa0 => write!(f, "fa0"),
+            Self::Fa1 => write!(f, "fa1"),
+            Self::Fa2 => write!(f, "fa2"),
+            Self::Fa3 => write!(f, "fa3"),
+            Self::Fa4 => write!(f, "fa4"),
+            Self::Fa5 => write!(f, "fa5"),
+            Self::Fa6 => write!(f, "fa6"),
+            Self::Fa7 => write!(f, "fa7"),
+        }
+    }
+}
+
 
 pub enum Directive {
     Section(Section),
     Global(String),
+    /// A 32-bit word of initialized data
+    Word(i32),
+    /// A 64-bit word of initialized data
+    Dword(i64),
+    /// A run of zero bytes
+    Zero(usize),
+    /// `.balign`: pad to a multiple of this many bytes
+    Align(i32),
+    /// `.attribute arch`: the ISA the code needs, checked by the linker
+    Arch(String),
+    /// `.ident`: the producer of the file
+    Ident(String),
 }
 
 impl Display for Directive {
@@ -138,13 +584,21 @@ impl Display for Directive {
         match self {
             Self::Section(section) => write!(f, "{}", section),
             Self::Global(symbol) => write!(f, ".globl {}", symbol),
+            Self::Word(value) => write!(f, "  .word {}", value),
+            Self::Dword(value) => write!(f, "  .dword {}", value),
+            Self::Zero(size) => write!(f, "  .zero {}", size),
+            Self::Align(bytes) => write!(f, ".balign {}", bytes),
+            Self::Arch(isa) => write!(f, ".attribute arch, \"{}\"", isa),
+            Self::Ident(producer) => write!(f, ".ident \"{}\"", producer),
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Section {
     Text,
     Data,
+    Rodata,
 }
 
 impl Display for Section {
@@ -152,6 +606,7 @@ impl Display for Section {
         match self {
             Self::Text => write!(f, ".text"),
             Self::Data => write!(f, ".data"),
+            Self::Rodata => write!(f, ".section .rodata"),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,68 @@
+//! Move coalescing on the generated assembly of a function.
+//!
+//! Instructions are lowered into the scratch registers `t0`-`t3` and the
+//! result is then moved to the location of the value, so a value that lives
+//! in a register costs an extra `mv rd, t2`. When the scratch register is not
+//! read again, the instruction that computed it can write `rd` directly.
+
+use super::asm::{AsmLine, Instruction, Reg};
+
+/// Registers the generator only uses while lowering a single IR instruction,
+/// so they never carry a value across a label or a jump
+const SCRATCH: [Reg; 4] = [Reg::T0, Reg::T1, Reg::T2, Reg::T3];
+
+/// Remove the moves from a scratch register that is written by the preceding
+/// instruction and dead afterwards, retargeting that instruction to the
+/// destination of the move, as well as moves of a register to itself.
+/// Returns the number of moves removed.
+pub fn coalesce_moves(lines: &mut Vec<AsmLine>) -> usize {
+    let mut removed = vec![false; lines.len()];
+    for i in 0..lines.len() {
+        let (rd, rs) = match lines[i] {
+            AsmLine::Instruction(Instruction::Mv { rd, rs }) => (rd, rs),
+            _ => continue,
+        };
+        if rd == rs {
+            removed[i] = true;
+            continue;
+        }
+        if !SCRATCH.contains(&rs) || !dead_after(&lines[i + 1..], rs) {
+            continue;
+        }
+        // The defining instruction must directly precede the move, so that
+        // nothing in between can read or write `rd`
+        let prev = (0..i)
+            .rev()
+            .find(|&j| !removed[j] && !matches!(lines[j], AsmLine::Comment(_)));
+        if let Some(AsmLine::Instruction(inst)) = prev.map(|j| &mut lines[j]) {
+            if let Some(dest) = inst.dest_mut().filter(|dest| **dest == rs) {
+                *dest = rd;
+                removed[i] = true;
+            }
+        }
+    }
+
+    let count = removed.iter().filter(|&&removed| removed).count();
+    let mut removed = removed.into_iter();
+    lines.retain(|_| !removed.next().unwrap());
+    count
+}
+
+/// Whether the scratch register `reg` is overwritten or abandoned before it
+/// is read again in `lines`
+fn dead_after(lines: &[AsmLine], reg: Reg) -> bool {
+    for line in lines {
+        let inst = match line {
+            AsmLine::Instruction(inst) => inst,
+            AsmLine::Comment(_) => continue,
+            AsmLine::Label(_) | AsmLine::Directive(_) => return true,
+        };
+        if inst.uses().contains(&reg) {
+            return false;
+        }
+        if inst.dest() == Some(reg) || matches!(inst, Instruction::J { .. } | Instruction::Call { .. } | Instruction::Ret) {
+            return true;
+        }
+    }
+    true
+}
@@ -0,0 +1,194 @@
+//! Peephole rewriting of the generated assembly of a function.
+//!
+//! Rules are data: a pattern of consecutive instructions and the
+//! instructions to replace them with, both written as assembly text. In a
+//! pattern, an operand written `$name` matches anything, and every occurrence
+//! of the same name must match the same operand; the replacement refers to the
+//! bound operands by name. For example
+//!
+//! ```text
+//! sw $a, $o($b)
+//! lw $a, $o($b)
+//! ```
+//!
+//! matches a load of the value that was just stored, and the replacement
+//! `sw $a, $o($b)` drops it. Matches never extend across a label, so a rule
+//! only needs to be valid for straight-line code.
+
+use std::collections::HashMap;
+
+use super::asm::{tokenize, AsmLine, Instruction};
+
+/// A rewrite rule: `pattern` and `replacement` hold one instruction per line
+pub struct Rule {
+    pub name: &'static str,
+    pub pattern: &'static str,
+    pub replacement: &'static str,
+}
+
+/// The rules applied to every function, tried in order at each position
+pub const RULES: &[Rule] = &[
+    Rule {
+        name: "self-move",
+        pattern: "mv $a, $a",
+        replacement: "",
+    },
+    Rule {
+        name: "add-zero-in-place",
+        pattern: "addi $a, $a, 0",
+        replacement: "",
+    },
+    Rule {
+        name: "add-zero",
+        pattern: "addi $a, $b, 0",
+        replacement: "mv $a, $b",
+    },
+    Rule {
+        name: "reload-stored",
+        pattern: "sw $a, $o($b)
+                  lw $a, $o($b)",
+        replacement: "sw $a, $o($b)",
+    },
+    Rule {
+        name: "forward-stored",
+        pattern: "sw $a, $o($b)
+                  lw $c, $o($b)",
+        replacement: "sw $a, $o($b)
+                      mv $c, $a",
+    },
+];
+
+/// A rule with its pattern and replacement split into tokens
+struct CompiledRule<'a> {
+    pattern: Vec<Vec<&'a str>>,
+    replacement: Vec<Vec<&'a str>>,
+}
+
+impl<'a> CompiledRule<'a> {
+    fn new(rule: &'a Rule) -> Self {
+        let split = |text: &'a str| -> Vec<Vec<&'a str>> {
+            text.lines().map(tokenize).filter(|tokens| !tokens.is_empty()).collect()
+        };
+        let compiled = Self {
+            pattern: split(rule.pattern),
+            replacement: split(rule.replacement),
+        };
+        assert!(!compiled.pattern.is_empty(), "Peephole rule {} has an empty pattern", rule.name);
+        compiled
+    }
+
+    /// The operands bound by matching the pattern against `insts`, if it matches
+    fn matches(&self, insts: &[Vec<String>]) -> Option<HashMap<&'a str, String>> {
+        let mut bindings = HashMap::new();
+        for (pattern, inst) in self.pattern.iter().zip(insts) {
+            if pattern.len() != inst.len() {
+                return None;
+            }
+            for (&expected, actual) in pattern.iter().zip(inst) {
+                match expected.strip_prefix('$') {
+                    Some(name) => {
+                        let bound = bindings.entry(name).or_insert_with(|| actual.clone());
+                        if *bound != *actual {
+                            return None;
+                        }
+                    }
+                    None if expected != actual.as_str() => return None,
+                    None => {}
+                }
+            }
+        }
+        Some(bindings)
+    }
+
+    /// The replacement instructions with the bound operands substituted
+    fn instantiate(&self, bindings: &HashMap<&str, String>) -> Vec<Instruction> {
+        self.replacement
+            .iter()
+            .map(|tokens| {
+                let tokens: Vec<&str> = tokens
+                    .iter()
+                    .map(|&token| match token.strip_prefix('$') {
+                        Some(name) => bindings[name].as_str(),
+                        None => token,
+                    })
+                    .collect();
+                Instruction::from_tokens(&tokens)
+                    .unwrap_or_else(|| panic!("Peephole replacement is not an instruction: {}", tokens.join(" ")))
+            })
+            .collect()
+    }
+}
+
+/// Apply `rules` to `lines` until none matches; returns the number of rewrites.
+/// After a rewrite the rules are tried again at the same position, so a
+/// replacement must not keep matching the rules forever.
+pub fn rewrite(lines: &mut Vec<AsmLine>, rules: &[Rule]) -> usize {
+    let rules: Vec<CompiledRule> = rules.iter().map(CompiledRule::new).collect();
+    let mut count = 0;
+    let mut pos = 0;
+    while pos < lines.len() {
+        // The instructions starting at `pos`, up to the next label or
+        // directive, as (line index, tokens)
+        let longest = rules.iter().map(|rule| rule.pattern.len()).max().unwrap_or(0);
+        let window: Vec<(usize, Vec<String>)> = lines[pos..]
+            .iter()
+            .enumerate()
+            .take_while(|(_, line)| matches!(line, AsmLine::Instruction(_) | AsmLine::Comment(_)))
+            .filter_map(|(i, line)| match line {
+                AsmLine::Instruction(inst) => {
+                    let text = inst.to_string();
+                    Some((pos + i, tokenize(&text).into_iter().map(String::from).collect()))
+                }
+                _ => None,
+            })
+            .take(longest)
+            .collect();
+        if window.first().map(|&(i, _)| i) != Some(pos) {
+            pos += 1;
+            continue;
+        }
+
+        let tokens: Vec<Vec<String>> = window.iter().map(|(_, tokens)| tokens.clone()).collect();
+        let rewrite = rules.iter().find_map(|rule| {
+            let len = rule.pattern.len();
+            if len > tokens.len() {
+                return None;
+            }
+            rule.matches(&tokens[..len]).map(|bindings| (len, rule.instantiate(&bindings)))
+        });
+        let Some((len, replacement)) = rewrite else {
+            pos += 1;
+            continue;
+        };
+
+        // Remove the matched instructions (keeping any comments between
+        // them) and put the replacement where the first one was
+        for &(i, _) in window[..len].iter().rev() {
+            lines.remove(i);
+        }
+        for (i, inst) in replacement.into_iter().enumerate() {
+            lines.insert(pos + i, AsmLine::Instruction(inst));
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Apply `rules` to assembly text with one instruction per line and return
+/// the result in the same form; for testing rules
+pub fn rewrite_text(asm: &str, rules: &[Rule]) -> String {
+    let mut lines: Vec<AsmLine> = asm
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let inst = line.parse().unwrap_or_else(|e| panic!("{}", e));
+            AsmLine::Instruction(inst)
+        })
+        .collect();
+    rewrite(&mut lines, rules);
+    lines
+        .iter()
+        .map(|line| line.to_string().trim().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
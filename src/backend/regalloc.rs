@@ -1,6 +1,7 @@
 use super::asm::Reg;
-use koopa::ir::{FunctionData, Value, ValueKind};
-use std::collections::HashMap;
+use crate::analysis::liveness::Liveness;
+use koopa::ir::{FunctionData, TypeKind, Value, ValueKind};
+use std::collections::{HashMap, HashSet};
 
 /// Location of a value - either in a register, on the stack, or an immediate
 #[derive(Debug, Clone, Copy)]
@@ -27,29 +28,57 @@ pub trait RegisterAllocator {
     /// Get the total stack frame size needed (for prologue/epilogue)
     fn stack_size(&self) -> i32;
 
+    /// Offset of the slot where `ra` is saved, if the function makes calls
+    fn ra_offset(&self) -> Option<i32>;
+
     /// Reset state for a new function
     fn reset(&mut self);
 }
 
 /// Stack-based allocator: all values go to the stack
-/// Uses only t0, t1, t2 as scratch registers for computation
+/// Uses only t0, t1, t2 as scratch registers for computation (and t3 for
+/// stack offsets too large for an immediate)
+///
+/// Frame layout, from sp upwards: outgoing arguments beyond the eighth,
+/// value slots, the objects of `alloc`s, and the saved `ra` at the top for
+/// non-leaf functions. Values whose live ranges do not overlap share a slot.
 pub struct StackAllocator {
-    /// Map from Value to stack offset
+    /// Map from Value to stack offset, assigned up front by `analyze`
     value_stack_offset: HashMap<Value, i32>,
-    /// Current stack frame size (before alignment)
-    current_offset: i32,
     /// Aligned stack frame size
     aligned_stack_size: i32,
+    /// Whether the function calls other functions (and must save `ra`)
+    has_calls: bool,
+    /// Alignment of the frame size in bytes
+    frame_align: i32,
+    /// Size of an `int` or a pointer in bytes
+    word_size: i32,
 }
 
 impl StackAllocator {
     pub fn new() -> Self {
+        Self::with_frame_align(16)
+    }
+
+    /// Create an allocator that rounds frames up to `align` bytes instead of
+    /// the 16 the ABI requires (for provoking alignment bugs in tests)
+    pub fn with_frame_align(align: i32) -> Self {
+        assert!(align >= 4 && align.count_ones() == 1, "Frame alignment must be a power of two of at least 4: {}", align);
         Self {
             value_stack_offset: HashMap::new(),
-            current_offset: 0,
             aligned_stack_size: 0,
+            has_calls: false,
+            frame_align: align,
+            word_size: 4,
         }
     }
+
+    /// Use words of `word_size` bytes for slots and objects; must match the
+    /// `int_width` of the generator
+    pub fn with_word_size(mut self, word_size: i32) -> Self {
+        self.word_size = word_size;
+        self
+    }
 }
 
 impl Default for StackAllocator {
@@ -58,30 +87,126 @@ impl Default for StackAllocator {
     }
 }
 
+/// Assign slot indices to `values` (in definition order) so that no two
+/// values that are live at the same time share a slot
+fn pack_slots(func: &FunctionData, values: &[Value]) -> HashMap<Value, usize> {
+    let tracked: HashSet<Value> = values.iter().copied().collect();
+    let liveness = Liveness::analyze(func, |value| tracked.contains(&value));
+
+    // A value interferes with everything live right after its definition.
+    // Operands dying at an instruction are not live after it, so the result
+    // may reuse their slot: operands are always loaded before the result is
+    // stored.
+    let mut interference: HashMap<Value, Vec<Value>> = HashMap::new();
+    let mut interfere = |a: Value, b: Value| {
+        if a != b {
+            interference.entry(a).or_default().push(b);
+            interference.entry(b).or_default().push(a);
+        }
+    };
+    for (bb, node) in func.layout().bbs() {
+        let mut live = liveness.live_out[bb].clone();
+        let insts: Vec<Value> = node.insts().keys().copied().collect();
+        for &inst in insts.iter().rev() {
+            if tracked.contains(&inst) {
+                live.remove(&inst);
+                for &other in &live {
+                    interfere(inst, other);
+                }
+            }
+            for used in func.dfg().value(inst).kind().value_uses() {
+                if tracked.contains(&used) {
+                    live.insert(used);
+                }
+            }
+            // The parameters of a jump target are written one by one while
+            // the arguments are read, so they must not share a slot with
+            // anything live at the jump, the arguments included
+            if let ValueKind::Jump(jump) = func.dfg().value(inst).kind() {
+                for &param in func.dfg().bb(jump.target()).params() {
+                    for &other in &live {
+                        interfere(param, other);
+                    }
+                }
+            }
+        }
+        // Parameters are defined together on entry to the block
+        let params = func.dfg().bb(*bb).params();
+        for &param in params {
+            for &other in live.iter().chain(params) {
+                interfere(param, other);
+            }
+        }
+    }
+
+    let mut slots = HashMap::new();
+    for &value in values {
+        let taken: HashSet<usize> = interference
+            .get(&value)
+            .into_iter()
+            .flatten()
+            .filter_map(|other| slots.get(other).copied())
+            .collect();
+        let slot = (0..).find(|slot| !taken.contains(slot)).unwrap();
+        slots.insert(value, slot);
+    }
+    slots
+}
+
 impl RegisterAllocator for StackAllocator {
     fn analyze(&mut self, func: &FunctionData) {
-        // First pass: count all values that need stack slots
-        let mut stack_size = 0;
-        for (_bb, node) in func.layout().bbs() {
-            for inst in node.insts().keys() {
-                let value_data = func.dfg().value(*inst);
-                // Instructions that produce a value need a stack slot
-                // (Return doesn't produce a value)
-                if !matches!(value_data.kind(), ValueKind::Return(_)) {
-                    stack_size += 4;
+        // Collect the values that need a word-sized slot (block parameters
+        // and instruction results) and the allocs, and find the largest
+        // number of stack-passed arguments of any call
+        let mut values = Vec::new();
+        let mut allocs = Vec::new();
+        let mut max_stack_args = 0;
+        for (&bb, node) in func.layout().bbs() {
+            values.extend(func.dfg().bb(bb).params());
+            for &inst in node.insts().keys() {
+                let value_data = func.dfg().value(inst);
+                match value_data.kind() {
+                    ValueKind::Alloc(_) => allocs.push(inst),
+                    _ if !value_data.ty().is_unit() => values.push(inst),
+                    _ => {}
+                }
+                if let ValueKind::Call(call) = value_data.kind() {
+                    self.has_calls = true;
+                    max_stack_args = max_stack_args.max(call.args().len().saturating_sub(8) as i32);
                 }
             }
         }
 
-        // Align to 16 bytes (RISC-V ABI requirement)
-        self.aligned_stack_size = (stack_size + 15) & !15;
+        // Value slots start above the outgoing-argument area
+        let word = self.word_size;
+        let base = max_stack_args * word;
+        let slots = pack_slots(func, &values);
+        for (&value, &slot) in &slots {
+            self.value_stack_offset.insert(value, base + slot as i32 * word);
+        }
+        let num_slots = slots.values().max().map_or(0, |&slot| slot as i32 + 1);
+
+        // An alloc needs room for the whole object it allocates; objects are
+        // made of 4-byte Koopa words, each taking a word of the target
+        let mut stack_size = base + num_slots * word;
+        for alloc in allocs {
+            let TypeKind::Pointer(ty) = func.dfg().value(alloc).ty().kind() else {
+                unreachable!("alloc always has a pointer type");
+            };
+            self.value_stack_offset.insert(alloc, stack_size);
+            stack_size += (ty.size() as i32 + 3) / 4 * word;
+        }
+        if self.has_calls {
+            stack_size += word;
+        }
+
+        // Align to 16 bytes (RISC-V ABI requirement), unless configured otherwise
+        self.aligned_stack_size = (stack_size + self.frame_align - 1) & !(self.frame_align - 1);
     }
 
     fn alloc(&mut self, value: Value) -> Location {
-        let offset = self.current_offset;
-        self.value_stack_offset.insert(value, offset);
-        self.current_offset += 4;
-        Location::Stack(offset)
+        // Every slot was already assigned by `analyze`
+        self.locate(value).expect("Value has no stack slot")
     }
 
     fn locate(&self, value: Value) -> Option<Location> {
@@ -94,9 +219,13 @@ impl RegisterAllocator for StackAllocator {
         self.aligned_stack_size
     }
 
+    fn ra_offset(&self) -> Option<i32> {
+        self.has_calls.then_some(self.aligned_stack_size - self.word_size)
+    }
+
     fn reset(&mut self) {
         self.value_stack_offset.clear();
-        self.current_offset = 0;
         self.aligned_stack_size = 0;
+        self.has_calls = false;
     }
 }
@@ -0,0 +1,43 @@
+//! Per-function code generation statistics, printed with `--codegen-report`.
+
+use std::fmt::{self, Display};
+
+/// Statistics of the code generated for one function
+#[derive(Debug, Clone)]
+pub struct FuncReport {
+    pub name: String,
+    /// Number of emitted machine instructions
+    pub instructions: usize,
+    /// Size of the stack frame in bytes
+    pub frame_size: i32,
+    /// Number of IR values kept in stack slots instead of registers
+    /// (`alloc`ed variables are not counted)
+    pub spills: usize,
+}
+
+/// Statistics of all functions of a program, in layout order
+#[derive(Debug, Clone, Default)]
+pub struct CodegenReport {
+    pub funcs: Vec<FuncReport>,
+}
+
+impl Display for CodegenReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.funcs
+            .iter()
+            .map(|func| func.name.len())
+            .chain(["function".len()])
+            .max()
+            .unwrap_or_default();
+        writeln!(f, "{:<width$}  {:>12}  {:>10}  {:>6}", "function", "instructions", "frame size", "spills")?;
+        for func in &self.funcs {
+            writeln!(
+                f,
+                "{:<width$}  {:>12}  {:>10}  {:>6}",
+                func.name, func.instructions, func.frame_size, func.spills,
+            )?;
+        }
+        let total = self.funcs.iter().map(|func| func.instructions).sum::<usize>();
+        write!(f, "{:<width$}  {:>12}", "total", total)
+    }
+}
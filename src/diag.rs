@@ -0,0 +1,150 @@
+//! Diagnostics (warnings, errors) and their rendering against the source text.
+
+use std::fmt::{self, Display};
+
+use crate::ast::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+            Self::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A message attached to a span, with optional notes pointing at related spans
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    /// Name of the flag controlling this diagnostic (e.g. `shadow` for `-Wshadow`)
+    pub flag: Option<&'static str>,
+    pub notes: Vec<(Span, String)>,
+}
+
+impl Diagnostic {
+    pub fn error(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            span,
+            flag: None,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn warning(flag: &'static str, span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            span,
+            flag: Some(flag),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.notes.push((span, message.into()));
+        self
+    }
+}
+
+/// File and 1-based line a line of preprocessed source came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineOrigin {
+    pub file: String,
+    pub line: usize,
+}
+
+/// Source text with a line index, used to turn byte offsets into `line:col`
+pub struct SourceFile<'a> {
+    name: &'a str,
+    text: &'a str,
+    line_starts: Vec<usize>,
+    /// Origin of each line of `text`, if it was preprocessed
+    origins: &'a [LineOrigin],
+}
+
+impl<'a> SourceFile<'a> {
+    pub fn new(name: &'a str, text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { name, text, line_starts, origins: &[] }
+    }
+
+    /// Report locations in the files the lines of the text came from
+    pub fn with_origins(mut self, origins: &'a [LineOrigin]) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    /// File name and line number to report for a 1-based line of the text
+    fn origin(&self, line: usize) -> (&str, usize) {
+        match self.origins.get(line - 1) {
+            Some(origin) => (&origin.file, origin.line),
+            // The empty line after a final newline
+            None => match self.origins.last() {
+                Some(last) => (&last.file, last.line + line - self.origins.len()),
+                None => (self.name, line),
+            },
+        }
+    }
+
+    /// 1-based line and column of a byte offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let col = self.text[self.line_starts[line]..offset].chars().count();
+        (line + 1, col + 1)
+    }
+
+    /// File, 1-based line in that file and 1-based column of a byte offset
+    pub fn location(&self, offset: usize) -> (&str, usize, usize) {
+        let (line, col) = self.line_col(offset);
+        let (file, origin_line) = self.origin(line);
+        (file, origin_line, col)
+    }
+
+    fn line_text(&self, line: usize) -> &'a str {
+        let start = self.line_starts[line - 1];
+        let end = self.line_starts.get(line).copied().unwrap_or(self.text.len());
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    fn render_one(&self, out: &mut String, severity: Severity, span: Span, message: &str) {
+        let (line, col) = self.line_col(span.start);
+        let (file, origin_line) = self.origin(line);
+        out.push_str(&format!("{}:{}:{}: {}: {}\n", file, origin_line, col, severity, message));
+        let text = self.line_text(line);
+        let width = self.text[span.start..span.end.max(span.start)]
+            .lines()
+            .next()
+            .map_or(1, |s| s.chars().count().max(1));
+        out.push_str(&format!("{:>5} | {}\n", origin_line, text));
+        out.push_str(&format!("      | {}{}\n", " ".repeat(col - 1), "^".repeat(width)));
+    }
+
+    /// Render a diagnostic in a GCC-like format, including its notes
+    pub fn render(&self, diag: &Diagnostic) -> String {
+        let mut out = String::new();
+        let message = match diag.flag {
+            Some(flag) => format!("{} [-W{}]", diag.message, flag),
+            None => diag.message.clone(),
+        };
+        self.render_one(&mut out, diag.severity, diag.span, &message);
+        for (span, note) in &diag.notes {
+            self.render_one(&mut out, Severity::Note, *span, note);
+        }
+        out
+    }
+}
@@ -0,0 +1,52 @@
+//! IR dumps between passes (`--print-after-all`).
+//!
+//! The IR is printed after lowering and after every pass that runs, so a
+//! miscompilation can be traced to the first pass whose output is wrong. Each
+//! dump goes either to stderr, under a header naming the stage, or to its own
+//! file `<nn>-<stage>.koopa` in a dump directory, numbered in pipeline order
+//! so the files list in the order they were written.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use koopa::back::KoopaGenerator;
+use koopa::ir::Program;
+
+pub struct IrDumper {
+    /// Directory of the dump files, or `None` to print to stderr
+    dir: Option<PathBuf>,
+    /// Number of dumps written so far
+    count: usize,
+}
+
+impl IrDumper {
+    /// A dumper printing to stderr
+    pub fn stderr() -> Self {
+        Self { dir: None, count: 0 }
+    }
+
+    /// A dumper writing one file per stage into `dir`, which is created if
+    /// it does not exist
+    pub fn in_dir(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir: Some(dir), count: 0 })
+    }
+
+    /// Dump `program` as it is after `stage` (`lowering` or a pass name)
+    pub fn dump(&mut self, stage: &str, program: &Program) -> io::Result<()> {
+        let mut ir = Vec::new();
+        KoopaGenerator::new(&mut ir).generate_on(program)?;
+        let index = self.count;
+        self.count += 1;
+        match &self.dir {
+            Some(dir) => std::fs::write(dir.join(format!("{:02}-{}.koopa", index, stage)), ir),
+            None => {
+                let mut stderr = io::stderr().lock();
+                writeln!(stderr, "// *** IR after {} ***", stage)?;
+                stderr.write_all(&ir)?;
+                writeln!(stderr)
+            }
+        }
+    }
+}
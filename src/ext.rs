@@ -0,0 +1,193 @@
+//! Language extensions beyond standard SysY, enabled with `--ext=<name>`.
+//!
+//! The keywords of an extension are only keywords when it is enabled, and
+//! ordinary identifiers otherwise, as in standard SysY (see `crate::lexer`).
+//! The extended syntax without a keyword of its own (`?:`, the bitwise
+//! operators, character literals) is always parsed. Using an extension
+//! without enabling it is an error, so standard programs are still checked
+//! strictly.
+
+use crate::ast::*;
+use crate::diag::Diagnostic;
+
+/// Which extensions are enabled
+#[derive(Debug, Default, Clone)]
+pub struct Extensions {
+    /// `--ext=for`: C-style `for (init; cond; step)` loops
+    pub for_loops: bool,
+    /// `--ext=do-while`: `do stmt while (cond);` loops
+    pub do_while: bool,
+    /// `--ext=ternary`: `cond ? a : b` expressions
+    pub ternary: bool,
+    /// `--ext=switch`: `switch` statements with `case` and `default` labels
+    pub switch: bool,
+    /// `--ext=bitwise`: the operators `&`, `|`, `^`, `<<` and `>>`
+    pub bitwise: bool,
+    /// `--ext=char`: the `char` type and character literals
+    pub char_type: bool,
+    /// `--ext=enum`: `enum` declarations of integer constants
+    pub enums: bool,
+    /// `--ext=static`: `static` local variables
+    pub static_locals: bool,
+}
+
+impl Extensions {
+    /// Enable an extension by its `--ext` name. Returns `false` if the name is
+    /// unknown.
+    pub fn enable(&mut self, name: &str) -> bool {
+        match name {
+            "for" => self.for_loops = true,
+            "do-while" => self.do_while = true,
+            "ternary" => self.ternary = true,
+            "switch" => self.switch = true,
+            "bitwise" => self.bitwise = true,
+            "char" => self.char_type = true,
+            "enum" => self.enums = true,
+            "static" => self.static_locals = true,
+            _ => return false,
+        }
+        true
+    }
+
+    /// If `word` is a keyword of an extension, the `--ext` name of that
+    /// extension and whether it is enabled
+    pub fn keyword(&self, word: &str) -> Option<(&'static str, bool)> {
+        match word {
+            "for" => Some(("for", self.for_loops)),
+            "do" => Some(("do-while", self.do_while)),
+            "switch" | "case" | "default" => Some(("switch", self.switch)),
+            "char" => Some(("char", self.char_type)),
+            "enum" => Some(("enum", self.enums)),
+            "static" => Some(("static", self.static_locals)),
+            _ => None,
+        }
+    }
+}
+
+struct ExtensionChecker<'a> {
+    enabled: &'a Extensions,
+    diags: Vec<Diagnostic>,
+}
+
+impl ExtensionChecker<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(block) => self.visit_block(block),
+            Stmt::If(if_stmt) => {
+                self.visit_stmt(&if_stmt.then_stmt);
+                if let Some(else_stmt) = &if_stmt.else_stmt {
+                    self.visit_stmt(else_stmt);
+                }
+            }
+            Stmt::While(while_stmt) => self.visit_stmt(&while_stmt.body),
+            Stmt::For(for_stmt) => {
+                if !self.enabled.for_loops {
+                    self.diags.push(Diagnostic::error(
+                        for_stmt.span,
+                        "`for` loops are an extension; enable them with --ext=for",
+                    ));
+                }
+                if let Some(ForInit::Decl(decl)) = &for_stmt.init {
+                    self.visit_var_decl(decl);
+                }
+                self.visit_stmt(&for_stmt.body);
+            }
+            Stmt::DoWhile(do_while) => {
+                if !self.enabled.do_while {
+                    self.diags.push(Diagnostic::error(
+                        do_while.span,
+                        "`do`-`while` loops are an extension; enable them with --ext=do-while",
+                    ));
+                }
+                self.visit_stmt(&do_while.body);
+            }
+            Stmt::Switch(switch) => {
+                if !self.enabled.switch {
+                    self.diags.push(Diagnostic::error(
+                        switch.span,
+                        "`switch` statements are an extension; enable them with --ext=switch",
+                    ));
+                }
+                self.visit_block(&switch.body);
+            }
+            Stmt::Return(_) | Stmt::Assign(_) | Stmt::Expr(_) | Stmt::Break(_) | Stmt::Continue(_) => {}
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        for item in &block.items {
+            match item {
+                BlockItem::Stmt(stmt) => self.visit_stmt(stmt),
+                BlockItem::Decl(decl) => self.visit_decl(decl),
+            }
+        }
+    }
+
+    /// Report a declaration of type `btype` if it is `char`; `span` is that
+    /// of the (first) declared name
+    fn visit_btype(&mut self, btype: BType, span: Span) {
+        if btype == BType::Char && !self.enabled.char_type {
+            self.diags.push(Diagnostic::error(span, "`char` is an extension; enable it with --ext=char"));
+        }
+    }
+
+    fn visit_var_decl(&mut self, decl: &VarDecl) {
+        self.visit_btype(decl.btype, decl.defs[0].span);
+        match decl.static_span {
+            Some(span) if !self.enabled.static_locals => self.diags.push(Diagnostic::error(
+                span,
+                "`static` variables are an extension; enable them with --ext=static",
+            )),
+            _ => {}
+        }
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Const(decl) => self.visit_btype(decl.btype, decl.defs[0].span),
+            Decl::Var(decl) => self.visit_var_decl(decl),
+            Decl::Enum(decl) => {
+                if !self.enabled.enums {
+                    self.diags.push(Diagnostic::error(
+                        decl.span,
+                        "`enum` declarations are an extension; enable them with --ext=enum",
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Report every use of an extension that is not enabled
+pub fn check(ast: &CompUnit, enabled: &Extensions) -> Vec<Diagnostic> {
+    let mut checker = ExtensionChecker { enabled, diags: Vec::new() };
+    for item in &ast.items {
+        match item {
+            GlobalItem::Decl(decl) => checker.visit_decl(decl),
+            GlobalItem::FuncDef(func_def) => {
+                for param in &func_def.params {
+                    checker.visit_btype(param.btype, param.span);
+                }
+                checker.visit_block(&func_def.block);
+            }
+        }
+    }
+    for (id, expr) in ast.exprs.iter() {
+        match expr {
+            Expr::Ternary(_, _, _, span) if !enabled.ternary => checker.diags.push(Diagnostic::error(
+                *span,
+                "`?:` is an extension; enable it with --ext=ternary",
+            )),
+            Expr::Bitwise(_, op, _, span) if !enabled.bitwise => checker.diags.push(Diagnostic::error(
+                *span,
+                format!("`{}` is an extension; enable bitwise operators with --ext=bitwise", op),
+            )),
+            Expr::Char(_) if !enabled.char_type => checker.diags.push(Diagnostic::error(
+                ast.exprs.span(id),
+                "character literals are an extension; enable them with --ext=char",
+            )),
+            _ => {}
+        }
+    }
+    checker.diags
+}
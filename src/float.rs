@@ -0,0 +1,121 @@
+//! How `float` is carried through the Koopa IR, which has no floating-point
+//! type.
+//!
+//! A `float` value is the `i32` holding its IEEE 754 single-precision bits:
+//! literals are `integer`s, and loads, stores and block arguments move floats
+//! like ints. What depends on the type is done by calls to functions that only
+//! exist in the IR (`@__yasysyc_fadd` and so on, see [`FloatOp`]), which the
+//! backend replaces with the instructions of the F extension. Which
+//! parameters and results are floats is recorded next to the IR in a
+//! [`FloatSignature`] per function, as the calling convention passes them in
+//! the floating-point registers.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// An operation on `float`s, emitted as a call to the function of [`FloatOp::name`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FloatOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Neg,
+    /// `==`, giving 0 or 1 like the other comparisons
+    Eq,
+    Lt,
+    Le,
+    /// Convert an `int` to the nearest `float`
+    FromInt,
+    /// Convert a `float` to an `int`, dropping the fractional part as C does
+    ToInt,
+}
+
+impl FloatOp {
+    pub const ALL: [FloatOp; 10] = [
+        Self::Add,
+        Self::Sub,
+        Self::Mul,
+        Self::Div,
+        Self::Neg,
+        Self::Eq,
+        Self::Lt,
+        Self::Le,
+        Self::FromInt,
+        Self::ToInt,
+    ];
+
+    /// Name of the function standing for the operation, without the `@`;
+    /// names starting with `__` are reserved, so no SysY function clashes
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Add => "__yasysyc_fadd",
+            Self::Sub => "__yasysyc_fsub",
+            Self::Mul => "__yasysyc_fmul",
+            Self::Div => "__yasysyc_fdiv",
+            Self::Neg => "__yasysyc_fneg",
+            Self::Eq => "__yasysyc_feq",
+            Self::Lt => "__yasysyc_flt",
+            Self::Le => "__yasysyc_fle",
+            Self::FromInt => "__yasysyc_itof",
+            Self::ToInt => "__yasysyc_ftoi",
+        }
+    }
+
+    /// The operation a function named `name` (without the `@`) stands for
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|op| op.name() == name)
+    }
+
+    /// Number of operands
+    pub fn arity(self) -> usize {
+        match self {
+            Self::Neg | Self::FromInt | Self::ToInt => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Which parameters and which result of a function are `float`s
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FloatSignature {
+    /// Whether each parameter is a `float`; array parameters are pointers
+    pub params: Vec<bool>,
+    pub ret: bool,
+}
+
+impl FloatSignature {
+    /// Whether anything is a `float`, so the signature needs recording
+    pub fn has_floats(&self) -> bool {
+        self.ret || self.params.contains(&true)
+    }
+}
+
+/// Written as a letter per parameter and one for the result, `f` for a float
+/// and `i` for anything else, e.g. `if->f` for `float f(int a, float b)`
+impl Display for FloatSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = |float: bool| if float { 'f' } else { 'i' };
+        let params: String = self.params.iter().map(|&float| letter(float)).collect();
+        write!(f, "{}->{}", params, letter(self.ret))
+    }
+}
+
+impl FromStr for FloatSignature {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid float signature: {}", text);
+        let letter = |c: char| match c {
+            'f' => Ok(true),
+            'i' => Ok(false),
+            _ => Err(invalid()),
+        };
+        let (params, ret) = text.trim().split_once("->").ok_or_else(invalid)?;
+        let mut ret = ret.chars();
+        let (Some(ret), None) = (ret.next(), ret.next()) else {
+            return Err(invalid());
+        };
+        Ok(Self { params: params.chars().map(letter).collect::<Result<_, _>>()?, ret: letter(ret)? })
+    }
+}
@@ -0,0 +1,91 @@
+//! Reproducers for internal compiler errors.
+//!
+//! When an optimization pass or code generation panics, the IR the failing
+//! stage got is written to `yasysyc-ice-<hash>.koopa`, with that stage and the
+//! ones after it in a header comment, so the failure can be replayed with
+//! `--from-koopa` without the original source.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::float::FloatSignature;
+
+const TITLE: &str = "// yasysyc internal compiler error reproducer";
+
+/// What the header of a reproducer records besides the IR
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Header {
+    /// The stages to run, in order; `codegen` stands for code generation
+    /// after the passes
+    pub pipeline: Vec<String>,
+    /// Functions the passes leave alone (`#pragma optimize off`)
+    pub unoptimized: Vec<String>,
+    /// Which parameters and results are `float`s, for the functions with
+    /// any, sorted by name; the IR does not tell (see `crate::float`)
+    pub floats: Vec<(String, FloatSignature)>,
+}
+
+/// Write `ir` and the `pipeline` it was fed to into a reproducer file in
+/// `dir`, returning its path. The name is derived from the contents, so the
+/// same failure always produces the same file.
+pub fn write_reproducer(
+    dir: &Path,
+    ir: &str,
+    pipeline: &[&str],
+    unoptimized: &HashSet<String>,
+    floats: &HashMap<String, FloatSignature>,
+) -> io::Result<PathBuf> {
+    let mut unoptimized: Vec<&str> = unoptimized.iter().map(String::as_str).collect();
+    unoptimized.sort_unstable();
+    let mut floats: Vec<String> = floats.iter().map(|(name, signature)| format!("{} {}", name, signature)).collect();
+    floats.sort_unstable();
+
+    let mut hasher = DefaultHasher::new();
+    ir.hash(&mut hasher);
+    pipeline.hash(&mut hasher);
+    unoptimized.hash(&mut hasher);
+    floats.hash(&mut hasher);
+    let path = dir.join(format!("yasysyc-ice-{:016x}.koopa", hasher.finish()));
+
+    let mut text = format!("{}\n// pipeline: {}\n", TITLE, pipeline.join(", "));
+    if !unoptimized.is_empty() {
+        text += &format!("// unoptimized: {}\n", unoptimized.join(", "));
+    }
+    for float in floats {
+        text += &format!("// float: {}\n", float);
+    }
+    text += "\n";
+    text += ir;
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+/// The header of a reproducer, or `None` if `text` does not start with one
+pub fn read_header(text: &str) -> Option<Header> {
+    let mut lines = text.lines();
+    if lines.next()? != TITLE {
+        return None;
+    }
+    let mut header = Header::default();
+    for line in lines.take_while(|line| line.starts_with("//")) {
+        if let Some(rest) = line.strip_prefix("// pipeline:") {
+            header.pipeline = names(rest);
+        } else if let Some(rest) = line.strip_prefix("// unoptimized:") {
+            header.unoptimized = names(rest);
+        } else if let Some(rest) = line.strip_prefix("// float:") {
+            let float = rest.trim().split_once(' ').and_then(|(name, signature)| {
+                Some((name.to_string(), signature.parse().ok()?))
+            });
+            header.floats.extend(float);
+        }
+    }
+    Some(header)
+}
+
+/// The names in a comma-separated list
+fn names(list: &str) -> Vec<String> {
+    list.split(',').map(str::trim).filter(|name| !name.is_empty()).map(String::from).collect()
+}
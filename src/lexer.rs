@@ -0,0 +1,292 @@
+//! The lexer feeding the parser generated from `sysy.lalrpop`.
+//!
+//! The keywords of the language extensions (`for`, `do`, `switch`, `case`,
+//! `default`, `char`, `enum` and `static`) are only keywords when their
+//! extension is enabled. Otherwise they are ordinary identifiers, as in
+//! standard SysY, so that a program naming a variable `default` still parses.
+
+use std::fmt::{self, Display};
+
+use lalrpop_util::ParseError;
+
+use crate::ast::{Span, SyntaxError};
+use crate::diag::Diagnostic;
+use crate::ext::Extensions;
+
+/// Every keyword, including those of the extensions
+const KEYWORDS: [&str; 18] = [
+    "int", "float", "void", "const", "return", "if", "else", "while", "break", "continue", "for", "do", "switch",
+    "case", "default", "char", "enum", "static",
+];
+
+/// The punctuators, longest first so that `<=` is not read as `<` and `=`
+const PUNCTUATORS: [&str; 30] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "(", ")", "{", "}", "[", "]", ";", ",", "=", "+", "-", "*", "/",
+    "%", "!", "<", ">", "?", ":", "&", "|", "^",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tok<'input> {
+    Keyword(&'static str),
+    Punct(&'static str),
+    Ident(&'input str),
+    DecInt(&'input str),
+    OctInt(&'input str),
+    HexInt(&'input str),
+    Float(&'input str),
+    HexFloat(&'input str),
+    /// A string literal, quotes included
+    Str(&'input str),
+    /// A character literal, quotes included
+    Char(&'input str),
+}
+
+impl Display for Tok<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Keyword(text) | Self::Punct(text) => write!(f, "{}", text),
+            Self::Ident(text)
+            | Self::DecInt(text)
+            | Self::OctInt(text)
+            | Self::HexInt(text)
+            | Self::Float(text)
+            | Self::HexFloat(text)
+            | Self::Str(text)
+            | Self::Char(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+pub type Spanned<'input> = Result<(usize, Tok<'input>, usize), SyntaxError>;
+
+pub struct Lexer<'input> {
+    text: &'input str,
+    pos: usize,
+    extensions: Extensions,
+}
+
+/// Length of the prefix of `text` whose characters satisfy `pred`
+fn prefix_len(text: &str, pred: impl Fn(char) -> bool) -> usize {
+    text.find(|c| !pred(c)).unwrap_or(text.len())
+}
+
+/// Length of the exponent at the start of `text` (`e` or `p`, an optional
+/// sign and decimal digits), or 0 if there is no complete one
+fn exponent_len(text: &str, markers: [char; 2]) -> usize {
+    let Some(rest) = text.strip_prefix(markers) else {
+        return 0;
+    };
+    let sign = usize::from(rest.starts_with(['+', '-']));
+    match prefix_len(&rest[sign..], |c| c.is_ascii_digit()) {
+        0 => 0,
+        digits => 1 + sign + digits,
+    }
+}
+
+/// The longest number at the start of `text`, which starts with a digit or
+/// with `.` and a digit, and its length
+fn number(text: &str) -> (Tok<'_>, usize) {
+    // Digits, then a fraction if there are digits on either side of the point
+    let mantissa = |text: &str, pred: fn(char) -> bool| {
+        let int_digits = prefix_len(text, pred);
+        let Some(fraction) = text[int_digits..].strip_prefix('.') else {
+            return (int_digits, int_digits, false);
+        };
+        match prefix_len(fraction, pred) {
+            0 if int_digits == 0 => (0, 0, false),
+            frac_digits => (int_digits, int_digits + 1 + frac_digits, true),
+        }
+    };
+    if let Some(rest) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        let (int_digits, len, _) = mantissa(rest, |c| c.is_ascii_hexdigit());
+        let exponent = exponent_len(&rest[len..], ['p', 'P']);
+        if len > 0 && exponent > 0 {
+            let len = 2 + len + exponent;
+            return (Tok::HexFloat(&text[..len]), len);
+        }
+        if int_digits > 0 {
+            let len = 2 + int_digits;
+            return (Tok::HexInt(&text[..len]), len);
+        }
+    }
+    let (int_digits, len, fraction) = mantissa(text, |c| c.is_ascii_digit());
+    let exponent = if len > 0 { exponent_len(&text[len..], ['e', 'E']) } else { 0 };
+    if fraction || exponent > 0 {
+        let len = len + exponent;
+        (Tok::Float(&text[..len]), len)
+    } else if let Some(digits) = text.strip_prefix('0') {
+        let len = 1 + prefix_len(digits, |c| matches!(c, '0'..='7'));
+        (Tok::OctInt(&text[..len]), len)
+    } else {
+        (Tok::DecInt(&text[..int_digits]), int_digits)
+    }
+}
+
+/// Length of the string literal at the start of `text`, if it is closed on
+/// the same line
+fn string_len(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some(i + 1),
+            '\n' => return None,
+            '\\' => match chars.next() {
+                Some((_, '\n')) | None => return None,
+                Some(_) => {}
+            },
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Length of the character literal at the start of `text`: a printable
+/// ASCII character other than `'` and `\`, or an escape as in strings
+fn char_len(text: &str) -> Option<usize> {
+    let mut chars = text.chars().skip(1);
+    let len = match chars.next()? {
+        '\\' => match chars.next()? {
+            '\n' => return None,
+            escape => 2 + escape.len_utf8(),
+        },
+        c @ ' '..='~' if c != '\'' => 2,
+        _ => return None,
+    };
+    text[len..].starts_with('\'').then_some(len + 1)
+}
+
+impl<'input> Lexer<'input> {
+    /// A lexer for `text` that reads the keywords of the given extensions
+    pub fn new(text: &'input str, extensions: &Extensions) -> Self {
+        Self { text, pos: 0, extensions: extensions.clone() }
+    }
+
+    /// Skip whitespace and comments
+    fn skip_trivia(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            let rest = &self.text[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find(['\n', '\r']).unwrap_or(trimmed.len());
+            } else if let Some(comment) = trimmed.strip_prefix("/*") {
+                let Some(end) = comment.find("*/") else {
+                    let span = Span::new(self.pos, self.pos + 2);
+                    return Err(SyntaxError { span, message: "unterminated block comment" });
+                };
+                self.pos += 2 + end + 2;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The token at the start of `rest`, and its length
+    fn token(&self, rest: &'input str) -> Result<(Tok<'input>, usize), &'static str> {
+        let first = rest.chars().next().unwrap_or_default();
+        if first == '_' || first.is_ascii_alphabetic() {
+            let word = &rest[..prefix_len(rest, |c| c == '_' || c.is_ascii_alphanumeric())];
+            let keyword = KEYWORDS.iter().find(|&&keyword| keyword == word);
+            let token = match (keyword, self.extensions.keyword(word)) {
+                (Some(&keyword), None | Some((_, true))) => Tok::Keyword(keyword),
+                _ => Tok::Ident(word),
+            };
+            return Ok((token, word.len()));
+        }
+        if first.is_ascii_digit() || (first == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit())) {
+            return Ok(number(rest));
+        }
+        match first {
+            '"' => {
+                let len = string_len(rest).ok_or("unterminated string literal")?;
+                return Ok((Tok::Str(&rest[..len]), len));
+            }
+            '\'' => {
+                let len = char_len(rest).ok_or("invalid character literal")?;
+                return Ok((Tok::Char(&rest[..len]), len));
+            }
+            _ => {}
+        }
+        match PUNCTUATORS.iter().find(|&&punct| rest.starts_with(punct)) {
+            Some(&punct) => Ok((Tok::Punct(punct), punct.len())),
+            None => Err("invalid token"),
+        }
+    }
+}
+
+impl<'input> Iterator for Lexer<'input> {
+    type Item = Spanned<'input>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.skip_trivia() {
+            // Nothing after an unterminated comment can be read
+            self.pos = self.text.len();
+            return Some(Err(e));
+        }
+        let rest = &self.text[self.pos..];
+        if rest.is_empty() {
+            return None;
+        }
+        let start = self.pos;
+        match self.token(rest) {
+            Ok((token, len)) => {
+                self.pos += len;
+                Some(Ok((start, token, self.pos)))
+            }
+            Err(message) => {
+                let len = rest.chars().next().map_or(1, char::len_utf8);
+                self.pos = self.text.len();
+                Some(Err(SyntaxError { span: Span::new(start, start + len), message }))
+            }
+        }
+    }
+}
+
+/// The last word before `offset`, in the statement or declaration around it,
+/// that would be a keyword of an extension that is not enabled, and the name
+/// of that extension: the likely cause of a syntax error at `offset`
+fn disabled_keyword_before(text: &str, offset: usize, extensions: &Extensions) -> Option<(Span, &'static str)> {
+    let mut found = None;
+    for token in Lexer::new(text, extensions) {
+        let Ok((start, token, end)) = token else {
+            break;
+        };
+        if start >= offset {
+            break;
+        }
+        match token {
+            Tok::Punct(";" | "{" | "}") => found = None,
+            Tok::Ident(word) => {
+                if let Some((name, false)) = extensions.keyword(word) {
+                    found = Some((Span::new(start, end), name));
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// The diagnostic of an error parsing `text` with the keywords of
+/// `extensions`. A syntax error after a keyword of an extension that is not
+/// enabled, which was read as an identifier, gets a note saying so.
+pub fn parse_error(text: &str, error: ParseError<usize, Tok<'_>, SyntaxError>, extensions: &Extensions) -> Diagnostic {
+    let diag = match error {
+        ParseError::User { error } => return Diagnostic::error(error.span, error.message),
+        ParseError::InvalidToken { location } => Diagnostic::error(Span::new(location, location + 1), "invalid token"),
+        ParseError::UnrecognizedEof { location, .. } => {
+            Diagnostic::error(Span::new(location, location), "unexpected end of file")
+        }
+        ParseError::UnrecognizedToken { token: (start, token, end), .. }
+        | ParseError::ExtraToken { token: (start, token, end) } => {
+            Diagnostic::error(Span::new(start, end), format!("unexpected '{}'", token))
+        }
+    };
+    match disabled_keyword_before(text, diag.span.start, extensions) {
+        Some((span, name)) => {
+            let word = &text[span.start..span.end];
+            diag.with_note(span, format!("`{}` is only a keyword with --ext={}", word, name))
+        }
+        None => diag,
+    }
+}
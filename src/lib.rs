@@ -1,2 +1,20 @@
+pub mod analysis;
 pub mod ast;
 pub mod backend;
+pub mod diag;
+pub mod dump;
+pub mod ext;
+pub mod float;
+pub mod ice;
+pub mod lexer;
+pub mod lint;
+pub mod lsp;
+pub mod opt;
+pub mod preprocess;
+pub mod roundtrip;
+pub mod semant;
+pub mod verify;
+
+use lalrpop_util::lalrpop_mod;
+
+lalrpop_mod!(#[allow(clippy::all)] pub sysy);
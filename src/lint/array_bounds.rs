@@ -0,0 +1,240 @@
+//! `-Warray-bounds`: warn when an array is indexed with a constant that is
+//! negative or past the end of its dimension.
+//!
+//! Only dimensions whose length is known are checked, so the omitted first
+//! dimension of an array parameter never is.
+
+use crate::ast::const_eval::{self, ConstScope, ConstValue};
+use crate::ast::*;
+use crate::diag::Diagnostic;
+use crate::semant::SymbolTable;
+
+/// A declared name
+struct Entry {
+    /// Value of a constant, if it could be evaluated
+    value: Option<ConstValue>,
+    /// Length of each dimension of an array, where it is known; empty for a scalar
+    dims: Vec<Option<usize>>,
+    /// Span of the declared name
+    span: Span,
+}
+
+struct BoundsChecker<'a> {
+    exprs: &'a ExprArena,
+    symbols: SymbolTable<Entry>,
+    diags: Vec<Diagnostic>,
+}
+
+impl ConstScope for BoundsChecker<'_> {
+    fn lookup_const(&self, ident: &Ident) -> Option<ConstValue> {
+        self.symbols.lookup(ident).and_then(|entry| entry.value.clone())
+    }
+}
+
+impl BoundsChecker<'_> {
+    fn dims(&self, dims: &[ConstExpr]) -> Vec<Option<usize>> {
+        dims.iter()
+            .map(|dim| match const_eval::eval(self.exprs, dim.expr, self) {
+                Ok(len) if len > 0 => Some(len as usize),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn visit_lval(&mut self, lval: &LVal) {
+        for (i, &index) in lval.indices.iter().enumerate() {
+            self.visit_expr(index);
+            let Some(entry) = self.symbols.lookup(&lval.ident) else {
+                continue;
+            };
+            let (Some(&Some(len)), Ok(value)) = (entry.dims.get(i), const_eval::eval(self.exprs, index, self)) else {
+                continue;
+            };
+            let message = if value < 0 {
+                format!("array index {} is before the beginning of '{}'", value, lval.ident)
+            } else if value as usize >= len {
+                let of = match entry.dims.len() {
+                    1 => String::new(),
+                    _ => format!("dimension {} of ", i + 1),
+                };
+                format!("array index {} is past the end of {}'{}', which has length {}", value, of, lval.ident, len)
+            } else {
+                continue;
+            };
+            let diag = Diagnostic::warning("array-bounds", self.exprs.span(index), message)
+                .with_note(entry.span, format!("array '{}' declared here", lval.ident));
+            self.diags.push(diag);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: ExprId) {
+        match &self.exprs[expr] {
+            Expr::LVal(lval) => self.visit_lval(lval),
+            Expr::Unary(_, expr) => self.visit_expr(*expr),
+            Expr::Binary(lhs, _, rhs) | Expr::Bitwise(lhs, _, rhs, _) => {
+                self.visit_expr(*lhs);
+                self.visit_expr(*rhs);
+            }
+            Expr::Ternary(cond, then_expr, else_expr, _) => {
+                self.visit_expr(*cond);
+                self.visit_expr(*then_expr);
+                self.visit_expr(*else_expr);
+            }
+            Expr::Call(_, args) => {
+                for &arg in args {
+                    self.visit_expr(arg);
+                }
+            }
+            Expr::Number(_) | Expr::Float(_) | Expr::Str(_) | Expr::Char(_) => {}
+        }
+    }
+
+    fn visit_init(&mut self, init: &InitVal) {
+        match init {
+            InitVal::Expr(expr) => self.visit_expr(*expr),
+            InitVal::List(items) => {
+                for item in items {
+                    self.visit_init(item);
+                }
+            }
+        }
+    }
+
+    fn visit_var_decl(&mut self, decl: &VarDecl) {
+        for def in &decl.defs {
+            if let Some(init) = &def.init {
+                self.visit_init(init);
+            }
+            let dims = self.dims(&def.dims);
+            self.symbols.declare(&def.id, Entry { value: None, dims, span: def.span });
+        }
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Const(decl) => {
+                for def in &decl.defs {
+                    self.visit_init(&def.init);
+                    let value = const_eval::eval_def(self.exprs, decl.btype, def, self).ok();
+                    let dims = self.dims(&def.dims);
+                    self.symbols.declare(&def.id, Entry { value, dims, span: def.span });
+                }
+            }
+            Decl::Var(decl) => self.visit_var_decl(decl),
+            Decl::Enum(decl) => {
+                let mut next = Some(0);
+                for enumerator in &decl.enumerators {
+                    let value = next
+                        .and_then(|next| const_eval::eval_enumerator(self.exprs, enumerator, next, self).ok());
+                    let entry = Entry { value: value.map(ConstValue::Int), dims: Vec::new(), span: enumerator.span };
+                    self.symbols.declare(&enumerator.id, entry);
+                    next = value.map(|value| value.wrapping_add(1));
+                }
+            }
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.symbols.enter_scope();
+        for item in &block.items {
+            match item {
+                BlockItem::Decl(decl) => self.visit_decl(decl),
+                BlockItem::Stmt(stmt) => self.visit_stmt(stmt),
+            }
+        }
+        self.symbols.exit_scope();
+    }
+
+    fn visit_for_step(&mut self, step: &ForStep) {
+        match step {
+            ForStep::Assign(assign) => {
+                self.visit_lval(&assign.lval);
+                self.visit_expr(assign.expr);
+            }
+            ForStep::Expr(expr) => self.visit_expr(*expr),
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Return(return_stmt) => {
+                if let Some(expr) = return_stmt.expr {
+                    self.visit_expr(expr);
+                }
+            }
+            Stmt::Assign(assign) => {
+                self.visit_lval(&assign.lval);
+                self.visit_expr(assign.expr);
+            }
+            Stmt::Block(block) => self.visit_block(block),
+            Stmt::Expr(expr) => {
+                if let Some(expr) = expr {
+                    self.visit_expr(*expr);
+                }
+            }
+            Stmt::If(if_stmt) => {
+                self.visit_expr(if_stmt.cond);
+                self.visit_stmt(&if_stmt.then_stmt);
+                if let Some(else_stmt) = &if_stmt.else_stmt {
+                    self.visit_stmt(else_stmt);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.visit_expr(while_stmt.cond);
+                self.visit_stmt(&while_stmt.body);
+            }
+            Stmt::DoWhile(do_while) => {
+                self.visit_stmt(&do_while.body);
+                self.visit_expr(do_while.cond);
+            }
+            Stmt::For(for_stmt) => {
+                self.symbols.enter_scope();
+                match &for_stmt.init {
+                    Some(ForInit::Decl(decl)) => self.visit_var_decl(decl),
+                    Some(ForInit::Step(step)) => self.visit_for_step(step),
+                    None => {}
+                }
+                if let Some(cond) = for_stmt.cond {
+                    self.visit_expr(cond);
+                }
+                self.visit_stmt(&for_stmt.body);
+                if let Some(step) = &for_stmt.step {
+                    self.visit_for_step(step);
+                }
+                self.symbols.exit_scope();
+            }
+            Stmt::Switch(switch) => {
+                self.visit_expr(switch.expr);
+                self.visit_block(&switch.body);
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => {}
+        }
+    }
+}
+
+pub fn check(ast: &CompUnit) -> Vec<Diagnostic> {
+    let mut checker = BoundsChecker {
+        exprs: &ast.exprs,
+        symbols: SymbolTable::new(),
+        diags: Vec::new(),
+    };
+    for item in &ast.items {
+        match item {
+            GlobalItem::Decl(decl) => checker.visit_decl(decl),
+            GlobalItem::FuncDef(func_def) => {
+                checker.symbols.enter_scope();
+                for param in &func_def.params {
+                    // The first dimension of an array parameter is omitted
+                    let dims = match &param.dims {
+                        Some(dims) => std::iter::once(None).chain(checker.dims(dims)).collect(),
+                        None => Vec::new(),
+                    };
+                    checker.symbols.declare(&param.id, Entry { value: None, dims, span: param.span });
+                }
+                checker.visit_block(&func_def.block);
+                checker.symbols.exit_scope();
+            }
+        }
+    }
+    checker.diags
+}
@@ -0,0 +1,305 @@
+//! `-Wconversion`: warn when a `float` value is implicitly converted to `int`
+//! (in an initializer, an assignment, a `return` or a call argument), which
+//! silently drops its fractional part.
+
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diag::Diagnostic;
+use crate::semant::SymbolTable;
+
+/// The scalar type of an expression, as far as conversions are concerned
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Ty {
+    Int,
+    Float,
+}
+
+impl From<&BType> for Ty {
+    fn from(btype: &BType) -> Self {
+        match btype {
+            BType::Int | BType::Char => Self::Int,
+            BType::Float => Self::Float,
+        }
+    }
+}
+
+impl From<&FuncType> for Ty {
+    fn from(func_type: &FuncType) -> Self {
+        match func_type {
+            // A `void` function has no value to convert
+            FuncType::Int | FuncType::Void => Self::Int,
+            FuncType::Float => Self::Float,
+        }
+    }
+}
+
+/// A function's return type and the types of its scalar parameters (`None`
+/// for an array parameter)
+type Signature = (Ty, Vec<Option<Ty>>);
+
+struct ConversionChecker<'a> {
+    exprs: &'a ExprArena,
+    /// Types of the variables and constants in scope
+    symbols: SymbolTable<Ty>,
+    functions: HashMap<Ident, Signature>,
+    /// Return type of the function being checked
+    ret_ty: Ty,
+    diags: Vec<Diagnostic>,
+}
+
+impl ConversionChecker<'_> {
+    fn lval_ty(&self, lval: &LVal) -> Ty {
+        self.symbols.lookup(&lval.ident).copied().unwrap_or(Ty::Int)
+    }
+
+    fn ty(&self, expr: ExprId) -> Ty {
+        match &self.exprs[expr] {
+            Expr::Float(_) => Ty::Float,
+            Expr::LVal(lval) => self.lval_ty(lval),
+            Expr::Unary(UnaryOp::Not, _) => Ty::Int,
+            Expr::Unary(_, expr) => self.ty(*expr),
+            Expr::Binary(lhs, op, rhs) => match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
+                    if self.ty(*lhs) == Ty::Float || self.ty(*rhs) == Ty::Float {
+                        Ty::Float
+                    } else {
+                        Ty::Int
+                    }
+                }
+                // Comparisons and logical operators yield 0 or 1
+                _ => Ty::Int,
+            },
+            Expr::Ternary(_, then_expr, else_expr, _) => {
+                if self.ty(*then_expr) == Ty::Float || self.ty(*else_expr) == Ty::Float {
+                    Ty::Float
+                } else {
+                    Ty::Int
+                }
+            }
+            Expr::Call(ident, _) => self.functions.get(ident).map_or(Ty::Int, |&(ret, _)| ret),
+            Expr::Number(_) | Expr::Char(_) | Expr::Bitwise(..) | Expr::Str(_) => Ty::Int,
+        }
+    }
+
+    /// Report `expr` if it is a `float` converted to an `int` `target`
+    fn check(&mut self, expr: ExprId, target: Ty, what: &str) {
+        if target == Ty::Int && self.ty(expr) == Ty::Float {
+            let span = self.exprs.span(expr);
+            self.diags.push(
+                Diagnostic::warning("conversion", span, format!("implicit conversion from float to int in {}", what))
+                    .with_note(
+                        span,
+                        "the fractional part is discarded; an explicit conversion would silence this, \
+                         but the extended dialect has no cast syntax yet",
+                    ),
+            );
+        }
+    }
+
+    /// Check the call arguments in `expr` and its operands
+    fn visit_expr(&mut self, expr: ExprId) {
+        match &self.exprs[expr] {
+            Expr::Call(ident, args) => {
+                let params = self.functions.get(ident).map(|(_, params)| params.clone()).unwrap_or_default();
+                for (i, &arg) in args.iter().enumerate() {
+                    self.visit_expr(arg);
+                    if let Some(&Some(param)) = params.get(i) {
+                        self.check(arg, param, &format!("argument {} of '{}'", i + 1, ident));
+                    }
+                }
+            }
+            Expr::Unary(_, expr) => self.visit_expr(*expr),
+            Expr::Binary(lhs, _, rhs) | Expr::Bitwise(lhs, _, rhs, _) => {
+                self.visit_expr(*lhs);
+                self.visit_expr(*rhs);
+            }
+            Expr::Ternary(cond, then_expr, else_expr, _) => {
+                self.visit_expr(*cond);
+                self.visit_expr(*then_expr);
+                self.visit_expr(*else_expr);
+            }
+            Expr::LVal(lval) => {
+                for &index in &lval.indices {
+                    self.visit_expr(index);
+                }
+            }
+            Expr::Number(_) | Expr::Char(_) | Expr::Float(_) | Expr::Str(_) => {}
+        }
+    }
+
+    fn visit_init(&mut self, init: &InitVal, target: Ty) {
+        match init {
+            InitVal::Expr(expr) => {
+                self.visit_expr(*expr);
+                self.check(*expr, target, "initializer");
+            }
+            InitVal::List(items) => {
+                for item in items {
+                    self.visit_init(item, target);
+                }
+            }
+        }
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Const(decl) => {
+                let ty = Ty::from(&decl.btype);
+                for def in &decl.defs {
+                    self.visit_init(&def.init, ty);
+                    self.symbols.declare(&def.id, ty);
+                }
+            }
+            Decl::Var(decl) => self.visit_var_decl(decl),
+            Decl::Enum(decl) => {
+                for enumerator in &decl.enumerators {
+                    self.symbols.declare(&enumerator.id, Ty::Int);
+                }
+            }
+        }
+    }
+
+    fn visit_var_decl(&mut self, decl: &VarDecl) {
+        let ty = Ty::from(&decl.btype);
+        for def in &decl.defs {
+            if let Some(init) = &def.init {
+                self.visit_init(init, ty);
+            }
+            self.symbols.declare(&def.id, ty);
+        }
+    }
+
+    fn visit_assign(&mut self, assign: &AssignStmt) {
+        for &index in &assign.lval.indices {
+            self.visit_expr(index);
+        }
+        self.visit_expr(assign.expr);
+        let target = self.lval_ty(&assign.lval);
+        self.check(assign.expr, target, "assignment");
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.symbols.enter_scope();
+        for item in &block.items {
+            match item {
+                BlockItem::Decl(decl) => self.visit_decl(decl),
+                BlockItem::Stmt(stmt) => self.visit_stmt(stmt),
+            }
+        }
+        self.symbols.exit_scope();
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Return(return_stmt) => {
+                if let Some(expr) = return_stmt.expr {
+                    self.visit_expr(expr);
+                    self.check(expr, self.ret_ty, "return value");
+                }
+            }
+            Stmt::Assign(assign) => self.visit_assign(assign),
+            Stmt::Block(block) => self.visit_block(block),
+            Stmt::Expr(expr) => {
+                if let Some(expr) = expr {
+                    self.visit_expr(*expr);
+                }
+            }
+            Stmt::If(if_stmt) => {
+                self.visit_expr(if_stmt.cond);
+                self.visit_stmt(&if_stmt.then_stmt);
+                if let Some(else_stmt) = &if_stmt.else_stmt {
+                    self.visit_stmt(else_stmt);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.visit_expr(while_stmt.cond);
+                self.visit_stmt(&while_stmt.body);
+            }
+            Stmt::For(for_stmt) => {
+                self.symbols.enter_scope();
+                match &for_stmt.init {
+                    Some(ForInit::Decl(decl)) => self.visit_var_decl(decl),
+                    Some(ForInit::Step(step)) => self.visit_for_step(step),
+                    None => {}
+                }
+                if let Some(cond) = for_stmt.cond {
+                    self.visit_expr(cond);
+                }
+                if let Some(step) = &for_stmt.step {
+                    self.visit_for_step(step);
+                }
+                self.visit_stmt(&for_stmt.body);
+                self.symbols.exit_scope();
+            }
+            Stmt::DoWhile(do_while) => {
+                self.visit_stmt(&do_while.body);
+                self.visit_expr(do_while.cond);
+            }
+            Stmt::Switch(switch) => {
+                self.visit_expr(switch.expr);
+                self.visit_block(&switch.body);
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => {}
+        }
+    }
+
+    fn visit_for_step(&mut self, step: &ForStep) {
+        match step {
+            ForStep::Assign(assign) => self.visit_assign(assign),
+            ForStep::Expr(expr) => self.visit_expr(*expr),
+        }
+    }
+}
+
+/// Signatures of the runtime library functions that take or return scalars
+fn runtime_functions() -> HashMap<Ident, Signature> {
+    let int = Some(Ty::Int);
+    let float = Some(Ty::Float);
+    [
+        ("getint", Ty::Int, vec![]),
+        ("getch", Ty::Int, vec![]),
+        ("getfloat", Ty::Float, vec![]),
+        ("getarray", Ty::Int, vec![None]),
+        ("getfarray", Ty::Int, vec![None]),
+        ("putint", Ty::Int, vec![int]),
+        ("putch", Ty::Int, vec![int]),
+        ("putfloat", Ty::Int, vec![float]),
+        ("putarray", Ty::Int, vec![int, None]),
+        ("putfarray", Ty::Int, vec![int, None]),
+    ]
+    .into_iter()
+    .map(|(name, ret, params)| (name.to_string().into(), (ret, params)))
+    .collect()
+}
+
+pub fn check(ast: &CompUnit) -> Vec<Diagnostic> {
+    let mut checker = ConversionChecker {
+        exprs: &ast.exprs,
+        symbols: SymbolTable::new(),
+        functions: runtime_functions(),
+        ret_ty: Ty::Int,
+        diags: Vec::new(),
+    };
+    for item in &ast.items {
+        match item {
+            GlobalItem::Decl(decl) => checker.visit_decl(decl),
+            GlobalItem::FuncDef(func_def) => {
+                let params = func_def
+                    .params
+                    .iter()
+                    .map(|param| param.dims.is_none().then(|| Ty::from(&param.btype)))
+                    .collect();
+                checker.functions.insert(func_def.id.clone(), (Ty::from(&func_def.func_type), params));
+                checker.ret_ty = Ty::from(&func_def.func_type);
+                checker.symbols.enter_scope();
+                for param in &func_def.params {
+                    checker.symbols.declare(&param.id, Ty::from(&param.btype));
+                }
+                checker.visit_block(&func_def.block);
+                checker.symbols.exit_scope();
+            }
+        }
+    }
+    checker.diags
+}
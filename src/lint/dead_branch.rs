@@ -0,0 +1,154 @@
+//! `-Wdead-branch`: warn when an `if`/`while` condition is a compile-time constant.
+//!
+//! `while (1)`-style loops (constant true) are the usual way to write an
+//! infinite loop, so they are only reported with `-Wdead-branch=loops`.
+
+use crate::ast::const_eval::{self, ConstScope, ConstValue};
+use crate::ast::*;
+use crate::diag::Diagnostic;
+use crate::semant::SymbolTable;
+
+struct DeadBranchChecker<'a> {
+    exprs: &'a ExprArena,
+    /// Values of the constants in scope; `None` marks a variable
+    symbols: SymbolTable<Option<ConstValue>>,
+    include_loops: bool,
+    diags: Vec<Diagnostic>,
+}
+
+impl ConstScope for DeadBranchChecker<'_> {
+    fn lookup_const(&self, ident: &Ident) -> Option<ConstValue> {
+        self.symbols.lookup(ident).cloned().flatten()
+    }
+}
+
+impl DeadBranchChecker<'_> {
+    /// Value of an expression if it only involves literals and constants
+    fn eval(&self, expr: ExprId) -> Option<i32> {
+        const_eval::eval(self.exprs, expr, self).ok()
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Const(decl) => {
+                for def in &decl.defs {
+                    let value = const_eval::eval_def(self.exprs, decl.btype, def, self).ok();
+                    self.symbols.declare(&def.id, value);
+                }
+            }
+            Decl::Var(decl) => {
+                for def in &decl.defs {
+                    self.symbols.declare(&def.id, None);
+                }
+            }
+            Decl::Enum(decl) => {
+                let mut next = Some(0);
+                for enumerator in &decl.enumerators {
+                    let value = next
+                        .and_then(|next| const_eval::eval_enumerator(self.exprs, enumerator, next, self).ok());
+                    self.symbols.declare(&enumerator.id, value.map(ConstValue::Int));
+                    next = value.map(|value| value.wrapping_add(1));
+                }
+            }
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.symbols.enter_scope();
+        for item in &block.items {
+            match item {
+                BlockItem::Decl(decl) => self.visit_decl(decl),
+                BlockItem::Stmt(stmt) => self.visit_stmt(stmt),
+            }
+        }
+        self.symbols.exit_scope();
+    }
+
+    fn check_loop_cond(&mut self, cond: ExprId, span: Span) {
+        match self.eval(cond) {
+            Some(0) => self.diags.push(Diagnostic::warning(
+                "dead-branch",
+                span,
+                "condition is always false; the loop body is never executed",
+            )),
+            Some(_) if self.include_loops => self.diags.push(Diagnostic::warning(
+                "dead-branch",
+                span,
+                "condition is always true; the loop only exits through break or return",
+            )),
+            _ => {}
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(block) => self.visit_block(block),
+            Stmt::If(if_stmt) => {
+                if let Some(value) = self.eval(if_stmt.cond) {
+                    let dead = if value != 0 { "else" } else { "then" };
+                    self.diags.push(Diagnostic::warning(
+                        "dead-branch",
+                        if_stmt.cond_span,
+                        format!("condition is always {}; the {} branch is never taken", value != 0, dead),
+                    ));
+                }
+                self.visit_stmt(&if_stmt.then_stmt);
+                if let Some(else_stmt) = &if_stmt.else_stmt {
+                    self.visit_stmt(else_stmt);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.check_loop_cond(while_stmt.cond, while_stmt.cond_span);
+                self.visit_stmt(&while_stmt.body);
+            }
+            Stmt::For(for_stmt) => {
+                self.symbols.enter_scope();
+                if let Some(ForInit::Decl(decl)) = &for_stmt.init {
+                    for def in &decl.defs {
+                        self.symbols.declare(&def.id, None);
+                    }
+                }
+                // An omitted condition is the usual way to write an infinite
+                // `for` loop and is not reported
+                if let Some(cond) = for_stmt.cond {
+                    self.check_loop_cond(cond, for_stmt.span);
+                }
+                self.visit_stmt(&for_stmt.body);
+                self.symbols.exit_scope();
+            }
+            Stmt::Switch(switch) => self.visit_block(&switch.body),
+            Stmt::DoWhile(do_while) => {
+                self.visit_stmt(&do_while.body);
+                // The body runs once whatever the condition, and
+                // `do { ... } while (0)` is a common idiom
+                if self.eval(do_while.cond) != Some(0) {
+                    self.check_loop_cond(do_while.cond, do_while.cond_span);
+                }
+            }
+            Stmt::Return(_) | Stmt::Assign(_) | Stmt::Expr(_) | Stmt::Break(_) | Stmt::Continue(_) => {}
+        }
+    }
+}
+
+pub fn check(ast: &CompUnit, include_loops: bool) -> Vec<Diagnostic> {
+    let mut checker = DeadBranchChecker {
+        exprs: &ast.exprs,
+        symbols: SymbolTable::new(),
+        include_loops,
+        diags: Vec::new(),
+    };
+    for item in &ast.items {
+        match item {
+            GlobalItem::Decl(decl) => checker.visit_decl(decl),
+            GlobalItem::FuncDef(func_def) => {
+                checker.symbols.enter_scope();
+                for param in &func_def.params {
+                    checker.symbols.declare(&param.id, None);
+                }
+                checker.visit_block(&func_def.block);
+                checker.symbols.exit_scope();
+            }
+        }
+    }
+    checker.diags
+}
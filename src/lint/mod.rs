@@ -0,0 +1,80 @@
+//! Optional warnings computed on the AST, enabled with `-W<name>` flags.
+
+pub mod array_bounds;
+pub mod conversion;
+pub mod dead_branch;
+pub mod shadow;
+pub mod uninit;
+
+use crate::ast::CompUnit;
+use crate::diag::Diagnostic;
+
+/// Which warnings are enabled
+#[derive(Debug, Default, Clone)]
+pub struct LintOptions {
+    /// `-Wshadow`: a declaration hides one from an enclosing scope
+    pub shadow: bool,
+    /// `-Wdead-branch`: an `if`/`while` condition is a compile-time constant
+    pub dead_branch: bool,
+    /// `-Wdead-branch=loops`: also report constant-true `while` conditions
+    pub dead_branch_loops: bool,
+    /// `-Wconversion`: a `float` value is implicitly converted to `int`
+    pub conversion: bool,
+    /// `-Wuninitialized`: a local variable may be read before it is assigned
+    pub uninitialized: bool,
+    /// `-Warray-bounds`: an array is indexed with a constant out of its bounds
+    pub array_bounds: bool,
+}
+
+impl LintOptions {
+    /// Enable or disable a warning by its `-W` name (`shadow`, `no-shadow`, `all`, ...).
+    /// Returns `false` if the name is unknown.
+    pub fn set(&mut self, name: &str) -> bool {
+        let (name, enabled) = match name.strip_prefix("no-") {
+            Some(name) => (name, false),
+            None => (name, true),
+        };
+        match name {
+            "all" => {
+                self.shadow = enabled;
+                self.dead_branch = enabled;
+                self.conversion = enabled;
+                self.uninitialized = enabled;
+                self.array_bounds = enabled;
+            }
+            "shadow" => self.shadow = enabled,
+            "dead-branch" => self.dead_branch = enabled,
+            "conversion" => self.conversion = enabled,
+            "uninitialized" => self.uninitialized = enabled,
+            "array-bounds" => self.array_bounds = enabled,
+            "dead-branch=loops" => {
+                self.dead_branch = enabled;
+                self.dead_branch_loops = enabled;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Run all enabled warnings over a compilation unit
+pub fn run(ast: &CompUnit, options: &LintOptions) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    if options.shadow {
+        diags.extend(shadow::check(ast));
+    }
+    if options.dead_branch {
+        diags.extend(dead_branch::check(ast, options.dead_branch_loops));
+    }
+    if options.conversion {
+        diags.extend(conversion::check(ast));
+    }
+    if options.uninitialized {
+        diags.extend(uninit::check(ast));
+    }
+    if options.array_bounds {
+        diags.extend(array_bounds::check(ast));
+    }
+    diags.sort_by_key(|diag| diag.span.start);
+    diags
+}
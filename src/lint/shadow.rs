@@ -0,0 +1,117 @@
+//! `-Wshadow`: warn when a declaration hides a name from an enclosing scope.
+
+use crate::ast::*;
+use crate::diag::Diagnostic;
+use crate::semant::SymbolTable;
+
+#[derive(Clone, Copy)]
+enum DeclKind {
+    Global,
+    Local,
+    Param,
+}
+
+struct ShadowChecker {
+    /// Declared names, with the span of the declaration
+    symbols: SymbolTable<(Span, DeclKind)>,
+    diags: Vec<Diagnostic>,
+}
+
+impl ShadowChecker {
+    fn declare(&mut self, ident: &Ident, span: Span, kind: DeclKind) {
+        if let Some(&(prev, prev_kind)) = self.symbols.lookup_enclosing(ident) {
+            let what = match prev_kind {
+                DeclKind::Global => "a global declaration",
+                DeclKind::Local => "a previous local",
+                DeclKind::Param => "a parameter",
+            };
+            self.diags.push(
+                Diagnostic::warning(
+                    "shadow",
+                    span,
+                    format!("declaration of '{}' shadows {}", ident, what),
+                )
+                .with_note(prev, "shadowed declaration is here"),
+            );
+        }
+        self.symbols.declare(ident, (span, kind));
+    }
+
+    fn visit_decl(&mut self, decl: &Decl, kind: DeclKind) {
+        match decl {
+            Decl::Const(decl) => {
+                for def in &decl.defs {
+                    self.declare(&def.id, def.span, kind);
+                }
+            }
+            Decl::Var(decl) => {
+                for def in &decl.defs {
+                    self.declare(&def.id, def.span, kind);
+                }
+            }
+            Decl::Enum(decl) => {
+                for enumerator in &decl.enumerators {
+                    self.declare(&enumerator.id, enumerator.span, kind);
+                }
+            }
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.symbols.enter_scope();
+        for item in &block.items {
+            match item {
+                BlockItem::Decl(decl) => self.visit_decl(decl, DeclKind::Local),
+                BlockItem::Stmt(stmt) => self.visit_stmt(stmt),
+            }
+        }
+        self.symbols.exit_scope();
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(block) => self.visit_block(block),
+            Stmt::If(if_stmt) => {
+                self.visit_stmt(&if_stmt.then_stmt);
+                if let Some(else_stmt) = &if_stmt.else_stmt {
+                    self.visit_stmt(else_stmt);
+                }
+            }
+            Stmt::While(while_stmt) => self.visit_stmt(&while_stmt.body),
+            Stmt::For(for_stmt) => {
+                self.symbols.enter_scope();
+                if let Some(ForInit::Decl(decl)) = &for_stmt.init {
+                    for def in &decl.defs {
+                        self.declare(&def.id, def.span, DeclKind::Local);
+                    }
+                }
+                self.visit_stmt(&for_stmt.body);
+                self.symbols.exit_scope();
+            }
+            Stmt::DoWhile(do_while) => self.visit_stmt(&do_while.body),
+            Stmt::Switch(switch) => self.visit_block(&switch.body),
+            Stmt::Return(_) | Stmt::Assign(_) | Stmt::Expr(_) | Stmt::Break(_) | Stmt::Continue(_) => {}
+        }
+    }
+}
+
+pub fn check(ast: &CompUnit) -> Vec<Diagnostic> {
+    let mut checker = ShadowChecker {
+        symbols: SymbolTable::new(),
+        diags: Vec::new(),
+    };
+    for item in &ast.items {
+        match item {
+            GlobalItem::Decl(decl) => checker.visit_decl(decl, DeclKind::Global),
+            GlobalItem::FuncDef(func_def) => {
+                checker.symbols.enter_scope();
+                for param in &func_def.params {
+                    checker.declare(&param.id, param.span, DeclKind::Param);
+                }
+                checker.visit_block(&func_def.block);
+                checker.symbols.exit_scope();
+            }
+        }
+    }
+    checker.diags
+}
@@ -0,0 +1,348 @@
+//! `-Wuninitialized`: warn when a local variable may be read before anything
+//! is assigned to it, on some path through the function.
+//!
+//! The analysis follows the structured control flow of the AST, tracking the
+//! set of local scalars that may still be uninitialized (arrays are not
+//! tracked, nor static locals, which start at zero). Paths merge by union.
+//! Loops need no fixed-point iteration: statements only ever initialize
+//! variables, so whatever may be uninitialized after an iteration already was
+//! at the start of the loop.
+
+use std::collections::BTreeSet;
+
+use crate::ast::const_eval::{self, ConstScope, ConstValue};
+use crate::ast::*;
+use crate::diag::Diagnostic;
+use crate::semant::SymbolTable;
+
+/// The tracked variables that may be uninitialized at a point of the
+/// function, by index in `UninitChecker::vars`; `None` where the point is
+/// unreachable (after a `return`, `break` or `continue`)
+type State = Option<BTreeSet<usize>>;
+
+/// The state where two paths merge
+fn join(a: State, b: State) -> State {
+    match (a, b) {
+        (Some(mut a), Some(b)) => {
+            a.extend(b);
+            Some(a)
+        }
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// What a name in scope refers to
+enum Name {
+    /// A constant, with its value if it could be evaluated
+    Const(Option<ConstValue>),
+    /// A variable, with its index in `vars` if it is tracked
+    Var(Option<usize>),
+}
+
+struct UninitChecker<'a> {
+    exprs: &'a ExprArena,
+    symbols: SymbolTable<Name>,
+    /// Tracked variables: their name and declaration span, and whether a read
+    /// has been reported already (each variable is reported once)
+    vars: Vec<(Ident, Span, bool)>,
+    /// States at the `break`s of each enclosing loop or `switch`, innermost last
+    breaks: Vec<State>,
+    /// States at the `continue`s of each enclosing loop, innermost last
+    continues: Vec<State>,
+    diags: Vec<Diagnostic>,
+}
+
+impl ConstScope for UninitChecker<'_> {
+    fn lookup_const(&self, ident: &Ident) -> Option<ConstValue> {
+        match self.symbols.lookup(ident) {
+            Some(Name::Const(value)) => value.clone(),
+            _ => None,
+        }
+    }
+}
+
+impl UninitChecker<'_> {
+    /// Whether a condition is a constant other than 0, as in `while (1)`
+    fn always_true(&self, cond: ExprId) -> bool {
+        const_eval::eval(self.exprs, cond, self).is_ok_and(|value| value != 0)
+    }
+
+    /// The tracked variable an lvalue refers to
+    fn tracked(&self, lval: &LVal) -> Option<usize> {
+        match self.symbols.lookup(&lval.ident) {
+            Some(Name::Var(var)) => *var,
+            _ => None,
+        }
+    }
+
+    /// Report the reads in `expr` of variables that may be uninitialized
+    fn visit_expr(&mut self, expr: ExprId, state: &State) {
+        match &self.exprs[expr] {
+            Expr::LVal(lval) => {
+                for &index in &lval.indices {
+                    self.visit_expr(index, state);
+                }
+                let Some(var) = self.tracked(lval) else {
+                    return;
+                };
+                let (ident, decl_span, reported) = &mut self.vars[var];
+                match state {
+                    Some(uninit) if uninit.contains(&var) && !*reported => {
+                        *reported = true;
+                        let diag = Diagnostic::warning(
+                            "uninitialized",
+                            lval.span,
+                            format!("'{}' may be used uninitialized", ident),
+                        )
+                        .with_note(*decl_span, "declared here without an initializer");
+                        self.diags.push(diag);
+                    }
+                    _ => {}
+                }
+            }
+            Expr::Unary(_, expr) => self.visit_expr(*expr, state),
+            Expr::Binary(lhs, _, rhs) | Expr::Bitwise(lhs, _, rhs, _) => {
+                self.visit_expr(*lhs, state);
+                self.visit_expr(*rhs, state);
+            }
+            Expr::Ternary(cond, then_expr, else_expr, _) => {
+                self.visit_expr(*cond, state);
+                self.visit_expr(*then_expr, state);
+                self.visit_expr(*else_expr, state);
+            }
+            Expr::Call(_, args) => {
+                for &arg in args {
+                    self.visit_expr(arg, state);
+                }
+            }
+            Expr::Number(_) | Expr::Float(_) | Expr::Str(_) | Expr::Char(_) => {}
+        }
+    }
+
+    fn visit_init(&mut self, init: &InitVal, state: &State) {
+        match init {
+            InitVal::Expr(expr) => self.visit_expr(*expr, state),
+            InitVal::List(items) => {
+                for item in items {
+                    self.visit_init(item, state);
+                }
+            }
+        }
+    }
+
+    fn visit_var_decl(&mut self, decl: &VarDecl, mut state: State) -> State {
+        for def in &decl.defs {
+            if let Some(init) = &def.init {
+                self.visit_init(init, &state);
+            }
+            let tracked = def.init.is_none() && def.dims.is_empty() && decl.static_span.is_none();
+            let var = tracked.then(|| {
+                self.vars.push((def.id.clone(), def.span, false));
+                self.vars.len() - 1
+            });
+            if let (Some(var), Some(uninit)) = (var, &mut state) {
+                uninit.insert(var);
+            }
+            self.symbols.declare(&def.id, Name::Var(var));
+        }
+        state
+    }
+
+    fn visit_decl(&mut self, decl: &Decl, state: State) -> State {
+        match decl {
+            Decl::Const(decl) => {
+                for def in &decl.defs {
+                    let value = const_eval::eval_def(self.exprs, decl.btype, def, self).ok();
+                    self.symbols.declare(&def.id, Name::Const(value));
+                }
+                state
+            }
+            Decl::Var(decl) => self.visit_var_decl(decl, state),
+            Decl::Enum(decl) => {
+                let mut next = Some(0);
+                for enumerator in &decl.enumerators {
+                    let value = next
+                        .and_then(|next| const_eval::eval_enumerator(self.exprs, enumerator, next, self).ok());
+                    self.symbols.declare(&enumerator.id, Name::Const(value.map(ConstValue::Int)));
+                    next = value.map(|value| value.wrapping_add(1));
+                }
+                state
+            }
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block, mut state: State) -> State {
+        self.symbols.enter_scope();
+        for item in &block.items {
+            state = match item {
+                BlockItem::Decl(decl) => self.visit_decl(decl, state),
+                BlockItem::Stmt(stmt) => self.visit_stmt(stmt, state),
+            };
+        }
+        self.symbols.exit_scope();
+        state
+    }
+
+    fn visit_assign(&mut self, assign: &AssignStmt, mut state: State) -> State {
+        for &index in &assign.lval.indices {
+            self.visit_expr(index, &state);
+        }
+        self.visit_expr(assign.expr, &state);
+        if let (Some(var), Some(uninit)) = (self.tracked(&assign.lval), &mut state) {
+            uninit.remove(&var);
+        }
+        state
+    }
+
+    fn visit_for_step(&mut self, step: &ForStep, state: State) -> State {
+        match step {
+            ForStep::Assign(assign) => self.visit_assign(assign, state),
+            ForStep::Expr(expr) => {
+                self.visit_expr(*expr, &state);
+                state
+            }
+        }
+    }
+
+    /// Visit the body of a loop entered in `state`, returning the states
+    /// where it continues (its end and its `continue`s) and where it breaks
+    fn visit_loop_body(&mut self, body: &Stmt, state: State) -> (State, State) {
+        self.breaks.push(None);
+        self.continues.push(None);
+        let end = self.visit_stmt(body, state);
+        let continues = self.continues.pop().unwrap();
+        let breaks = self.breaks.pop().unwrap();
+        (join(end, continues), breaks)
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt, mut state: State) -> State {
+        match stmt {
+            Stmt::Return(return_stmt) => {
+                if let Some(expr) = return_stmt.expr {
+                    self.visit_expr(expr, &state);
+                }
+                None
+            }
+            Stmt::Assign(assign) => self.visit_assign(assign, state),
+            Stmt::Block(block) => self.visit_block(block, state),
+            Stmt::Expr(expr) => {
+                if let Some(expr) = expr {
+                    self.visit_expr(*expr, &state);
+                }
+                state
+            }
+            Stmt::If(if_stmt) => {
+                self.visit_expr(if_stmt.cond, &state);
+                let then_state = self.visit_stmt(&if_stmt.then_stmt, state.clone());
+                let else_state = match &if_stmt.else_stmt {
+                    Some(else_stmt) => self.visit_stmt(else_stmt, state),
+                    None => state,
+                };
+                join(then_state, else_state)
+            }
+            Stmt::While(while_stmt) => {
+                self.visit_expr(while_stmt.cond, &state);
+                let (_, breaks) = self.visit_loop_body(&while_stmt.body, state.clone());
+                // The loop is left when the condition is false, unless it never is
+                if self.always_true(while_stmt.cond) { breaks } else { join(state, breaks) }
+            }
+            Stmt::DoWhile(do_while) => {
+                let (state, breaks) = self.visit_loop_body(&do_while.body, state);
+                self.visit_expr(do_while.cond, &state);
+                if self.always_true(do_while.cond) { breaks } else { join(state, breaks) }
+            }
+            Stmt::For(for_stmt) => {
+                self.symbols.enter_scope();
+                state = match &for_stmt.init {
+                    Some(ForInit::Decl(decl)) => self.visit_var_decl(decl, state),
+                    Some(ForInit::Step(step)) => self.visit_for_step(step, state),
+                    None => state,
+                };
+                if let Some(cond) = for_stmt.cond {
+                    self.visit_expr(cond, &state);
+                }
+                let (end, breaks) = self.visit_loop_body(&for_stmt.body, state.clone());
+                if let Some(step) = &for_stmt.step {
+                    self.visit_for_step(step, end);
+                }
+                self.symbols.exit_scope();
+                match for_stmt.cond {
+                    Some(cond) if !self.always_true(cond) => join(state, breaks),
+                    _ => breaks,
+                }
+            }
+            Stmt::Switch(switch) => {
+                self.visit_expr(switch.expr, &state);
+                // Each label is entered from the `switch` itself, besides
+                // falling through from the code before it
+                self.breaks.push(None);
+                self.symbols.enter_scope();
+                let mut current = None;
+                let mut labels = switch.labels.iter().map(|label| label.position).peekable();
+                for i in 0..=switch.body.items.len() {
+                    while labels.next_if_eq(&i).is_some() {
+                        current = join(current, state.clone());
+                    }
+                    current = match switch.body.items.get(i) {
+                        Some(BlockItem::Decl(decl)) => self.visit_decl(decl, current),
+                        Some(BlockItem::Stmt(stmt)) => self.visit_stmt(stmt, current),
+                        None => current,
+                    };
+                }
+                self.symbols.exit_scope();
+                let breaks = self.breaks.pop().unwrap();
+                let has_default = switch.labels.iter().any(|label| label.value.is_none());
+                let end = join(current, breaks);
+                if has_default { end } else { join(end, state) }
+            }
+            Stmt::Break(_) => {
+                if let Some(breaks) = self.breaks.last_mut() {
+                    *breaks = join(breaks.take(), state);
+                }
+                None
+            }
+            Stmt::Continue(_) => {
+                if let Some(continues) = self.continues.last_mut() {
+                    *continues = join(continues.take(), state);
+                }
+                None
+            }
+        }
+    }
+}
+
+pub fn check(ast: &CompUnit) -> Vec<Diagnostic> {
+    let mut checker = UninitChecker {
+        exprs: &ast.exprs,
+        symbols: SymbolTable::new(),
+        vars: Vec::new(),
+        breaks: Vec::new(),
+        continues: Vec::new(),
+        diags: Vec::new(),
+    };
+    for item in &ast.items {
+        match item {
+            GlobalItem::Decl(decl) => match decl {
+                // Globals are zero-initialized, so they are never tracked
+                Decl::Var(decl) => {
+                    for def in &decl.defs {
+                        checker.symbols.declare(&def.id, Name::Var(None));
+                    }
+                }
+                decl => {
+                    checker.visit_decl(decl, None);
+                }
+            },
+            GlobalItem::FuncDef(func_def) => {
+                checker.symbols.enter_scope();
+                for param in &func_def.params {
+                    checker.symbols.declare(&param.id, Name::Var(None));
+                }
+                checker.visit_block(&func_def.block, Some(BTreeSet::new()));
+                checker.symbols.exit_scope();
+            }
+        }
+    }
+    checker.diags
+}
@@ -0,0 +1,373 @@
+//! Language server mode (`--lsp`): speaks the Language Server Protocol over
+//! stdin and stdout, reporting the diagnostics of the checks run before
+//! lowering (parsing, extensions, semantic checks and the enabled warnings)
+//! as files are opened and edited, and answering requests for semantic
+//! tokens and document symbols.
+//!
+//! Documents are synchronized in full on every change. Positions count
+//! characters rather than UTF-16 code units, which only differs outside
+//! ASCII.
+
+pub mod json;
+pub mod outline;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use json::Json;
+use outline::{MODIFIER_LEGEND, TokenKind};
+
+use crate::ast::{CompUnit, ExprArena, Span};
+use crate::diag::{Diagnostic, LineOrigin, Severity, SourceFile};
+use crate::ext::{self, Extensions};
+use crate::lexer::{self, Lexer};
+use crate::lint::{self, LintOptions};
+use crate::preprocess::{self, Preprocessed};
+use crate::semant;
+use crate::sysy::CompUnitParser;
+
+/// JSON-RPC error codes
+const PARSE_ERROR: f64 = -32700.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+/// The flags given with `--lsp`, applied to every document
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub extensions: Extensions,
+    pub lints: LintOptions,
+}
+
+/// A document after the checks: the preprocessed text the spans refer to,
+/// the AST if it parsed, and what the checks reported
+pub struct Analysis {
+    /// The file name the document was preprocessed as
+    pub name: String,
+    pub text: String,
+    pub origins: Vec<LineOrigin>,
+    pub ast: Option<CompUnit>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Run the checks `--koopa` runs before lowering over `text`, the contents of
+/// the file `name`
+pub fn analyze(name: &str, text: &str, options: &Options) -> Analysis {
+    let Preprocessed { text, origins } = match preprocess::preprocess(name, text) {
+        Ok(preprocessed) => preprocessed,
+        Err(e) => {
+            // Point at the directive if it is in this file
+            let offset = if e.file == name {
+                text.split_inclusive('\n').take(e.line - 1).map(str::len).sum()
+            } else {
+                0
+            };
+            let diag = Diagnostic::error(Span::new(offset, offset), e.to_string());
+            let (text, origins) = (text.to_string(), Vec::new());
+            return Analysis { name: name.to_string(), text, origins, ast: None, diagnostics: vec![diag] };
+        }
+    };
+    let mut analysis = Analysis { name: name.to_string(), text, origins, ast: None, diagnostics: Vec::new() };
+    let lexer = Lexer::new(&analysis.text, &options.extensions);
+    let ast = match CompUnitParser::new().parse(&mut ExprArena::default(), lexer) {
+        Ok(ast) => ast,
+        Err(e) => {
+            let diag = lexer::parse_error(&analysis.text, e, &options.extensions);
+            analysis.diagnostics.push(diag);
+            return analysis;
+        }
+    };
+    analysis.diagnostics.extend(ext::check(&ast, &options.extensions));
+    if let Err(errors) = semant::check(&ast) {
+        analysis.diagnostics.extend(errors);
+    }
+    analysis.diagnostics.extend(lint::run(&ast, &options.lints));
+    analysis.ast = Some(ast);
+    analysis
+}
+
+impl Analysis {
+    fn source(&self) -> SourceFile<'_> {
+        SourceFile::new(&self.name, &self.text).with_origins(&self.origins)
+    }
+
+    /// 0-based line and character of `offset` in the document, or `None` if
+    /// it is in an included file
+    fn position(source: &SourceFile, name: &str, offset: usize) -> Option<(usize, usize)> {
+        let (file, line, col) = source.location(offset);
+        (file == name).then_some((line - 1, col - 1))
+    }
+
+    /// The semantic tokens of the document, encoded as the protocol wants:
+    /// five numbers per token, relative to the previous one
+    pub fn semantic_tokens(&self) -> Vec<usize> {
+        let Some(ast) = &self.ast else {
+            return Vec::new();
+        };
+        let source = self.source();
+        let mut data = Vec::new();
+        let (mut last_line, mut last_col) = (0, 0);
+        for token in outline::semantic_tokens(ast) {
+            let Some((line, col)) = Self::position(&source, &self.name, token.span.start) else {
+                continue;
+            };
+            let delta_col = if line == last_line { col - last_col } else { col };
+            let length = token.span.end - token.span.start;
+            data.extend([line - last_line, delta_col, length, token.kind.index(), token.modifiers()]);
+            (last_line, last_col) = (line, col);
+        }
+        data
+    }
+
+    fn range(&self, source: &SourceFile, span: Span) -> Option<Json> {
+        let start = Self::position(source, &self.name, span.start)?;
+        let end = Self::position(source, &self.name, span.end).unwrap_or(start);
+        Some(range_json(start, end))
+    }
+
+    /// The protocol's `Diagnostic`s; those in included files are reported at
+    /// the start of the document, naming their location
+    pub fn diagnostics(&self, uri: &str) -> Vec<Json> {
+        let source = self.source();
+        let mut diagnostics = Vec::new();
+        for diag in &self.diagnostics {
+            let (range, message) = match self.range(&source, diag.span) {
+                Some(range) => (range, diag.message.clone()),
+                None => {
+                    let (file, line, _) = source.location(diag.span.start);
+                    (range_json((0, 0), (0, 0)), format!("{}:{}: {}", file, line, diag.message))
+                }
+            };
+            let severity: usize = match diag.severity {
+                Severity::Error => 1,
+                Severity::Warning => 2,
+                Severity::Note => 3,
+            };
+            let notes = diag.notes.iter().filter_map(|(span, note)| {
+                let location = Json::object([("uri", uri.into()), ("range", self.range(&source, *span)?)]);
+                Some(Json::object([("location", location), ("message", note.as_str().into())]))
+            });
+            let mut fields = vec![
+                ("range", range),
+                ("severity", severity.into()),
+                ("source", "yasysyc".into()),
+                ("message", message.into()),
+                ("relatedInformation", notes.collect::<Vec<_>>().into()),
+            ];
+            if let Some(flag) = diag.flag {
+                fields.push(("code", format!("-W{}", flag).into()));
+            }
+            diagnostics.push(Json::object(fields));
+        }
+        diagnostics
+    }
+
+    /// The protocol's `DocumentSymbol`s for the file scope of the document
+    pub fn document_symbols(&self) -> Vec<Json> {
+        let Some(ast) = &self.ast else {
+            return Vec::new();
+        };
+        let source = self.source();
+        let mut symbols = Vec::new();
+        for symbol in outline::document_symbols(ast) {
+            let range = self.range(&source, symbol.span);
+            let (Some(range), Some(selection)) = (range, self.range(&source, symbol.name_span)) else {
+                continue;
+            };
+            // `SymbolKind` numbers of the protocol
+            let kind: usize = match symbol.kind {
+                TokenKind::Function => 12,
+                TokenKind::Variable if symbol.constant => 14,
+                TokenKind::Variable | TokenKind::Parameter => 13,
+                TokenKind::EnumMember => 22,
+            };
+            symbols.push(Json::object([
+                ("name", symbol.name.into()),
+                ("kind", kind.into()),
+                ("range", range),
+                ("selectionRange", selection),
+            ]));
+        }
+        symbols
+    }
+}
+
+fn range_json((start_line, start_col): (usize, usize), (end_line, end_col): (usize, usize)) -> Json {
+    let position = |line: usize, col: usize| Json::object([("line", line.into()), ("character", col.into())]);
+    Json::object([("start", position(start_line, start_col)), ("end", position(end_line, end_col))])
+}
+
+/// The path of a `file://` URI, used to resolve the `#include`s of the
+/// document; other URIs are used as they are
+pub fn uri_path(uri: &str) -> String {
+    let Some(path) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let hex = tail.get(..2).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (byte, hex) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Read one message: headers, a blank line and a body of `Content-Length`
+/// bytes. Returns `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        match header.split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("Content-Length") => {
+                length = value.trim().parse::<usize>().ok();
+            }
+            _ => {}
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message without a Content-Length header"));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn response(id: Json, result: Json) -> Json {
+    Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)])
+}
+
+fn error_response(id: Json, code: f64, message: &str) -> Json {
+    let error = Json::object([("code", Json::Number(code)), ("message", message.into())]);
+    Json::object([("jsonrpc", "2.0".into()), ("id", id), ("error", error)])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+}
+
+/// What the server can do, as answered to `initialize`
+fn capabilities() -> Json {
+    let legend = Json::object([
+        ("tokenTypes", TokenKind::LEGEND.iter().map(|&name| name.into()).collect::<Vec<_>>().into()),
+        ("tokenModifiers", MODIFIER_LEGEND.iter().map(|&name| name.into()).collect::<Vec<_>>().into()),
+    ]);
+    Json::object([
+        // Full text on every change
+        ("textDocumentSync", 1usize.into()),
+        ("semanticTokensProvider", Json::object([("legend", legend), ("full", true.into())])),
+        ("documentSymbolProvider", true.into()),
+    ])
+}
+
+/// The open documents and their analyses
+struct Server {
+    options: Options,
+    documents: HashMap<String, Analysis>,
+}
+
+impl Server {
+    /// Check the new contents of the document `uri` and publish its
+    /// diagnostics
+    fn update(&mut self, output: &mut impl Write, uri: &str, text: &str) -> io::Result<()> {
+        let analysis = analyze(&uri_path(uri), text, &self.options);
+        let params = Json::object([("uri", uri.into()), ("diagnostics", analysis.diagnostics(uri).into())]);
+        self.documents.insert(uri.to_string(), analysis);
+        write_message(output, &notification("textDocument/publishDiagnostics", params))
+    }
+
+    /// Handle a request or notification. Returns `false` once the client
+    /// asks the server to exit.
+    fn handle(&mut self, output: &mut impl Write, message: &Json) -> io::Result<bool> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or_default();
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let uri = params.get("textDocument").and_then(|document| document.get("uri")).and_then(Json::as_str);
+        let document = uri.and_then(|uri| self.documents.get(uri));
+        let result = match method {
+            "initialize" => {
+                let info = Json::object([("name", "yasysyc".into()), ("version", env!("CARGO_PKG_VERSION").into())]);
+                Json::object([("capabilities", capabilities()), ("serverInfo", info)])
+            }
+            "textDocument/semanticTokens/full" => {
+                let data = document.map(Analysis::semantic_tokens).unwrap_or_default();
+                Json::object([("data", data.into_iter().map(Json::from).collect::<Vec<_>>().into())])
+            }
+            "textDocument/documentSymbol" => document.map(Analysis::document_symbols).unwrap_or_default().into(),
+            "shutdown" => Json::Null,
+            "exit" => return Ok(false),
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                // Full synchronization: the last change holds the whole text
+                let text = match method {
+                    "textDocument/didOpen" => params.get("textDocument").and_then(|document| document.get("text")),
+                    _ => {
+                        let changes = params.get("contentChanges").and_then(Json::as_array);
+                        changes.and_then(|changes| changes.last()?.get("text"))
+                    }
+                };
+                if let (Some(uri), Some(text)) = (uri, text.and_then(Json::as_str)) {
+                    self.update(output, uri, text)?;
+                }
+                return Ok(true);
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = uri {
+                    self.documents.remove(uri);
+                    let params = Json::object([("uri", uri.into()), ("diagnostics", Json::Array(Vec::new()))]);
+                    write_message(output, &notification("textDocument/publishDiagnostics", params))?;
+                }
+                return Ok(true);
+            }
+            _ => {
+                // Unknown notifications are ignored; unknown requests answered
+                if let Some(id) = message.get("id") {
+                    let error = error_response(id.clone(), METHOD_NOT_FOUND, &format!("unknown method '{}'", method));
+                    write_message(output, &error)?;
+                }
+                return Ok(true);
+            }
+        };
+        if let Some(id) = message.get("id") {
+            write_message(output, &response(id.clone(), result))?;
+        }
+        Ok(true)
+    }
+}
+
+/// Serve the messages read from `input` until the client sends `exit` or
+/// closes it, writing responses and notifications to `output`
+pub fn serve(input: &mut impl BufRead, output: &mut impl Write, options: Options) -> io::Result<()> {
+    let mut server = Server { options, documents: HashMap::new() };
+    while let Some(body) = read_message(input)? {
+        let message = match Json::parse(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                write_message(output, &error_response(Json::Null, PARSE_ERROR, &e))?;
+                continue;
+            }
+        };
+        if !server.handle(output, &message)? {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,287 @@
+//! What the language server tells the editor about the names in a file:
+//! semantic tokens, telling functions, variables, parameters and constants
+//! apart at every use, and the document symbols of the file scope.
+
+use crate::ast::*;
+use crate::semant::SymbolTable;
+
+/// What a name refers to, as a semantic token type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Function,
+    Variable,
+    Parameter,
+    /// An enumerator of `--ext=enum`
+    EnumMember,
+}
+
+impl TokenKind {
+    /// The token types in the order of the legend the server announces
+    pub const LEGEND: [&'static str; 4] = ["function", "variable", "parameter", "enumMember"];
+
+    /// Index of the kind in `LEGEND`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The token modifiers in the order of the legend: bit 0 marks a
+/// declaration, bit 1 a constant
+pub const MODIFIER_LEGEND: [&str; 2] = ["declaration", "readonly"];
+
+/// An occurrence of a name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub span: Span,
+    pub kind: TokenKind,
+    /// Whether this is where the name is declared
+    pub declaration: bool,
+    /// Whether the name is a `const` (enumerators are implied to be)
+    pub constant: bool,
+}
+
+impl Token {
+    /// The modifier bits of the token
+    pub fn modifiers(&self) -> usize {
+        usize::from(self.declaration) | (usize::from(self.constant) << 1)
+    }
+}
+
+/// A name declared at file scope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentSymbol {
+    pub name: String,
+    pub kind: TokenKind,
+    pub constant: bool,
+    /// The function header, or the name of anything else
+    pub span: Span,
+    pub name_span: Span,
+}
+
+struct TokenCollector<'a> {
+    exprs: &'a ExprArena,
+    /// Kind of each name in scope, and whether it is a constant
+    symbols: SymbolTable<(TokenKind, bool)>,
+    tokens: Vec<Token>,
+}
+
+/// Span of `ident` written at `start`
+fn name_span(start: usize, ident: &Ident) -> Span {
+    Span::new(start, start + ident.value.len())
+}
+
+impl TokenCollector<'_> {
+    fn declare(&mut self, ident: &Ident, span: Span, kind: TokenKind, constant: bool) {
+        self.symbols.declare(ident, (kind, constant));
+        self.tokens.push(Token { span, kind, declaration: true, constant });
+    }
+
+    fn visit_lval(&mut self, lval: &LVal) {
+        // Names that resolve to nothing are reported by the semantic checks
+        if let Some(&(kind, constant)) = self.symbols.lookup(&lval.ident) {
+            let span = name_span(lval.span.start, &lval.ident);
+            self.tokens.push(Token { span, kind, declaration: false, constant });
+        }
+        for &index in &lval.indices {
+            self.visit_expr(index);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: ExprId) {
+        match &self.exprs[expr] {
+            Expr::LVal(lval) => self.visit_lval(lval),
+            Expr::Call(ident, args) => {
+                let span = name_span(self.exprs.span(expr).start, ident);
+                self.tokens.push(Token { span, kind: TokenKind::Function, declaration: false, constant: false });
+                for &arg in args {
+                    self.visit_expr(arg);
+                }
+            }
+            Expr::Unary(_, operand) => self.visit_expr(*operand),
+            Expr::Binary(lhs, _, rhs) | Expr::Bitwise(lhs, _, rhs, _) => {
+                self.visit_expr(*lhs);
+                self.visit_expr(*rhs);
+            }
+            Expr::Ternary(cond, then_expr, else_expr, _) => {
+                self.visit_expr(*cond);
+                self.visit_expr(*then_expr);
+                self.visit_expr(*else_expr);
+            }
+            Expr::Number(_) | Expr::Float(_) | Expr::Char(_) | Expr::Str(_) => {}
+        }
+    }
+
+    fn visit_init(&mut self, init: &InitVal) {
+        match init {
+            InitVal::Expr(expr) => self.visit_expr(*expr),
+            InitVal::List(items) => items.iter().for_each(|item| self.visit_init(item)),
+        }
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Const(decl) => {
+                for def in &decl.defs {
+                    def.dims.iter().for_each(|dim| self.visit_expr(dim.expr));
+                    self.visit_init(&def.init);
+                    self.declare(&def.id, def.span, TokenKind::Variable, true);
+                }
+            }
+            Decl::Var(decl) => self.visit_var_decl(decl),
+            Decl::Enum(decl) => {
+                for enumerator in &decl.enumerators {
+                    if let Some(value) = &enumerator.value {
+                        self.visit_expr(value.expr);
+                    }
+                    self.declare(&enumerator.id, enumerator.span, TokenKind::EnumMember, true);
+                }
+            }
+        }
+    }
+
+    fn visit_var_decl(&mut self, decl: &VarDecl) {
+        for def in &decl.defs {
+            def.dims.iter().for_each(|dim| self.visit_expr(dim.expr));
+            if let Some(init) = &def.init {
+                self.visit_init(init);
+            }
+            self.declare(&def.id, def.span, TokenKind::Variable, false);
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.symbols.enter_scope();
+        for item in &block.items {
+            match item {
+                BlockItem::Decl(decl) => self.visit_decl(decl),
+                BlockItem::Stmt(stmt) => self.visit_stmt(stmt),
+            }
+        }
+        self.symbols.exit_scope();
+    }
+
+    fn visit_assign(&mut self, assign: &AssignStmt) {
+        self.visit_lval(&assign.lval);
+        self.visit_expr(assign.expr);
+    }
+
+    fn visit_for_step(&mut self, step: &ForStep) {
+        match step {
+            ForStep::Assign(assign) => self.visit_assign(assign),
+            ForStep::Expr(expr) => self.visit_expr(*expr),
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Return(return_stmt) => {
+                if let Some(expr) = return_stmt.expr {
+                    self.visit_expr(expr);
+                }
+            }
+            Stmt::Assign(assign) => self.visit_assign(assign),
+            Stmt::Block(block) => self.visit_block(block),
+            Stmt::Expr(expr) => {
+                if let Some(expr) = expr {
+                    self.visit_expr(*expr);
+                }
+            }
+            Stmt::If(if_stmt) => {
+                self.visit_expr(if_stmt.cond);
+                self.visit_stmt(&if_stmt.then_stmt);
+                if let Some(else_stmt) = &if_stmt.else_stmt {
+                    self.visit_stmt(else_stmt);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.visit_expr(while_stmt.cond);
+                self.visit_stmt(&while_stmt.body);
+            }
+            Stmt::For(for_stmt) => {
+                self.symbols.enter_scope();
+                match &for_stmt.init {
+                    Some(ForInit::Decl(decl)) => self.visit_var_decl(decl),
+                    Some(ForInit::Step(step)) => self.visit_for_step(step),
+                    None => {}
+                }
+                if let Some(cond) = for_stmt.cond {
+                    self.visit_expr(cond);
+                }
+                if let Some(step) = &for_stmt.step {
+                    self.visit_for_step(step);
+                }
+                self.visit_stmt(&for_stmt.body);
+                self.symbols.exit_scope();
+            }
+            Stmt::DoWhile(do_while) => {
+                self.visit_stmt(&do_while.body);
+                self.visit_expr(do_while.cond);
+            }
+            Stmt::Switch(switch) => {
+                self.visit_expr(switch.expr);
+                for label in &switch.labels {
+                    if let Some(value) = label.value {
+                        self.visit_expr(value);
+                    }
+                }
+                self.visit_block(&switch.body);
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => {}
+        }
+    }
+}
+
+/// The names of `ast`, in source order. Only names that resolve are
+/// included, except for called functions, which may be in the runtime
+/// library.
+pub fn semantic_tokens(ast: &CompUnit) -> Vec<Token> {
+    let mut collector = TokenCollector { exprs: &ast.exprs, symbols: SymbolTable::new(), tokens: Vec::new() };
+    for item in &ast.items {
+        match item {
+            GlobalItem::Decl(decl) => collector.visit_decl(decl),
+            GlobalItem::FuncDef(func_def) => {
+                collector.declare(&func_def.id, func_def.name_span, TokenKind::Function, false);
+                collector.symbols.enter_scope();
+                for param in &func_def.params {
+                    if let Some(dims) = &param.dims {
+                        dims.iter().for_each(|dim| collector.visit_expr(dim.expr));
+                    }
+                    collector.declare(&param.id, param.span, TokenKind::Parameter, false);
+                }
+                collector.visit_block(&func_def.block);
+                collector.symbols.exit_scope();
+            }
+        }
+    }
+    // Initializers and case labels are visited after what they precede
+    collector.tokens.sort_by_key(|token| token.span.start);
+    collector.tokens
+}
+
+/// The functions, global variables, constants and enumerators of `ast`, in
+/// source order
+pub fn document_symbols(ast: &CompUnit) -> Vec<DocumentSymbol> {
+    let mut symbols = Vec::new();
+    let mut push = |ident: &Ident, kind, constant, span, name_span| {
+        symbols.push(DocumentSymbol { name: ident.value.clone(), kind, constant, span, name_span });
+    };
+    for item in &ast.items {
+        match item {
+            GlobalItem::FuncDef(func_def) => {
+                push(&func_def.id, TokenKind::Function, false, func_def.span, func_def.name_span);
+            }
+            GlobalItem::Decl(Decl::Const(decl)) => {
+                decl.defs.iter().for_each(|def| push(&def.id, TokenKind::Variable, true, def.span, def.span));
+            }
+            GlobalItem::Decl(Decl::Var(decl)) => {
+                decl.defs.iter().for_each(|def| push(&def.id, TokenKind::Variable, false, def.span, def.span));
+            }
+            GlobalItem::Decl(Decl::Enum(decl)) => {
+                for enumerator in &decl.enumerators {
+                    push(&enumerator.id, TokenKind::EnumMember, true, enumerator.span, enumerator.span);
+                }
+            }
+        }
+    }
+    symbols
+}
@@ -1,19 +1,45 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 use anyhow::Result;
 
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
 use koopa::back::KoopaGenerator;
-use lalrpop_util::lalrpop_mod;
-use yasysyc::backend::AsmGenerator;
-
-lalrpop_mod!(sysy);
+use koopa::front::Driver;
+use koopa::ir::{Program, Value, ValueKind};
+use yasysyc::ast::ExprArena;
+use yasysyc::ast::emit::EmitOptions;
+use yasysyc::backend::regalloc::StackAllocator;
+use yasysyc::backend::{AsmGenerator, AsmOptions, IntWidth};
+use yasysyc::diag::SourceFile;
+use yasysyc::dump::IrDumper;
+use yasysyc::ext::{self, Extensions};
+use yasysyc::float::{FloatOp, FloatSignature};
+use yasysyc::ice;
+use yasysyc::lexer::{self, Lexer};
+use yasysyc::lint::{self, LintOptions};
+use yasysyc::lsp;
+use yasysyc::opt::{self, PassOptions};
+use yasysyc::preprocess::{self, Preprocessed};
+use yasysyc::roundtrip;
+use yasysyc::semant;
+use yasysyc::sysy;
+use yasysyc::verify;
 
 #[derive(Parser)]
 #[command(name = "yasysyc")]
 #[command(about = "SysY compiler", long_about = None)]
+#[command(args_override_self = true)]
 struct Cli {
     /// Input SysY source file
-    input: String,
+    #[arg(required_unless_present = "lsp")]
+    input: Option<String>,
+
+    /// Further input files, only accepted to be rejected with a clear error
+    #[arg(hide = true)]
+    more_inputs: Vec<String>,
 
     /// Output file path (optional)
     #[arg(short, long)]
@@ -29,14 +55,279 @@ struct Cli {
 
     #[arg(long)]
     debug: bool,
+
+    /// Match the output format and flag semantics expected by the course autotest
+    #[arg(long)]
+    grader_compat: bool,
+
+    /// Optimization level (0 disables all IR optimizations)
+    #[arg(short = 'O', value_name = "LEVEL", default_value_t = 0, num_args = 0..=1, default_missing_value = "1")]
+    opt_level: u8,
+
+    /// Run this optimization pass even if the -O level does not (may be repeated)
+    #[arg(long, value_name = "PASS")]
+    enable_pass: Vec<String>,
+
+    /// Leave out this optimization pass (may be repeated)
+    #[arg(long, value_name = "PASS")]
+    disable_pass: Vec<String>,
+
+    /// Largest number of instructions loop unrolling may add for one loop
+    #[arg(long, value_name = "N", default_value_t = PassOptions::default().unroll_threshold)]
+    unroll_threshold: usize,
+
+    /// Copies of the body per iteration of loops whose trip count is unknown (0 or 1: none)
+    #[arg(long, value_name = "N", default_value_t = PassOptions::default().unroll_factor)]
+    unroll_factor: usize,
+
+    /// Initialize locals declared without an initializer (debugging aid)
+    #[arg(long, value_name = "MODE")]
+    init_locals: Option<InitLocals>,
+
+    /// Enable a warning (e.g. -Wshadow), or disable it with -Wno-<name>
+    #[arg(short = 'W', value_name = "WARNING")]
+    warnings: Vec<String>,
+
+    /// Enable a language extension (may be repeated): for, do-while, ternary, switch, bitwise, char,
+    /// enum, static
+    #[arg(long = "ext", value_name = "NAME")]
+    extensions: Vec<String>,
+
+    /// Only lower and generate code for this function (may be repeated);
+    /// the others are emitted as declarations
+    #[arg(long, value_name = "NAME")]
+    only_function: Vec<String>,
+
+    /// Emit this function as a declaration only (may be repeated)
+    #[arg(long, value_name = "NAME")]
+    skip_function: Vec<String>,
+
+    /// Read Koopa IR, such as a crash reproducer, instead of SysY source, and
+    /// run the stages its `// pipeline:` header lists in place of the -O level
+    #[arg(long)]
+    from_koopa: bool,
+
+    /// Print the Koopa IR, parse it back and check that nothing was lost
+    /// (for testing the IR printer)
+    #[arg(long, hide = true)]
+    roundtrip_ir: bool,
+
+    /// Check that the IR is well formed after lowering and after every pass
+    /// (always on in debug builds)
+    #[arg(long)]
+    verify_ir: bool,
+
+    /// Print the time each pass took and how it changed the program to stderr
+    #[arg(long)]
+    stats: bool,
+
+    /// Print the Koopa IR to stderr after lowering and after every pass
+    #[arg(long)]
+    print_after_all: bool,
+
+    /// With --print-after-all, write each dump to its own file in this
+    /// directory instead
+    #[arg(long, value_name = "DIR")]
+    dump_dir: Option<String>,
+
+    /// Print per-function instruction counts, frame sizes and spills to stderr
+    #[arg(long)]
+    codegen_report: bool,
+
+    /// Only keep stack frames word-aligned, breaking the ABI (for testing the
+    /// alignment checks)
+    #[arg(long)]
+    no_stack_align: bool,
+
+    /// Trap at run time when a call is made with a misaligned sp
+    #[arg(long)]
+    check_stack_align: bool,
+
+    /// Width of `int` in the generated RISC-V code, in bits (64 needs RV64)
+    #[arg(long, value_name = "BITS", default_value = "32")]
+    int_width: IntWidthArg,
+
+    /// ISA to record in the assembly with `.attribute arch` (e.g. rv32im)
+    #[arg(long, value_name = "ISA")]
+    march: Option<String>,
+
+    /// Run as a language server on stdin and stdout, checking the files the
+    /// editor opens with the given -W and --ext flags
+    #[arg(long, conflicts_with_all = ["input", "output", "koopa", "riscv", "from_koopa"])]
+    lsp: bool,
+}
+
+
+
+impl Cli {
+    /// The input file, which clap requires unless `--lsp` is given
+    fn input(&self) -> &str {
+        self.input.as_deref().expect("no input file without --lsp")
+    }
+
+    /// The extensions enabled with `--ext`
+    fn extensions(&self) -> Result<Extensions> {
+        let mut extensions = Extensions::default();
+        for name in &self.extensions {
+            if !extensions.enable(name) {
+                anyhow::bail!("unknown extension '--ext={}'", name);
+            }
+        }
+        Ok(extensions)
+    }
+
+    /// The warnings enabled with `-W`; unknown ones are reported and ignored
+    fn lint_options(&self) -> LintOptions {
+        let mut lint_options = LintOptions::default();
+        for name in &self.warnings {
+            if !lint_options.set(name) {
+                eprintln!("warning: unknown warning option '-W{}'", name);
+            }
+        }
+        lint_options
+    }
+
+    /// Reject flag combinations where one flag would silently be ignored or
+    /// the output would not be what the flags ask for
+    fn validate(&self) -> Result<(), clap::Error> {
+        let mut cmd = Cli::command();
+        if !self.more_inputs.is_empty() {
+            return Err(cmd.error(
+                ErrorKind::TooManyValues,
+                format!("multi-file compilation is not supported ({} input files given)", self.more_inputs.len() + 1),
+            ));
+        }
+        if self.koopa && self.riscv {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                "--koopa and --riscv cannot be used together; pick one output format",
+            ));
+        }
+        if self.codegen_report && !self.riscv {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                "--codegen-report requires --riscv",
+            ));
+        }
+        if (self.no_stack_align || self.check_stack_align) && !self.riscv {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                "--no-stack-align and --check-stack-align require --riscv",
+            ));
+        }
+        if self.int_width != IntWidthArg::W32 && !self.riscv {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                "--int-width requires --riscv; the Koopa IR only has 32-bit ints",
+            ));
+        }
+        if let Some(isa) = &self.march {
+            if !self.riscv {
+                return Err(cmd.error(ErrorKind::ArgumentConflict, "--march requires --riscv"));
+            }
+            // The generated code uses the M extension (`mul`, `div`, `rem`),
+            // and `ld`/`sd` for 64-bit ints
+            if !isa.starts_with("rv32") && !isa.starts_with("rv64") {
+                return Err(cmd.error(
+                    ErrorKind::InvalidValue,
+                    format!("--march={} is not an ISA string (rv32... or rv64...)", isa),
+                ));
+            }
+            if self.int_width == IntWidthArg::W64 && !isa.starts_with("rv64") {
+                return Err(cmd.error(
+                    ErrorKind::ArgumentConflict,
+                    format!("--int-width=64 generates RV64 code, which --march={} cannot run", isa),
+                ));
+            }
+            let extensions = isa[4..].split('_').next().unwrap_or_default();
+            if !extensions.contains(['m', 'g']) {
+                return Err(cmd.error(
+                    ErrorKind::InvalidValue,
+                    format!("--march={} lacks the M extension, which the generated code uses", isa),
+                ));
+            }
+        }
+        if self.int_width != IntWidthArg::W32 && self.grader_compat {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                "--grader-compat targets RV32 and cannot be used with --int-width=64",
+            ));
+        }
+        if self.from_koopa && !self.koopa && !self.riscv {
+            return Err(cmd.error(ErrorKind::MissingRequiredArgument, "--from-koopa requires --koopa or --riscv"));
+        }
+        if self.from_koopa && (!self.only_function.is_empty() || !self.skip_function.is_empty()) {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                "--only-function and --skip-function apply to lowering, which --from-koopa skips",
+            ));
+        }
+        if self.dump_dir.is_some() && !self.print_after_all {
+            return Err(cmd.error(ErrorKind::ArgumentConflict, "--dump-dir requires --print-after-all"));
+        }
+        if self.debug && (self.koopa || self.riscv) {
+            return Err(cmd.error(
+                ErrorKind::ArgumentConflict,
+                "--debug only applies when printing the AST, not with --koopa or --riscv",
+            ));
+        }
+        // The autotest always invokes `compiler -koopa|-riscv input -o output`
+        if self.grader_compat {
+            if !self.koopa && !self.riscv {
+                return Err(cmd.error(
+                    ErrorKind::MissingRequiredArgument,
+                    "--grader-compat requires one of -koopa/-riscv",
+                ));
+            }
+            if self.output.is_none() {
+                return Err(cmd.error(
+                    ErrorKind::MissingRequiredArgument,
+                    "--grader-compat requires an -o output file",
+                ));
+            }
+            if self.opt_level >= 2 {
+                return Err(cmd.error(
+                    ErrorKind::ArgumentConflict,
+                    format!("-O{} cannot be used with --grader-compat; use -O0 or -O1", self.opt_level),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InitLocals {
+    /// Store 0 into every uninitialized local
+    Zero,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum IntWidthArg {
+    #[value(name = "32")]
+    W32,
+    #[value(name = "64")]
+    W64,
+}
 
+impl From<IntWidthArg> for IntWidth {
+    fn from(width: IntWidthArg) -> Self {
+        match width {
+            IntWidthArg::W32 => IntWidth::W32,
+            IntWidthArg::W64 => IntWidth::W64,
+        }
+    }
+}
 
 fn main() -> Result<()> {
+    // Extra arguments from YASYSYC_FLAGS are appended after the real ones, so
+    // they can override the fixed flags of a wrapped invocation (e.g. the judge)
+    let env_flags = std::env::var("YASYSYC_FLAGS").unwrap_or_default();
+
     // Replace -koopa with --koopa for clap compatibility
     // (test tool uses -koopa, but clap expects --koopa for long options)
     let args = std::env::args()
+        .chain(env_flags.split_whitespace().map(String::from))
         .map(|arg| {
             match arg.as_str() {
                 "-koopa" => "--koopa".to_string(),
@@ -45,6 +336,7 @@ fn main() -> Result<()> {
             }
         });
     let cli = Cli::try_parse_from(args).unwrap_or_else(|e| e.exit());
+    cli.validate().unwrap_or_else(|e| e.exit());
 
 
 
@@ -58,28 +350,163 @@ fn main() -> Result<()> {
     // let cli = debug_cli;
     // // <<<< DEBUG
 
-    let input = read_to_string(&cli.input)?;
+    if cli.lsp {
+        let options = lsp::Options { extensions: cli.extensions()?, lints: cli.lint_options() };
+        lsp::serve(&mut std::io::stdin().lock(), &mut std::io::stdout().lock(), options)?;
+        return Ok(());
+    }
 
-    let ast = sysy::CompUnitParser::new().parse(&input)
-        .map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
+    let input = read_to_string(cli.input())?;
+    let lowered = if cli.from_koopa {
+        load_koopa(&input)?
+    } else {
+        match lower(&cli, &input)? {
+            Some(lowered) => lowered,
+            None => return Ok(()),
+        }
+    };
+    let Lowered { program: mut koopa_ir, string_literals, static_locals, float_signatures, unoptimized, pipeline } =
+        lowered;
+    if let Some(isa) = &cli.march {
+        // Float operations are left as calls to their `FloatOp` functions
+        // until code generation, which uses the F extension for them
+        let is_float_op = |func| FloatOp::from_name(&koopa_ir.func(func).name()[1..]).is_some();
+        let uses_floats = !float_signatures.is_empty() || koopa_ir.func_layout().iter().copied().any(is_float_op);
+        let extensions = isa[4..].split('_').next().unwrap_or_default();
+        if uses_floats && !extensions.contains(['f', 'd', 'g']) {
+            anyhow::bail!("--march={} lacks the F extension, which the generated code uses for float", isa);
+        }
+    }
+    if cli.int_width == IntWidthArg::W64 {
+        // The runtime library is compiled with 4-byte `int`s, so it would read
+        // and write the 8-byte elements of arrays (and `putf` its arguments)
+        // at the wrong offsets
+        const NARROW_RUNTIME: [&str; 5] = ["getarray", "getfarray", "putarray", "putfarray", "putf"];
+        for &func in koopa_ir.func_layout() {
+            let data = koopa_ir.func(func);
+            for (_, node) in data.layout().bbs() {
+                for &inst in node.insts().keys() {
+                    let ValueKind::Call(call) = data.dfg().value(inst).kind() else {
+                        continue;
+                    };
+                    let callee = &koopa_ir.func(call.callee()).name()[1..];
+                    if NARROW_RUNTIME.contains(&callee) {
+                        anyhow::bail!(
+                            "{} cannot be called with --int-width=64; the runtime library uses 4-byte ints",
+                            callee
+                        );
+                    }
+                }
+            }
+        }
+    }
+    let partial_program = !cli.only_function.is_empty() || !cli.skip_function.is_empty();
+    let pass_options = PassOptions {
+        unroll_threshold: cli.unroll_threshold,
+        unroll_factor: cli.unroll_factor,
+        partial_program,
+    };
+    // A reproducer lists the passes that were enabled when it was written
+    let mut passes = opt::pipeline_with_options(if pipeline.is_some() { 0 } else { cli.opt_level }, pass_options);
+    for name in pipeline.iter().flatten().filter(|name| *name != "codegen") {
+        if !passes.set_enabled(name, true) {
+            anyhow::bail!("unknown pass '{}' in the pipeline of {}", name, cli.input());
+        }
+    }
+    for (names, enabled) in [(&cli.enable_pass, true), (&cli.disable_pass, false)] {
+        for name in names {
+            if !passes.set_enabled(name, enabled) {
+                anyhow::bail!("unknown pass '{}'; the passes are {}", name, passes.names().join(", "));
+            }
+        }
+    }
+    let options = AsmOptions {
+        grader_compat: cli.grader_compat,
+        check_stack_align: cli.check_stack_align,
+        int_width: cli.int_width.into(),
+        arch: cli.march.clone(),
+        partial_program,
+    };
+    let word_size = options.int_width.bytes();
+    let frame_align = if cli.no_stack_align { 4 } else { 16 };
 
+    if cli.stats {
+        passes.collect_stats();
+    }
 
-    if !cli.koopa && !cli.riscv {
-        if cli.debug {
-            println!("{:#?}", ast);
-            return Ok(());
-        } else {
-            println!("{}", ast);
-            return Ok(());
-        }
+    // Registered before the verifier, so the IR a pass broke is still dumped
+    if cli.print_after_all {
+        let mut dumper = match &cli.dump_dir {
+            Some(dir) => IrDumper::in_dir(dir)?,
+            None => IrDumper::stderr(),
+        };
+        dumper.dump("lowering", &koopa_ir)?;
+        passes.add_invalidation_hook(move |name, program| {
+            if let Err(e) = dumper.dump(name, program) {
+                eprintln!("warning: failed to dump the IR after {}: {}", name, e);
+            }
+        });
     }
 
-    let koopa_ir = ast.emit();
+    // A pass leaving malformed IR is reported as a crash of that pass
+    let verify_ir = cli.verify_ir || cfg!(debug_assertions);
+    if verify_ir {
+        passes.add_invalidation_hook(|name, program| {
+            if let Err(e) = verify::verify(program) {
+                panic!("invalid IR after pass {}: {}", name, e);
+            }
+        });
+    }
+
+    // Passes and codegen should never fail on IR we emitted; if they do, leave
+    // a reproducer behind so the failure can be replayed without the source
+    passes.keep_snapshots();
+    let compiled = panic::catch_unwind(AssertUnwindSafe(|| {
+        let lowered = if verify_ir { verify::verify(&koopa_ir) } else { Ok(()) };
+        if let Err(e) = lowered {
+            panic!("invalid IR from lowering: {}", e);
+        }
+        passes.run(&mut koopa_ir, &unoptimized);
+        if cli.stats {
+            eprint!("{}", opt::stats::report(passes.stats()));
+        }
+        cli.riscv.then(|| {
+            let mut generator = AsmGenerator::with_allocator(StackAllocator::with_frame_align(frame_align).with_word_size(word_size))
+                .with_options(options)
+                .with_string_literals(string_literals)
+                .with_static_locals(static_locals)
+                .with_float_signatures(float_signatures.clone());
+            generator.visit_program(&koopa_ir);
+            if cli.codegen_report {
+                eprintln!("{}", generator.report());
+            }
+            generator.to_string()
+        })
+    }));
+    let asm = match compiled {
+        Ok(asm) => asm,
+        Err(_) => {
+            // The failing stage may have left the IR half-rewritten, so the
+            // reproducer starts from the IR it got. Without a snapshot the
+            // pipeline never started, and the program is as lowered.
+            let (ir, mut stages) = match passes.snapshot() {
+                Some(snapshot) => (snapshot.ir.clone(), snapshot.remaining.clone()),
+                None => (print_ir(&koopa_ir)?, passes.enabled()),
+            };
+            if cli.riscv {
+                stages.push("codegen");
+            }
+            let path = ice::write_reproducer(Path::new("."), &ir, &stages, &unoptimized, &float_signatures)?;
+            anyhow::bail!("internal compiler error; reproducer written to {}", path.display());
+        }
+    };
+
+    if cli.roundtrip_ir {
+        roundtrip::check(&koopa_ir).map_err(|e| anyhow::anyhow!("IR round trip failed: {}", e))?;
+    }
 
     if cli.koopa {
-        let mut writer = Vec::new();
-        KoopaGenerator::new(&mut writer).generate_on(&koopa_ir)?;
-        let ir = String::from_utf8(writer)?;
+        let ir = print_ir(&koopa_ir)?;
         if let Some(output) = cli.output {
             std::fs::write(output, ir.as_bytes())?;
         } else {
@@ -88,8 +515,7 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    if cli.riscv {
-        let asm = AsmGenerator::generate(&koopa_ir);
+    if let Some(asm) = asm {
         if let Some(output) = cli.output {
             std::fs::write(output, asm.as_bytes())?;
         } else {
@@ -100,3 +526,114 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// A program ready for the optimization passes
+struct Lowered {
+    program: Program,
+    /// The globals holding string literals, see `Emitted::string_literals`
+    string_literals: HashSet<Value>,
+    /// The globals holding static locals, see `Emitted::static_locals`
+    static_locals: HashSet<Value>,
+    /// See `Emitted::float_signatures`
+    float_signatures: HashMap<String, FloatSignature>,
+    /// Functions the passes leave alone
+    unoptimized: HashSet<String>,
+    /// The stages a reproducer lists, which replace those of the -O level
+    pipeline: Option<Vec<String>>,
+}
+
+/// Check and lower SysY source. Returns `None` when only the AST is printed.
+fn lower(cli: &Cli, input: &str) -> Result<Option<Lowered>> {
+    let Preprocessed { text: input, origins } = match preprocess::preprocess(cli.input(), input) {
+        Ok(preprocessed) => preprocessed,
+        Err(e) => {
+            eprintln!("{}", e);
+            anyhow::bail!("Failed to preprocess input");
+        }
+    };
+
+    let source = SourceFile::new(cli.input(), &input).with_origins(&origins);
+
+    let extensions = cli.extensions()?;
+    let ast = match sysy::CompUnitParser::new().parse(&mut ExprArena::default(), Lexer::new(&input, &extensions)) {
+        Ok(ast) => ast,
+        Err(e) => {
+            eprint!("{}", source.render(&lexer::parse_error(&input, e, &extensions)));
+            anyhow::bail!("Failed to parse input");
+        }
+    };
+
+    let ext_errors = ext::check(&ast, &extensions);
+    for diag in &ext_errors {
+        eprint!("{}", source.render(diag));
+    }
+    if !ext_errors.is_empty() {
+        anyhow::bail!("Failed to compile input");
+    }
+    if let Err(errors) = semant::check(&ast) {
+        for diag in &errors {
+            eprint!("{}", source.render(diag));
+        }
+        anyhow::bail!("Failed to compile input");
+    }
+
+    for diag in lint::run(&ast, &cli.lint_options()) {
+        eprint!("{}", source.render(&diag));
+    }
+
+
+    if !cli.koopa && !cli.riscv {
+        if cli.debug {
+            println!("{:#?}", ast);
+            return Ok(None);
+        } else {
+            println!("{}", ast);
+            return Ok(None);
+        }
+    }
+
+    let emit_options = EmitOptions {
+        zero_init_locals: cli.init_locals == Some(InitLocals::Zero),
+        only_functions: cli.only_function.clone(),
+        skip_functions: cli.skip_function.clone(),
+    };
+    let emitted = ast.emit_all(emit_options);
+    for diag in &emitted.errors {
+        eprint!("{}", source.render(diag));
+    }
+    if !emitted.errors.is_empty() {
+        anyhow::bail!("Failed to compile input");
+    }
+    let unoptimized = opt::pragma::unoptimized_functions(&input, &ast);
+    Ok(Some(Lowered {
+        program: emitted.program,
+        string_literals: emitted.string_literals,
+        static_locals: emitted.static_locals,
+        float_signatures: emitted.float_signatures,
+        unoptimized,
+        pipeline: None,
+    }))
+}
+
+/// Parse Koopa IR, with the stages, unoptimized functions and float
+/// signatures of its header if it is a crash reproducer
+fn load_koopa(input: &str) -> Result<Lowered> {
+    let program = Driver::from(input.to_string())
+        .generate_program()
+        .map_err(|e| anyhow::anyhow!("Failed to parse Koopa IR: {:?}", e))?;
+    let header = ice::read_header(input);
+    Ok(Lowered {
+        program,
+        string_literals: HashSet::new(),
+        static_locals: HashSet::new(),
+        float_signatures: header.iter().flat_map(|header| header.floats.iter().cloned()).collect(),
+        unoptimized: header.iter().flat_map(|header| header.unoptimized.iter().cloned()).collect(),
+        pipeline: header.map(|header| header.pipeline),
+    })
+}
+
+fn print_ir(program: &Program) -> Result<String> {
+    let mut writer = Vec::new();
+    KoopaGenerator::new(&mut writer).generate_on(program)?;
+    Ok(String::from_utf8(writer)?)
+}
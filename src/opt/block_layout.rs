@@ -0,0 +1,146 @@
+//! Hot/cold basic block layout.
+//!
+//! The backend emits blocks in layout order and lets a jump to the next block
+//! fall through, so the layout decides which edges cost a jump. This pass
+//! reorders blocks into chains that follow the likely successor of each block:
+//!
+//! - a branch that stays inside a loop is likelier than one that exits it, so
+//!   loop bodies stay contiguous;
+//! - a block that leaves the function from a branch whose other side keeps
+//!   running (an early or error return) is cold and moved to the end.
+//!
+//! A block is only placed once all of its forward predecessors are, so
+//! balanced `if`/`else` code keeps its source order.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::{BasicBlock, FunctionData, Value, ValueKind};
+
+use crate::analysis::Cfg;
+
+pub fn run(func: &mut FunctionData) {
+    if func.layout().entry_bb().is_some() {
+        reorder(func);
+    }
+}
+
+/// The loops of a function, found from the back edges of its graph
+struct Loops {
+    /// Headers of the loops containing each block
+    headers: HashMap<BasicBlock, HashSet<BasicBlock>>,
+}
+
+impl Loops {
+    fn new(cfg: &Cfg) -> Self {
+        // The body of a loop is everything that reaches the latch without
+        // passing through the header
+        let mut headers: HashMap<_, HashSet<_>> = HashMap::new();
+        for (latch, header) in cfg.back_edges() {
+            let mut body = HashSet::from([header]);
+            let mut work = vec![latch];
+            while let Some(bb) = work.pop() {
+                if body.insert(bb) {
+                    work.extend(cfg.preds(bb).iter().copied());
+                }
+            }
+            for bb in body {
+                headers.entry(bb).or_default().insert(header);
+            }
+        }
+        Self { headers }
+    }
+
+    fn loops_of(&self, bb: BasicBlock) -> HashSet<BasicBlock> {
+        self.headers.get(&bb).cloned().unwrap_or_default()
+    }
+}
+
+/// Whether `bb` leaves the function: it returns, or jumps straight to a block
+/// that does nothing but return (as left by return merging)
+fn exits(func: &FunctionData, bb: BasicBlock) -> bool {
+    let is_ret = |bb: BasicBlock| {
+        let node = func.layout().bbs().node(&bb).unwrap();
+        node.insts().keys().all(|&inst| {
+            matches!(func.dfg().value(inst).kind(), ValueKind::Return(_) | ValueKind::Load(_))
+        }) && node.insts().back_key().is_some()
+    };
+    let node = func.layout().bbs().node(&bb).unwrap();
+    match node.insts().back_key().map(|&inst| func.dfg().value(inst).kind()) {
+        Some(ValueKind::Return(_)) => true,
+        Some(ValueKind::Jump(jump)) => is_ret(jump.target()),
+        _ => false,
+    }
+}
+
+/// Blocks that are only entered from a branch whose other side does not exit
+fn cold_blocks(func: &FunctionData, cfg: &Cfg) -> HashSet<BasicBlock> {
+    cfg.blocks()[1..]
+        .iter()
+        .copied()
+        .filter(|&bb| {
+            exits(func, bb)
+                && !cfg.preds(bb).is_empty()
+                && cfg.preds(bb).iter().all(|&pred| {
+                    let succs = cfg.succs(pred);
+                    succs.len() == 2 && succs.iter().any(|&other| other != bb && !exits(func, other))
+                })
+        })
+        .collect()
+}
+
+/// Successors of `bb`, likeliest first
+fn ranked_successors(cfg: &Cfg, loops: &Loops, bb: BasicBlock, cold: &HashSet<BasicBlock>) -> Vec<BasicBlock> {
+    let mut succs = cfg.succs(bb).to_vec();
+    if let [t, f] = succs[..] {
+        let bb_loops = loops.loops_of(bb);
+        let stays = |target| loops.loops_of(target).is_superset(&bb_loops);
+        let f_likelier = (cold.contains(&t) && !cold.contains(&f)) || (stays(f) && !stays(t));
+        if f_likelier {
+            succs.swap(0, 1);
+        }
+    }
+    succs
+}
+
+fn reorder(func: &mut FunctionData) {
+    let cfg = Cfg::new(func);
+    let loops = Loops::new(&cfg);
+    let cold = cold_blocks(func, &cfg);
+    let order = cfg.blocks();
+
+    let mut placed = HashSet::new();
+    let mut layout = Vec::with_capacity(order.len());
+    let ready = |bb: BasicBlock, placed: &HashSet<BasicBlock>| {
+        !placed.contains(&bb)
+            && !cold.contains(&bb)
+            && cfg.preds(bb).iter().all(|&pred| placed.contains(&pred) || cfg.is_back_edge(pred, bb))
+    };
+
+    let mut current = Some(order[0]);
+    while let Some(bb) = current {
+        placed.insert(bb);
+        layout.push(bb);
+        current = ranked_successors(&cfg, &loops, bb, &cold)
+            .into_iter()
+            .find(|&succ| ready(succ, &placed))
+            .or_else(|| order.iter().copied().find(|&bb| ready(bb, &placed)))
+            .or_else(|| {
+                // Only blocks in a cycle not entered through a back edge are
+                // left; take them in their original order
+                order.iter().copied().find(|bb| !placed.contains(bb) && !cold.contains(bb))
+            });
+    }
+    layout.extend(order.iter().copied().filter(|bb| cold.contains(bb)));
+
+    if layout != order {
+        // Re-adding a block creates an empty node, so move its instructions over
+        for bb in layout {
+            let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+            func.layout_mut().bbs_mut().remove(&bb);
+            func.layout_mut().bbs_mut().push_key_back(bb).unwrap();
+            for inst in insts {
+                func.layout_mut().bb_mut(bb).insts_mut().push_key_back(inst).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Constant folding: evaluate binary instructions whose operands are both
+//! integer constants.
+//!
+//! The AST is already folded while it is lowered, but other passes can leave
+//! constant operands behind (if-conversion of `if (1)` computes its mask from
+//! the constant condition). Each folded instruction is removed from its block
+//! and its value replaced by the integer, so its users see a constant and may
+//! fold in turn. Arithmetic wraps around and shift amounts are taken modulo
+//! 32, as the RISC-V instructions do; a division or remainder by zero is left
+//! to the backend.
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Value, ValueKind};
+
+use super::stats;
+
+pub fn run(func: &mut FunctionData) {
+    // Users may come before their operands in the layout once blocks are
+    // reordered, so sweep until nothing changes
+    while fold_once(func) {}
+}
+
+fn fold_once(func: &mut FunctionData) -> bool {
+    let insts: Vec<(BasicBlock, Value)> = func.layout().bbs()
+        .iter()
+        .flat_map(|(&bb, node)| node.insts().keys().map(move |&inst| (bb, inst)))
+        .collect();
+    let mut changed = false;
+    for (bb, inst) in insts {
+        let ValueKind::Binary(binary) = func.dfg().value(inst).kind() else {
+            continue;
+        };
+        let int = |value| match func.dfg().value(value).kind() {
+            ValueKind::Integer(int) => Some(int.value()),
+            _ => None,
+        };
+        let (Some(lhs), Some(rhs)) = (int(binary.lhs()), int(binary.rhs())) else {
+            continue;
+        };
+        let Some(result) = fold(binary.op(), lhs, rhs) else {
+            continue;
+        };
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        func.dfg_mut().replace_value_with(inst).integer(result);
+        stats::bump("instructions folded", 1);
+        changed = true;
+    }
+    changed
+}
+
+/// Value of `lhs op rhs`, unless it is a division or remainder by zero
+pub(crate) fn fold(op: BinaryOp, lhs: i32, rhs: i32) -> Option<i32> {
+    Some(match op {
+        BinaryOp::NotEq => (lhs != rhs) as i32,
+        BinaryOp::Eq => (lhs == rhs) as i32,
+        BinaryOp::Gt => (lhs > rhs) as i32,
+        BinaryOp::Lt => (lhs < rhs) as i32,
+        BinaryOp::Ge => (lhs >= rhs) as i32,
+        BinaryOp::Le => (lhs <= rhs) as i32,
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div | BinaryOp::Mod if rhs == 0 => return None,
+        BinaryOp::Div => lhs.wrapping_div(rhs),
+        BinaryOp::Mod => lhs.wrapping_rem(rhs),
+        BinaryOp::And => lhs & rhs,
+        BinaryOp::Or => lhs | rhs,
+        BinaryOp::Xor => lhs ^ rhs,
+        BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+        BinaryOp::Shr => (lhs as u32).wrapping_shr(rhs as u32) as i32,
+        BinaryOp::Sar => lhs.wrapping_shr(rhs as u32),
+    })
+}
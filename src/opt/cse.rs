@@ -0,0 +1,98 @@
+//! Local common subexpression elimination: within each basic block, an
+//! instruction computing the same thing as an earlier one is replaced by it.
+//!
+//! Binary operations and address computations (`getptr`, `getelemptr`) are
+//! pure, so two of them with the same operator and operands always agree. The
+//! operands of commutative operators are matched in either order. A `load`
+//! agrees with an earlier load of the same address, or reads the value an
+//! earlier `store` wrote there, as long as nothing in between may write to
+//! the address: a store to memory that may alias it (see
+//! [`crate::analysis::alias`]), or a call that may reach it.
+
+use std::collections::HashMap;
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Value, ValueKind};
+
+use super::{remap_operands, stats};
+use crate::analysis::alias::{call_may_write, may_alias};
+
+/// What an instruction computes, for the instructions that can be reused
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Key {
+    Binary(BinaryOp, Value, Value),
+    GetPtr(Value, Value),
+    GetElemPtr(Value, Value),
+    Load(Value),
+}
+
+pub fn run(func: &mut FunctionData) {
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        eliminate_in_block(func, bb);
+    }
+}
+
+fn is_commutative(op: BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Add | BinaryOp::Mul | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor | BinaryOp::Eq | BinaryOp::NotEq
+    )
+}
+
+fn eliminate_in_block(func: &mut FunctionData, bb: BasicBlock) {
+    let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+    let mut available: HashMap<Key, Value> = HashMap::new();
+    for inst in insts {
+        // Users later in the block have been rewritten already, so a chain
+        // of duplicates is found link by link
+        let key = match func.dfg().value(inst).kind() {
+            ValueKind::Binary(binary) => {
+                let (op, lhs, rhs) = (binary.op(), binary.lhs(), binary.rhs());
+                if is_commutative(op) && available.contains_key(&Key::Binary(op, rhs, lhs)) {
+                    Key::Binary(op, rhs, lhs)
+                } else {
+                    Key::Binary(op, lhs, rhs)
+                }
+            }
+            ValueKind::GetPtr(get_ptr) => Key::GetPtr(get_ptr.src(), get_ptr.index()),
+            ValueKind::GetElemPtr(get_elem_ptr) => Key::GetElemPtr(get_elem_ptr.src(), get_elem_ptr.index()),
+            ValueKind::Load(load) => Key::Load(load.src()),
+            ValueKind::Store(store) => {
+                let (value, dest) = (store.value(), store.dest());
+                available.retain(|key, _| !matches!(*key, Key::Load(addr) if may_alias(func, addr, dest)));
+                // Arguments are read from their registers, which calls
+                // clobber, so they are only used where they are stored
+                if !matches!(func.dfg().value(value).kind(), ValueKind::FuncArgRef(_)) {
+                    available.insert(Key::Load(dest), value);
+                }
+                continue;
+            }
+            ValueKind::Call(_) => {
+                available.retain(|key, _| !matches!(*key, Key::Load(addr) if call_may_write(func, addr)));
+                continue;
+            }
+            _ => continue,
+        };
+        match available.get(&key) {
+            Some(&earlier) => replace(func, bb, inst, earlier),
+            None => {
+                available.insert(key, inst);
+            }
+        }
+    }
+}
+
+/// Point the users of `inst` at `earlier` instead, and delete `inst`
+fn replace(func: &mut FunctionData, bb: BasicBlock, inst: Value, earlier: Value) {
+    let map = HashMap::from([(inst, earlier)]);
+    let users: Vec<Value> = func.dfg().value(inst).used_by().iter().copied().collect();
+    for user in users {
+        let mut data = func.dfg().value(user).clone();
+        remap_operands(data.kind_mut(), &map);
+        func.dfg_mut().replace_value_with(user).raw(data);
+    }
+    func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+    func.dfg_mut().remove_value(inst);
+    stats::bump("instructions reused", 1);
+}
@@ -0,0 +1,144 @@
+//! Dead global elimination: delete the global variables that are never read.
+//!
+//! A global is read if some instruction other than a `store` to it uses its
+//! address, directly or through element pointers: a `load`, or a call it is
+//! passed to, which could read it. The stores to the others are removed along
+//! with the computations that only fed them, then the globals themselves and
+//! their initializers, so they take no room in `.data`.
+//!
+//! Globals used by a function left out of the pipeline are kept, and so is
+//! every global when only part of the program is compiled: the functions
+//! emitted as declarations are defined elsewhere and may read any of them.
+
+use std::collections::HashSet;
+
+use koopa::ir::{FunctionData, Program, Value, ValueKind};
+
+use super::{Pass, stats};
+
+pub struct DeadGlobals {
+    /// Whether some functions were left out of the program, see
+    /// [`super::PassOptions::partial_program`]
+    pub partial_program: bool,
+}
+
+impl Pass for DeadGlobals {
+    fn name(&self) -> &'static str {
+        "dead-globals"
+    }
+
+    fn run(&mut self, program: &mut Program, excluded: &HashSet<String>) {
+        if self.partial_program {
+            return;
+        }
+        let dead = dead_globals(program, excluded);
+        if dead.is_empty() {
+            return;
+        }
+        for func in program.funcs_mut().values_mut() {
+            remove_stores(func, &dead);
+        }
+        for global in program.inst_layout().to_vec() {
+            if dead.contains(&global) {
+                remove_global(program, global);
+            }
+        }
+        stats::bump("globals removed", dead.len());
+    }
+}
+
+/// The global a pointer points into, if any
+fn base(func: &FunctionData, mut value: Value) -> Option<Value> {
+    loop {
+        if value.is_global() {
+            return Some(value);
+        }
+        match func.dfg().value(value).kind() {
+            ValueKind::GetElemPtr(get_elem_ptr) => value = get_elem_ptr.src(),
+            ValueKind::GetPtr(get_ptr) => value = get_ptr.src(),
+            _ => return None,
+        }
+    }
+}
+
+/// Globals that no function reads
+fn dead_globals(program: &Program, excluded: &HashSet<String>) -> HashSet<Value> {
+    let mut read = HashSet::new();
+    for func in program.funcs().values() {
+        let keep_all = excluded.contains(&func.name()[1..]);
+        for (_, node) in func.layout().bbs() {
+            for &inst in node.insts().keys() {
+                match func.dfg().value(inst).kind() {
+                    ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_) => {}
+                    ValueKind::Store(store) if !keep_all => read.extend(base(func, store.value())),
+                    kind => read.extend(kind.value_uses().filter_map(|value| base(func, value))),
+                }
+            }
+        }
+    }
+    program.inst_layout().iter().copied().filter(|global| !read.contains(global)).collect()
+}
+
+/// Delete the stores into the `dead` globals, and the instructions left
+/// without users that only computed what they stored or where
+fn remove_stores(func: &mut FunctionData, dead: &HashSet<Value>) {
+    let mut work = Vec::new();
+    for (_, node) in func.layout().bbs() {
+        for &inst in node.insts().keys() {
+            let ValueKind::Store(store) = func.dfg().value(inst).kind() else {
+                continue;
+            };
+            if base(func, store.dest()).is_some_and(|global| dead.contains(&global)) {
+                work.push(inst);
+            }
+        }
+    }
+
+    let mut removed = HashSet::new();
+    while let Some(inst) = work.pop() {
+        if removed.contains(&inst) {
+            continue;
+        }
+        let Some(bb) = func.layout().parent_bb(inst) else {
+            continue;
+        };
+        let data = func.dfg().value(inst);
+        let unused = match data.kind() {
+            ValueKind::Store(_) => true,
+            ValueKind::Binary(_) | ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_) | ValueKind::Load(_) => {
+                data.used_by().is_empty()
+            }
+            _ => false,
+        };
+        if !unused {
+            continue;
+        }
+        work.extend(data.kind().value_uses().filter(|value| !value.is_global()));
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        func.dfg_mut().remove_value(inst);
+        removed.insert(inst);
+    }
+}
+
+/// Delete `global` and its initializer
+fn remove_global(program: &mut Program, global: Value) {
+    let ValueKind::GlobalAlloc(alloc) = program.borrow_value(global).kind().clone() else {
+        unreachable!("globals are allocated with global_alloc");
+    };
+    program.remove_value(global);
+    let mut work = vec![alloc.init()];
+    while let Some(value) = work.pop() {
+        let elems = {
+            let data = program.borrow_value(value);
+            if !data.used_by().is_empty() {
+                continue;
+            }
+            match data.kind() {
+                ValueKind::Aggregate(aggregate) => aggregate.elems().to_vec(),
+                _ => Vec::new(),
+            }
+        };
+        program.remove_value(value);
+        work.extend(elems);
+    }
+}
@@ -0,0 +1,247 @@
+//! Induction variable canonicalization, on the SSA form `mem2reg` builds.
+//!
+//! The basic induction variables of each simple loop (see
+//! [`crate::analysis::induction`]) are rewritten in two ways:
+//!
+//! - **Merging counters**: of the variables stepping by the same constant,
+//!   only the first is kept as a counter. The others always differ from it by
+//!   what they differ by on entry, so they become `add %i, <offset>` at the
+//!   top of the header, with the offset computed once in the preheader.
+//! - **Reducing scaled counters**: `mul %i, c` or `shl %i, k` inside the loop
+//!   becomes a counter of its own, starting at the scaled start of `%i` and
+//!   stepping by the scaled step, so array indices such as `a[4 * i]` cost an
+//!   `add` per iteration instead of a multiplication per use.
+//!
+//! ```text
+//!   %pre:  jump %loop(0, 5)                %pre:  jump %loop(0, 0)
+//!   %loop(%i: i32, %j: i32):               %loop(%i: i32, %m: i32):
+//!          %k = mul %i, 4          =>             %j = add %i, 5
+//!          ...                                    ...
+//!          %i1 = add %i, 1                        %i1 = add %i, 1
+//!          %j1 = add %j, 1                        %m1 = add %m, 4
+//!          jump %loop(%i1, %j1)                   jump %loop(%i1, %m1)
+//! ```
+//!
+//! Arithmetic wraps around, so the rewritten values are exact even where the
+//! counters overflow. Instructions left without users, such as the step of a
+//! merged counter, are deleted.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Type, Value, ValueKind};
+
+use super::const_fold::fold;
+use super::remap_operands;
+use crate::analysis::induction::{InductionVar, Loop, induction_vars, integer, jump_args, simple_loops};
+use crate::analysis::{Cfg, DomTree};
+
+pub fn run(func: &mut FunctionData) {
+    // Each rewrite replaces a header, which changes the loops around it, so
+    // they are found again after every one
+    loop {
+        let cfg = Cfg::new(func);
+        let Some(dom_tree) = DomTree::new(&cfg) else {
+            return;
+        };
+        let plan = simple_loops(func, &cfg, &dom_tree).into_iter().find_map(|found| plan(func, found));
+        let Some(plan) = plan else {
+            return;
+        };
+        rewrite(func, plan);
+    }
+}
+
+/// The rewrites of one loop
+struct Plan {
+    found: Loop,
+    /// Counters replaced by another one plus an offset, with that other one
+    merged: Vec<(InductionVar, InductionVar)>,
+    /// New counters, each with the counter it scales, the factor, and the
+    /// multiplications it stands for
+    scaled: Vec<(InductionVar, i32, Vec<Value>)>,
+}
+
+fn plan(func: &FunctionData, found: Loop) -> Option<Plan> {
+    let vars = induction_vars(func, &found);
+
+    let mut canonical: HashMap<i32, InductionVar> = HashMap::new();
+    let mut merged = Vec::new();
+    for &var in &vars {
+        match canonical.get(&var.step) {
+            Some(&kept) => merged.push((var, kept)),
+            None => {
+                canonical.insert(var.step, var);
+            }
+        }
+    }
+
+    let by_param: HashMap<Value, InductionVar> = vars.iter().map(|&var| (var.param, var)).collect();
+    let mut scaled: Vec<(InductionVar, i32, Vec<Value>)> = Vec::new();
+    let mut blocks: Vec<BasicBlock> = found.blocks.iter().copied().collect();
+    blocks.sort_by_key(|&bb| func.layout().bbs().keys().position(|&other| other == bb));
+    for bb in blocks {
+        for &inst in func.layout().bbs().node(&bb).unwrap().insts().keys() {
+            let Some((var, factor)) = scale(func, &by_param, inst) else {
+                continue;
+            };
+            match scaled.iter_mut().find(|(other, f, _)| other.param == var.param && *f == factor) {
+                Some((_, _, insts)) => insts.push(inst),
+                None => scaled.push((var, factor, vec![inst])),
+            }
+        }
+    }
+
+    (!merged.is_empty() || !scaled.is_empty()).then_some(Plan { found, merged, scaled })
+}
+
+/// The counter `inst` multiplies by a constant, and the constant, if it is
+/// `mul %i, c`, `mul c, %i` or `shl %i, k` for a counter `%i`
+fn scale(func: &FunctionData, vars: &HashMap<Value, InductionVar>, inst: Value) -> Option<(InductionVar, i32)> {
+    let ValueKind::Binary(binary) = func.dfg().value(inst).kind() else {
+        return None;
+    };
+    let (var, factor) = match (binary.op(), vars.get(&binary.lhs()), vars.get(&binary.rhs())) {
+        (BinaryOp::Mul, Some(&var), None) => (var, integer(func, binary.rhs())?),
+        (BinaryOp::Mul, None, Some(&var)) => (var, integer(func, binary.lhs())?),
+        (BinaryOp::Shl, Some(&var), None) => (var, 1i32.wrapping_shl(integer(func, binary.rhs())? as u32)),
+        _ => return None,
+    };
+    // Multiplying by 0 or 1 is left to other passes
+    (factor != 0 && factor != 1).then_some((var, factor))
+}
+
+fn insts_of(func: &FunctionData, bb: BasicBlock) -> Vec<Value> {
+    func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect()
+}
+
+/// `lhs op rhs`, folded if both are constants, or computed right before the
+/// terminator of `bb`
+fn binary_before_end(func: &mut FunctionData, bb: BasicBlock, op: BinaryOp, lhs: Value, rhs: Value) -> Value {
+    if let (Some(lhs), Some(rhs)) = (integer(func, lhs), integer(func, rhs)) {
+        let result = fold(op, lhs, rhs).expect("only wrapping arithmetic is folded here");
+        return func.dfg_mut().new_value().integer(result);
+    }
+    let &term = func.layout().bbs().node(&bb).unwrap().insts().back_key().unwrap();
+    let value = func.dfg_mut().new_value().binary(op, lhs, rhs);
+    let insts = func.layout_mut().bb_mut(bb).insts_mut();
+    insts.remove(&term);
+    insts.push_key_back(value).unwrap();
+    insts.push_key_back(term).unwrap();
+    value
+}
+
+fn rewrite(func: &mut FunctionData, plan: Plan) {
+    let Plan { found, merged, scaled } = plan;
+    let Loop { header, preheader, latch, .. } = found;
+    let old_params = func.dfg().bb(header).params().to_vec();
+    let merged_params: HashSet<Value> = merged.iter().map(|(var, _)| var.param).collect();
+    let kept: Vec<usize> = (0..old_params.len()).filter(|&i| !merged_params.contains(&old_params[i])).collect();
+
+    // The new header takes the kept parameters and one per scaled counter
+    let mut params: Vec<(Option<String>, Type)> = kept
+        .iter()
+        .map(|&i| {
+            let data = func.dfg().value(old_params[i]);
+            (data.name().clone(), data.ty().clone())
+        })
+        .collect();
+    params.extend(scaled.iter().map(|_| (None, Type::get_i32())));
+    let name = func.dfg().bb(header).name().clone();
+    let new = func.dfg_mut().new_bb().basic_block_with_param_names(name, params);
+    func.layout_mut().bbs_mut().push_key_back(new).unwrap();
+    for inst in insts_of(func, header) {
+        func.layout_mut().bb_mut(header).insts_mut().remove(&inst);
+        func.layout_mut().bb_mut(new).insts_mut().push_key_back(inst).unwrap();
+    }
+    let new_params = func.dfg().bb(new).params().to_vec();
+
+    let mut map: HashMap<Value, Value> =
+        kept.iter().zip(&new_params).map(|(&i, &param)| (old_params[i], param)).collect();
+    for (var, kept_var) in merged.iter().rev() {
+        let offset = binary_before_end(func, preheader, BinaryOp::Sub, var.init, kept_var.init);
+        let value = func.dfg_mut().new_value().binary(BinaryOp::Add, map[&kept_var.param], offset);
+        func.layout_mut().bb_mut(new).insts_mut().push_key_front(value).unwrap();
+        map.insert(var.param, value);
+    }
+    let mut scaled_inits = Vec::new();
+    for ((var, factor, insts), &param) in scaled.iter().zip(&new_params[kept.len()..]) {
+        let factor_value = func.dfg_mut().new_value().integer(*factor);
+        scaled_inits.push(binary_before_end(func, preheader, BinaryOp::Mul, var.init, factor_value));
+        map.extend(insts.iter().map(|&inst| (inst, param)));
+    }
+
+    // Point every use at the new values, then drop the multiplications
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    for &bb in &bbs {
+        for inst in insts_of(func, bb) {
+            let mut data = func.dfg().value(inst).clone();
+            if data.kind().value_uses().any(|used| map.contains_key(&used)) {
+                remap_operands(data.kind_mut(), &map);
+                func.dfg_mut().replace_value_with(inst).raw(data);
+            }
+        }
+    }
+    let mut dead: Vec<Value> = scaled.iter().flat_map(|(_, _, insts)| insts.iter().copied()).collect();
+
+    // Pass the new parameters their arguments, dropping those of the merged
+    // counters, whose steps may be left unused
+    let mut scaled_updates = Vec::new();
+    for ((var, factor, _), &param) in scaled.iter().zip(&new_params[kept.len()..]) {
+        let step = func.dfg_mut().new_value().integer(var.step.wrapping_mul(*factor));
+        scaled_updates.push(binary_before_end(func, latch, BinaryOp::Add, param, step));
+    }
+    for (bb, extra) in [(preheader, scaled_inits), (latch, scaled_updates)] {
+        let old_args = jump_args(func, bb);
+        let mut args: Vec<Value> = kept.iter().map(|&i| old_args[i]).collect();
+        args.extend(extra);
+        let &term = func.layout().bbs().node(&bb).unwrap().insts().back_key().unwrap();
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&term);
+        func.dfg_mut().remove_value(term);
+        let jump = func.dfg_mut().new_value().jump_with_args(new, args);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(jump).unwrap();
+        dead.extend(old_args.into_iter().filter(|arg| !arg.is_global()));
+    }
+
+    take_place(func, header, new);
+    func.layout_mut().bbs_mut().remove(&header);
+    func.dfg_mut().remove_bb(header);
+    remove_dead(func, dead);
+}
+
+/// Put `new` where `old` is in the layout, keeping the order of the others
+fn take_place(func: &mut FunctionData, old: BasicBlock, new: BasicBlock) {
+    let order: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    let position = order.iter().position(|&bb| bb == old).unwrap();
+    let after = order[position + 1..].iter().copied().filter(|&bb| bb != new);
+    for bb in std::iter::once(new).chain(after).collect::<Vec<_>>() {
+        let insts = insts_of(func, bb);
+        func.layout_mut().bbs_mut().remove(&bb);
+        func.layout_mut().bbs_mut().push_key_back(bb).unwrap();
+        for inst in insts {
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(inst).unwrap();
+        }
+    }
+}
+
+/// Delete the pure instructions among `candidates` that have no users, and
+/// in turn those only they used
+fn remove_dead(func: &mut FunctionData, mut candidates: Vec<Value>) {
+    let mut removed = HashSet::new();
+    while let Some(value) = candidates.pop() {
+        if removed.contains(&value) {
+            continue;
+        }
+        let Some(bb) = func.layout().parent_bb(value) else {
+            continue;
+        };
+        let data = func.dfg().value(value);
+        if !matches!(data.kind(), ValueKind::Binary(_)) || !data.used_by().is_empty() {
+            continue;
+        }
+        candidates.extend(data.kind().value_uses().filter(|used| !used.is_global()));
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&value);
+        func.dfg_mut().remove_value(value);
+        removed.insert(value);
+    }
+}
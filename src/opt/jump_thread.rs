@@ -0,0 +1,166 @@
+//! Jump threading: skip a conditional branch whose outcome the jumping block
+//! already decides.
+//!
+//! A flag set right before control reaches a test of it, as in
+//!
+//! ```c
+//! int found = 0;
+//! while (i < n) { if (a[i] == x) { found = 1; break; } i = i + 1; }
+//! if (found) ...
+//! ```
+//!
+//! leaves blocks such as `store 1, @found; jump %test` where `%test` only
+//! computes the condition from variables and branches. Evaluating `%test`
+//! with the constants just stored gives the branch target, so the jump goes
+//! there directly:
+//!
+//! ```text
+//!   %brk:   store 1, @found; jump %test      %brk:   store 1, @found; jump %then
+//!   %test:  %0 = load @found           =>    %test:  ...
+//!           %1 = ne %0, 0
+//!           br %1, %then, %else
+//! ```
+//!
+//! Only local `int` variables that are never accessed through a pointer are
+//! tracked, so no call or other store can change them between the store and
+//! the test. A test block nothing jumps to any more is deleted.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, FunctionData, Type, Value, ValueKind};
+
+use super::const_fold::fold;
+
+pub fn run(func: &mut FunctionData) {
+    let Some(entry) = func.layout().entry_bb() else {
+        return;
+    };
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        // Follow the chain of decided tests, stopping if it runs in a circle
+        let mut visited = HashSet::new();
+        while let Some((jump, target)) = threaded_jump(func, bb) {
+            if !visited.insert(target) {
+                break;
+            }
+            func.layout_mut().bb_mut(bb).insts_mut().remove(&jump);
+            func.dfg_mut().remove_value(jump);
+            let jump = func.dfg_mut().new_value().jump(target);
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(jump).unwrap();
+        }
+    }
+
+    let unused: Vec<BasicBlock> = func
+        .layout()
+        .bbs()
+        .keys()
+        .copied()
+        .filter(|&bb| bb != entry && func.dfg().bb(bb).used_by().is_empty() && test(func, bb).is_some())
+        .collect();
+    for bb in unused {
+        let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+        for &inst in &insts {
+            func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        }
+        for inst in insts.into_iter().rev() {
+            func.dfg_mut().remove_value(inst);
+        }
+        func.layout_mut().bbs_mut().remove(&bb);
+        func.dfg_mut().remove_bb(bb);
+    }
+}
+
+/// Whether `var` is a local `int` only ever loaded from or stored to directly
+fn is_tracked(func: &FunctionData, var: Value) -> bool {
+    if var.is_global() {
+        return false;
+    }
+    let data = func.dfg().value(var);
+    matches!(data.kind(), ValueKind::Alloc(_))
+        && data.ty() == &Type::get_pointer(Type::get_i32())
+        && data.used_by().iter().all(|&user| match func.dfg().value(user).kind() {
+            ValueKind::Load(_) => true,
+            ValueKind::Store(store) => store.dest() == var && store.value() != var,
+            _ => false,
+        })
+}
+
+/// The instructions of `bb` before its branch, if it only computes the
+/// branch condition from tracked variables, with values used nowhere else
+fn test(func: &FunctionData, bb: BasicBlock) -> Option<Vec<Value>> {
+    if !func.dfg().bb(bb).params().is_empty() {
+        return None;
+    }
+    let mut insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+    let branch = insts.pop()?;
+    let ValueKind::Branch(branch_data) = func.dfg().value(branch).kind() else {
+        return None;
+    };
+    if !branch_data.true_args().is_empty() || !branch_data.false_args().is_empty() {
+        return None;
+    }
+    let values: HashSet<Value> = insts.iter().copied().collect();
+    let pure = insts.iter().all(|&inst| {
+        let data = func.dfg().value(inst);
+        let computes = match data.kind() {
+            ValueKind::Load(load) => is_tracked(func, load.src()),
+            ValueKind::Binary(_) => true,
+            _ => false,
+        };
+        computes && data.used_by().iter().all(|user| values.contains(user) || *user == branch)
+    });
+    pure.then_some(insts)
+}
+
+/// The plain `jump` ending `bb` and the block it should jump to instead, if
+/// it jumps to a test decided by the constants `bb` stores
+fn threaded_jump(func: &FunctionData, bb: BasicBlock) -> Option<(Value, BasicBlock)> {
+    let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+    let (&jump, rest) = insts.split_last()?;
+    let ValueKind::Jump(jump_data) = func.dfg().value(jump).kind() else {
+        return None;
+    };
+    let target = jump_data.target();
+    if !jump_data.args().is_empty() || target == bb {
+        return None;
+    }
+    let test_insts = test(func, target)?;
+
+    // The constant last stored to each variable in `bb`
+    let mut stored: HashMap<Value, i32> = HashMap::new();
+    let mut seen = HashSet::new();
+    for &inst in rest.iter().rev() {
+        let ValueKind::Store(store) = func.dfg().value(inst).kind() else {
+            continue;
+        };
+        if !seen.insert(store.dest()) {
+            continue;
+        }
+        if let ValueKind::Integer(int) = func.dfg().value(store.value()).kind() {
+            stored.insert(store.dest(), int.value());
+        }
+    }
+
+    let mut known: HashMap<Value, i32> = HashMap::new();
+    let value_of = |known: &HashMap<Value, i32>, value: Value| match func.dfg().value(value).kind() {
+        ValueKind::Integer(int) => Some(int.value()),
+        _ => known.get(&value).copied(),
+    };
+    for inst in test_insts {
+        let value = match func.dfg().value(inst).kind() {
+            ValueKind::Load(load) => *stored.get(&load.src())?,
+            ValueKind::Binary(binary) => {
+                fold(binary.op(), value_of(&known, binary.lhs())?, value_of(&known, binary.rhs())?)?
+            }
+            _ => unreachable!("test blocks only load and compute"),
+        };
+        known.insert(inst, value);
+    }
+    let &branch = func.layout().bbs().node(&target).unwrap().insts().back_key().unwrap();
+    let ValueKind::Branch(branch) = func.dfg().value(branch).kind() else {
+        unreachable!("test blocks end in a branch");
+    };
+    let taken = if value_of(&known, branch.cond())? != 0 { branch.true_bb() } else { branch.false_bb() };
+    Some((jump, taken))
+}
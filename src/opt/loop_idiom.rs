@@ -0,0 +1,288 @@
+//! Unrolling of fill and copy loops.
+//!
+//! Loops that store one array element per iteration, such as
+//! `while (i < n) { a[i] = 0; i = i + 1; }` or the `for` equivalent copying
+//! `b[i]` into `a[i]`, are emitted as
+//!
+//! ```text
+//!   %cond:  %0 = load @i; %1 = lt %0, N; br %1, %body, %end
+//!   %body:  ...; store %v, %p                       (%p points into an array)
+//!           %2 = load @i; %3 = add %2, 1; store %3, @i; jump %cond
+//! ```
+//!
+//! so each element costs a reload of the counter, its increment and store, and
+//! a compare and branch. The runtime library has no `memset`/`memcpy` to call
+//! instead, so these loops are given an unrolled copy that runs first and
+//! handles four elements per iteration while at least four remain:
+//!
+//! ```text
+//!   %fill_cond:  %4 = load @i; %5 = lt %4, N - 3; br %5, %fill_body, %cond
+//!   %fill_body:  %6 = load @i; <body for %6>; <body for %6 + 1>; ...
+//!                %7 = add %6, 4; store %7, @i; jump %fill_cond
+//! ```
+//!
+//! The original loop then finishes the last few elements. With a variable
+//! bound `n`, `n - 3` could wrap around, so the unrolled copy instead checks
+//! `i < n` and `n - i > 3`: the difference of the two only wraps (to a
+//! negative value) when there are more than `i32::MAX` elements left.
+
+use std::collections::HashMap;
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Value, ValueKind};
+
+use super::remap_operands;
+
+/// Number of iterations run by each iteration of the unrolled copy
+const UNROLL: i32 = 4;
+
+/// Loop bodies with more instructions than this are not unrolled, to bound
+/// code growth
+const MAX_BODY_INSTS: usize = 16;
+
+/// A loop matching the pattern in the module documentation
+struct FillLoop {
+    header: BasicBlock,
+    /// One iteration of the body, without the counter increment and the jump
+    insts: Vec<Value>,
+    counter: Value,
+    bound: Bound,
+    /// `jump %cond` instructions entering the loop, and their blocks
+    entries: Vec<(BasicBlock, Value)>,
+}
+
+/// The loop-invariant upper bound of the counter
+enum Bound {
+    Const(i32),
+    /// Loaded from a variable the body cannot store to
+    Var(Value),
+}
+
+pub fn run(func: &mut FunctionData) {
+    // The original loops are kept, so find them all before unrolling any
+    let loops: Vec<FillLoop> = func.layout().bbs().keys().filter_map(|&bb| fill_loop(func, bb)).collect();
+    for fill_loop in loops {
+        unroll(func, fill_loop);
+    }
+}
+
+fn block_insts(func: &FunctionData, bb: BasicBlock) -> Vec<Value> {
+    func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect()
+}
+
+/// Whether `value` is a variable: a global or a local `alloc`
+fn is_variable(func: &FunctionData, value: Value) -> bool {
+    value.is_global() || matches!(func.dfg().value(value).kind(), ValueKind::Alloc(_))
+}
+
+/// Whether `value` is a load of `counter`
+fn is_counter_load(func: &FunctionData, value: Value, counter: Value) -> bool {
+    matches!(func.dfg().value(value).kind(), ValueKind::Load(load) if load.src() == counter)
+}
+
+/// Match the loop whose condition block is `header`
+fn fill_loop(func: &FunctionData, header: BasicBlock) -> Option<FillLoop> {
+    if !func.dfg().bb(header).params().is_empty() {
+        return None;
+    }
+
+    // %cond: loads; lt <load counter>, <bound>; br
+    let header_insts = block_insts(func, header);
+    let (&branch, rest) = header_insts.split_last()?;
+    let (&cmp, loads) = rest.split_last()?;
+    let ValueKind::Branch(branch) = func.dfg().value(branch).kind() else {
+        return None;
+    };
+    let ValueKind::Binary(cmp_data) = func.dfg().value(cmp).kind() else {
+        return None;
+    };
+    if branch.cond() != cmp || cmp_data.op() != BinaryOp::Lt {
+        return None;
+    }
+    let ValueKind::Load(counter_load) = func.dfg().value(cmp_data.lhs()).kind() else {
+        return None;
+    };
+    let counter = counter_load.src();
+    if !is_variable(func, counter) {
+        return None;
+    }
+    let bound = match func.dfg().value(cmp_data.rhs()).kind() {
+        ValueKind::Integer(int) => Bound::Const(int.value().checked_sub(UNROLL - 1)?),
+        ValueKind::Load(load) if load.src() != counter && is_variable(func, load.src()) => Bound::Var(load.src()),
+        _ => return None,
+    };
+    if !loads.iter().all(|&load| load == cmp_data.lhs() || load == cmp_data.rhs()) {
+        return None;
+    }
+
+    // The body, possibly followed by a separate step block (`for` loops),
+    // each only entered from the block before it
+    let mut body = vec![branch.true_bb()];
+    let mut insts = Vec::new();
+    let latch = loop {
+        let bb = *body.last().unwrap();
+        if bb == header || body[..body.len() - 1].contains(&bb) || func.dfg().bb(bb).used_by().len() != 1 {
+            return None;
+        }
+        let mut bb_insts = block_insts(func, bb);
+        let jump = bb_insts.pop()?;
+        let ValueKind::Jump(jump_data) = func.dfg().value(jump).kind() else {
+            return None;
+        };
+        insts.extend(bb_insts);
+        if jump_data.target() == header {
+            break jump;
+        }
+        if body.len() == 2 {
+            return None;
+        }
+        body.push(jump_data.target());
+    };
+
+    // ...; %a = load counter; %b = add %a, 1; store %b, counter
+    let [.., load, add, store] = insts[..] else {
+        return None;
+    };
+    let ValueKind::Store(store_data) = func.dfg().value(store).kind() else {
+        return None;
+    };
+    let ValueKind::Binary(add_data) = func.dfg().value(add).kind() else {
+        return None;
+    };
+    let is_one = |value: Value| matches!(func.dfg().value(value).kind(), ValueKind::Integer(int) if int.value() == 1);
+    let increments = add_data.op() == BinaryOp::Add
+        && ((add_data.lhs() == load && is_one(add_data.rhs())) || (add_data.rhs() == load && is_one(add_data.lhs())));
+    if store_data.dest() != counter || store_data.value() != add || !increments || !is_counter_load(func, load, counter) {
+        return None;
+    }
+    insts.truncate(insts.len() - 3);
+    if insts.len() > MAX_BODY_INSTS || !is_fill_body(func, &insts, counter) {
+        return None;
+    }
+
+    // Every other way into the header must be a plain jump from outside
+    let users = func.dfg().bb(header).used_by();
+    let entries: Vec<(BasicBlock, Value)> = func.layout().bbs().iter()
+        .filter_map(|(&bb, node)| {
+            let &last = node.insts().back_key()?;
+            (last != latch && users.contains(&last)).then_some((bb, last))
+        })
+        .collect();
+    let plain_jumps = entries.iter().all(|&(_, jump)| {
+        matches!(func.dfg().value(jump).kind(), ValueKind::Jump(jump) if jump.args().is_empty())
+    });
+    if entries.is_empty() || entries.len() + 1 != users.len() || !plain_jumps {
+        return None;
+    }
+
+    Some(FillLoop { header, insts, counter, bound, entries })
+}
+
+/// Whether `insts` only compute one element address and value and store
+/// the value there
+fn is_fill_body(func: &FunctionData, insts: &[Value], counter: Value) -> bool {
+    let mut stores = 0;
+    for &inst in insts {
+        match func.dfg().value(inst).kind() {
+            ValueKind::Load(_) | ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_) | ValueKind::Binary(_) => {}
+            ValueKind::Store(store) => {
+                let dest = store.dest();
+                if dest == counter
+                    || dest.is_global()
+                    || !matches!(func.dfg().value(dest).kind(), ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_))
+                {
+                    return false;
+                }
+                stores += 1;
+            }
+            _ => return false,
+        }
+    }
+    stores == 1
+}
+
+/// Create a new basic block at the end of the layout
+fn new_bb(func: &mut FunctionData, name: &str) -> BasicBlock {
+    let bb = func.dfg_mut().new_bb().basic_block(Some(name.into()));
+    func.layout_mut().bbs_mut().push_key_back(bb).unwrap();
+    bb
+}
+
+fn unroll(func: &mut FunctionData, fill_loop: FillLoop) {
+    let FillLoop { header, insts, counter, bound, entries } = fill_loop;
+    let cond_bb = new_bb(func, "%fill_cond");
+    let body_bb = new_bb(func, "%fill_body");
+    let push = |func: &mut FunctionData, bb: BasicBlock, inst: Value| {
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(inst).unwrap();
+        inst
+    };
+
+    // %fill_cond: counter < bound - (UNROLL - 1), or for a variable bound
+    // counter < bound && bound - counter > UNROLL - 1
+    let index = func.dfg_mut().new_value().load(counter);
+    push(func, cond_bb, index);
+    let cmp = match bound {
+        Bound::Const(limit) => {
+            let limit = func.dfg_mut().new_value().integer(limit);
+            let cmp = func.dfg_mut().new_value().binary(BinaryOp::Lt, index, limit);
+            push(func, cond_bb, cmp)
+        }
+        Bound::Var(var) => {
+            let bound = func.dfg_mut().new_value().load(var);
+            push(func, cond_bb, bound);
+            let below = func.dfg_mut().new_value().binary(BinaryOp::Lt, index, bound);
+            push(func, cond_bb, below);
+            let left = func.dfg_mut().new_value().binary(BinaryOp::Sub, bound, index);
+            push(func, cond_bb, left);
+            let rest = func.dfg_mut().new_value().integer(UNROLL - 1);
+            let enough = func.dfg_mut().new_value().binary(BinaryOp::Gt, left, rest);
+            push(func, cond_bb, enough);
+            let cmp = func.dfg_mut().new_value().binary(BinaryOp::And, below, enough);
+            push(func, cond_bb, cmp)
+        }
+    };
+    let branch = func.dfg_mut().new_value().branch(cmp, body_bb, header);
+    push(func, cond_bb, branch);
+
+    // %fill_body: the body once per element, with the counter loads replaced
+    // by its value for that element
+    let base = func.dfg_mut().new_value().load(counter);
+    push(func, body_bb, base);
+    for k in 0..UNROLL {
+        let index = if k == 0 {
+            base
+        } else {
+            let offset = func.dfg_mut().new_value().integer(k);
+            let index = func.dfg_mut().new_value().binary(BinaryOp::Add, base, offset);
+            push(func, body_bb, index)
+        };
+        let mut map = HashMap::new();
+        for &inst in &insts {
+            if is_counter_load(func, inst, counter) {
+                map.insert(inst, index);
+                continue;
+            }
+            let mut data = func.dfg().value(inst).clone();
+            remap_operands(data.kind_mut(), &map);
+            let copy = func.dfg_mut().new_value().raw(data);
+            func.dfg_mut().set_value_name(copy, None);
+            push(func, body_bb, copy);
+            map.insert(inst, copy);
+        }
+    }
+    let step = func.dfg_mut().new_value().integer(UNROLL);
+    let next = func.dfg_mut().new_value().binary(BinaryOp::Add, base, step);
+    push(func, body_bb, next);
+    let store = func.dfg_mut().new_value().store(next, counter);
+    push(func, body_bb, store);
+    let jump = func.dfg_mut().new_value().jump(cond_bb);
+    push(func, body_bb, jump);
+
+    // Enter the loop through the unrolled copy
+    for (bb, jump) in entries {
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&jump);
+        func.dfg_mut().remove_value(jump);
+        let jump = func.dfg_mut().new_value().jump(cond_bb);
+        push(func, bb, jump);
+    }
+}
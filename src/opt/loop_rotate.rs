@@ -0,0 +1,94 @@
+//! Loop rotation: turn `while` loops into a guarded `do-while`.
+//!
+//! The emitter lowers `while (c) body` to
+//!
+//! ```text
+//!   pre:    ...; jump %cond
+//!   %cond:  <c>; br c, %body, %end
+//!   %body:  ...; jump %cond
+//! ```
+//!
+//! so every iteration runs a jump back to the header followed by its branch.
+//! Rotation copies the header into each latch (a block jumping back to it),
+//! replacing the `jump %cond` with the condition check itself. The original
+//! header is then only executed once, as the guard on loop entry.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, FunctionData, Value, ValueKind};
+
+use super::remap_operands;
+
+/// Headers larger than this are not duplicated, to bound code growth
+const MAX_HEADER_INSTS: usize = 16;
+
+pub fn run(func: &mut FunctionData) {
+    rotate_loops(func);
+}
+
+fn rotate_loops(func: &mut FunctionData) {
+    let order: HashMap<BasicBlock, usize> = func.layout().bbs().keys()
+        .enumerate()
+        .map(|(i, &bb)| (bb, i))
+        .collect();
+
+    // Back edges: `jump H` from a block laid out after its target H
+    let latches: Vec<(BasicBlock, Value, BasicBlock)> = func.layout().bbs().iter()
+        .filter_map(|(&bb, node)| {
+            let &jump = node.insts().back_key()?;
+            match func.dfg().value(jump).kind() {
+                ValueKind::Jump(j) if order[&j.target()] <= order[&bb] => {
+                    Some((bb, jump, j.target()))
+                }
+                _ => None,
+            }
+        })
+        .collect();
+
+    for (latch, jump, header) in latches {
+        if latch != header && is_rotatable(func, header) {
+            copy_header_into(func, header, latch, jump);
+        }
+    }
+}
+
+/// A header can be duplicated if it is a small condition check whose values
+/// are not used outside of it
+fn is_rotatable(func: &FunctionData, header: BasicBlock) -> bool {
+    if !func.dfg().bb(header).params().is_empty() {
+        return false;
+    }
+    let insts: HashSet<Value> = func.layout().bbs().node(&header).unwrap().insts().keys().copied().collect();
+    if insts.len() > MAX_HEADER_INSTS {
+        return false;
+    }
+    let Some(&last) = func.layout().bbs().node(&header).unwrap().insts().back_key() else {
+        return false;
+    };
+    if !matches!(func.dfg().value(last).kind(), ValueKind::Branch(_)) {
+        return false;
+    }
+    insts.iter().all(|&inst| {
+        let data = func.dfg().value(inst);
+        !matches!(data.kind(), ValueKind::Alloc(_))
+            && data.used_by().iter().all(|user| insts.contains(user))
+    })
+}
+
+/// Replace the `jump header` at the end of `latch` with a copy of `header`
+fn copy_header_into(func: &mut FunctionData, header: BasicBlock, latch: BasicBlock, jump: Value) {
+    func.layout_mut().bb_mut(latch).insts_mut().remove(&jump);
+    func.dfg_mut().remove_value(jump);
+
+    let header_insts: Vec<Value> = func.layout().bbs().node(&header).unwrap().insts().keys().copied().collect();
+    let mut map = HashMap::new();
+    for inst in header_insts {
+        let mut data = func.dfg().value(inst).clone();
+        remap_operands(data.kind_mut(), &map);
+        let copy = func.dfg_mut().new_value().raw(data);
+        func.dfg_mut().set_value_name(copy, None);
+        func.layout_mut().bb_mut(latch).insts_mut().push_key_back(copy).unwrap();
+        map.insert(inst, copy);
+    }
+}
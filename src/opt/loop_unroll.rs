@@ -0,0 +1,339 @@
+//! Loop unrolling of straight-line loop bodies.
+//!
+//! A loop whose body is a chain of blocks without branches of its own, as
+//! emitted for `while (c) { a; b; }`, is unrolled in one of two ways:
+//!
+//! - **Fully**, when the number of iterations is known: the condition is
+//!   `i op N` for a local counter `i` that starts at a constant, is only
+//!   changed by a constant step at the end of the body, and `N` is a
+//!   constant. The loop is replaced by that many copies of the body.
+//! - **Partially** by a factor `F`, otherwise: each iteration runs the body
+//!   `F` times, checking the condition between the copies.
+//!
+//! ```text
+//!   %cond:  <c>; br %c, %body, %end         %cond:          <c>; br %c, %body, %end
+//!   %body:  <body>; jump %cond        =>    %body:          <body>; jump %unroll_cond
+//!                                           %unroll_cond:   <c>; br %c, %unroll_body, %end
+//!                                           %unroll_body:   <body>; jump %cond
+//! ```
+//!
+//! Both are bounded by a threshold on the number of instructions the copies
+//! add. Loads and stores are copied as they are, so the counter still lives
+//! in memory; later passes clean up after the copies.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Program, Value, ValueKind};
+
+use super::{Pass, remap_operands, stats};
+
+/// The pass, with its limits (see `PassOptions`)
+pub struct LoopUnroll {
+    /// Largest number of instructions the copies of a body may add
+    pub threshold: usize,
+    /// Number of copies of the body in each iteration of a partially
+    /// unrolled loop; 0 or 1 disables partial unrolling
+    pub factor: usize,
+}
+
+impl Pass for LoopUnroll {
+    fn name(&self) -> &'static str {
+        "loop-unroll"
+    }
+
+    fn run(&mut self, program: &mut Program, excluded: &HashSet<String>) {
+        for func in program.funcs_mut().values_mut() {
+            if !excluded.contains(&func.name()[1..]) {
+                self.unroll_loops(func);
+            }
+        }
+    }
+}
+
+/// A loop with a straight-line body
+struct Loop {
+    header: BasicBlock,
+    /// The blocks of the body, in order; the last one jumps to the header
+    body: Vec<BasicBlock>,
+    /// The instructions of the body, without the jumps between its blocks
+    insts: Vec<Value>,
+    exit: BasicBlock,
+    /// The plain `jump` to the header from outside the loop, if it is the
+    /// only way in, with its block
+    entry: Option<(BasicBlock, Value)>,
+}
+
+impl LoopUnroll {
+    fn unroll_loops(&self, func: &mut FunctionData) {
+        let loops: Vec<Loop> = func.layout().bbs().keys().filter_map(|&bb| find_loop(func, bb)).collect();
+        for found in loops {
+            if found.insts.is_empty() || found.insts.len() > self.threshold {
+                continue;
+            }
+            let max_trips = self.threshold / found.insts.len();
+            match trip_count(func, &found, max_trips) {
+                Some(trips) => {
+                    unroll_fully(func, found, trips);
+                    stats::bump("loops unrolled fully", 1);
+                }
+                None if self.factor > 1 && found.insts.len() * (self.factor - 1) <= self.threshold => {
+                    unroll_partially(func, &found, self.factor);
+                    stats::bump("loops unrolled partially", 1);
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+fn block_insts(func: &FunctionData, bb: BasicBlock) -> Vec<Value> {
+    func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect()
+}
+
+/// Match the loop whose condition block is `header`
+fn find_loop(func: &FunctionData, header: BasicBlock) -> Option<Loop> {
+    if !func.dfg().bb(header).params().is_empty() {
+        return None;
+    }
+    // The header only computes the condition
+    let header_insts = block_insts(func, header);
+    let (&branch, rest) = header_insts.split_last()?;
+    let ValueKind::Branch(branch) = func.dfg().value(branch).kind() else {
+        return None;
+    };
+    let computes = |&inst: &Value| matches!(func.dfg().value(inst).kind(), ValueKind::Load(_) | ValueKind::Binary(_));
+    if !rest.iter().all(computes) {
+        return None;
+    }
+
+    let (mut body, mut insts) = (Vec::new(), Vec::new());
+    let mut bb = branch.true_bb();
+    let latch = loop {
+        if bb == header || body.contains(&bb) || func.dfg().bb(bb).used_by().len() != 1 {
+            return None;
+        }
+        body.push(bb);
+        let mut bb_insts = block_insts(func, bb);
+        let jump = bb_insts.pop()?;
+        let ValueKind::Jump(jump_data) = func.dfg().value(jump).kind() else {
+            return None;
+        };
+        if !jump_data.args().is_empty() {
+            return None;
+        }
+        insts.extend(bb_insts);
+        if jump_data.target() == header {
+            break jump;
+        }
+        bb = jump_data.target();
+    };
+    let exit = branch.false_bb();
+    if exit == header || body.contains(&exit) {
+        return None;
+    }
+
+    // The values of the loop are not used after it, so copies can stand in
+    let values: HashSet<Value> = header_insts.iter().chain(&insts).copied().collect();
+    let contained = values
+        .iter()
+        .all(|&value| func.dfg().value(value).used_by().iter().all(|user| values.contains(user)));
+    if !contained {
+        return None;
+    }
+
+    let users = func.dfg().bb(header).used_by();
+    let entry = match users.len() {
+        2 => func.layout().bbs().iter().find_map(|(&bb, node)| {
+            let &last = node.insts().back_key()?;
+            let plain = matches!(func.dfg().value(last).kind(), ValueKind::Jump(jump) if jump.args().is_empty());
+            (last != latch && plain && users.contains(&last)).then_some((bb, last))
+        }),
+        _ => None,
+    };
+    Some(Loop { header, body, insts, exit, entry })
+}
+
+fn integer(func: &FunctionData, value: Value) -> Option<i32> {
+    match func.dfg().value(value).kind() {
+        ValueKind::Integer(int) => Some(int.value()),
+        _ => None,
+    }
+}
+
+/// The number of iterations of a loop counting from a constant to a
+/// constant, if it is at most `max_trips`
+fn trip_count(func: &FunctionData, found: &Loop, max_trips: usize) -> Option<usize> {
+    // %cond: %i = load @i; %c = op %i, N; br %c, ...
+    let header_insts = block_insts(func, found.header);
+    let [load, cmp, _] = header_insts[..] else {
+        return None;
+    };
+    let ValueKind::Binary(cmp) = func.dfg().value(cmp).kind() else {
+        return None;
+    };
+    let ValueKind::Load(load_data) = func.dfg().value(load).kind() else {
+        return None;
+    };
+    let (counter, bound) = (load_data.src(), integer(func, cmp.rhs())?);
+    if cmp.lhs() != load || counter.is_global() || !matches!(func.dfg().value(counter).kind(), ValueKind::Alloc(_)) {
+        return None;
+    }
+
+    // The counter is a scalar only accessed directly, so nothing but its
+    // stores changes it, and the only one in the loop ends the body:
+    // %a = load @i; %b = add %a, step; store %b, @i
+    let direct = func.dfg().value(counter).used_by().iter().all(|&user| match func.dfg().value(user).kind() {
+        ValueKind::Load(_) => true,
+        ValueKind::Store(store) => store.dest() == counter && store.value() != counter,
+        _ => false,
+    });
+    let [.., step_load, add, store] = found.insts[..] else {
+        return None;
+    };
+    let stores_counter =
+        |inst: &Value| matches!(func.dfg().value(*inst).kind(), ValueKind::Store(store) if store.dest() == counter);
+    if !direct || found.insts.iter().filter(|inst| stores_counter(inst)).count() != 1 || !stores_counter(&store) {
+        return None;
+    }
+    let (ValueKind::Load(step_load_data), ValueKind::Binary(add_data), ValueKind::Store(store_data)) = (
+        func.dfg().value(step_load).kind(),
+        func.dfg().value(add).kind(),
+        func.dfg().value(store).kind(),
+    ) else {
+        return None;
+    };
+    if step_load_data.src() != counter || store_data.value() != add || add_data.lhs() != step_load {
+        return None;
+    }
+    let step = match add_data.op() {
+        BinaryOp::Add => integer(func, add_data.rhs())?,
+        BinaryOp::Sub => integer(func, add_data.rhs())?.checked_neg()?,
+        _ => return None,
+    };
+
+    // The counter starts at the constant stored last before the loop
+    let (entry_bb, _) = found.entry?;
+    let start = block_insts(func, entry_bb).into_iter().rev().find_map(|inst| match func.dfg().value(inst).kind() {
+        ValueKind::Store(store) if store.dest() == counter => Some(integer(func, store.value())),
+        _ => None,
+    })??;
+
+    let holds = |i: i64| {
+        let bound = bound as i64;
+        match cmp.op() {
+            BinaryOp::Lt => Some(i < bound),
+            BinaryOp::Le => Some(i <= bound),
+            BinaryOp::Gt => Some(i > bound),
+            BinaryOp::Ge => Some(i >= bound),
+            BinaryOp::NotEq => Some(i != bound),
+            BinaryOp::Eq => Some(i == bound),
+            _ => None,
+        }
+    };
+    let (mut i, mut trips) = (start as i64, 0);
+    while holds(i)? {
+        trips += 1;
+        i += step as i64;
+        // Past the limit, or wrapping around, which is not worth modelling
+        if trips > max_trips || i32::try_from(i).is_err() {
+            return None;
+        }
+    }
+    Some(trips)
+}
+
+/// Create a new basic block at the end of the layout
+fn new_bb(func: &mut FunctionData, name: &str) -> BasicBlock {
+    let bb = func.dfg_mut().new_bb().basic_block(Some(name.into()));
+    func.layout_mut().bbs_mut().push_key_back(bb).unwrap();
+    bb
+}
+
+/// Append a copy of `insts` to `bb`
+fn copy_insts(func: &mut FunctionData, insts: &[Value], bb: BasicBlock) {
+    let mut map = HashMap::new();
+    for &inst in insts {
+        let mut data = func.dfg().value(inst).clone();
+        remap_operands(data.kind_mut(), &map);
+        let copy = func.dfg_mut().new_value().raw(data);
+        func.dfg_mut().set_value_name(copy, None);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(copy).unwrap();
+        map.insert(inst, copy);
+    }
+}
+
+fn push_jump(func: &mut FunctionData, bb: BasicBlock, target: BasicBlock) {
+    let jump = func.dfg_mut().new_value().jump(target);
+    func.layout_mut().bb_mut(bb).insts_mut().push_key_back(jump).unwrap();
+}
+
+/// Replace the loop by `trips` copies of its body, and delete it
+fn unroll_fully(func: &mut FunctionData, found: Loop, trips: usize) {
+    let Loop { header, body, insts, exit, entry } = found;
+    let (entry_bb, entry_jump) = entry.unwrap();
+    let unrolled = new_bb(func, "%unroll");
+    for _ in 0..trips {
+        copy_insts(func, &insts, unrolled);
+    }
+    push_jump(func, unrolled, exit);
+    func.layout_mut().bb_mut(entry_bb).insts_mut().remove(&entry_jump);
+    func.dfg_mut().remove_value(entry_jump);
+    push_jump(func, entry_bb, unrolled);
+
+    // Nothing outside the loop uses its values, and within each block the
+    // users come after the values they use
+    let blocks: Vec<BasicBlock> = std::iter::once(header).chain(body).collect();
+    let values: Vec<Value> = blocks.iter().flat_map(|&bb| block_insts(func, bb)).collect();
+    for &bb in &blocks {
+        for inst in block_insts(func, bb) {
+            func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        }
+    }
+    for value in values.into_iter().rev() {
+        func.dfg_mut().remove_value(value);
+    }
+    for bb in blocks {
+        func.layout_mut().bbs_mut().remove(&bb);
+        func.dfg_mut().remove_bb(bb);
+    }
+}
+
+/// Make each iteration of the loop run the body `factor` times, checking the
+/// condition before each extra copy
+fn unroll_partially(func: &mut FunctionData, found: &Loop, factor: usize) {
+    let header_insts = block_insts(func, found.header);
+    let (&branch, cond_insts) = header_insts.split_last().unwrap();
+    let ValueKind::Branch(branch) = func.dfg().value(branch).kind() else {
+        unreachable!("loop header without a branch");
+    };
+    let cond = branch.cond();
+
+    let latch = *found.body.last().unwrap();
+    let &jump = func.layout().bbs().node(&latch).unwrap().insts().back_key().unwrap();
+    func.layout_mut().bb_mut(latch).insts_mut().remove(&jump);
+    func.dfg_mut().remove_value(jump);
+    let mut last = latch;
+    for _ in 1..factor {
+        let cond_bb = new_bb(func, "%unroll_cond");
+        let body_bb = new_bb(func, "%unroll_body");
+        push_jump(func, last, cond_bb);
+
+        let mut map = HashMap::new();
+        for &inst in cond_insts {
+            let mut data = func.dfg().value(inst).clone();
+            remap_operands(data.kind_mut(), &map);
+            let copy = func.dfg_mut().new_value().raw(data);
+            func.dfg_mut().set_value_name(copy, None);
+            func.layout_mut().bb_mut(cond_bb).insts_mut().push_key_back(copy).unwrap();
+            map.insert(inst, copy);
+        }
+        let cond = map.get(&cond).copied().unwrap_or(cond);
+        let branch = func.dfg_mut().new_value().branch(cond, body_bb, found.exit);
+        func.layout_mut().bb_mut(cond_bb).insts_mut().push_key_back(branch).unwrap();
+
+        copy_insts(func, &found.insts, body_bb);
+        last = body_bb;
+    }
+    push_jump(func, last, found.header);
+}
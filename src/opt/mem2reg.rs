@@ -0,0 +1,387 @@
+//! Promotion of scalar locals to SSA values ("mem2reg").
+//!
+//! A local `alloc i32` that is only ever loaded from and stored to is
+//! replaced by the values stored into it: each load becomes the value of the
+//! last store on the path to it. Where paths with different values meet, the
+//! block gets a parameter (Koopa's equivalent of a phi), placed with the
+//! iterated dominance frontier of the stores, and each predecessor passes its
+//! value as a jump argument:
+//!
+//! ```text
+//!   %entry:  @i = alloc i32          %entry:  jump %cond(0)
+//!            store 0, @i
+//!            jump %cond               %cond(%i: i32):
+//!   %cond:   %0 = load @i      =>             %c = lt %i, 10
+//!            %c = lt %0, 10                   br %c, %body, %end
+//!            br %c, %body, %end       %body:  %1 = add %i, 1
+//!   %body:   %1 = load @i                     jump %cond(%1)
+//!            %2 = add %1, 1
+//!            store %2, @i
+//!            jump %cond
+//! ```
+//!
+//! The backend copies jump arguments but not branch arguments, so a branch
+//! edge into a block with parameters goes through a new block holding the
+//! jump. A load that no store reaches reads `undef`. Parameters keep their
+//! `alloc`: the backend reads function arguments from the argument registers,
+//! which calls clobber. Unreachable blocks are deleted first, as they have no
+//! place in the dominator tree.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, FunctionData, Type, Value, ValueKind};
+
+use super::{remap_operands, stats};
+use crate::analysis::{Cfg, DomTree};
+
+pub fn run(func: &mut FunctionData) {
+    let Some(entry) = func.layout().entry_bb() else {
+        return;
+    };
+    remove_unreachable(func);
+    let allocs = promotable_allocs(func);
+    if allocs.is_empty() {
+        return;
+    }
+    let cfg = Cfg::new(func);
+    if !cfg.preds(entry).is_empty() {
+        // The entry block cannot take parameters
+        return;
+    }
+    let dom_tree = DomTree::new(&cfg).unwrap();
+    let phis = place_phis(func, &dom_tree, &allocs);
+    let order: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    let blocks = add_params(func, &phis);
+    let renamed = rename(func, &cfg, &dom_tree, &allocs, &phis, &blocks);
+    rebuild_terminators(func, &blocks, renamed.args);
+    remove_accesses(func, &allocs, renamed.replaced, &renamed.removed);
+    stats::bump("allocs promoted", allocs.len());
+    if func.dfg().value(renamed.undef).used_by().is_empty() {
+        func.dfg_mut().remove_value(renamed.undef);
+    }
+    for (&old, &new) in &blocks {
+        if old != new {
+            func.layout_mut().bbs_mut().remove(&old);
+            func.dfg_mut().remove_bb(old);
+        }
+    }
+    restore_order(func, &order, &blocks);
+}
+
+/// Put the new blocks where the blocks they replace were, with each edge
+/// block right before its target
+fn restore_order(func: &mut FunctionData, order: &[BasicBlock], blocks: &HashMap<BasicBlock, BasicBlock>) {
+    let cfg = Cfg::new(func);
+    let placed: HashSet<BasicBlock> = blocks.values().copied().collect();
+    let mut edges: HashMap<BasicBlock, Vec<BasicBlock>> = HashMap::new();
+    for &bb in func.layout().bbs().keys() {
+        if !placed.contains(&bb) {
+            let target = cfg.succs(bb)[0];
+            edges.entry(target).or_default().push(bb);
+        }
+    }
+    for &old in order {
+        let new = blocks[&old];
+        for bb in edges.remove(&new).into_iter().flatten().chain([new]) {
+            let insts = insts_of(func, bb);
+            func.layout_mut().bbs_mut().remove(&bb);
+            func.layout_mut().bbs_mut().push_key_back(bb).unwrap();
+            for inst in insts {
+                func.layout_mut().bb_mut(bb).insts_mut().push_key_back(inst).unwrap();
+            }
+        }
+    }
+}
+
+fn insts_of(func: &FunctionData, bb: BasicBlock) -> Vec<Value> {
+    func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect()
+}
+
+/// Delete the blocks that cannot be reached from the entry, such as the
+/// `%unreachable` blocks holding the code after a `return`
+fn remove_unreachable(func: &mut FunctionData) {
+    let cfg = Cfg::new(func);
+    let dead: Vec<BasicBlock> = cfg.blocks().iter().copied().filter(|&bb| !cfg.is_reachable(bb)).collect();
+    let mut values = Vec::new();
+    for &bb in &dead {
+        for inst in insts_of(func, bb) {
+            func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+            values.push(inst);
+        }
+    }
+    // A value can only be removed once nothing uses it, and the users of
+    // values in dead blocks are in dead blocks too
+    while !values.is_empty() {
+        let (unused, used): (Vec<Value>, Vec<Value>) =
+            values.into_iter().partition(|&value| func.dfg().value(value).used_by().is_empty());
+        assert!(!unused.is_empty(), "Values of unreachable blocks used from reachable ones");
+        for value in unused {
+            func.dfg_mut().remove_value(value);
+        }
+        values = used;
+    }
+    for bb in dead {
+        func.layout_mut().bbs_mut().remove(&bb);
+        func.dfg_mut().remove_bb(bb);
+    }
+}
+
+/// The `alloc i32`s only used as the address of loads and stores, except the
+/// slots of parameters
+fn promotable_allocs(func: &FunctionData) -> Vec<Value> {
+    let mut allocs = Vec::new();
+    for (_, node) in func.layout().bbs() {
+        for &inst in node.insts().keys() {
+            let data = func.dfg().value(inst);
+            if !matches!(data.kind(), ValueKind::Alloc(_)) || data.ty() != &Type::get_pointer(Type::get_i32()) {
+                continue;
+            }
+            let promotable = data.used_by().iter().all(|&user| match func.dfg().value(user).kind() {
+                ValueKind::Load(_) => true,
+                ValueKind::Store(store) => {
+                    store.dest() == inst
+                        && store.value() != inst
+                        && !matches!(func.dfg().value(store.value()).kind(), ValueKind::FuncArgRef(_))
+                }
+                _ => false,
+            });
+            if promotable {
+                allocs.push(inst);
+            }
+        }
+    }
+    allocs
+}
+
+/// The promoted allocs that get a parameter in each block, in parameter order
+fn place_phis(func: &FunctionData, dom_tree: &DomTree, allocs: &[Value]) -> HashMap<BasicBlock, Vec<Value>> {
+    let mut phis: HashMap<BasicBlock, Vec<Value>> = HashMap::new();
+    for &alloc in allocs {
+        let stores: HashSet<BasicBlock> = func
+            .dfg()
+            .value(alloc)
+            .used_by()
+            .iter()
+            .filter(|&&user| matches!(func.dfg().value(user).kind(), ValueKind::Store(_)))
+            .map(|&user| func.layout().parent_bb(user).unwrap())
+            .collect();
+        let mut placed = HashSet::new();
+        let mut work: Vec<BasicBlock> = stores.iter().copied().collect();
+        while let Some(bb) = work.pop() {
+            for frontier in dom_tree.frontier(bb) {
+                if placed.insert(frontier) {
+                    phis.entry(frontier).or_default().push(alloc);
+                    if !stores.contains(&frontier) {
+                        work.push(frontier);
+                    }
+                }
+            }
+        }
+    }
+    phis
+}
+
+/// Move the instructions of each block that gets parameters into a new block
+/// with them, as parameters cannot be added to an existing block. Returns the
+/// block holding the instructions of each block, which is itself if it gets
+/// no parameters.
+fn add_params(func: &mut FunctionData, phis: &HashMap<BasicBlock, Vec<Value>>) -> HashMap<BasicBlock, BasicBlock> {
+    let mut blocks = HashMap::new();
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        let Some(allocs) = phis.get(&bb) else {
+            blocks.insert(bb, bb);
+            continue;
+        };
+        let name = func.dfg().bb(bb).name().clone();
+        let params = allocs
+            .iter()
+            .map(|&alloc| {
+                let name = func.dfg().value(alloc).name().as_ref().map(|name| format!("%{}", &name[1..]));
+                (name, Type::get_i32())
+            })
+            .collect();
+        let new = func.dfg_mut().new_bb().basic_block_with_param_names(name, params);
+        func.layout_mut().bbs_mut().push_key_back(new).unwrap();
+        for inst in insts_of(func, bb) {
+            func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+            func.layout_mut().bb_mut(new).insts_mut().push_key_back(inst).unwrap();
+        }
+        blocks.insert(bb, new);
+    }
+    blocks
+}
+
+/// What renaming found: the loads and stores to delete, the value each load
+/// reads, and the arguments to pass along each edge into a block with
+/// parameters, as (block, successor, arguments)
+struct Renamed {
+    /// The value read where no store reaches
+    undef: Value,
+    removed: Vec<Value>,
+    replaced: HashMap<Value, Value>,
+    args: Vec<(BasicBlock, BasicBlock, Vec<Value>)>,
+}
+
+/// Walk the dominator tree, tracking the current value of each promoted
+/// alloc, which is what its loads read
+fn rename(
+    func: &mut FunctionData,
+    cfg: &Cfg,
+    dom_tree: &DomTree,
+    allocs: &[Value],
+    phis: &HashMap<BasicBlock, Vec<Value>>,
+    blocks: &HashMap<BasicBlock, BasicBlock>,
+) -> Renamed {
+    let promoted: HashSet<Value> = allocs.iter().copied().collect();
+    let undef = func.dfg_mut().new_value().undef(Type::get_i32());
+    let mut renamed = Renamed { undef, removed: Vec::new(), replaced: HashMap::new(), args: Vec::new() };
+    let mut stack = vec![(dom_tree.entry(), HashMap::new())];
+    while let Some((bb, mut current)) = stack.pop() {
+        let block = blocks[&bb];
+        let params = func.dfg().bb(block).params().to_vec();
+        for (&alloc, param) in phis.get(&bb).into_iter().flatten().zip(params) {
+            current.insert(alloc, param);
+        }
+        for inst in insts_of(func, block) {
+            match func.dfg().value(inst).kind() {
+                ValueKind::Load(load) if promoted.contains(&load.src()) => {
+                    let value = current.get(&load.src()).copied().unwrap_or(undef);
+                    renamed.replaced.insert(inst, value);
+                    renamed.removed.push(inst);
+                }
+                ValueKind::Store(store) if promoted.contains(&store.dest()) => {
+                    current.insert(store.dest(), store.value());
+                    renamed.removed.push(inst);
+                }
+                _ => {}
+            }
+        }
+        for &succ in cfg.succs(bb) {
+            if let Some(succ_allocs) = phis.get(&succ) {
+                let args = succ_allocs.iter().map(|alloc| current.get(alloc).copied().unwrap_or(undef)).collect();
+                renamed.args.push((block, succ, args));
+            }
+        }
+        for &child in dom_tree.children(bb) {
+            stack.push((child, current.clone()));
+        }
+    }
+    renamed
+}
+
+/// Point the terminators at the blocks that now hold the instructions,
+/// passing the arguments of their parameters; an argument-carrying edge of a
+/// branch goes through a new block that jumps with them
+fn rebuild_terminators(
+    func: &mut FunctionData,
+    blocks: &HashMap<BasicBlock, BasicBlock>,
+    args: Vec<(BasicBlock, BasicBlock, Vec<Value>)>,
+) {
+    // Each edge is listed once per argument list, in successor order
+    let mut edges: HashMap<BasicBlock, Vec<(BasicBlock, Vec<Value>)>> = HashMap::new();
+    for (block, succ, args) in args {
+        edges.entry(block).or_default().push((succ, args));
+    }
+    for (block, mut edges) in edges {
+        edges.reverse();
+        let &term = func.layout().bbs().node(&block).unwrap().insts().back_key().unwrap();
+        let mut take = |target: BasicBlock| -> Option<Vec<Value>> {
+            match edges.last() {
+                Some((succ, _)) if *succ == target => edges.pop().map(|(_, args)| args),
+                _ => None,
+            }
+        };
+        let new_term = match func.dfg().value(term).kind().clone() {
+            ValueKind::Jump(jump) => {
+                let args = take(jump.target()).unwrap_or_default();
+                func.dfg_mut().new_value().jump_with_args(blocks[&jump.target()], args)
+            }
+            ValueKind::Branch(branch) => {
+                let mut targets = Vec::new();
+                for target in [branch.true_bb(), branch.false_bb()] {
+                    targets.push(match take(target) {
+                        Some(args) => {
+                            let name = func.dfg().bb(blocks[&target]).name().clone();
+                            let edge = func.dfg_mut().new_bb().basic_block(name.map(|name| format!("{}_edge", name)));
+                            func.layout_mut().bbs_mut().push_key_back(edge).unwrap();
+                            let jump = func.dfg_mut().new_value().jump_with_args(blocks[&target], args);
+                            func.layout_mut().bb_mut(edge).insts_mut().push_key_back(jump).unwrap();
+                            edge
+                        }
+                        None => blocks[&target],
+                    });
+                }
+                func.dfg_mut().new_value().branch(branch.cond(), targets[0], targets[1])
+            }
+            kind => unreachable!("Edge out of a block ending in {:?}", kind),
+        };
+        func.layout_mut().bb_mut(block).insts_mut().remove(&term);
+        func.dfg_mut().remove_value(term);
+        func.layout_mut().bb_mut(block).insts_mut().push_key_back(new_term).unwrap();
+    }
+
+    // Terminators without arguments to pass may still target a block that
+    // was moved
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        let Some(&term) = func.layout().bbs().node(&bb).unwrap().insts().back_key() else {
+            continue;
+        };
+        let mut data = func.dfg().value(term).clone();
+        let retarget = |target: &mut BasicBlock| match blocks.get(target) {
+            Some(&new) if new != *target => {
+                *target = new;
+                true
+            }
+            _ => false,
+        };
+        let moved = match data.kind_mut() {
+            ValueKind::Jump(jump) => retarget(jump.target_mut()),
+            ValueKind::Branch(branch) => {
+                let true_moved = retarget(branch.true_bb_mut());
+                retarget(branch.false_bb_mut()) || true_moved
+            }
+            _ => false,
+        };
+        if moved {
+            func.dfg_mut().replace_value_with(term).raw(data);
+        }
+    }
+}
+
+/// Make the users of promoted loads use the loaded values, then delete the
+/// promoted loads, stores and allocs
+fn remove_accesses(func: &mut FunctionData, allocs: &[Value], replaced: HashMap<Value, Value>, removed: &[Value]) {
+    // A load may read the value of an earlier promoted load
+    let resolve = |mut value: Value| {
+        while let Some(&next) = replaced.get(&value) {
+            value = next;
+        }
+        value
+    };
+    let map: HashMap<Value, Value> = replaced.keys().map(|&load| (load, resolve(load))).collect();
+    let removed_set: HashSet<Value> = removed.iter().copied().collect();
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        for inst in insts_of(func, bb) {
+            if removed_set.contains(&inst) {
+                continue;
+            }
+            let mut data = func.dfg().value(inst).clone();
+            if data.kind().value_uses().any(|used| map.contains_key(&used)) {
+                remap_operands(data.kind_mut(), &map);
+                func.dfg_mut().replace_value_with(inst).raw(data);
+            }
+        }
+    }
+
+    // Stores use loads, and loads use allocs
+    let (loads, stores): (Vec<Value>, Vec<Value>) = removed.iter().partition(|&&inst| replaced.contains_key(&inst));
+    for inst in stores.into_iter().chain(loads).chain(allocs.iter().copied()) {
+        let bb = func.layout().parent_bb(inst).unwrap();
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        func.dfg_mut().remove_value(inst);
+    }
+}
@@ -0,0 +1,108 @@
+//! Block merging: straighten chains of blocks joined by unconditional jumps.
+//!
+//! Lowering ends every `if`, loop and `&&`/`||` with a join block, so the IR
+//! is full of blocks that only jump on and blocks split from their only
+//! predecessor:
+//!
+//! ```text
+//!   %a:  ...; br %c, %b, %d          %a:  ...; br %c, %e, %d
+//!   %b:  jump %e                =>   %e:  <e>; <f>; ret %0
+//!   %e:  <e>; jump %f
+//!   %f:  <f>; ret %0
+//! ```
+//!
+//! An empty block ending in a jump without arguments is bypassed: whatever
+//! targets it targets its destination instead. A block ending in a jump
+//! without arguments to a block that has no other predecessor then absorbs
+//! that block. Blocks with parameters are left alone, and the entry block is
+//! never bypassed or absorbed.
+
+use koopa::ir::{BasicBlock, FunctionData, Value, ValueKind};
+
+pub fn run(func: &mut FunctionData) {
+    let Some(entry) = func.layout().entry_bb() else {
+        return;
+    };
+    while let Some((bb, target)) = find_forwarder(func, entry) {
+        bypass(func, bb, target);
+    }
+    while let Some((bb, jump, succ)) = find_mergeable(func, entry) {
+        merge(func, bb, jump, succ);
+    }
+}
+
+/// The plain `jump` ending `bb`, with its target
+fn plain_jump(func: &FunctionData, bb: BasicBlock) -> Option<(Value, BasicBlock)> {
+    let &last = func.layout().bbs().node(&bb).unwrap().insts().back_key()?;
+    match func.dfg().value(last).kind() {
+        ValueKind::Jump(jump) if jump.args().is_empty() => Some((last, jump.target())),
+        _ => None,
+    }
+}
+
+/// A block other than the entry that holds nothing but a plain `jump` to
+/// another block, with that block
+fn find_forwarder(func: &FunctionData, entry: BasicBlock) -> Option<(BasicBlock, BasicBlock)> {
+    func.layout().bbs().iter().find_map(|(&bb, node)| {
+        if bb == entry || node.insts().len() != 1 || !func.dfg().bb(bb).params().is_empty() {
+            return None;
+        }
+        let (_, target) = plain_jump(func, bb)?;
+        (target != bb).then_some((bb, target))
+    })
+}
+
+/// Point every jump and branch to `bb` at `target` instead, and delete `bb`
+fn bypass(func: &mut FunctionData, bb: BasicBlock, target: BasicBlock) {
+    let users: Vec<Value> = func.dfg().bb(bb).used_by().iter().copied().collect();
+    for user in users {
+        let mut data = func.dfg().value(user).clone();
+        match data.kind_mut() {
+            ValueKind::Jump(jump) => *jump.target_mut() = target,
+            ValueKind::Branch(branch) => {
+                if branch.true_bb() == bb {
+                    *branch.true_bb_mut() = target;
+                }
+                if branch.false_bb() == bb {
+                    *branch.false_bb_mut() = target;
+                }
+            }
+            kind => unreachable!("Block used by {:?}", kind),
+        }
+        func.dfg_mut().replace_value_with(user).raw(data);
+    }
+    let (jump, _) = plain_jump(func, bb).unwrap();
+    func.layout_mut().bb_mut(bb).insts_mut().remove(&jump);
+    func.dfg_mut().remove_value(jump);
+    func.layout_mut().bbs_mut().remove(&bb);
+    func.dfg_mut().remove_bb(bb);
+}
+
+/// A block ending in a plain `jump` that is the only way into its target,
+/// with the jump and the target
+fn find_mergeable(func: &FunctionData, entry: BasicBlock) -> Option<(BasicBlock, Value, BasicBlock)> {
+    func.layout().bbs().keys().find_map(|&bb| {
+        let (jump, succ) = plain_jump(func, bb)?;
+        let users = func.dfg().bb(succ).used_by();
+        let mergeable = succ != bb
+            && succ != entry
+            && func.dfg().bb(succ).params().is_empty()
+            && users.len() == 1
+            && users.contains(&jump);
+        mergeable.then_some((bb, jump, succ))
+    })
+}
+
+/// Move the instructions of `succ` to the end of `bb` in place of `jump`, and
+/// delete `succ`
+fn merge(func: &mut FunctionData, bb: BasicBlock, jump: Value, succ: BasicBlock) {
+    func.layout_mut().bb_mut(bb).insts_mut().remove(&jump);
+    func.dfg_mut().remove_value(jump);
+    let insts: Vec<Value> = func.layout().bbs().node(&succ).unwrap().insts().keys().copied().collect();
+    for inst in insts {
+        func.layout_mut().bb_mut(succ).insts_mut().remove(&inst);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(inst).unwrap();
+    }
+    func.layout_mut().bbs_mut().remove(&succ);
+    func.dfg_mut().remove_bb(succ);
+}
@@ -0,0 +1,71 @@
+//! Return-block merging: give each function a single `ret`.
+//!
+//! Every `ret v` is replaced by a store of `v` into a return slot and a jump to
+//! a shared exit block, which loads the slot and returns it:
+//!
+//! ```text
+//!   %then:  ret 1            %then:  store 1, %ret; jump %exit
+//!   %else:  ret 2     =>     %else:  store 2, %ret; jump %exit
+//!                            %exit:  %0 = load %ret; ret %0
+//! ```
+//!
+//! so the backend emits the epilogue once per function instead of once per
+//! `return` statement.
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, FunctionData, TypeKind, Value, ValueKind};
+
+pub fn run(func: &mut FunctionData) {
+    merge_returns(func);
+}
+
+fn merge_returns(func: &mut FunctionData) {
+    let Some(entry) = func.layout().entry_bb() else {
+        return;
+    };
+    let returns: Vec<(BasicBlock, Value)> = func.layout().bbs().iter()
+        .filter_map(|(&bb, node)| {
+            let &last = node.insts().back_key()?;
+            matches!(func.dfg().value(last).kind(), ValueKind::Return(_)).then_some((bb, last))
+        })
+        .collect();
+    if returns.len() < 2 {
+        return;
+    }
+
+    let ret_ty = match func.ty().kind() {
+        TypeKind::Function(_, ret_ty) => ret_ty.clone(),
+        _ => unreachable!("function type expected"),
+    };
+    let slot = (!ret_ty.is_unit()).then(|| {
+        let slot = func.dfg_mut().new_value().alloc(ret_ty);
+        func.dfg_mut().set_value_name(slot, Some("%ret".into()));
+        func.layout_mut().bb_mut(entry).insts_mut().push_key_front(slot).unwrap();
+        slot
+    });
+
+    let exit = func.dfg_mut().new_bb().basic_block(Some("%exit".into()));
+    func.layout_mut().bbs_mut().push_key_back(exit).unwrap();
+    let ret_value = slot.map(|slot| {
+        let load = func.dfg_mut().new_value().load(slot);
+        func.layout_mut().bb_mut(exit).insts_mut().push_key_back(load).unwrap();
+        load
+    });
+    let ret = func.dfg_mut().new_value().ret(ret_value);
+    func.layout_mut().bb_mut(exit).insts_mut().push_key_back(ret).unwrap();
+
+    for (bb, ret) in returns {
+        let value = match func.dfg().value(ret).kind() {
+            ValueKind::Return(ret) => ret.value(),
+            _ => unreachable!(),
+        };
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&ret);
+        func.dfg_mut().remove_value(ret);
+        if let (Some(slot), Some(value)) = (slot, value) {
+            let store = func.dfg_mut().new_value().store(value, slot);
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(store).unwrap();
+        }
+        let jump = func.dfg_mut().new_value().jump(exit);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(jump).unwrap();
+    }
+}
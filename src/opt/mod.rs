@@ -0,0 +1,300 @@
+//! Optimization passes over Koopa IR programs.
+
+pub mod block_layout;
+pub mod const_fold;
+pub mod cse;
+pub mod dead_globals;
+pub mod ind_vars;
+pub mod jump_thread;
+pub mod loop_idiom;
+pub mod loop_rotate;
+pub mod loop_unroll;
+pub mod mem2reg;
+pub mod merge_blocks;
+pub mod merge_returns;
+pub mod pragma;
+pub mod select;
+pub mod stats;
+pub mod strength_reduce;
+pub mod tail_call;
+
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use koopa::back::KoopaGenerator;
+use koopa::ir::{FunctionData, Program, Value, ValueKind};
+
+use loop_unroll::LoopUnroll;
+use stats::{Counts, PassStats};
+
+/// An optimization pass over a whole program
+pub trait Pass {
+    /// Name of the pass, as given to `--enable-pass`/`--disable-pass` and
+    /// recorded in crash reproducers
+    fn name(&self) -> &'static str;
+
+    /// Rewrite `program`, leaving out the functions named in `excluded`
+    /// (without the `@` prefix)
+    fn run(&mut self, program: &mut Program, excluded: &HashSet<String>);
+}
+
+/// A pass that rewrites one function at a time, independently of the others
+#[derive(Clone, Copy)]
+pub struct FunctionPass {
+    pub name: &'static str,
+    pub run: fn(&mut FunctionData),
+}
+
+impl Pass for FunctionPass {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn run(&mut self, program: &mut Program, excluded: &HashSet<String>) {
+        for func in program.funcs_mut().values_mut() {
+            if !excluded.contains(&func.name()[1..]) {
+                (self.run)(func);
+            }
+        }
+    }
+}
+
+impl<P: Pass + ?Sized> Pass for Box<P> {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn run(&mut self, program: &mut Program, excluded: &HashSet<String>) {
+        (**self).run(program, excluded)
+    }
+}
+
+/// Called with the name of each pass after it has run, and the program it
+/// left. Passes do not report whether they changed anything, so anything
+/// derived from the IR before the pass must be assumed stale.
+pub type InvalidationHook = Box<dyn FnMut(&str, &Program)>;
+
+/// The program as a pass got it, printed as Koopa IR, kept in case the pass
+/// crashes (see `PassManager::keep_snapshots`)
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub ir: String,
+    /// The enabled passes from the one that got the program on; empty once
+    /// all of them have run
+    pub remaining: Vec<&'static str>,
+}
+
+/// Runs registered passes in registration order, skipping disabled ones
+#[derive(Default)]
+pub struct PassManager {
+    /// The registered passes, and whether each one is enabled
+    passes: Vec<(Box<dyn Pass>, bool)>,
+    hooks: Vec<InvalidationHook>,
+    /// Statistics of the passes run so far, if they are collected
+    stats: Option<Vec<PassStats>>,
+    /// Whether the program is printed before each pass
+    keep_snapshots: bool,
+    snapshot: Option<Snapshot>,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a pass after those registered so far
+    pub fn register(&mut self, pass: impl Pass + 'static, enabled: bool) {
+        self.passes.push((Box::new(pass), enabled));
+    }
+
+    /// Enable or disable the pass named `name`. Returns `false` if no pass
+    /// has that name.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.passes.iter_mut().find(|(pass, _)| pass.name() == name) {
+            Some((_, flag)) => {
+                *flag = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Names of all registered passes, in order
+    pub fn names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|(pass, _)| pass.name()).collect()
+    }
+
+    /// Names of the passes that will run, in order
+    pub fn enabled(&self) -> Vec<&'static str> {
+        self.passes.iter().filter(|(_, enabled)| *enabled).map(|(pass, _)| pass.name()).collect()
+    }
+
+    /// Call `hook` after every pass that runs
+    pub fn add_invalidation_hook(&mut self, hook: impl FnMut(&str, &Program) + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Time the passes and measure the program around each one from now on
+    /// (`--stats`), which costs a walk over the program per pass
+    pub fn collect_stats(&mut self) {
+        self.stats.get_or_insert_with(Vec::new);
+    }
+
+    /// Statistics of the passes run since `collect_stats`, in order
+    pub fn stats(&self) -> &[PassStats] {
+        self.stats.as_deref().unwrap_or_default()
+    }
+
+    /// Print the program before each pass, and once more after the last, so
+    /// that when a pass or the code generation after them crashes,
+    /// `snapshot` has the IR it got rather than a half-rewritten program.
+    /// This costs printing the program once per pass.
+    pub fn keep_snapshots(&mut self) {
+        self.keep_snapshots = true;
+    }
+
+    /// The program as the last pass to start got it, or as all passes left
+    /// it once they are done; `None` unless snapshots are kept and a run
+    /// has started
+    pub fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// Run the enabled passes over `program`, in order, leaving out the
+    /// functions named in `excluded` (without the `@` prefix)
+    pub fn run(&mut self, program: &mut Program, excluded: &HashSet<String>) {
+        let mut remaining = self.enabled();
+        for (pass, enabled) in &mut self.passes {
+            if !*enabled {
+                continue;
+            }
+            if self.keep_snapshots {
+                self.snapshot = Some(Snapshot { ir: print(program), remaining: remaining.clone() });
+            }
+            remaining.remove(0);
+            match &mut self.stats {
+                Some(stats) => {
+                    let before = Counts::of(program);
+                    stats::start_counting();
+                    let start = Instant::now();
+                    pass.run(program, excluded);
+                    let time = start.elapsed();
+                    let counters = stats::stop_counting();
+                    stats.push(PassStats { name: pass.name(), time, before, after: Counts::of(program), counters });
+                }
+                None => pass.run(program, excluded),
+            }
+            for hook in &mut self.hooks {
+                hook(pass.name(), program);
+            }
+        }
+        if self.keep_snapshots {
+            self.snapshot = Some(Snapshot { ir: print(program), remaining });
+        }
+    }
+}
+
+/// `program` as Koopa IR text
+fn print(program: &Program) -> String {
+    let mut ir = Vec::new();
+    KoopaGenerator::new(&mut ir).generate_on(program).expect("printing to memory cannot fail");
+    String::from_utf8(ir).expect("Koopa IR is ASCII")
+}
+
+/// Settings of the passes that take any
+#[derive(Debug, Clone, Copy)]
+pub struct PassOptions {
+    /// Largest number of instructions loop unrolling may add for one loop
+    pub unroll_threshold: usize,
+    /// Number of body copies per iteration of a partially unrolled loop; 0
+    /// or 1 leaves loops whose trip count is unknown alone
+    pub unroll_factor: usize,
+    /// Only part of the program is compiled (`--only-function`,
+    /// `--skip-function`): the functions left out may read any global, so
+    /// none is dead
+    pub partial_program: bool,
+}
+
+impl Default for PassOptions {
+    fn default() -> Self {
+        Self { unroll_threshold: 64, unroll_factor: 1, partial_program: false }
+    }
+}
+
+/// All passes, in the order they run, with those of the given `-O` level enabled
+pub fn pipeline(level: u8) -> PassManager {
+    pipeline_with_options(level, PassOptions::default())
+}
+
+pub fn pipeline_with_options(level: u8, options: PassOptions) -> PassManager {
+    // Each pass with the lowest level that enables it
+    let passes: Vec<(Box<dyn Pass>, u8)> = vec![
+        (Box::new(FunctionPass { name: "select", run: select::run }), 1),
+        (Box::new(FunctionPass { name: "const-fold", run: const_fold::run }), 1),
+        (Box::new(dead_globals::DeadGlobals { partial_program: options.partial_program }), 1),
+        (Box::new(FunctionPass { name: "jump-thread", run: jump_thread::run }), 1),
+        (Box::new(FunctionPass { name: "strength-reduce", run: strength_reduce::run }), 1),
+        (Box::new(tail_call::TailCalls), 1),
+        (Box::new(FunctionPass { name: "loop-idiom", run: loop_idiom::run }), 1),
+        (Box::new(LoopUnroll { threshold: options.unroll_threshold, factor: options.unroll_factor }), 1),
+        (Box::new(FunctionPass { name: "loop-rotate", run: loop_rotate::run }), 1),
+        (Box::new(FunctionPass { name: "mem2reg", run: mem2reg::run }), 2),
+        (Box::new(FunctionPass { name: "ind-vars", run: ind_vars::run }), 2),
+        (Box::new(FunctionPass { name: "cse", run: cse::run }), 1),
+        (Box::new(FunctionPass { name: "merge-returns", run: merge_returns::run }), 1),
+        (Box::new(FunctionPass { name: "merge-blocks", run: merge_blocks::run }), 1),
+        (Box::new(FunctionPass { name: "block-layout", run: block_layout::run }), 1),
+    ];
+    let mut manager = PassManager::new();
+    for (pass, min_level) in passes {
+        manager.register(pass, level >= min_level);
+    }
+    manager
+}
+
+/// Run the optimization pipeline for the given `-O` level
+pub fn optimize(program: &mut Program, level: u8) {
+    pipeline(level).run(program, &HashSet::new());
+}
+
+/// Rewrite the value operands of an instruction according to `map`
+/// (operands not in the map are left untouched)
+pub(crate) fn remap_operands(kind: &mut ValueKind, map: &HashMap<Value, Value>) {
+    let remap = |value: &mut Value| {
+        if let Some(&new) = map.get(value) {
+            *value = new;
+        }
+    };
+    match kind {
+        ValueKind::Load(load) => remap(load.src_mut()),
+        ValueKind::Store(store) => {
+            remap(store.value_mut());
+            remap(store.dest_mut());
+        }
+        ValueKind::GetPtr(get_ptr) => {
+            remap(get_ptr.src_mut());
+            remap(get_ptr.index_mut());
+        }
+        ValueKind::GetElemPtr(get_elem_ptr) => {
+            remap(get_elem_ptr.src_mut());
+            remap(get_elem_ptr.index_mut());
+        }
+        ValueKind::Binary(binary) => {
+            remap(binary.lhs_mut());
+            remap(binary.rhs_mut());
+        }
+        ValueKind::Branch(branch) => {
+            remap(branch.cond_mut());
+            branch.true_args_mut().iter_mut().for_each(remap);
+            branch.false_args_mut().iter_mut().for_each(remap);
+        }
+        ValueKind::Jump(jump) => jump.args_mut().iter_mut().for_each(remap),
+        ValueKind::Call(call) => call.args_mut().iter_mut().for_each(remap),
+        ValueKind::Return(ret) => {
+            if let Some(value) = ret.value_mut() {
+                remap(value);
+            }
+        }
+        _ => {}
+    }
+}
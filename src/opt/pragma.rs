@@ -0,0 +1,44 @@
+//! Per-function pragmas, written as `//` comments directly above a function
+//! definition:
+//!
+//! ```c
+//! // yasysyc: optimize(0)
+//! int f() { ... }
+//! ```
+//!
+//! `optimize(0)` leaves the function out of the optimization pipeline, which
+//! helps narrow a miscompile down to one function. Comments without the
+//! `yasysyc:` prefix, and pragmas not listed here, are ignored.
+
+use std::collections::HashSet;
+
+use crate::ast::{CompUnit, GlobalItem};
+
+/// The pragmas in the comment lines directly above `offset` (a blank line
+/// ends the comment block)
+fn pragmas_above(source: &str, offset: usize) -> Vec<&str> {
+    let mut lines = source[..offset].rsplit('\n');
+    // Anything on the line of the definition itself comes before it
+    if lines.next().is_some_and(|line| !line.trim().is_empty()) {
+        return Vec::new();
+    }
+    lines
+        .map(str::trim)
+        .map_while(|line| line.strip_prefix("//"))
+        .filter_map(|comment| comment.trim().strip_prefix("yasysyc:"))
+        .map(str::trim)
+        .collect()
+}
+
+/// Names of the functions marked `// yasysyc: optimize(0)` in `source`
+pub fn unoptimized_functions(source: &str, ast: &CompUnit) -> HashSet<String> {
+    ast.items
+        .iter()
+        .filter_map(|item| match item {
+            GlobalItem::FuncDef(func_def) => Some(func_def),
+            GlobalItem::Decl(_) => None,
+        })
+        .filter(|func_def| pragmas_above(source, func_def.span.start).contains(&"optimize(0)"))
+        .map(|func_def| func_def.id.value.clone())
+        .collect()
+}
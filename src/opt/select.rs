@@ -0,0 +1,197 @@
+//! If-conversion: turn simple `if`/`else` diamonds into branchless selects.
+//!
+//! A conditional assignment such as `if (c) x = a; else x = b;` is emitted as
+//!
+//! ```text
+//!   %bb:    ...; br %c, %then, %else
+//!   %then:  <a>; store %a, @x; jump %end
+//!   %else:  <b>; store %b, @x; jump %end
+//! ```
+//!
+//! When both arms are short and free of side effects other than the final
+//! store, they are hoisted into `%bb` and the store picks its value with a
+//! mask instead of a branch (Koopa has no select instruction):
+//!
+//! ```text
+//!   %bb:    ...; <a>; <b>
+//!           %r = xor %b, (and (xor %a, %b), (sub 0, %c))
+//!           store %r, @x; jump %end
+//! ```
+//!
+//! A missing arm (`if (c) x = a;`) selects the current value of `@x` instead.
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Value, ValueKind};
+
+/// Arms with more instructions than this (besides the store and jump) are
+/// kept as branches, since both arms always execute after the conversion
+const MAX_ARM_INSTS: usize = 4;
+
+pub fn run(func: &mut FunctionData) {
+    // Converting an inner diamond can turn an outer one into a candidate
+    while let Some(diamond) = find_diamond(func) {
+        convert(func, diamond);
+    }
+}
+
+/// An arm of a diamond: a block that computes a value and stores it
+struct Arm {
+    bb: BasicBlock,
+    /// Side-effect-free instructions computing the stored value
+    insts: Vec<Value>,
+    value: Value,
+    dest: Value,
+    join: BasicBlock,
+}
+
+struct Diamond {
+    head: BasicBlock,
+    branch: Value,
+    cond: Value,
+    true_arm: Option<Arm>,
+    false_arm: Option<Arm>,
+    dest: Value,
+    join: BasicBlock,
+}
+
+fn find_diamond(func: &FunctionData) -> Option<Diamond> {
+    func.layout().bbs().iter().find_map(|(&head, node)| {
+        let &branch = node.insts().back_key()?;
+        let ValueKind::Branch(br) = func.dfg().value(branch).kind() else {
+            return None;
+        };
+        let (t, f) = (br.true_bb(), br.false_bb());
+        if t == f || t == head || f == head {
+            return None;
+        }
+        let (true_arm, false_arm) = (arm(func, t), arm(func, f));
+        let (dest, join) = match (&true_arm, &false_arm) {
+            (Some(a), Some(b)) if a.dest == b.dest && a.join == b.join => (a.dest, a.join),
+            (Some(a), None) if a.join == f => (a.dest, f),
+            (None, Some(b)) if b.join == t => (b.dest, t),
+            _ => return None,
+        };
+        Some(Diamond {
+            head,
+            branch,
+            cond: br.cond(),
+            true_arm: true_arm.filter(|_| join != t),
+            false_arm: false_arm.filter(|_| join != f),
+            dest,
+            join,
+        })
+    })
+}
+
+/// Match `bb` as a diamond arm: only reachable from one branch, and made of
+/// speculatable instructions followed by `store; jump`
+fn arm(func: &FunctionData, bb: BasicBlock) -> Option<Arm> {
+    let data = func.dfg().bb(bb);
+    if !data.params().is_empty() || data.used_by().len() != 1 {
+        return None;
+    }
+    let insts: Vec<Value> = func.layout().bbs().node(&bb)?.insts().keys().copied().collect();
+    let (&jump, rest) = insts.split_last()?;
+    let (&store, rest) = rest.split_last()?;
+    let ValueKind::Jump(jump) = func.dfg().value(jump).kind() else {
+        return None;
+    };
+    let ValueKind::Store(store) = func.dfg().value(store).kind() else {
+        return None;
+    };
+    if !jump.args().is_empty() || rest.len() > MAX_ARM_INSTS {
+        return None;
+    }
+    if !rest.iter().all(|&inst| is_speculatable(func, inst)) {
+        return None;
+    }
+    Some(Arm {
+        bb,
+        insts: rest.to_vec(),
+        value: store.value(),
+        dest: store.dest(),
+        join: jump.target(),
+    })
+}
+
+/// Whether an instruction can be executed even if its arm is not taken
+fn is_speculatable(func: &FunctionData, inst: Value) -> bool {
+    match func.dfg().value(inst).kind() {
+        ValueKind::Binary(binary) => !matches!(binary.op(), BinaryOp::Div | BinaryOp::Mod),
+        // Loads directly from a variable cannot fault
+        ValueKind::Load(load) => {
+            load.src().is_global() || matches!(func.dfg().value(load.src()).kind(), ValueKind::Alloc(_))
+        }
+        _ => false,
+    }
+}
+
+fn convert(func: &mut FunctionData, diamond: Diamond) {
+    let Diamond { head, branch, cond, true_arm, false_arm, dest, join } = diamond;
+    func.layout_mut().bb_mut(head).insts_mut().remove(&branch);
+    func.dfg_mut().remove_value(branch);
+
+    let push = |func: &mut FunctionData, inst: Value| {
+        func.layout_mut().bb_mut(head).insts_mut().push_key_back(inst).unwrap();
+        inst
+    };
+
+    // Hoist both arms, then read the current value for a missing arm
+    let arm_value = |func: &mut FunctionData, arm: Option<Arm>| match arm {
+        Some(arm) => {
+            for &inst in &arm.insts {
+                func.layout_mut().bb_mut(arm.bb).insts_mut().remove(&inst);
+                push(func, inst);
+            }
+            remove_arm(func, arm.bb);
+            arm.value
+        }
+        None => {
+            let load = func.dfg_mut().new_value().load(dest);
+            push(func, load)
+        }
+    };
+    let a = arm_value(func, true_arm);
+    let b = arm_value(func, false_arm);
+
+    // r = b ^ ((a ^ b) & -c), with c normalized to 0 or 1
+    let is_bool = matches!(
+        func.dfg().value(cond).kind(),
+        ValueKind::Binary(binary) if matches!(
+            binary.op(),
+            BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge
+        )
+    );
+    let cond = if is_bool {
+        cond
+    } else {
+        let zero = func.dfg_mut().new_value().integer(0);
+        let ne = func.dfg_mut().new_value().binary(BinaryOp::NotEq, cond, zero);
+        push(func, ne)
+    };
+    let zero = func.dfg_mut().new_value().integer(0);
+    let mask = func.dfg_mut().new_value().binary(BinaryOp::Sub, zero, cond);
+    push(func, mask);
+    let diff = func.dfg_mut().new_value().binary(BinaryOp::Xor, a, b);
+    push(func, diff);
+    let pick = func.dfg_mut().new_value().binary(BinaryOp::And, diff, mask);
+    push(func, pick);
+    let result = func.dfg_mut().new_value().binary(BinaryOp::Xor, b, pick);
+    push(func, result);
+
+    let store = func.dfg_mut().new_value().store(result, dest);
+    push(func, store);
+    let jump = func.dfg_mut().new_value().jump(join);
+    push(func, jump);
+}
+
+/// Delete an arm whose computation has been hoisted (only `store; jump` remain)
+fn remove_arm(func: &mut FunctionData, bb: BasicBlock) {
+    let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+    for inst in insts {
+        func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        func.dfg_mut().remove_value(inst);
+    }
+    func.layout_mut().bbs_mut().remove(&bb);
+    func.dfg_mut().remove_bb(bb);
+}
@@ -0,0 +1,136 @@
+//! Per-pass statistics (`--stats`): how long each pass took and how it changed
+//! the size of the program.
+//!
+//! The program is measured before and after each pass, so the size counters
+//! are net changes: a pass that removes two loads and adds one reports
+//! `loads -1`. On top of those, a pass can count what it did itself with
+//! [`bump`] (e.g. "instructions folded"), which the pass manager collects into
+//! [`PassStats::counters`].
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::time::Duration;
+
+use koopa::ir::{Program, ValueKind};
+
+/// Sizes of a program, as measured between passes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub insts: usize,
+    pub blocks: usize,
+    pub calls: usize,
+    pub loads: usize,
+    pub stores: usize,
+    pub globals: usize,
+}
+
+impl Counts {
+    pub fn of(program: &Program) -> Self {
+        let mut counts = Self { globals: program.inst_layout().len(), ..Self::default() };
+        for func in program.funcs().values() {
+            counts.blocks += func.layout().bbs().len();
+            for (_, node) in func.layout().bbs() {
+                for &inst in node.insts().keys() {
+                    counts.insts += 1;
+                    match func.dfg().value(inst).kind() {
+                        ValueKind::Call(_) => counts.calls += 1,
+                        ValueKind::Load(_) => counts.loads += 1,
+                        ValueKind::Store(_) => counts.stores += 1,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    /// The counters that differ from `self` in `after`, with the change
+    fn changes(&self, after: &Self) -> Vec<(&'static str, isize)> {
+        let pairs = [
+            ("insts", self.insts, after.insts),
+            ("blocks", self.blocks, after.blocks),
+            ("calls", self.calls, after.calls),
+            ("loads", self.loads, after.loads),
+            ("stores", self.stores, after.stores),
+            ("globals", self.globals, after.globals),
+        ];
+        pairs
+            .into_iter()
+            .filter(|&(_, before, after)| before != after)
+            .map(|(name, before, after)| (name, after as isize - before as isize))
+            .collect()
+    }
+}
+
+thread_local! {
+    /// The counters of the pass running now, while statistics are collected
+    static COUNTERS: RefCell<Option<Vec<(&'static str, usize)>>> = const { RefCell::new(None) };
+}
+
+/// Add `n` to the counter `name` of the running pass. Does nothing unless
+/// the pass manager collects statistics.
+pub fn bump(name: &'static str, n: usize) {
+    COUNTERS.with_borrow_mut(|counters| {
+        let Some(counters) = counters else {
+            return;
+        };
+        match counters.iter_mut().find(|(counter, _)| *counter == name) {
+            Some((_, count)) => *count += n,
+            None => counters.push((name, n)),
+        }
+    });
+}
+
+/// Start counting for a pass about to run
+pub(super) fn start_counting() {
+    COUNTERS.with_borrow_mut(|counters| *counters = Some(Vec::new()));
+}
+
+/// The counters bumped since `start_counting`, in the order first bumped
+pub(super) fn stop_counting() -> Vec<(&'static str, usize)> {
+    COUNTERS.with_borrow_mut(|counters| counters.take().unwrap_or_default())
+}
+
+/// What one run of a pass took and did
+#[derive(Debug, Clone)]
+pub struct PassStats {
+    pub name: &'static str,
+    pub time: Duration,
+    pub before: Counts,
+    pub after: Counts,
+    /// What the pass counted itself with [`bump`]
+    pub counters: Vec<(&'static str, usize)>,
+}
+
+impl PassStats {
+    /// The counters the pass changed, with the net change
+    pub fn changes(&self) -> Vec<(&'static str, isize)> {
+        self.before.changes(&self.after)
+    }
+}
+
+/// A table with a line per pass run, and one for the whole pipeline. The
+/// counters a pass keeps itself follow its net changes after a `;`.
+pub fn report(stats: &[PassStats]) -> String {
+    let mut text = String::from("pass statistics:\n");
+    let mut line = |name: &str, time: Duration, changes: Vec<(&str, isize)>, counters: &[(&str, usize)]| {
+        let mut changes = if changes.is_empty() {
+            "no change".to_string()
+        } else {
+            changes.iter().map(|(counter, change)| format!("{} {:+}", counter, change)).collect::<Vec<_>>().join(", ")
+        };
+        if !counters.is_empty() {
+            let counters: Vec<String> =
+                counters.iter().map(|(counter, count)| format!("{} {}", counter, count)).collect();
+            changes += &format!("; {}", counters.join(", "));
+        }
+        writeln!(text, "  {:<16} {:>9.3}ms  {}", name, time.as_secs_f64() * 1000.0, changes).unwrap();
+    };
+    for pass in stats {
+        line(pass.name, pass.time, pass.changes(), &pass.counters);
+    }
+    if let (Some(first), Some(last)) = (stats.first(), stats.last()) {
+        line("total", stats.iter().map(|pass| pass.time).sum(), first.before.changes(&last.after), &[]);
+    }
+    text
+}
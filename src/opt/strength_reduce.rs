@@ -0,0 +1,102 @@
+//! Strength reduction: multiply, divide and take remainders by a constant
+//! power of two with shifts and masks instead of `mul`, `div` and `mod`.
+//!
+//! A multiplication by `2^k` is a left shift. Signed division truncates
+//! towards zero while an arithmetic shift rounds down, so a negative dividend
+//! is first biased by `2^k - 1`; the bias is the sign mask of the dividend
+//! (`sar x, 31`, which is 0 or -1) with its low `k` bits kept:
+//!
+//! ```text
+//!                        %s = sar %x, 31
+//!                        %b = and %s, 2^k - 1
+//!   %q = div %x, 2^k =>  %t = add %x, %b
+//!                        %q = sar %t, k
+//! ```
+//!
+//! The remainder clears the low bits of the biased dividend instead
+//! (`%r = sub %x, (and %t, -2^k)`), which gives it the sign of the dividend,
+//! as `%` does. The final instruction keeps the value of the original one, so
+//! its users are unchanged.
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Value, ValueKind};
+
+pub fn run(func: &mut FunctionData) {
+    let bbs: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        reduce_in_block(func, bb);
+    }
+}
+
+/// `k` if `value` is the constant `2^k` for some `k >= 1`
+fn power_of_two(func: &FunctionData, value: Value) -> Option<i32> {
+    match func.dfg().value(value).kind() {
+        ValueKind::Integer(int) if int.value() > 1 && int.value().count_ones() == 1 => {
+            Some(int.value().trailing_zeros() as i32)
+        }
+        _ => None,
+    }
+}
+
+fn reduce_in_block(func: &mut FunctionData, bb: BasicBlock) {
+    let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+    let mut layout = Vec::with_capacity(insts.len());
+    let mut changed = false;
+    for &inst in &insts {
+        if let ValueKind::Binary(binary) = func.dfg().value(inst).kind() {
+            let (op, lhs, rhs) = (binary.op(), binary.lhs(), binary.rhs());
+            let reduced = match op {
+                BinaryOp::Mul => match (power_of_two(func, lhs), power_of_two(func, rhs)) {
+                    (_, Some(k)) => Some((lhs, k)),
+                    (Some(k), None) => Some((rhs, k)),
+                    (None, None) => None,
+                },
+                BinaryOp::Div | BinaryOp::Mod => power_of_two(func, rhs).map(|k| (lhs, k)),
+                _ => None,
+            };
+            if let Some((x, k)) = reduced {
+                reduce(func, inst, op, x, k, &mut layout);
+                changed = true;
+            }
+        }
+        layout.push(inst);
+    }
+    if changed {
+        for inst in insts {
+            func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        }
+        for inst in layout {
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(inst).unwrap();
+        }
+    }
+}
+
+fn integer(func: &mut FunctionData, value: i32) -> Value {
+    func.dfg_mut().new_value().integer(value)
+}
+
+/// Turn `inst`, which is `x op 2^k`, into shifts and masks, appending the
+/// instructions it needs to `layout`
+fn reduce(func: &mut FunctionData, inst: Value, op: BinaryOp, x: Value, k: i32, layout: &mut Vec<Value>) {
+    let mut binary = |func: &mut FunctionData, op: BinaryOp, lhs: Value, rhs: Value| {
+        let value = func.dfg_mut().new_value().binary(op, lhs, rhs);
+        layout.push(value);
+        value
+    };
+    let (op, lhs, rhs) = match op {
+        BinaryOp::Mul => (BinaryOp::Shl, x, integer(func, k)),
+        _ => {
+            let (thirty_one, mask) = (integer(func, 31), integer(func, (1 << k) - 1));
+            let sign = binary(func, BinaryOp::Sar, x, thirty_one);
+            let bias = binary(func, BinaryOp::And, sign, mask);
+            let biased = binary(func, BinaryOp::Add, x, bias);
+            if op == BinaryOp::Div {
+                (BinaryOp::Sar, biased, integer(func, k))
+            } else {
+                let low_cleared = integer(func, -(1 << k));
+                (BinaryOp::Sub, x, binary(func, BinaryOp::And, biased, low_cleared))
+            }
+        }
+    };
+    func.dfg_mut().replace_value_with(inst).binary(op, lhs, rhs);
+}
@@ -0,0 +1,181 @@
+//! Tail-call elimination for self-recursive calls.
+//!
+//! A function that returns the result of calling itself does not need a new
+//! frame for the call: it can store the arguments into its own parameter
+//! slots and start over. The entry block is split after the prologue that
+//! copies the incoming arguments into their slots, and each tail call jumps
+//! back to the rest of it:
+//!
+//! ```text
+//!   %entry:  @n = alloc i32           %entry:  @n = alloc i32
+//!            store %n, @n                      store %n, @n
+//!            <body>                            jump %tail_entry
+//!            ...               =>     %tail_entry:
+//!   %else:   <args>                            <body>
+//!            %r = call @f(%a)                  ...
+//!            ret %r                   %else:   <args>
+//!                                              store %a, @n
+//!                                              jump %tail_entry
+//! ```
+//!
+//! A call passing the address of a local array is kept, as the array would
+//! be overwritten while the next iteration reads it through the parameter.
+//! So are the calls of functions with a `char` parameter, whose prologue
+//! narrows the incoming argument before storing it.
+
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::builder_traits::*;
+use koopa::ir::{BasicBlock, Function, FunctionData, Program, Value, ValueKind};
+
+use super::{Pass, stats};
+
+/// The pass itself; it needs the handle of each function to recognize calls
+/// to it, so it runs over the whole program
+pub struct TailCalls;
+
+impl Pass for TailCalls {
+    fn name(&self) -> &'static str {
+        "tail-call"
+    }
+
+    fn run(&mut self, program: &mut Program, excluded: &HashSet<String>) {
+        let funcs: Vec<Function> = program.func_layout().to_vec();
+        for handle in funcs {
+            let func = program.func_mut(handle);
+            if !excluded.contains(&func.name()[1..]) {
+                eliminate(func, handle);
+            }
+        }
+    }
+}
+
+/// A call of the function itself whose result is returned right away
+struct TailCall {
+    bb: BasicBlock,
+    call: Value,
+    ret: Value,
+    args: Vec<Value>,
+}
+
+fn eliminate(func: &mut FunctionData, this: Function) {
+    let Some(entry) = func.layout().entry_bb() else {
+        return;
+    };
+    let Some((prologue_len, slots)) = prologue(func, entry) else {
+        return;
+    };
+    let calls: Vec<TailCall> = tail_calls(func, this)
+        .into_iter()
+        .filter(|call| call.args.iter().all(|&arg| !points_into_frame(func, arg, &slots)))
+        .collect();
+    if calls.is_empty() {
+        return;
+    }
+
+    let body = split_entry(func, entry, prologue_len);
+    for call in calls {
+        // A call in the entry block was moved along with the body
+        let bb = if call.bb == entry { body } else { call.bb };
+        for inst in [call.ret, call.call] {
+            func.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+            func.dfg_mut().remove_value(inst);
+        }
+        for (&arg, &slot) in call.args.iter().zip(&slots) {
+            let store = func.dfg_mut().new_value().store(arg, slot);
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(store).unwrap();
+        }
+        let jump = func.dfg_mut().new_value().jump(body);
+        func.layout_mut().bb_mut(bb).insts_mut().push_key_back(jump).unwrap();
+        stats::bump("calls turned into jumps", 1);
+    }
+}
+
+/// The number of instructions at the start of the entry block that allocate
+/// slots or copy incoming arguments into them, and the slot of each
+/// parameter, if every parameter is copied there unchanged
+fn prologue(func: &FunctionData, entry: BasicBlock) -> Option<(usize, Vec<Value>)> {
+    let mut slots = HashMap::new();
+    let mut len = 0;
+    for &inst in func.layout().bbs().node(&entry).unwrap().insts().keys() {
+        match func.dfg().value(inst).kind() {
+            ValueKind::Alloc(_) => {}
+            ValueKind::Store(store) => match func.dfg().value(store.value()).kind() {
+                ValueKind::FuncArgRef(arg) => {
+                    slots.insert(arg.index(), store.dest());
+                }
+                _ => break,
+            },
+            _ => break,
+        }
+        len += 1;
+    }
+    let slots = (0..func.params().len()).map(|index| slots.get(&index).copied()).collect::<Option<_>>()?;
+    Some((len, slots))
+}
+
+fn tail_calls(func: &FunctionData, this: Function) -> Vec<TailCall> {
+    let mut calls = Vec::new();
+    for (&bb, node) in func.layout().bbs() {
+        let insts: Vec<Value> = node.insts().keys().copied().collect();
+        let [.., call, ret] = insts[..] else {
+            continue;
+        };
+        let (ValueKind::Return(ret_value), ValueKind::Call(call_value)) =
+            (func.dfg().value(ret).kind(), func.dfg().value(call).kind())
+        else {
+            continue;
+        };
+        let returns_call = match ret_value.value() {
+            Some(value) => value == call,
+            None => func.dfg().value(call).ty().is_unit(),
+        };
+        if call_value.callee() == this && returns_call {
+            calls.push(TailCall { bb, call, ret, args: call_value.args().to_vec() });
+        }
+    }
+    calls
+}
+
+/// Whether `value` is an address inside a local `alloc` other than the slot
+/// of a parameter
+fn points_into_frame(func: &FunctionData, mut value: Value, slots: &[Value]) -> bool {
+    loop {
+        if value.is_global() {
+            return false;
+        }
+        match func.dfg().value(value).kind() {
+            ValueKind::GetElemPtr(get_elem_ptr) => value = get_elem_ptr.src(),
+            ValueKind::GetPtr(get_ptr) => value = get_ptr.src(),
+            ValueKind::Alloc(_) => return !slots.contains(&value),
+            _ => return false,
+        }
+    }
+}
+
+/// Move everything after the first `prologue_len` instructions of the entry
+/// block into a new block right after it, which the entry jumps to
+fn split_entry(func: &mut FunctionData, entry: BasicBlock, prologue_len: usize) -> BasicBlock {
+    let order: Vec<BasicBlock> = func.layout().bbs().keys().copied().collect();
+    let body = func.dfg_mut().new_bb().basic_block(Some("%tail_entry".into()));
+    func.layout_mut().bbs_mut().push_key_back(body).unwrap();
+    let insts: Vec<Value> =
+        func.layout().bbs().node(&entry).unwrap().insts().keys().skip(prologue_len).copied().collect();
+    for inst in insts {
+        func.layout_mut().bb_mut(entry).insts_mut().remove(&inst);
+        func.layout_mut().bb_mut(body).insts_mut().push_key_back(inst).unwrap();
+    }
+    let jump = func.dfg_mut().new_value().jump(body);
+    func.layout_mut().bb_mut(entry).insts_mut().push_key_back(jump).unwrap();
+
+    // Keep the blocks after the entry in their order, after the new one
+    for bb in order.into_iter().skip(1) {
+        let insts: Vec<Value> = func.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+        func.layout_mut().bbs_mut().remove(&bb);
+        func.layout_mut().bbs_mut().push_key_back(bb).unwrap();
+        for inst in insts {
+            func.layout_mut().bb_mut(bb).insts_mut().push_key_back(inst).unwrap();
+        }
+    }
+    body
+}
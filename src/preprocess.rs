@@ -0,0 +1,327 @@
+//! A minimal C preprocessor, run on the source before parsing.
+//!
+//! It supports object-like `#define` and `#undef`, `#include "file"`
+//! (relative to the including file) and `#ifdef`/`#ifndef`/`#else`/`#endif`,
+//! which is enough for shared headers with include guards and named
+//! constants. Function-like macros, `#if` expressions and `<...>` includes
+//! are rejected.
+//!
+//! Each line of the input becomes exactly one line of the output (a directive
+//! or a skipped line becomes an empty one, an `#include` the lines of the
+//! included file), and the origin of every output line is recorded so that
+//! diagnostics can point into the original files. A file without directives
+//! comes out unchanged, comments included.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::Path;
+
+use crate::diag::LineOrigin;
+
+/// Maximum nesting of `#include`s, reached by a header including itself
+/// without an include guard
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+/// The preprocessed source and the origin of each of its lines
+#[derive(Debug)]
+pub struct Preprocessed {
+    pub text: String,
+    pub origins: Vec<LineOrigin>,
+}
+
+/// A malformed or unsupported directive, or an unreadable `#include`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: error: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// Preprocess `text`, the contents of the file `name`
+pub fn preprocess(name: &str, text: &str) -> Result<Preprocessed, PreprocessError> {
+    let mut preprocessor = Preprocessor {
+        macros: HashMap::new(),
+        out: Preprocessed { text: String::new(), origins: Vec::new() },
+        depth: 0,
+    };
+    preprocessor.file(name, text)?;
+    Ok(preprocessor.out)
+}
+
+/// A piece of a source line, as far as macro expansion is concerned
+enum Piece<'a> {
+    Ident(&'a str),
+    Comment(&'a str),
+    /// Anything else: literals, punctuation and whitespace
+    Other(&'a str),
+}
+
+/// Split `line` into pieces. `in_comment` tells whether the line starts
+/// inside a block comment, and is updated for the next line.
+fn pieces<'a>(line: &'a str, in_comment: &mut bool) -> Vec<Piece<'a>> {
+    let bytes = line.as_bytes();
+    let len = bytes.len();
+    let mut pieces = Vec::new();
+    let mut i = 0;
+    if *in_comment {
+        match line.find("*/") {
+            Some(end) => {
+                i = end + 2;
+                *in_comment = false;
+            }
+            None => i = len,
+        }
+        pieces.push(Piece::Comment(&line[..i]));
+    }
+    while i < len {
+        let start = i;
+        let piece = match bytes[i] {
+            b'/' if line[i..].starts_with("//") => {
+                // The line terminator is not part of the comment
+                i = line.trim_end_matches(['\n', '\r']).len().max(i + 2);
+                Piece::Comment(&line[start..i])
+            }
+            b'/' if line[i..].starts_with("/*") => {
+                match line[i + 2..].find("*/") {
+                    Some(end) => i += end + 4,
+                    None => {
+                        i = len;
+                        *in_comment = true;
+                    }
+                }
+                Piece::Comment(&line[start..i])
+            }
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < len && bytes[i] != quote && bytes[i] != b'\n' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i = (i + 1).min(len);
+                Piece::Other(&line[start..i])
+            }
+            b'_' | b'a'..=b'z' | b'A'..=b'Z' => {
+                while i < len && (bytes[i] == b'_' || bytes[i].is_ascii_alphanumeric()) {
+                    i += 1;
+                }
+                Piece::Ident(&line[start..i])
+            }
+            // A number, including its suffix and exponent (`0x1Fp-2`), so that
+            // no part of it is taken for an identifier
+            b'0'..=b'9' => {
+                i += 1;
+                while i < len {
+                    match bytes[i] {
+                        b'_' | b'.' => {}
+                        b'+' | b'-' if matches!(bytes[i - 1], b'e' | b'E' | b'p' | b'P') => {}
+                        byte if byte.is_ascii_alphanumeric() => {}
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                Piece::Other(&line[start..i])
+            }
+            _ => {
+                i += line[i..].chars().next().unwrap().len_utf8();
+                Piece::Other(&line[start..i])
+            }
+        };
+        pieces.push(piece);
+    }
+    pieces
+}
+
+/// `line` with its comments replaced by spaces
+fn strip_comments(line: &str, in_comment: &mut bool) -> String {
+    pieces(line, in_comment)
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Ident(text) | Piece::Other(text) => text,
+            Piece::Comment(_) => " ",
+        })
+        .collect()
+}
+
+/// Split the leading identifier off `text`
+fn split_ident(text: &str) -> Option<(&str, &str)> {
+    let end = text
+        .find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+        .unwrap_or(text.len());
+    let starts_with_digit = text.starts_with(|c: char| c.is_ascii_digit());
+    (end > 0 && !starts_with_digit).then(|| text.split_at(end))
+}
+
+/// An `#ifdef` or `#ifndef` whose `#endif` has not been reached yet
+struct Conditional {
+    /// Whether the lines of the current branch are kept
+    taken: bool,
+    seen_else: bool,
+    /// Line of the `#ifdef`, for reporting a missing `#endif`
+    line: usize,
+}
+
+struct Preprocessor {
+    /// Replacement text of each defined macro
+    macros: HashMap<String, String>,
+    out: Preprocessed,
+    /// Number of files being included
+    depth: usize,
+}
+
+impl Preprocessor {
+    fn push_line(&mut self, text: &str, file: &str, line: usize) {
+        self.out.text.push_str(text);
+        self.out.origins.push(LineOrigin { file: file.to_string(), line });
+    }
+
+    /// Expand the macros in `text`, except those in `disabled`, which are
+    /// being expanded already
+    fn expand(&self, text: &str, in_comment: &mut bool, disabled: &mut Vec<String>) -> String {
+        let mut out = String::new();
+        for piece in pieces(text, in_comment) {
+            match piece {
+                Piece::Ident(name) if !disabled.iter().any(|macro_name| macro_name == name) => {
+                    match self.macros.get(name) {
+                        Some(replacement) => {
+                            disabled.push(name.to_string());
+                            out.push_str(&self.expand(replacement, &mut false, disabled));
+                            disabled.pop();
+                        }
+                        None => out.push_str(name),
+                    }
+                }
+                Piece::Ident(text) | Piece::Comment(text) | Piece::Other(text) => out.push_str(text),
+            }
+        }
+        out
+    }
+
+    fn file(&mut self, name: &str, text: &str) -> Result<(), PreprocessError> {
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        let mut in_comment = false;
+        for (i, line) in text.split_inclusive('\n').enumerate() {
+            let line_no = i + 1;
+            let error = |message: String| PreprocessError { file: name.to_string(), line: line_no, message };
+            let taken = conditionals.iter().all(|conditional| conditional.taken);
+            // Whether the enclosing conditionals, if any, are all taken
+            let outer_taken = conditionals.iter().rev().skip(1).all(|conditional| conditional.taken);
+            let newline = if line.ends_with('\n') { "\n" } else { "" };
+
+            // A line starting inside a block comment is not a directive
+            let mut line_in_comment = in_comment;
+            let code = strip_comments(line, &mut in_comment);
+            let Some(directive) = code.trim_start().strip_prefix('#').filter(|_| !line_in_comment) else {
+                let text = if taken {
+                    self.expand(line, &mut line_in_comment, &mut Vec::new())
+                } else {
+                    newline.to_string()
+                };
+                self.push_line(&text, name, line_no);
+                continue;
+            };
+            let directive = directive.trim();
+            let (keyword, rest) = split_ident(directive).unwrap_or((directive, ""));
+            let rest = rest.trim();
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let Some((macro_name, "")) = split_ident(rest) else {
+                        return Err(error(format!("#{} expects a single macro name", keyword)));
+                    };
+                    let defined = self.macros.contains_key(macro_name);
+                    let taken = defined == (keyword == "ifdef");
+                    conditionals.push(Conditional { taken, seen_else: false, line: line_no });
+                }
+                // Only the nesting of `#if`s matters in a skipped region
+                "if" if !taken => conditionals.push(Conditional { taken: false, seen_else: false, line: line_no }),
+                "else" => {
+                    let Some(conditional) = conditionals.last_mut() else {
+                        return Err(error("#else without #ifdef".into()));
+                    };
+                    if conditional.seen_else {
+                        return Err(error("#else after #else".into()));
+                    }
+                    conditional.taken = !conditional.taken;
+                    conditional.seen_else = true;
+                }
+                "endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(error("#endif without #ifdef".into()));
+                    }
+                }
+                "elif" if outer_taken => return Err(error("#elif is not supported".into())),
+                _ if !taken => {}
+                "define" => {
+                    let Some((macro_name, body)) = split_ident(rest) else {
+                        return Err(error("#define expects a macro name".into()));
+                    };
+                    if body.starts_with('(') {
+                        return Err(error(format!("'{}': function-like macros are not supported", macro_name)));
+                    }
+                    let body = body.trim();
+                    match self.macros.get(macro_name) {
+                        Some(old) if old != body => {
+                            return Err(error(format!("'{}' redefined with a different value", macro_name)));
+                        }
+                        _ => {}
+                    }
+                    self.macros.insert(macro_name.to_string(), body.to_string());
+                }
+                "undef" => {
+                    let Some((macro_name, "")) = split_ident(rest) else {
+                        return Err(error("#undef expects a single macro name".into()));
+                    };
+                    self.macros.remove(macro_name);
+                }
+                "include" => {
+                    let Some(path) = rest.strip_prefix('"').and_then(|path| path.strip_suffix('"')) else {
+                        return Err(error(format!(
+                            "#include {} is not supported; only #include \"file\" is, relative to the including file",
+                            rest
+                        )));
+                    };
+                    if self.depth == MAX_INCLUDE_DEPTH {
+                        return Err(error(format!(
+                            "#include nested more than {} deep; does a header include itself without an include guard?",
+                            MAX_INCLUDE_DEPTH
+                        )));
+                    }
+                    let dir = Path::new(name).parent().unwrap_or(Path::new(""));
+                    let path = dir.join(path).display().to_string();
+                    let mut included = std::fs::read_to_string(&path)
+                        .map_err(|e| error(format!("cannot open '{}': {}", path, e)))?;
+                    // The line after the #include must not be joined to the
+                    // last line of the header
+                    if !included.is_empty() && !included.ends_with('\n') {
+                        included.push('\n');
+                    }
+                    self.depth += 1;
+                    self.file(&path, &included)?;
+                    self.depth -= 1;
+                    continue;
+                }
+                // The null directive
+                "" => {}
+                _ => return Err(error(format!("unsupported preprocessing directive '#{}'", keyword))),
+            }
+            self.push_line(newline, name, line_no);
+        }
+        match conditionals.last() {
+            Some(conditional) => Err(PreprocessError {
+                file: name.to_string(),
+                line: conditional.line,
+                message: "missing #endif for this conditional".into(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
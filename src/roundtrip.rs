@@ -0,0 +1,94 @@
+//! Round-trip check of the emitted Koopa IR (`--roundtrip-ir`).
+//!
+//! The IR is printed, parsed back with koopa's own parser, and the two
+//! programs are compared structurally. This catches printing problems (missing
+//! types, invalid names) that only show up when another tool reads our IR.
+
+use std::mem::discriminant;
+
+use koopa::back::KoopaGenerator;
+use koopa::front::Driver;
+use koopa::ir::{FunctionData, Program};
+
+/// Print `program`, parse the text back, and describe the first structural
+/// difference between the two programs, if any
+pub fn check(program: &Program) -> Result<(), String> {
+    let mut text = Vec::new();
+    KoopaGenerator::new(&mut text)
+        .generate_on(program)
+        .map_err(|e| format!("failed to print IR: {}", e))?;
+    let text = String::from_utf8(text).map_err(|e| format!("IR is not UTF-8: {}", e))?;
+    let parsed = Driver::from(text)
+        .generate_program()
+        .map_err(|e| format!("koopa rejected the emitted IR: {:?}", e))?;
+    diff(program, &parsed)
+}
+
+/// Compare two programs: the same globals, and the same functions (in layout
+/// order) with the same names, types, basic blocks and instruction kinds
+fn diff(ours: &Program, parsed: &Program) -> Result<(), String> {
+    if ours.inst_layout().len() != parsed.inst_layout().len() {
+        return Err(format!(
+            "{} globals emitted, {} parsed back",
+            ours.inst_layout().len(),
+            parsed.inst_layout().len(),
+        ));
+    }
+    for (&a, &b) in ours.inst_layout().iter().zip(parsed.inst_layout()) {
+        let (a, b) = (ours.borrow_value(a), parsed.borrow_value(b));
+        if a.name() != b.name() || a.ty() != b.ty() {
+            return Err(format!(
+                "global {:?}: {} emitted, {:?}: {} parsed back",
+                a.name(), a.ty(), b.name(), b.ty(),
+            ));
+        }
+    }
+
+    if ours.func_layout().len() != parsed.func_layout().len() {
+        return Err(format!(
+            "{} functions emitted, {} parsed back",
+            ours.func_layout().len(),
+            parsed.func_layout().len(),
+        ));
+    }
+    for (&a, &b) in ours.func_layout().iter().zip(parsed.func_layout()) {
+        diff_func(ours.func(a), parsed.func(b))?;
+    }
+    Ok(())
+}
+
+fn diff_func(ours: &FunctionData, parsed: &FunctionData) -> Result<(), String> {
+    let name = ours.name();
+    if name != parsed.name() || ours.ty() != parsed.ty() {
+        return Err(format!(
+            "function {}: {} emitted, {}: {} parsed back",
+            name, ours.ty(), parsed.name(), parsed.ty(),
+        ));
+    }
+    let (bbs, parsed_bbs) = (ours.layout().bbs(), parsed.layout().bbs());
+    if bbs.len() != parsed_bbs.len() {
+        return Err(format!(
+            "function {}: {} basic blocks emitted, {} parsed back",
+            name, bbs.len(), parsed_bbs.len(),
+        ));
+    }
+    for (i, ((_, node), (_, parsed_node))) in bbs.iter().zip(parsed_bbs.iter()).enumerate() {
+        let (insts, parsed_insts) = (node.insts(), parsed_node.insts());
+        if insts.len() != parsed_insts.len() {
+            return Err(format!(
+                "function {}, block {}: {} instructions emitted, {} parsed back",
+                name, i, insts.len(), parsed_insts.len(),
+            ));
+        }
+        for (&inst, &parsed_inst) in insts.keys().zip(parsed_insts.keys()) {
+            let (a, b) = (ours.dfg().value(inst), parsed.dfg().value(parsed_inst));
+            if discriminant(a.kind()) != discriminant(b.kind()) || a.ty() != b.ty() {
+                return Err(format!(
+                    "function {}, block {}: {:?}: {} emitted, {:?}: {} parsed back",
+                    name, i, a.kind(), a.ty(), b.kind(), b.ty(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
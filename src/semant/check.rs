@@ -0,0 +1,782 @@
+//! Type checking, run on the AST before it is lowered to Koopa IR.
+//!
+//! The checker resolves names and validates what IR emission takes for
+//! granted:
+//!
+//! - every name is declared before it is used, and only once per scope (the
+//!   parameters of a function share the scope of its body);
+//! - operands, conditions and initializers are values, not arrays, string
+//!   literals or the result of a call returning nothing;
+//! - only variables are assigned, and arrays are indexed with `int`s at most
+//!   once per dimension;
+//! - calls match the signature of the callee, array arguments included, down
+//!   to the lengths of their dimensions;
+//! - no `/` or `%` has a constant divisor of zero;
+//! - `return` has a value exactly when the function is not `void`;
+//! - `break` and `continue` are inside a loop (or a `switch`, for `break`);
+//! - array sizes, `case` labels and the initializers of constants, globals
+//!   and static locals are constant;
+//! - `main` is defined as `int main()`.
+//!
+//! Errors are reported with their location here rather than as a panic
+//! halfway through emission.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::rc::Rc;
+
+use super::SymbolTable;
+use crate::ast::const_eval::{self, ConstError, ConstScope, ConstValue, Scalar};
+use crate::ast::*;
+use crate::diag::Diagnostic;
+
+/// The type of an expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    /// An `int`; `char` values are ints too
+    Int,
+    Float,
+    /// The pointer an array decays to: the element type, and the dimensions
+    /// of the arrays it points to (empty for a pointer to scalars)
+    Array(BType, Vec<usize>),
+    /// A string literal, only allowed as the format of `putf`
+    Str,
+    /// The result of a call to a function that returns nothing
+    Void,
+}
+
+impl Type {
+    /// The type of the value of a scalar of type `btype`
+    fn scalar(btype: BType) -> Self {
+        match btype {
+            BType::Float => Self::Float,
+            BType::Int | BType::Char => Self::Int,
+        }
+    }
+
+    /// Whether this is an `int` or `float` value, which operators apply to
+    fn is_value(&self) -> bool {
+        matches!(self, Self::Int | Self::Float)
+    }
+
+    /// The type of an arithmetic result on values of types `self` and `other`
+    fn arithmetic(&self, other: &Self) -> Self {
+        if *self == Self::Float || *other == Self::Float { Self::Float } else { Self::Int }
+    }
+}
+
+impl From<&FuncType> for Type {
+    fn from(func_type: &FuncType) -> Self {
+        match func_type {
+            FuncType::Int => Self::Int,
+            FuncType::Float => Self::Float,
+            FuncType::Void => Self::Void,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::Array(btype, dims) => {
+                write!(f, "{}[]", btype)?;
+                for dim in dims {
+                    write!(f, "[{}]", dim)?;
+                }
+                Ok(())
+            }
+            Self::Str => write!(f, "a string literal"),
+            Self::Void => write!(f, "void"),
+        }
+    }
+}
+
+/// Parameter and return types of a function
+#[derive(Debug, Clone)]
+pub struct Signature {
+    /// `Type::Void` for a function that returns nothing
+    pub ret: Type,
+    /// The type of each parameter: a value, an array, or a string literal
+    /// for the format of `putf`
+    pub params: Vec<Type>,
+    /// Whether more arguments than parameters may be passed (`putf`)
+    pub variadic: bool,
+}
+
+/// What type checking learned about a well-formed program
+#[derive(Debug, Default)]
+pub struct TypedInfo {
+    /// The type of every expression, by id
+    pub types: HashMap<ExprId, Type>,
+}
+
+/// A variable, parameter or constant
+#[derive(Debug, Clone)]
+struct Object {
+    btype: BType,
+    /// Number of array dimensions (0 for a scalar)
+    rank: usize,
+    /// Lengths of the dimensions after the first, which an array parameter
+    /// omits
+    inner_dims: Vec<usize>,
+    /// The value of a constant; `None` for a variable
+    value: Option<ConstValue>,
+}
+
+impl Object {
+    fn new(btype: BType, dims: &[usize], value: Option<ConstValue>) -> Self {
+        let inner_dims = dims.get(1..).unwrap_or_default().to_vec();
+        Self { btype, rank: dims.len(), inner_dims, value }
+    }
+}
+
+/// What a name refers to
+#[derive(Debug, Clone)]
+enum Symbol {
+    Object(Object),
+    Func(Rc<Signature>),
+    /// A declaration whose error has already been reported, such as an array
+    /// with a size that is not constant: its uses are not checked
+    Invalid,
+}
+
+/// `ConstError` messages are written for panics; diagnostics start in
+/// lowercase
+fn const_error(error: &ConstError) -> String {
+    let message = error.to_string();
+    let mut chars = message.chars();
+    chars.next().map(|c| c.to_lowercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// `n` followed by `word`, pluralized unless `n` is 1
+fn count(n: usize, word: &str) -> String {
+    format!("{} {}{}", n, word, if n == 1 { "" } else { "s" })
+}
+
+/// The span of `ident` at the start of `span`, such as the name of a call
+fn name_span(ident: &Ident, span: Span) -> Span {
+    Span::new(span.start, span.start + ident.value.len())
+}
+
+struct Checker<'a> {
+    exprs: &'a ExprArena,
+    /// Each name in scope, and the span of its definition (`None` for the
+    /// runtime library)
+    symbols: SymbolTable<(Symbol, Option<Span>)>,
+    /// The function being checked and its return type
+    func: Option<(Ident, Type)>,
+    /// Number of loops around the statement being checked, which `continue`
+    /// needs
+    loops: usize,
+    /// Number of loops and `switch`es around it, which `break` needs
+    breakables: usize,
+    info: TypedInfo,
+    diags: Vec<Diagnostic>,
+}
+
+impl ConstScope for Checker<'_> {
+    fn lookup_const(&self, ident: &Ident) -> Option<ConstValue> {
+        match self.lookup(ident) {
+            Some(Symbol::Object(object)) => object.value.clone(),
+            _ => None,
+        }
+    }
+}
+
+impl Checker<'_> {
+    fn error(&mut self, span: Span, message: impl Into<String>) {
+        self.diags.push(Diagnostic::error(span, message));
+    }
+
+    fn lookup(&self, ident: &Ident) -> Option<&Symbol> {
+        self.symbols.lookup(ident).map(|(symbol, _)| symbol)
+    }
+
+    /// Declare `ident`, defined at `span`, reporting it if the name is
+    /// already defined in the same scope
+    fn declare(&mut self, ident: &Ident, span: Span, symbol: Symbol) {
+        let Some((_, first)) = self.symbols.declare(ident, (symbol, Some(span))) else {
+            return;
+        };
+        let diag = match first {
+            Some(first) => Diagnostic::error(span, format!("redefinition of '{}'", ident))
+                .with_note(first, "previous definition is here"),
+            None => Diagnostic::error(span, format!("redefinition of runtime library function '{}'", ident)),
+        };
+        self.diags.push(diag);
+    }
+
+    /// Declare the functions of the SysY runtime library, as IR emission does
+    fn declare_runtime(&mut self) {
+        let array = || Type::Array(BType::Int, Vec::new());
+        let float_array = || Type::Array(BType::Float, Vec::new());
+        let runtime = [
+            ("getint", vec![], Type::Int),
+            ("getch", vec![], Type::Int),
+            ("getfloat", vec![], Type::Float),
+            ("getarray", vec![array()], Type::Int),
+            ("getfarray", vec![float_array()], Type::Int),
+            ("putint", vec![Type::Int], Type::Void),
+            ("putch", vec![Type::Int], Type::Void),
+            ("putfloat", vec![Type::Float], Type::Void),
+            ("putarray", vec![Type::Int, array()], Type::Void),
+            ("putfarray", vec![Type::Int, float_array()], Type::Void),
+            ("starttime", vec![], Type::Void),
+            ("stoptime", vec![], Type::Void),
+        ];
+        for (name, params, ret) in runtime {
+            let signature = Signature { ret, params, variadic: false };
+            self.symbols.declare(&Ident::from(name.to_string()), (Symbol::Func(Rc::new(signature)), None));
+        }
+        let putf = Signature { ret: Type::Void, params: vec![Type::Str], variadic: true };
+        self.symbols.declare(&Ident::from("putf".to_string()), (Symbol::Func(Rc::new(putf)), None));
+    }
+
+    /// Type `expr` and its operands, or `None` if it has an error (already
+    /// reported)
+    fn expr(&mut self, expr: ExprId) -> Option<Type> {
+        let ty = self.expr_type(expr)?;
+        self.info.types.insert(expr, ty.clone());
+        Some(ty)
+    }
+
+    fn expr_type(&mut self, expr: ExprId) -> Option<Type> {
+        let exprs = self.exprs;
+        let span = exprs.span(expr);
+        match &exprs[expr] {
+            Expr::Number(_) | Expr::Char(_) => Some(Type::Int),
+            Expr::Float(_) => Some(Type::Float),
+            Expr::Str(_) => Some(Type::Str),
+            Expr::Unary(op, operand) => {
+                let ty = self.expect_value(*operand, &format!("the operand of '{}'", op))?;
+                Some(if let UnaryOp::Not = op { Type::Int } else { ty })
+            }
+            Expr::Binary(lhs, op, rhs) => {
+                let what = format!("an operand of '{}'", op);
+                let divisor = *rhs;
+                let (lhs, rhs) = (self.expect_value(*lhs, &what), self.expect_value(*rhs, &what));
+                let (lhs, rhs) = (lhs?, rhs?);
+                // It would trap at run time, or be folded into the wrong value
+                if matches!(op, BinaryOp::Div | BinaryOp::Mod) && const_eval::eval(exprs, divisor, &*self) == Ok(0) {
+                    let what = if let BinaryOp::Div = op { "division" } else { "remainder" };
+                    self.error(span, format!("{} by zero is undefined", what));
+                    return None;
+                }
+                match op {
+                    BinaryOp::Mod if lhs == Type::Float || rhs == Type::Float => {
+                        self.error(span, "the operands of '%' must be ints, not float");
+                        None
+                    }
+                    BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
+                        Some(lhs.arithmetic(&rhs))
+                    }
+                    // Comparisons and logical operators yield 0 or 1
+                    _ => Some(Type::Int),
+                }
+            }
+            Expr::Bitwise(lhs, op, rhs, _) => {
+                let what = format!("an operand of '{}'", op);
+                let (lhs, rhs) = (self.expect_int(*lhs, &what), self.expect_int(*rhs, &what));
+                lhs.and(rhs)
+            }
+            Expr::Ternary(cond, then_expr, else_expr, op_span) => {
+                self.expect_value(*cond, "the condition of '?:'");
+                let (then_ty, else_ty) = (self.expr(*then_expr), self.expr(*else_expr));
+                match (then_ty?, else_ty?) {
+                    (then_ty, else_ty) if then_ty.is_value() && else_ty.is_value() => {
+                        Some(then_ty.arithmetic(&else_ty))
+                    }
+                    // Arrays of the same type decay to the same pointer type
+                    (then_ty @ Type::Array(..), else_ty) if then_ty == else_ty => Some(then_ty),
+                    (then_ty, else_ty) => {
+                        self.error(
+                            *op_span,
+                            format!("the arms of '?:' have incompatible types: {} and {}", then_ty, else_ty),
+                        );
+                        None
+                    }
+                }
+            }
+            Expr::LVal(lval) => self.lval(lval).map(|(_, ty)| ty),
+            Expr::Call(ident, args) => self.call(ident, args, span),
+        }
+    }
+
+    /// Type `expr`, reporting it unless it is an `int` or `float` value
+    fn expect_value(&mut self, expr: ExprId, what: &str) -> Option<Type> {
+        let ty = self.expr(expr)?;
+        if ty.is_value() {
+            return Some(ty);
+        }
+        let span = self.exprs.span(expr);
+        let message = match &self.exprs[expr] {
+            Expr::Call(ident, _) if ty == Type::Void => {
+                format!("'{}' returns void, so its result cannot be used as {}", ident, what)
+            }
+            _ => format!("{} must be an int or float value, not {}", what, ty),
+        };
+        self.error(span, message);
+        None
+    }
+
+    /// Type `expr`, reporting it unless it is an `int` value
+    fn expect_int(&mut self, expr: ExprId, what: &str) -> Option<Type> {
+        let ty = self.expect_value(expr, what)?;
+        if ty == Type::Int {
+            return Some(ty);
+        }
+        let span = self.exprs.span(expr);
+        self.error(span, format!("{} must be an int, not {}", what, ty));
+        None
+    }
+
+    /// Evaluate the constant expression `expr`, reporting it if it is not
+    /// constant or not an `int`
+    fn constant(&mut self, expr: ExprId, what: &str) -> Option<i32> {
+        match self.constant_value(expr, what)? {
+            Scalar::Int(value) => Some(value),
+            Scalar::Float(_) => {
+                let span = self.exprs.span(expr);
+                self.error(span, format!("{} must be an int, not float", what));
+                None
+            }
+        }
+    }
+
+    /// Evaluate the constant expression `expr` of either type, reporting it
+    /// if it is not constant
+    fn constant_value(&mut self, expr: ExprId, what: &str) -> Option<Scalar> {
+        self.expect_value(expr, what)?;
+        match const_eval::eval_scalar(self.exprs, expr, &*self) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                let span = self.exprs.span(expr);
+                self.error(span, format!("{} must be a constant expression: {}", what, const_error(&e)));
+                None
+            }
+        }
+    }
+
+    /// Evaluate the array dimensions of `ident`, outermost first
+    fn dims(&mut self, dims: &[ConstExpr], ident: &Ident) -> Option<Vec<usize>> {
+        let mut lens = Vec::new();
+        let mut valid = true;
+        for dim in dims {
+            match self.constant(dim.expr, "an array size") {
+                Some(len) if len > 0 => lens.push(len as usize),
+                Some(len) => {
+                    let span = self.exprs.span(dim.expr);
+                    self.error(span, format!("the size of array '{}' must be positive, not {}", ident, len));
+                    valid = false;
+                }
+                None => valid = false,
+            }
+        }
+        valid.then_some(lens)
+    }
+
+    /// Check an initializer of an object with dimensions `dims`: its shape,
+    /// and that its elements are values, constant ones if `constant`.
+    /// Returns whether it is valid.
+    fn init(&mut self, init: &InitVal, dims: &[usize], def_id: &Ident, span: Span, constant: bool) -> bool {
+        let elems = match init.try_flatten(dims, def_id) {
+            Ok(elems) => elems,
+            Err(message) => {
+                self.error(span, message);
+                return false;
+            }
+        };
+        let mut valid = true;
+        for expr in elems.into_iter().flatten() {
+            valid &= if constant {
+                self.constant_value(expr, "an initializer").is_some()
+            } else {
+                self.expect_value(expr, "an initializer").is_some()
+            };
+        }
+        valid
+    }
+
+    /// Resolve an lvalue to the object it names and the type of its value
+    fn lval(&mut self, lval: &LVal) -> Option<(Object, Type)> {
+        for &index in &lval.indices {
+            self.expect_int(index, "an array index");
+        }
+        let object = match self.lookup(&lval.ident) {
+            Some(Symbol::Object(object)) => object.clone(),
+            Some(Symbol::Func(_)) => {
+                self.error(lval.span, format!("'{}' is a function, not a variable", lval.ident));
+                return None;
+            }
+            Some(Symbol::Invalid) => return None,
+            None => {
+                let span = name_span(&lval.ident, lval.span);
+                self.error(span, format!("use of undeclared identifier '{}'", lval.ident));
+                return None;
+            }
+        };
+        let indices = lval.indices.len();
+        if indices > object.rank {
+            let message = match object.rank {
+                0 => format!("'{}' is not an array and cannot be indexed", lval.ident),
+                rank => format!(
+                    "'{}' has {} but is indexed {} times",
+                    lval.ident,
+                    count(rank, "dimension"),
+                    indices
+                ),
+            };
+            self.error(lval.span, message);
+            return None;
+        }
+        let ty = if indices == object.rank {
+            Type::scalar(object.btype)
+        } else {
+            Type::Array(object.btype, object.inner_dims[indices..].to_vec())
+        };
+        Some((object, ty))
+    }
+
+    fn call(&mut self, ident: &Ident, args: &[ExprId], span: Span) -> Option<Type> {
+        let arg_types: Vec<Option<Type>> = args.iter().map(|&arg| self.expr(arg)).collect();
+        let signature = match self.lookup(ident) {
+            Some(Symbol::Func(signature)) => signature.clone(),
+            Some(Symbol::Object(_)) => {
+                self.error(span, format!("'{}' is not a function", ident));
+                return None;
+            }
+            Some(Symbol::Invalid) => return None,
+            None => {
+                // SysY has no prototypes: a function must be defined before
+                // its first call
+                self.error(name_span(ident, span), format!("call to undeclared function '{}'", ident));
+                return None;
+            }
+        };
+        let params = signature.params.len();
+        if args.len() < params || (args.len() > params && !signature.variadic) {
+            let at_least = if signature.variadic { "at least " } else { "" };
+            self.error(
+                span,
+                format!("'{}' takes {}{}, but {} given", ident, at_least, count(params, "argument"), args.len()),
+            );
+        }
+        for (i, (&arg, ty)) in args.iter().zip(arg_types).enumerate() {
+            let Some(ty) = ty else {
+                continue;
+            };
+            let message = match signature.params.get(i) {
+                Some(Type::Str) if matches!(self.exprs[arg], Expr::Str(_)) => continue,
+                Some(Type::Str) => format!("argument {} of '{}' must be a string literal, not {}", i + 1, ident, ty),
+                Some(param @ Type::Array(..)) if *param == ty => continue,
+                // Indexing the parameter would use the wrong row length
+                Some(param @ Type::Array(..)) if matches!(ty, Type::Array(..)) => format!(
+                    "argument {} of '{}' has type {}, but the parameter has type {}",
+                    i + 1,
+                    ident,
+                    ty,
+                    param
+                ),
+                Some(Type::Array(..)) => format!("argument {} of '{}' must be an array, not {}", i + 1, ident, ty),
+                // C passes the extra arguments of a variadic function as
+                // `double`s, which the IR has no room for
+                None if ty == Type::Float => format!(
+                    "argument {} of '{}' is a float, which cannot be passed as an extra argument",
+                    i + 1,
+                    ident
+                ),
+                // Values, including the extra arguments of `putf`
+                _ if ty.is_value() => continue,
+                _ => format!("argument {} of '{}' must be an int or float value, not {}", i + 1, ident, ty),
+            };
+            let span = self.exprs.span(arg);
+            self.error(span, message);
+        }
+        Some(signature.ret.clone())
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Const(decl) => {
+                for def in &decl.defs {
+                    let symbol = self.const_def(decl.btype, def);
+                    self.declare(&def.id, def.span, symbol);
+                }
+            }
+            Decl::Var(decl) => self.visit_var_decl(decl),
+            Decl::Enum(decl) => {
+                let mut next = Some(0);
+                for enumerator in &decl.enumerators {
+                    // An enumerator without a value after an invalid one is
+                    // invalid too, without a second error
+                    let value = match &enumerator.value {
+                        Some(value) => self.constant(value.expr, "an enumerator value"),
+                        None => next,
+                    };
+                    let symbol = match value {
+                        Some(value) => Symbol::Object(Object::new(BType::Int, &[], Some(ConstValue::Int(value)))),
+                        None => Symbol::Invalid,
+                    };
+                    self.declare(&enumerator.id, enumerator.span, symbol);
+                    next = value.map(|value| value.wrapping_add(1));
+                }
+            }
+        }
+    }
+
+    fn const_def(&mut self, btype: BType, def: &ConstDef) -> Symbol {
+        let Some(dims) = self.dims(&def.dims, &def.id) else {
+            return Symbol::Invalid;
+        };
+        if !self.init(&def.init, &dims, &def.id, def.span, true) {
+            return Symbol::Invalid;
+        }
+        match const_eval::eval_def(self.exprs, btype, def, &*self) {
+            Ok(value) => Symbol::Object(Object::new(btype, &dims, Some(value))),
+            Err(e) => {
+                self.error(def.span, const_error(&e));
+                Symbol::Invalid
+            }
+        }
+    }
+
+    fn visit_var_decl(&mut self, decl: &VarDecl) {
+        // Globals and static locals are initialized before the program starts
+        let constant = self.symbols.is_file_scope() || decl.static_span.is_some();
+        for def in &decl.defs {
+            let Some(dims) = self.dims(&def.dims, &def.id) else {
+                self.declare(&def.id, def.span, Symbol::Invalid);
+                continue;
+            };
+            // As in emission, the variable is in scope in its own initializer
+            self.declare(&def.id, def.span, Symbol::Object(Object::new(decl.btype, &dims, None)));
+            if let Some(init) = &def.init {
+                self.init(init, &dims, &def.id, def.span, constant);
+            }
+        }
+    }
+
+    fn visit_func_def(&mut self, func_def: &FuncDef) {
+        let param_types: Vec<Option<Type>> = func_def
+            .params
+            .iter()
+            .map(|param| match &param.dims {
+                Some(dims) => self.dims(dims, &param.id).map(|dims| Type::Array(param.btype, dims)),
+                None => Some(Type::scalar(param.btype)),
+            })
+            .collect();
+        let ret = Type::from(&func_def.func_type);
+        let symbol = match param_types.iter().cloned().collect::<Option<Vec<Type>>>() {
+            Some(params) => Symbol::Func(Rc::new(Signature { ret: ret.clone(), params, variadic: false })),
+            None => Symbol::Invalid,
+        };
+        // Declared before the body, so that the function can call itself
+        self.declare(&func_def.id, func_def.span, symbol);
+        self.func = Some((func_def.id.clone(), ret));
+
+        self.symbols.enter_scope();
+        for (param, ty) in func_def.params.iter().zip(param_types) {
+            let symbol = match ty {
+                Some(Type::Array(btype, dims)) => {
+                    Symbol::Object(Object { btype, rank: dims.len() + 1, inner_dims: dims, value: None })
+                }
+                Some(_) => Symbol::Object(Object::new(param.btype, &[], None)),
+                None => Symbol::Invalid,
+            };
+            self.declare(&param.id, param.span, symbol);
+        }
+        // The parameters are in the scope of the body, so that they cannot
+        // be redefined there
+        self.visit_items(&func_def.block.items);
+        self.symbols.exit_scope();
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.symbols.enter_scope();
+        self.visit_items(&block.items);
+        self.symbols.exit_scope();
+    }
+
+    fn visit_items(&mut self, items: &[BlockItem]) {
+        for item in items {
+            match item {
+                BlockItem::Decl(decl) => self.visit_decl(decl),
+                BlockItem::Stmt(stmt) => self.visit_stmt(stmt),
+            }
+        }
+    }
+
+    fn visit_assign(&mut self, assign: &AssignStmt) {
+        let target = self.lval(&assign.lval);
+        self.expect_value(assign.expr, "the assigned value");
+        let Some((object, ty)) = target else {
+            return;
+        };
+        let lval = &assign.lval;
+        if object.value.is_some() {
+            self.error(lval.span, format!("cannot assign to constant '{}'", lval.ident));
+        } else if let Type::Array(..) = ty {
+            self.error(lval.span, format!("cannot assign to array '{}' as a whole", lval.ident));
+        }
+    }
+
+    fn visit_return(&mut self, return_stmt: &ReturnStmt) {
+        let (func, ret) = self.func.clone().expect("return outside of a function");
+        match (return_stmt.expr, ret) {
+            (Some(expr), Type::Void) => {
+                self.expr(expr);
+                let span = self.exprs.span(expr);
+                self.error(span, format!("void function '{}' should not return a value", func));
+            }
+            (Some(expr), _) => {
+                self.expect_value(expr, "the return value");
+            }
+            (None, Type::Void) => {}
+            (None, ret) => {
+                let message = format!("non-void function '{}' should return a value of type {}", func, ret);
+                self.error(return_stmt.span, message);
+            }
+        }
+    }
+
+    fn visit_for_step(&mut self, step: &ForStep) {
+        match step {
+            ForStep::Assign(assign) => self.visit_assign(assign),
+            ForStep::Expr(expr) => {
+                self.expr(*expr);
+            }
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Return(return_stmt) => self.visit_return(return_stmt),
+            Stmt::Assign(assign) => self.visit_assign(assign),
+            Stmt::Block(block) => self.visit_block(block),
+            Stmt::Expr(expr) => {
+                if let Some(expr) = expr {
+                    self.expr(*expr);
+                }
+            }
+            Stmt::If(if_stmt) => {
+                self.expect_value(if_stmt.cond, "a condition");
+                self.visit_stmt(&if_stmt.then_stmt);
+                if let Some(else_stmt) = &if_stmt.else_stmt {
+                    self.visit_stmt(else_stmt);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.expect_value(while_stmt.cond, "a condition");
+                self.visit_loop_body(&while_stmt.body);
+            }
+            Stmt::For(for_stmt) => {
+                self.symbols.enter_scope();
+                match &for_stmt.init {
+                    Some(ForInit::Decl(decl)) => self.visit_var_decl(decl),
+                    Some(ForInit::Step(step)) => self.visit_for_step(step),
+                    None => {}
+                }
+                if let Some(cond) = for_stmt.cond {
+                    self.expect_value(cond, "a condition");
+                }
+                if let Some(step) = &for_stmt.step {
+                    self.visit_for_step(step);
+                }
+                self.visit_loop_body(&for_stmt.body);
+                self.symbols.exit_scope();
+            }
+            Stmt::DoWhile(do_while) => {
+                self.visit_loop_body(&do_while.body);
+                self.expect_value(do_while.cond, "a condition");
+            }
+            Stmt::Switch(switch) => {
+                self.expect_int(switch.expr, "the value of a switch");
+                let mut cases: HashMap<i32, Span> = HashMap::new();
+                let mut default: Option<Span> = None;
+                for label in &switch.labels {
+                    let first = match label.value {
+                        Some(value) => match self.constant(value, "a case value") {
+                            Some(value) => cases.insert(value, label.span).map(|first| (first, "duplicate case value")),
+                            None => None,
+                        },
+                        None => default.replace(label.span).map(|first| (first, "multiple default labels")),
+                    };
+                    if let Some((first, message)) = first {
+                        self.diags.push(
+                            Diagnostic::error(label.span, format!("{} in switch", message))
+                                .with_note(first, "first used here"),
+                        );
+                    }
+                }
+                self.breakables += 1;
+                self.visit_block(&switch.body);
+                self.breakables -= 1;
+            }
+            Stmt::Break(span) if self.breakables == 0 => {
+                self.error(*span, "'break' statement not in a loop or switch");
+            }
+            Stmt::Continue(span) if self.loops == 0 => {
+                self.error(*span, "'continue' statement not in a loop");
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => {}
+        }
+    }
+
+    /// Check that the program defines `int main()`, which the runtime calls
+    fn check_main(&mut self) {
+        let main = Ident::from("main".to_string());
+        let (signature, span) = match self.symbols.lookup(&main) {
+            Some((Symbol::Func(signature), Some(span))) => (signature.clone(), *span),
+            Some((Symbol::Object(_), Some(span))) => {
+                self.error(*span, "'main' must be a function");
+                return;
+            }
+            // An invalid `main` has been reported already
+            Some(_) => return,
+            None => {
+                self.error(Span::default(), "the program has no 'main' function");
+                return;
+            }
+        };
+        if signature.ret != Type::Int {
+            self.error(span, format!("'main' must return int, not {}", signature.ret));
+        }
+        if !signature.params.is_empty() {
+            let params = count(signature.params.len(), "parameter");
+            self.error(span, format!("'main' must take no parameters, not {}", params));
+        }
+    }
+
+    fn visit_loop_body(&mut self, body: &Stmt) {
+        self.loops += 1;
+        self.breakables += 1;
+        self.visit_stmt(body);
+        self.loops -= 1;
+        self.breakables -= 1;
+    }
+}
+
+/// Type check a compilation unit, returning the types of its expressions or
+/// the errors found
+pub fn check(ast: &CompUnit) -> Result<TypedInfo, Vec<Diagnostic>> {
+    let mut checker = Checker {
+        exprs: &ast.exprs,
+        symbols: SymbolTable::new(),
+        func: None,
+        loops: 0,
+        breakables: 0,
+        info: TypedInfo::default(),
+        diags: Vec::new(),
+    };
+    checker.declare_runtime();
+    for item in &ast.items {
+        match item {
+            GlobalItem::Decl(decl) => checker.visit_decl(decl),
+            GlobalItem::FuncDef(func_def) => checker.visit_func_def(func_def),
+        }
+    }
+    checker.check_main();
+    if checker.diags.is_empty() { Ok(checker.info) } else { Err(checker.diags) }
+}
@@ -0,0 +1,8 @@
+//! Name resolution and checks on the AST, shared by the lints and IR
+//! emission.
+
+pub mod check;
+pub mod symbols;
+
+pub use check::{check, Signature, Type, TypedInfo};
+pub use symbols::SymbolTable;
@@ -0,0 +1,71 @@
+//! Scoped symbol tables.
+//!
+//! Every pass that resolves names (IR emission, and the lints that need to
+//! know what a name refers to) walks the AST keeping one of these: a stack of
+//! scopes, from the file scope outwards to the innermost block, each mapping
+//! the names declared in it to whatever the pass records about them (a Koopa
+//! value, a constant's value, a type, the span of the declaration...).
+
+use std::collections::HashMap;
+
+use crate::ast::Ident;
+
+/// A stack of scopes mapping names to symbols of type `T`, innermost last.
+/// The file scope is always present.
+#[derive(Debug, Clone)]
+pub struct SymbolTable<T> {
+    scopes: Vec<HashMap<Ident, T>>,
+}
+
+impl<T> Default for SymbolTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SymbolTable<T> {
+    /// A table with only the (empty) file scope
+    pub fn new() -> Self {
+        Self { scopes: vec![HashMap::new()] }
+    }
+
+    /// Open a scope, such as a block or the parameters of a function
+    pub fn enter_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Close the innermost scope, dropping its symbols
+    pub fn exit_scope(&mut self) {
+        assert!(self.scopes.len() > 1, "cannot exit the file scope");
+        self.scopes.pop();
+    }
+
+    /// Whether the innermost scope is the file scope
+    pub fn is_file_scope(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
+    /// Declare `ident` in the innermost scope, returning the symbol it
+    /// replaces if it was already declared there
+    pub fn declare(&mut self, ident: &Ident, symbol: T) -> Option<T> {
+        self.scopes.last_mut().unwrap().insert(ident.clone(), symbol)
+    }
+
+    /// The symbol `ident` refers to: its declaration in the innermost scope
+    /// that has one
+    pub fn lookup(&self, ident: &Ident) -> Option<&T> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(ident))
+    }
+
+    /// The symbol `ident` is declared as in the innermost scope only
+    pub fn lookup_local(&self, ident: &Ident) -> Option<&T> {
+        self.scopes.last().unwrap().get(ident)
+    }
+
+    /// The symbol `ident` refers to outside the innermost scope, which a
+    /// declaration in that scope would shadow
+    pub fn lookup_enclosing(&self, ident: &Ident) -> Option<&T> {
+        let (_, enclosing) = self.scopes.split_last().unwrap();
+        enclosing.iter().rev().find_map(|scope| scope.get(ident))
+    }
+}
@@ -0,0 +1,156 @@
+//! Structural checks of Koopa IR (`--verify-ir`).
+//!
+//! The passes and the backend assume the IR they are given is well formed;
+//! when it is not, they tend to miscompile rather than crash. The verifier
+//! checks what they rely on:
+//!
+//! - every block ends with its only terminator (`br`, `jump` or `ret`);
+//! - every value is defined before it is used: in an earlier instruction of
+//!   the same block, or in a block that dominates the use;
+//! - the operands of binary operations are `i32`, and calls and jumps pass
+//!   as many arguments as the callee or target takes, of the right types;
+//! - `alloc`s are in the entry block.
+//!
+//! Uses in blocks unreachable from the entry are not checked for dominance,
+//! which is undefined there.
+
+use std::collections::HashMap;
+
+use koopa::ir::{BasicBlock, FunctionData, Program, Type, TypeKind, Value, ValueKind};
+
+use crate::analysis::{Cfg, DomTree};
+
+/// Check every function of `program`, describing the first problem found
+pub fn verify(program: &Program) -> Result<(), String> {
+    for &handle in program.func_layout() {
+        let func = program.func(handle);
+        verify_function(program, func).map_err(|e| format!("in {}: {}", func.name(), e))?;
+    }
+    Ok(())
+}
+
+fn verify_function(program: &Program, func: &FunctionData) -> Result<(), String> {
+    let Some(entry) = func.layout().entry_bb() else {
+        return Ok(());
+    };
+    let cfg = Cfg::new(func);
+    let dom_tree = DomTree::new(&cfg).unwrap();
+
+    // Position of each instruction in its block, and the block of each
+    // block parameter
+    let mut position: HashMap<Value, usize> = HashMap::new();
+    let mut param_bb: HashMap<Value, BasicBlock> = HashMap::new();
+    for (&bb, node) in func.layout().bbs() {
+        position.extend(node.insts().keys().enumerate().map(|(i, &inst)| (inst, i)));
+        param_bb.extend(func.dfg().bb(bb).params().iter().map(|&param| (param, bb)));
+    }
+
+    for (&bb, node) in func.layout().bbs() {
+        let name = block_name(func, bb);
+        let insts: Vec<Value> = node.insts().keys().copied().collect();
+        match insts.last() {
+            Some(&last) if is_terminator(func, last) => {}
+            _ => return Err(format!("block {} does not end with a terminator", name)),
+        }
+        for (i, &inst) in insts.iter().enumerate() {
+            let data = func.dfg().value(inst);
+            if i + 1 < insts.len() && is_terminator(func, inst) {
+                return Err(format!("block {} has a terminator before its end", name));
+            }
+            if matches!(data.kind(), ValueKind::Alloc(_)) && bb != entry {
+                return Err(format!("alloc in block {} instead of the entry block", name));
+            }
+            for used in data.kind().value_uses() {
+                if used.is_global() {
+                    continue;
+                }
+                let def_bb = match func.dfg().value(used).kind() {
+                    ValueKind::Integer(_)
+                    | ValueKind::ZeroInit(_)
+                    | ValueKind::Undef(_)
+                    | ValueKind::Aggregate(_)
+                    | ValueKind::FuncArgRef(_) => continue,
+                    ValueKind::BlockArgRef(_) => param_bb.get(&used).copied(),
+                    _ => func.layout().parent_bb(used),
+                };
+                let Some(def_bb) = def_bb else {
+                    return Err(format!("block {} uses a value that is not in the layout", name));
+                };
+                let defined = if def_bb == bb {
+                    // A block parameter is defined on entry to its block
+                    position.get(&used).is_none_or(|&def| def < i)
+                } else {
+                    !dom_tree.is_reachable(bb) || dom_tree.dominates(def_bb, bb)
+                };
+                if !defined {
+                    return Err(format!("block {} uses a value before it is defined", name));
+                }
+            }
+            check_types(program, func, inst).map_err(|e| format!("block {}: {}", name, e))?;
+        }
+    }
+    Ok(())
+}
+
+fn is_terminator(func: &FunctionData, inst: Value) -> bool {
+    matches!(func.dfg().value(inst).kind(), ValueKind::Branch(_) | ValueKind::Jump(_) | ValueKind::Return(_))
+}
+
+fn block_name(func: &FunctionData, bb: BasicBlock) -> String {
+    func.dfg().bb(bb).name().clone().unwrap_or_else(|| "(unnamed)".to_string())
+}
+
+/// The type of `value`, a global or a value of `func`
+fn ty(program: &Program, func: &FunctionData, value: Value) -> Type {
+    if value.is_global() { program.borrow_value(value).ty().clone() } else { func.dfg().value(value).ty().clone() }
+}
+
+/// Check that the arguments `args` match the parameter types `params`
+fn check_args(program: &Program, func: &FunctionData, args: &[Value], params: &[Type]) -> Result<(), String> {
+    if args.len() != params.len() {
+        return Err(format!("{} arguments passed for {} parameters", args.len(), params.len()));
+    }
+    for (i, (&arg, param)) in args.iter().zip(params).enumerate() {
+        let arg_ty = ty(program, func, arg);
+        if &arg_ty != param {
+            return Err(format!("argument {} has type {}, expected {}", i + 1, arg_ty, param));
+        }
+    }
+    Ok(())
+}
+
+fn check_types(program: &Program, func: &FunctionData, inst: Value) -> Result<(), String> {
+    let block_params = |bb: BasicBlock| -> Vec<Type> {
+        func.dfg().bb(bb).params().iter().map(|&param| func.dfg().value(param).ty().clone()).collect()
+    };
+    match func.dfg().value(inst).kind() {
+        ValueKind::Binary(binary) => {
+            for operand in [binary.lhs(), binary.rhs()] {
+                let operand_ty = ty(program, func, operand);
+                if operand_ty != Type::get_i32() {
+                    return Err(format!("{:?} operand has type {}, expected i32", binary.op(), operand_ty));
+                }
+            }
+        }
+        ValueKind::Call(call) => {
+            let callee = program.func(call.callee());
+            let TypeKind::Function(params, _) = callee.ty().kind() else {
+                unreachable!("functions have function types");
+            };
+            check_args(program, func, call.args(), params).map_err(|e| format!("call of {}: {}", callee.name(), e))?;
+        }
+        ValueKind::Jump(jump) => {
+            let target = jump.target();
+            check_args(program, func, jump.args(), &block_params(target))
+                .map_err(|e| format!("jump to {}: {}", block_name(func, target), e))?;
+        }
+        ValueKind::Branch(branch) => {
+            for (target, args) in [(branch.true_bb(), branch.true_args()), (branch.false_bb(), branch.false_args())] {
+                check_args(program, func, args, &block_params(target))
+                    .map_err(|e| format!("branch to {}: {}", block_name(func, target), e))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
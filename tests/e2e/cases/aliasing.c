@@ -0,0 +1,48 @@
+int g[4];
+
+int through_param(int a[], int b[]) {
+  a[0] = 1;
+  b[0] = 2;
+  return a[0];
+}
+
+int through_global(int a[]) {
+  g[1] = 3;
+  a[1] = 4;
+  return g[1];
+}
+
+void set(int a[], int v) {
+  a[0] = v;
+}
+
+int escaped() {
+  int c[2];
+  c[0] = 5;
+  set(c, 6);
+  return c[0];
+}
+
+int kept() {
+  int d[3];
+  d[0] = 7;
+  d[1] = 8;
+  g[0] = d[0];
+  d[2] = d[1] + d[0];
+  putint(d[2]);
+  putch(10);
+  return d[0] + g[0];
+}
+
+int main() {
+  int x[2];
+  putint(through_param(x, x));
+  putch(10);
+  putint(through_global(g));
+  putch(10);
+  putint(escaped());
+  putch(10);
+  putint(kept());
+  putch(10);
+  return 0;
+}
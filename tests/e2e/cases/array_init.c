@@ -0,0 +1,25 @@
+int g[2][3] = {{1, 2}, {3}};
+int h[3][2] = {1, 2, {3}, 4, 5};
+int z[4][4];
+int flat[5] = {7, 8};
+int s = 4;
+
+int main() {
+  int a[2][3] = {{1, 2}, {3}};
+  int b[2][2][2] = {1, 2, {3}, 4, 5};
+  int c[4] = {s, s * 2};
+  int d[3][2] = {};
+  int sum = 0;
+  int i = 0;
+  while (i < 2) {
+    int j = 0;
+    while (j < 3) {
+      sum = sum + a[i][j] * 10 + g[i][j];
+      j = j + 1;
+    }
+    i = i + 1;
+  }
+  sum = sum + h[1][0] * 100 + h[2][0] + h[2][1] + z[3][3] + flat[1] + flat[4];
+  sum = sum + b[0][0][0] + b[0][1][0] * 2 + b[0][1][1] * 3 + b[1][0][0] * 4 + b[1][1][0] * 5 + b[1][1][1];
+  return (sum + c[0] + c[1] + c[3] + d[2][1]) % 256;
+}
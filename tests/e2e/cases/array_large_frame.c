@@ -0,0 +1,20 @@
+int third(int x) {
+  return x % 3;
+}
+
+int main() {
+  int big[1000];
+  int tail = 7;
+  int i = 0;
+  while (i < 1000) {
+    big[i] = i;
+    i = i + 1;
+  }
+  int s = 0;
+  i = 0;
+  while (i < 1000) {
+    s = s + third(big[i]);
+    i = i + 1;
+  }
+  return (s + big[999] + tail) % 256;
+}
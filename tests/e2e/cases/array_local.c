@@ -0,0 +1,20 @@
+int g[8];
+
+int main() {
+  int a[10];
+  int n = 10;
+  int i = 0;
+  while (i < n) {
+    a[i] = i * i;
+    i = i + 1;
+  }
+  int sum = 0;
+  i = 0;
+  while (i < n) {
+    sum = sum + a[i];
+    g[i % 8] = g[i % 8] + a[n - 1 - i];
+    i = i + 1;
+  }
+  a[a[2] - 1] = 100;
+  return sum + a[3] + g[1] - g[7];
+}
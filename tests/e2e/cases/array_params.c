@@ -0,0 +1,37 @@
+int g[2][3] = {{1, 2, 3}, {4, 5, 6}};
+
+int sum(int a[], int n) {
+  int s = 0;
+  int i = 0;
+  while (i < n) {
+    s = s + a[i];
+    i = i + 1;
+  }
+  return s;
+}
+
+int fill(int a[], int n, int v) {
+  int i = 0;
+  while (i < n) {
+    a[i] = v + i;
+    i = i + 1;
+  }
+  return 0;
+}
+
+int trace(int m[][3], int n) {
+  int t = 0;
+  int i = 0;
+  while (i < n) {
+    t = t + m[i][i];
+    i = i + 1;
+  }
+  return t + sum(m[1], 3);
+}
+
+int main() {
+  int a[5];
+  fill(a, 5, 3);
+  int m[3][3] = {{1, 2, 3}, {4, 5, 6}, {7, 8, 9}};
+  return sum(a, 5) + trace(m, 3) + trace(g, 2) + sum(g[1], 2);
+}
@@ -0,0 +1,40 @@
+// yasysyc-flags: --ext=bitwise
+const int MASK = (1 << 4) - 1;
+int flags[2] = {MASK & 6, MASK ^ 5};
+
+int popcount(int x) {
+  int n = 0;
+  while (x != 0) {
+    n = n + (x & 1);
+    x = x >> 1;
+    if (x < 0) x = x & 2147483647;
+  }
+  return n;
+}
+
+int main() {
+  int a = 12;
+  int b = 10;
+  putint(a & b);
+  putch(32);
+  putint(a | b);
+  putch(32);
+  putint(a ^ b);
+  putch(32);
+  putint(a << 3);
+  putch(32);
+  putint(-a >> 2);
+  putch(10);
+  // Precedence: == binds tighter than &, + tighter than <<
+  putint(a & 4 == 4);
+  putch(32);
+  putint(1 << 2 + 1);
+  putch(32);
+  putint(a | b ^ 6 & 3);
+  putch(32);
+  putint(a > 1 << 3 && b & 2);
+  putch(10);
+  putint(popcount(255) + popcount(MASK) + flags[0] + flags[1]);
+  putch(10);
+  return a & MASK;
+}
@@ -0,0 +1,11 @@
+int main() {
+    const int a = 1;
+    {
+        const int a = 2;
+        {
+            const int b = a * 10;
+            const int a = b + 3;
+            return a + b;
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// yasysyc-flags: --ext=char
+const char NL = '\n';
+const char greeting[5] = {'h', 'e', 'l', 'l', 'o'};
+char wrapped = 321;
+
+int put_all(char s[], int n) {
+  int i = 0;
+  while (i < n) {
+    putch(s[i]);
+    i = i + 1;
+  }
+  putch(NL);
+  return n;
+}
+
+int shout(char c) {
+  return c - 'a' + 'A';
+}
+
+int main() {
+  char buf[6];
+  int i = 0;
+  while (i < 5) {
+    buf[i] = shout(greeting[i]);
+    i = i + 1;
+  }
+  put_all(buf, 5);
+  put_all(greeting, 5);
+
+  // Stores keep only the low 8 bits
+  char c = 'z' + 256;
+  buf[5] = 512 + '\t';
+  putint(c);
+  putch(' ');
+  putint(buf[5]);
+  putch(' ');
+  putint(wrapped);
+  putch(' ');
+  putint(shout(353));
+  putch('\n');
+
+  putint('\\' + '\'' + '"' + '\0');
+  putch(NL);
+  return c;
+}
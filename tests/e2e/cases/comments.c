@@ -0,0 +1,14 @@
+// Line comments and /* block comments */ are skipped by the lexer
+/* A block comment
+ * spanning several lines, with * and / inside: a * b / c
+ */
+int square(int x) { // trailing comment
+  return x /* inline */ * x;
+}
+
+int main() {
+  int a = 3; /**/ int b = 4; /***/
+  /* // a line comment inside a block comment */
+  // /* a block comment opener inside a line comment
+  return square(a) + /* nested-looking /* */ square(b);
+}
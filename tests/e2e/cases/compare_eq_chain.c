@@ -0,0 +1,3 @@
+int main() {
+    return (1 == 1 == 1) + (2 != 3 != 1) * 2 + (-4 == -4) * 4 + (0 != 0) * 8;
+}
@@ -0,0 +1,4 @@
+int main() {
+    int x = 0, y = 7;
+    return (x == 0) + (0 == y) * 2 + (y != 0) * 4 + !x * 8 + !y * 16;
+}
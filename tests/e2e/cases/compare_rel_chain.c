@@ -0,0 +1,3 @@
+int main() {
+    return 3 > 2 > 1 == 0 < 1 <= 1;
+}
@@ -0,0 +1,3 @@
+int main() {
+    return (-3 < 2) + (-1 > -2) * 2 + (-5 <= -5) * 4 + (2 >= -7) * 8;
+}
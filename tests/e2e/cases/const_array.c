@@ -0,0 +1,18 @@
+const int dims[2] = {2, 3};
+const int table[dims[0]][dims[1]] = {{1, 2, 3}, {4, 5, 6}};
+int g[table[1][2] - table[0][0]] = {table[0][1], table[1][0] * 2};
+
+int main() {
+  const int local[3] = {dims[1] * 2, table[1][1], -1};
+  int acc = 0;
+  int i = 0;
+  while (i < dims[0]) {
+    int j = 0;
+    while (j < dims[1]) {
+      acc = acc + table[i][j];
+      j = j + 1;
+    }
+    i = i + 1;
+  }
+  return acc + g[0] + g[1] + g[4] + local[0] + local[i];
+}
@@ -0,0 +1,22 @@
+int f(int a, int b) {
+  int r = 0;
+  if (a) if (b) r = 1; else r = 2;
+  return r;
+}
+
+int g(int a, int b) {
+  if (a)
+    if (b)
+      return 1;
+    else if (a > 1)
+      return 2;
+    else
+      return 3;
+  while (b) if (a) return 4; else b = b - 1;
+  return 5;
+}
+
+int main() {
+  return f(0, 0) + f(0, 1) * 3 + f(1, 0) * 9 + f(1, 1) * 27
+    + g(0, 0) * 2 + g(1, 1) + g(2, 0) + g(1, 0) + g(0, 3);
+}
@@ -0,0 +1,29 @@
+// yasysyc-flags: --ext=do-while
+int main() {
+  int i = 0;
+  int sum = 0;
+  do {
+    i = i + 1;
+    if (i % 3 == 0) continue;
+    if (i > 10) break;
+    sum = sum + i;
+  } while (i < 20);
+  putint(sum);
+  putch(10);
+
+  // The body runs once even though the condition is false
+  int n = 0;
+  do n = n + 5; while (0);
+  putint(n);
+  putch(10);
+
+  int j = 3;
+  do {
+    int k = 0;
+    do k = k + 1; while (k < j);
+    putint(k);
+    j = j - 1;
+  } while (j);
+  putch(10);
+  return i;
+}
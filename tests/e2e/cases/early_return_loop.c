@@ -0,0 +1,23 @@
+int find(int target) {
+  int i = 0;
+  while (i < 100) {
+    if (i * i == target) return i;
+    if (i * i > target) return -1;
+    i = i + 1;
+  }
+  return -2;
+}
+
+int check(int x) {
+  if (x < 0) return 0;
+  int s = 0;
+  while (x > 0) {
+    s = s + x % 10;
+    x = x / 10;
+  }
+  return s;
+}
+
+int main() {
+  return find(49) + find(50) * 3 + check(-4) + check(1234) + find(169);
+}
@@ -0,0 +1,38 @@
+// yasysyc-flags: --ext=enum --ext=switch
+enum Color { RED, GREEN = 5, BLUE, };
+enum { SIZE = BLUE + 2 };
+
+int table[SIZE];
+
+int weight(int color) {
+  switch (color) {
+    case RED:
+      return 1;
+    case GREEN:
+      return 10;
+    case BLUE:
+      return 100;
+  }
+  return 0;
+}
+
+int main() {
+  enum { FIRST = -2, SECOND, THIRD };
+  int i = 0;
+  while (i < SIZE) {
+    table[i] = weight(i);
+    i = i + 1;
+  }
+  putint(RED + GREEN + BLUE);
+  putch(10);
+  putint(table[RED] + table[GREEN] + table[BLUE] + table[SIZE - 1]);
+  putch(10);
+  putint(FIRST * 100 + SECOND * 10 + THIRD);
+  putch(10);
+  {
+    enum { RED = 7 };
+    putint(RED);
+    putch(10);
+  }
+  return SIZE;
+}
@@ -0,0 +1,77 @@
+int g[10];
+int n = 7;
+
+int sum(int a[], int len) {
+  int s = 0;
+  int i = 0;
+  while (i < len) {
+    s = s + a[i] * (i + 1);
+    i = i + 1;
+  }
+  return s;
+}
+
+int fill(int a[], int len, int v) {
+  int i = 0;
+  while (i < len) {
+    a[i] = v;
+    i = i + 1;
+  }
+  return i;
+}
+
+int main() {
+  int a[13];
+  int b[13];
+  int m[3][5];
+
+  // Constant bound, not a multiple of the unroll factor
+  int i = 0;
+  while (i < 13) {
+    a[i] = 0;
+    i = i + 1;
+  }
+  putint(i);
+  putch(10);
+
+  // Variable bound and a copy between arrays
+  i = 0;
+  while (i < n) {
+    b[i] = a[i] + i * 3;
+    i = i + 1;
+  }
+  putint(sum(b, n));
+  putch(10);
+
+  // Array parameter, bounds shorter than the unroll factor
+  fill(g, 10, 4);
+  fill(a, 2, 9);
+  fill(b, 0, 1);
+  // A bound so low that subtracting the unroll factor from it wraps around
+  fill(b, -2147483647 - 1, 2);
+  putint(sum(g, 10) + sum(a, 13) + sum(b, 3));
+  putch(10);
+
+  // Rows of a 2-D array, and a start that is not 0
+  int r = 0;
+  while (r < 3) {
+    int c = r;
+    while (c < 5) {
+      m[r][c] = r * 10 + c;
+      c = c + 1;
+    }
+    r = r + 1;
+  }
+  putint(m[0][0] + m[1][4] + m[2][2] + m[2][3]);
+  putch(10);
+
+  // Overlapping shift within one array
+  i = 0;
+  while (i < 12) {
+    a[i] = a[i + 1] + 1;
+    i = i + 1;
+  }
+  putint(sum(a, 13));
+  putch(10);
+  return i;
+}
@@ -0,0 +1,42 @@
+float half(float x) {
+  return x / 2;
+}
+
+int main() {
+  float a = 1.5;
+  float b = 0.25;
+  int n = 3;
+  putfloat(a + b);
+  putch(10);
+  putfloat(a - b * n);
+  putch(10);
+  putfloat(-a * 4);
+  putch(10);
+  float third = 1;
+  third = third / 3;
+  putfloat(third);
+  putch(10);
+  putfloat(half(n));
+  putch(10);
+  putfloat(n / 2 + a);
+  putch(10);
+  a = a + 1;
+  putfloat(a);
+  putch(10);
+
+  // Conversions to int drop the fractional part
+  int t = -2.75 * 2;
+  int u = a * 3;
+  int v = third * 300;
+  float w = -7;
+  int x = w / 2;
+  putint(t);
+  putch(32);
+  putint(u);
+  putch(32);
+  putint(v);
+  putch(32);
+  putint(x);
+  putch(10);
+  return u + v;
+}
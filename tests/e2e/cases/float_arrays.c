@@ -0,0 +1,42 @@
+const float weights[4] = {0.5, 1.5, 2, 4};
+float g[3] = {1, 2.5};
+float total;
+
+float dot(float a[], float b[], int n) {
+  int i = 0;
+  float s = 0;
+  while (i < n) {
+    s = s + a[i] * b[i];
+    i = i + 1;
+  }
+  return s;
+}
+
+int main() {
+  float values[8];
+  int n = getfarray(values);
+  float scale = getfloat();
+  int i = 0;
+  while (i < n) {
+    values[i] = values[i] * scale;
+    i = i + 1;
+  }
+  putfarray(n, values);
+
+  float w[4];
+  i = 0;
+  while (i < 4) {
+    w[i] = weights[i];
+    i = i + 1;
+  }
+  total = dot(values, w, 4);
+  putfloat(total);
+  putch(10);
+
+  g[2] = weights[3] + g[1];
+  putfarray(3, g);
+  float m[2][2] = {{1, 2}, {3}};
+  putfloat(m[1][0] + m[0][1] + m[1][1]);
+  putch(10);
+  return total;
+}
@@ -0,0 +1,50 @@
+int check(float x, float y) {
+  int r = 0;
+  if (x < y) r = r + 1;
+  if (x > y) r = r + 2;
+  if (x <= y) r = r + 4;
+  if (x >= y) r = r + 8;
+  if (x == y) r = r + 16;
+  if (x != y) r = r + 32;
+  return r;
+}
+
+int main() {
+  float zero = 0;
+  float small = 0.5;
+  putint(check(small, 1));
+  putch(32);
+  putint(check(2, small));
+  putch(32);
+  putint(check(small, small));
+  putch(32);
+  putint(check(-small, zero));
+  putch(10);
+
+  // A float is true unless it is zero
+  if (zero) putint(1); else putint(0);
+  if (small) putint(1); else putint(0);
+  putint(!zero);
+  putint(!small);
+  putint(small && zero);
+  putint(small || zero);
+  putint(zero || 0.25);
+  putch(10);
+
+  // Comparisons with an int operand compare as floats
+  int i = 1;
+  putint(i < small + 1);
+  putint(i == small * 2);
+  putint(small > 0);
+  putch(10);
+
+  float x = 10;
+  int steps = 0;
+  while (x > 1) {
+    x = x / 2;
+    steps = steps + 1;
+  }
+  putint(steps);
+  putch(10);
+  return steps;
+}
@@ -0,0 +1,25 @@
+// yasysyc-flags: -Wconversion
+// Every conversion below is reported, and each still truncates toward zero
+int truncated(float x) {
+  return x;
+}
+
+int twice(int n) {
+  return n * 2;
+}
+
+int main() {
+  float f = -7.75;
+  int a = f;
+  int b;
+  b = f / 2;
+  putint(a);
+  putch(32);
+  putint(b);
+  putch(32);
+  putint(truncated(2.5));
+  putch(32);
+  putint(twice(f * -1));
+  putch(10);
+  return a + 10;
+}
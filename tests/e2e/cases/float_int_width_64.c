@@ -0,0 +1,26 @@
+// yasysyc-flags: --int-width=64
+float g = -1.25;
+
+float combine(float a, int b, float c) {
+  return a * b - c;
+}
+
+float sum10(float a0, float a1, float a2, float a3, float a4, float a5, float a6, float a7, int n, float a8) {
+  return a0 + a1 + a2 + a3 + a4 + a5 + a6 + a7 + a8 * n;
+}
+
+int main() {
+  float a[3] = {1.5, 2.5, -3};
+  a[2] = a[2] + g;
+  putfloat(a[2]);
+  putch(10);
+  putfloat(combine(a[0], 4, a[1]));
+  putch(10);
+  float s = sum10(a[0], a[1], a[2], 1, 2, 3, 4, 5, 6, 0.5);
+  putfloat(s);
+  putch(10);
+  int r = s * 2;
+  putint(r);
+  putch(10);
+  return r;
+}
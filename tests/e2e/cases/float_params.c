@@ -0,0 +1,36 @@
+// Ten floats fill fa0-fa7 and then a0-a1
+float weighted(float a0, float a1, float a2, float a3, float a4, float a5, float a6, float a7, float a8, float a9) {
+  return a0 + a1 * 2 + a2 * 3 + a3 * 4 + a4 * 5 + a5 * 6 + a6 * 7 + a7 * 8 + a8 * 9 + a9 * 10;
+}
+
+// Ints fill a0-a7 and floats fa0-fa7; the last four arguments go on the stack
+float mixed(int i0, float f0, int i1, float f1, int i2, float f2, int i3, float f3, int i4, float f4,
+            int i5, float f5, int i6, float f6, int i7, float f7, int i8, float f8, int i9, float f9) {
+  int ints = i0 + i1 * 2 + i2 * 3 + i3 * 4 + i4 * 5 + i5 * 6 + i6 * 7 + i7 * 8 + i8 * 9 + i9 * 10;
+  float floats = f0 - f1 * 2 + f2 * 3 - f3 * 4 + f4 * 5 - f5 * 6 + f6 * 7 - f7 * 8 + f8 * 9 - f9 * 10;
+  return ints + floats / 4;
+}
+
+float power(float x, int n) {
+  if (n == 0) return 1;
+  return x * power(x, n - 1);
+}
+
+int to_int(float x) {
+  return x;
+}
+
+int main() {
+  float half = 0.5;
+  putfloat(weighted(1, 2, 3, 4, 5, 6, 7, 8, 9, 10));
+  putch(10);
+  putfloat(weighted(half, half, half, half, half, half, half, half, half, 1.5));
+  putch(10);
+  putfloat(mixed(1, 0.5, 2, 1.5, 3, 2.5, 4, 3.5, 5, 4.5, 6, 5.5, 7, 6.5, 8, 7.5, 9, 8.5, 10, 9.5));
+  putch(10);
+  putfloat(power(1.5, 4));
+  putch(10);
+  putint(to_int(power(half, 3) * 100));
+  putch(10);
+  return weighted(1, 2, 3, 4, 5, 6, 7, 8, 9, 10) / 10;
+}
@@ -0,0 +1,23 @@
+// yasysyc-flags: --ext=for
+int a[10];
+
+int main() {
+  int i;
+  for (i = 0; i < 10; i = i + 1) a[i] = i * i;
+
+  int sum = 0;
+  for (int j = 0; j < 10; j = j + 1) {
+    if (j % 3 == 0) continue;
+    if (j == 8) break;
+    sum = sum + a[j];
+  }
+
+  int k = 0;
+  for (;;) {
+    k = k + 1;
+    if (k > 5) break;
+  }
+  for (int j = 3; j; j = j - 1)
+    for (int i = 0; i < j; i = i + 1) sum = sum + 1;
+  return sum + k + i;
+}
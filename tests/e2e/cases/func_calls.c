@@ -0,0 +1,16 @@
+int square(int x) {
+  return x * x;
+}
+
+int sum_squares(int a, int b) {
+  return square(a) + square(b);
+}
+
+int fib(int n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+
+int main() {
+  return sum_squares(3, 4) + fib(10);
+}
@@ -0,0 +1,14 @@
+int weighted(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+  return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h + 9 * i + 10 * j;
+}
+
+int countdown(int n, int acc) {
+  if (n == 0) return acc;
+  return countdown(n - 1, acc + n);
+}
+
+int main() {
+  int x = weighted(1, 1, 1, 1, 1, 1, 1, 1, 1, 1);
+  int y = weighted(0, 0, 0, 0, 0, 0, 0, 0, 1, 2);
+  return (x + y + countdown(10, 0)) % 256;
+}
@@ -0,0 +1,63 @@
+int partial[6] = {1, 2, 3};
+int nested[3][4] = {{1}, {}, {5, 6, 7, 8}};
+int mixed[2][3][2] = {1, 2, {3}, {4, 5}, {{6}, 7, 8}};
+int leading_zeros[8] = {0, 0, 0, 0, 0, 9};
+int empty[2][5] = {};
+int uninit[4][3];
+int tail[100] = {-1};
+const int table[2][2][3] = {{{1, 2}, {3}}, {{}, {4, 5, 6}}};
+
+void print(int a[], int n) {
+  int i = 0;
+  while (i < n) {
+    putint(a[i]);
+    putch(32);
+    i = i + 1;
+  }
+  putch(10);
+}
+
+int main() {
+  int i = 0;
+  print(partial, 6);
+  while (i < 3) {
+    print(nested[i], 4);
+    i = i + 1;
+  }
+  i = 0;
+  while (i < 2) {
+    int j = 0;
+    while (j < 3) {
+      print(mixed[i][j], 2);
+      j = j + 1;
+    }
+    i = i + 1;
+  }
+  print(leading_zeros, 8);
+  print(empty[1], 5);
+  print(uninit[3], 3);
+  int sum = 0;
+  i = 0;
+  while (i < 100) {
+    sum = sum + tail[i] * (i + 1);
+    i = i + 1;
+  }
+  putint(sum);
+  putch(10);
+  i = 0;
+  while (i < 2) {
+    int j = 0;
+    while (j < 2) {
+      int k = 0;
+      while (k < 3) {
+        putint(table[i][j][k]);
+        k = k + 1;
+      }
+      putch(32);
+      j = j + 1;
+    }
+    i = i + 1;
+  }
+  putch(10);
+  return nested[2][3] + mixed[1][2][0];
+}
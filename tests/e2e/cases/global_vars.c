@@ -0,0 +1,19 @@
+const int N = 5;
+int counter;
+int base = N * 10 + 1, step = -2;
+
+int bump(int by) {
+  counter = counter + by;
+  return counter;
+}
+
+int main() {
+  int i = 0;
+  while (i < N) {
+    bump(i);
+    i = i + 1;
+  }
+  base = base + step;
+  int counter = 100;
+  return bump(base) + counter;
+}
@@ -0,0 +1,10 @@
+int main() {
+    int a = 3, b = 0;
+    if (a > 2) {
+        b = 10;
+    } else {
+        b = 20;
+    }
+    if (a == 0) b = b + 1;
+    return b;
+}
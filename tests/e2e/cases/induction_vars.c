@@ -0,0 +1,62 @@
+// yasysyc-flags: -O2
+int a[64];
+int grid[8][8];
+
+int main() {
+  int n = getint();
+  int i = 0;
+  int j = 3;
+  int k = 40;
+  while (i < 16) {
+    a[4 * i] = i + j;
+    a[4 * i + 1] = k * 2;
+    i = i + 1;
+    j = j + 1;
+    k = k - 2;
+  }
+  putint(i);
+  putch(32);
+  putint(j);
+  putch(32);
+  putint(k);
+  putch(10);
+
+  int s = 0;
+  int r = 0;
+  while (r < 8) {
+    int c = 0;
+    while (c < 8) {
+      grid[r][c] = r * 8 + c;
+      c = c + 1;
+    }
+    r = r + 1;
+  }
+  r = 7;
+  while (r >= 0) {
+    s = s + grid[r][7 - r] * 3;
+    r = r - 1;
+  }
+  putint(s);
+  putch(10);
+
+  int big = 1000;
+  int t = 0;
+  int m = 0;
+  while (m < n) {
+    t = t + big * 5 + m * 8;
+    big = big + 100;
+    m = m + 1;
+  }
+  putint(t);
+  putch(10);
+
+  i = 0;
+  s = 0;
+  while (i < 64) {
+    s = s + a[i];
+    i = i + 1;
+  }
+  putint(s);
+  putch(10);
+  return 0;
+}
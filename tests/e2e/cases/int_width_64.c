@@ -0,0 +1,36 @@
+// yasysyc-flags: --int-width=64
+int g = 7;
+int table[2][3] = {{1, 2, 3}, {4, 5, 6}};
+int zeros[4];
+
+int sum_row(int row[], int n) {
+  int s = 0;
+  int i = 0;
+  while (i < n) {
+    s = s + row[i];
+    i = i + 1;
+  }
+  return s;
+}
+
+int many(int a, int b, int c, int d, int e, int f, int g2, int h, int i, int j) {
+  return a - b + c - d + e - f + g2 - h + i * j;
+}
+
+int main() {
+  int local[3][2];
+  int i = 0;
+  while (i < 3) {
+    local[i][0] = i * 10;
+    local[i][1] = -i;
+    i = i + 1;
+  }
+  zeros[3] = g;
+  putint(sum_row(table[1], 3));
+  putch(10);
+  putint(sum_row(local[2], 2) + zeros[3] + zeros[0]);
+  putch(10);
+  putint(many(1, 2, 3, 4, 5, 6, 7, 8, 9, -10));
+  putch(10);
+  return local[1][0] + table[0][2];
+}
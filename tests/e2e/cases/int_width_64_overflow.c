@@ -0,0 +1,33 @@
+// yasysyc-flags: --int-width=64
+// int still wraps around at 32 bits when it takes 8 bytes in memory
+int big = 2147483647;
+int wrapped[3];
+
+int square(int x) {
+  return x * x;
+}
+
+int main() {
+  int n = 65536;
+  int s = 1;
+  int i = 0;
+  while (i < 31) {
+    s = s * 2;
+    i = i + 1;
+  }
+  wrapped[0] = big + 1;
+  wrapped[1] = square(n) + 7;
+  wrapped[2] = s - 1;
+  putint(wrapped[0]);
+  putch(10);
+  putint(wrapped[1]);
+  putch(10);
+  putint(wrapped[2]);
+  putch(10);
+  putint(s + s == 0);
+  putch(10);
+  if (wrapped[0] < 0) {
+    return 3;
+  }
+  return 0;
+}
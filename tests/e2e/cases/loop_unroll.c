@@ -0,0 +1,32 @@
+// yasysyc-flags: --ext=for --unroll-factor=3
+int squares[8];
+
+int main() {
+  int i = 0;
+  while (i < 8) {
+    squares[i] = i * i;
+    i = i + 1;
+  }
+  int s = 0;
+  int j = 10;
+  while (j > 0) {
+    s = s + squares[j % 8];
+    j = j - 3;
+  }
+  putint(s);
+  putch(10);
+  int n = getint();
+  int k = 0;
+  int t = 0;
+  while (k < n) {
+    t = t + k;
+    k = k + 1;
+  }
+  putint(t);
+  putch(10);
+  for (int m = 5; m != 0; m = m - 1) {
+    putint(m);
+  }
+  putch(10);
+  return i + j;
+}
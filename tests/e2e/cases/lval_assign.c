@@ -0,0 +1,22 @@
+int g[3][4];
+int h;
+
+int main() {
+  int a[3][4];
+  int x;
+  int i = 0;
+  while (i < 3) {
+    int j = 0;
+    while (j < 4) {
+      a[i][j] = i * 4 + j;
+      g[i][j] = a[i][j] * 2;
+      j = j + 1;
+    }
+    i = i + 1;
+  }
+  x = a[2][3];
+  h = g[1][2];
+  a[a[0][1]][a[0][2]] = 99;
+  g[2][0] = g[2][0] + a[1][2];
+  return x + h + a[1][2] + g[2][0];
+}
@@ -0,0 +1,10 @@
+int sign(int x) {
+  if (x > 0) return 1;
+  if (x < 0) return -1;
+}
+
+int main() {
+  putint(sign(5));
+  putint(sign(-5));
+  putch(10);
+}
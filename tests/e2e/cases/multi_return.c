@@ -0,0 +1,13 @@
+int classify(int x) {
+  if (x < 0) return 1;
+  if (x == 0) return 2;
+  while (x > 10) {
+    if (x % 7 == 0) return 3;
+    x = x - 3;
+  }
+  return 4;
+}
+
+int main() {
+  return classify(-5) * 1000 % 256 + classify(0) * 100 + classify(21) * 10 + classify(5);
+}
@@ -0,0 +1,14 @@
+int id(int x) {
+  return x;
+}
+
+int pick(int a, int b, int c) {
+  if (a > b) return id(c);
+  return id(id(a) + id(b));
+}
+
+int main() {
+  int x = pick(id(3), id(1), pick(1, 2, 9));
+  int y = pick(id(x), id(x) - 1, id(5)) + id(pick(0, 0, 0));
+  return x * 10 + y;
+}
@@ -0,0 +1,27 @@
+#include "preprocess.h"
+#include "preprocess.h"
+
+#define FACTOR 3
+#define SCALE FACTOR
+#define UNUSED 1
+#undef UNUSED
+
+int table[SIZE];
+
+int main() {
+  int i = 0;
+  while (i < SIZE) {
+    table[i] = square(i) * SCALE;
+    i = i + 1;
+  }
+#ifdef DEBUG
+  putint(-1);
+#else
+  putint(table[LAST]); // SIZE is not expanded in comments
+#endif
+  putch(10);
+#ifdef UNUSED
+  return 1;
+#endif
+  return table[2];
+}
@@ -0,0 +1,12 @@
+// Shared by preprocess.c; included twice to check the include guard
+#ifndef PREPROCESS_H
+#define PREPROCESS_H
+
+#define SIZE 8
+#define LAST (SIZE - 1)
+
+int square(int x) {
+  return x * x;
+}
+
+#endif
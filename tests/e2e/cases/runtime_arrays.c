@@ -0,0 +1,28 @@
+int reverse(int a[], int n) {
+  int i = 0;
+  while (i < n / 2) {
+    int t = a[i];
+    a[i] = a[n - 1 - i];
+    a[n - 1 - i] = t;
+    i = i + 1;
+  }
+  return 0;
+}
+
+int main() {
+  int a[16];
+  int m[2][8];
+  int n = getarray(a);
+  putarray(n, a);
+  reverse(a, n);
+  putarray(n, a);
+  int k = getarray(m[1]);
+  putarray(k, m[1]);
+  int c = getint();
+  int x = getch();
+  x = getch();
+  putint(c);
+  putch(x);
+  putch(10);
+  return n + k + a[0];
+}
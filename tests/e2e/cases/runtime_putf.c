@@ -0,0 +1,11 @@
+int main() {
+  int a[3] = {4, 5, 6};
+  int i = 0;
+  putf("start\n");
+  while (i < 3) {
+    putf("a[%d] = %d\n", i, a[i]);
+    i = i + 1;
+  }
+  putf("%d%c%d \"done\"\t%d\n", 1, 45, 2, i * 100);
+  return 0;
+}
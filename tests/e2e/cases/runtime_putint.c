@@ -0,0 +1,14 @@
+int main() {
+  int a[4] = {3, 1, 4, 1};
+  int i = 0;
+  starttime();
+  while (i < 4) {
+    putint(a[i] * 10);
+    putch(32);
+    i = i + 1;
+  }
+  putch(10);
+  putarray(4, a);
+  stoptime();
+  return a[2];
+}
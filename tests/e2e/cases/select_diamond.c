@@ -0,0 +1,29 @@
+int max(int a, int b) {
+  int m;
+  if (a > b) m = a;
+  else m = b;
+  return m;
+}
+
+int clamp(int x, int lo, int hi) {
+  if (x < lo) x = lo;
+  if (x > hi) x = hi;
+  return x;
+}
+
+int main() {
+  int i = 0;
+  int acc = 0;
+  int odd = 0;
+  while (i < 20) {
+    if (i % 2) odd = odd + 1;
+    else acc = acc + i;
+    if (!(i - 7)) acc = acc * 2;
+    else {
+      if (i) acc = acc + 1;
+      else acc = acc - 1;
+    }
+    i = i + 1;
+  }
+  return max(acc, odd) + clamp(-5, 0, 10) + clamp(50, 0, 10) + max(-3, -8);
+}
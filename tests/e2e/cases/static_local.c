@@ -0,0 +1,41 @@
+// yasysyc-flags: --ext=static
+int counter = 100;
+
+int next() {
+  static int counter = 0;
+  counter = counter + 1;
+  return counter;
+}
+
+int fib_memo(int n) {
+  static int memo[30];
+  if (n < 2) return n;
+  if (memo[n]) return memo[n];
+  memo[n] = fib_memo(n - 1) + fib_memo(n - 2);
+  return memo[n];
+}
+
+int twice() {
+  static int calls = 10;
+  {
+    static int calls = 20;
+    calls = calls + 2;
+    putint(calls);
+    putch(32);
+  }
+  calls = calls + 1;
+  return calls;
+}
+
+int main() {
+  next();
+  next();
+  putint(next());
+  putch(10);
+  putint(fib_memo(25));
+  putch(10);
+  twice();
+  putint(twice());
+  putch(10);
+  return counter;
+}
@@ -0,0 +1,38 @@
+// yasysyc-flags: --ext=static
+// Static `x_y` of `f` and static `y` of `f_x` would get the same global name,
+// as would a static `y` of `g` and the global below
+int __g_y = 1000;
+
+int f() {
+  static int x_y = 1;
+  x_y = x_y * 2;
+  return x_y;
+}
+
+int f_x() {
+  static int y = 10;
+  y = y + 1;
+  return y;
+}
+
+int g() {
+  static int y = 100;
+  y = y + 100;
+  return y;
+}
+
+int main() {
+  int i = 0;
+  while (i < 3) {
+    putint(f());
+    putch(32);
+    putint(f_x());
+    putch(32);
+    putint(g());
+    putch(10);
+    i = i + 1;
+  }
+  putint(__g_y);
+  putch(10);
+  return f() + f_x();
+}
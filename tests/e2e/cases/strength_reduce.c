@@ -0,0 +1,23 @@
+int scale(int x) {
+  return x * 8 + 2 * x;
+}
+
+int main() {
+  int values[6] = {37, -37, 16, -16, 1, -1};
+  int i = 0;
+  while (i < 6) {
+    int x = values[i];
+    putint(x / 4);
+    putch(32);
+    putint(x % 4);
+    putch(32);
+    putint(x / 2);
+    putch(32);
+    putint(x % 16);
+    putch(32);
+    putint(scale(x));
+    putch(10);
+    i = i + 1;
+  }
+  return 0;
+}
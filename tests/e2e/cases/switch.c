@@ -0,0 +1,48 @@
+// yasysyc-flags: --ext=switch
+int classify(int x) {
+  int r = 0;
+  switch (x) {
+    case 0:
+      r = 10;
+      break;
+    case 1:
+    case 2:
+      r = 20;
+    case 3:
+      r = r + 3;
+      break;
+    default:
+      r = -1;
+      break;
+    case 5 * 2:
+      r = 100;
+  }
+  return r;
+}
+
+int main() {
+  int i = 0;
+  int sum = 0;
+  while (i < 12) {
+    switch (i % 4) {
+      case 1:
+        i = i + 1;
+        continue;
+      case 3:
+        sum = sum + 1000;
+    }
+    putint(classify(i));
+    putch(32);
+    sum = sum + classify(i);
+    i = i + 1;
+  }
+  putch(10);
+  switch (sum) {
+  }
+  switch (sum) {
+    default:
+      putint(sum);
+  }
+  putch(10);
+  return sum % 256;
+}
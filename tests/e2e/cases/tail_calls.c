@@ -0,0 +1,41 @@
+int gcd(int a, int b) {
+  if (b == 0) return a;
+  return gcd(b, a % b);
+}
+
+int sum(int n, int acc) {
+  if (n == 0) return acc;
+  return sum(n - 1, acc + n);
+}
+
+void count_down(int n) {
+  if (n < 0) return;
+  putint(n);
+  putch(32);
+  count_down(n - 1);
+}
+
+int total(int a[], int n) {
+  if (n == 0) return 0;
+  return a[n - 1] + total(a, n - 1);
+}
+
+int last(int a[], int n) {
+  if (n == 1) return a[0];
+  return last(a, n - 1);
+}
+
+int main() {
+  int a[5] = {3, 1, 4, 1, 5};
+  putint(gcd(1071, 462));
+  putch(10);
+  putint(sum(1000, 0));
+  putch(10);
+  count_down(5);
+  putch(10);
+  putint(total(a, 5));
+  putch(10);
+  putint(last(a, 5));
+  putch(10);
+  return 0;
+}
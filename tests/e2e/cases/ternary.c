@@ -0,0 +1,47 @@
+// yasysyc-flags: --ext=ternary
+const int N = 3 > 2 ? 4 : 5;
+int arr[N] = {1, 2, 3, 4};
+int calls = 0;
+
+int count(int x) {
+  calls = calls + 1;
+  return x;
+}
+
+int sign(int x) {
+  return x > 0 ? 1 : x < 0 ? -1 : 0;
+}
+
+int sum(int a[], int n) {
+  int s = 0;
+  int i = 0;
+  while (i < n) {
+    s = s + a[i];
+    i = i + 1;
+  }
+  return s;
+}
+
+int main() {
+  int local[2] = {10, 20};
+  putint(sign(5) * 100 + sign(-7) * 10 + sign(0));
+  putch(10);
+
+  // Only the taken arm is evaluated
+  int x = 1 ? count(7) : count(8);
+  putint(x + calls * 10);
+  putch(10);
+
+  int i = 0;
+  int total = 0;
+  while (i < 6) {
+    total = total + (i % 2 ? i : -i) + arr[i < N ? i : 0];
+    i = i + 1;
+  }
+  putint(total);
+  putch(10);
+
+  putint(sum(total > 0 ? arr : local, 2));
+  putch(10);
+  return N;
+}
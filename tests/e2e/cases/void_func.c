@@ -0,0 +1,31 @@
+int total = 0;
+
+void add(int x) {
+  if (x < 0) return;
+  total = total + x;
+}
+
+void print_row(int a[], int n) {
+  int i = 0;
+  while (i < n) {
+    putint(a[i]);
+    putch(32);
+    i = i + 1;
+  }
+  putch(10);
+  return;
+}
+
+void nothing() {}
+
+int main() {
+  int a[4] = {3, -1, 4, 1};
+  int i = 0;
+  while (i < 4) {
+    add(a[i]);
+    i = i + 1;
+  }
+  nothing();
+  print_row(a, 4);
+  return total;
+}
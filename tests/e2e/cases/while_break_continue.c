@@ -0,0 +1,10 @@
+int main() {
+    int i = 0, sum = 0;
+    while (1) {
+        i = i + 1;
+        if (i % 3 == 0) continue;
+        if (i > 20) break;
+        sum = sum + i;
+    }
+    return sum;
+}
@@ -0,0 +1,16 @@
+int main() {
+  int i = 0;
+  int sum = 0;
+  while (i < 10) {
+    int j = 0;
+    while (j < i) {
+      j = j + 1;
+      if (j == 3) continue;
+      sum = sum + j;
+    }
+    i = i + 1;
+    if (sum > 100) break;
+  }
+  while (0) sum = sum + 1;
+  return sum;
+}
@@ -20,6 +20,8 @@ pub enum TestError {
     Mismatch {
         expected: i32,
         actual: i32,
+        expected_stdout: String,
+        actual_stdout: String,
         asm: String,
     },
 }
@@ -32,14 +34,22 @@ impl fmt::Display for TestError {
             TestError::Compile(msg) => write!(f, "Compilation error (yasysyc): {}", msg),
             TestError::Assemble(msg) => write!(f, "Assembly error (riscv-gcc): {}", msg),
             TestError::Run(msg) => write!(f, "Runtime error: {}", msg),
-            TestError::Mismatch { expected, actual, asm } => {
+            TestError::Mismatch { expected, actual, expected_stdout, actual_stdout, asm } => {
                 write!(
                     f,
-                    "Result mismatch:\n  GCC reference exit code: {}\n  yasysyc exit code: {}\n\nGenerated assembly:\n{}",
+                    "Result mismatch:\n  GCC reference exit code: {}\n  yasysyc exit code: {}\n",
                     expected,
-                    actual,
-                    asm
-                )
+                    actual
+                )?;
+                if expected_stdout != actual_stdout {
+                    write!(
+                        f,
+                        "  GCC reference output:\n{}\n  yasysyc output:\n{}\n",
+                        expected_stdout,
+                        actual_stdout
+                    )?;
+                }
+                write!(f, "\nGenerated assembly:\n{}", asm)
             }
         }
     }
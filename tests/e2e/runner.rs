@@ -5,11 +5,44 @@
 
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 mod common;
 use common::TestError;
 
+/// Optimization levels every test case is compiled and checked at
+const OPT_LEVELS: &[&str] = &["-O0", "-O1"];
+
+/// Directory of the SysY runtime library linked into every test program
+fn sylib_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/e2e/sylib")
+}
+
+/// Standard input for a run of a test case: the contents of the `.in` file
+/// next to it, if there is one
+fn stdin_for(source_path: &Path) -> Result<Stdio, TestError> {
+    let input_path = source_path.with_extension("in");
+    if !input_path.exists() {
+        return Ok(Stdio::null());
+    }
+    fs::File::open(&input_path)
+        .map(Stdio::from)
+        .map_err(|e| TestError::Io(format!("Failed to open {}: {}", input_path.display(), e)))
+}
+
+/// Extra yasysyc flags for a test case, given in a `// yasysyc-flags: ...`
+/// comment on its first line (e.g. to enable a language extension)
+fn flags_for(source_path: &Path) -> Result<Vec<String>, TestError> {
+    let source = fs::read_to_string(source_path)
+        .map_err(|e| TestError::Io(format!("Failed to read {}: {}", source_path.display(), e)))?;
+    let flags = source
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("// yasysyc-flags:"))
+        .unwrap_or_default();
+    Ok(flags.split_whitespace().map(String::from).collect())
+}
+
 /// Get the path to the yasysyc binary
 fn get_compiler_path() -> PathBuf {
     // Try debug build first, then release
@@ -40,7 +73,10 @@ fn run_test(source_path: &Path) -> datatest_stable::Result<()> {
     let ref_compile = Command::new("riscv64-unknown-elf-gcc")
         .args(["-o"])
         .arg(&ref_exe)
+        .arg("-include")
+        .arg(sylib_dir().join("sylib.h"))
         .arg(source_path)
+        .arg(sylib_dir().join("sylib.c"))
         .output()
         .map_err(|e| TestError::Reference(format!("Failed to run riscv-gcc: {}", e)))?;
 
@@ -55,29 +91,53 @@ fn run_test(source_path: &Path) -> datatest_stable::Result<()> {
     let ref_output = Command::new("spike")
         .args(["pk"])
         .arg(&ref_exe)
+        .stdin(stdin_for(source_path)?)
         .output()
         .map_err(|e| TestError::Reference(format!("Failed to run spike (reference): {}", e)))?;
 
     let expected = ref_output.status.code().unwrap_or(-1);
+    let expected_stdout = String::from_utf8_lossy(&ref_output.stdout);
 
     // ============================================================
-    // Test implementation: yasysyc -> GCC (assemble) -> run
+    // Test implementation: yasysyc -> GCC (assemble) -> run,
+    // once per optimization level
     // ============================================================
-    let asm_path = temp_dir.path().join("test.S");
-    let test_exe = temp_dir.path().join("test");
+    for opt_level in OPT_LEVELS {
+        run_yasysyc(source_path, temp_dir.path(), opt_level, expected, &expected_stdout)?;
+    }
+
+    Ok(())
+}
 
-    // Step 1: Compile SysY to RISC-V assembly with yasysyc
+/// Compile a test case with yasysyc at one optimization level, run it, and
+/// compare its exit code and output against the reference
+fn run_yasysyc(
+    source_path: &Path,
+    temp_dir: &Path,
+    opt_level: &str,
+    expected: i32,
+    expected_stdout: &str,
+) -> datatest_stable::Result<()> {
+    let asm_path = temp_dir.join("test.S");
+    let test_exe = temp_dir.join("test");
+
+    // Step 1: Compile SysY to RISC-V assembly with yasysyc, checking on the
+    // way that the IR survives a print/parse round trip, and at run time that
+    // every call is made with an aligned sp
     let compiler = get_compiler_path();
     let compile_status = Command::new(&compiler)
-        .args(["--riscv", "-o"])
+        .args(["--riscv", opt_level, "--roundtrip-ir", "--check-stack-align"])
+        .args(flags_for(source_path)?)
+        .arg("-o")
         .arg(&asm_path)
         .arg(source_path)
         .output()
-        .map_err(|e| TestError::Compile(format!("Failed to run yasysyc: {}", e)))?;
+        .map_err(|e| TestError::Compile(format!("Failed to run yasysyc {}: {}", opt_level, e)))?;
 
     if !compile_status.status.success() {
         return Err(TestError::Compile(format!(
-            "yasysyc compilation failed:\nstdout: {}\nstderr: {}",
+            "yasysyc {} compilation failed:\nstdout: {}\nstderr: {}",
+            opt_level,
             String::from_utf8_lossy(&compile_status.stdout),
             String::from_utf8_lossy(&compile_status.stderr)
         )).into());
@@ -88,6 +148,7 @@ fn run_test(source_path: &Path) -> datatest_stable::Result<()> {
         .args(["-o"])
         .arg(&test_exe)
         .arg(&asm_path)
+        .arg(sylib_dir().join("sylib.c"))
         .output()
         .map_err(|e| TestError::Assemble(format!("Failed to run riscv-gcc: {}", e)))?;
 
@@ -105,19 +166,23 @@ fn run_test(source_path: &Path) -> datatest_stable::Result<()> {
     let run_output = Command::new("spike")
         .args(["pk"])
         .arg(&test_exe)
+        .stdin(stdin_for(source_path)?)
         .output()
         .map_err(|e| TestError::Run(format!("Failed to run spike: {}", e)))?;
 
     let actual = run_output.status.code().unwrap_or(-1);
+    let actual_stdout = String::from_utf8_lossy(&run_output.stdout);
 
     // ============================================================
     // Compare results
     // ============================================================
-    if actual != expected {
+    if actual != expected || actual_stdout != expected_stdout {
         let asm_content = fs::read_to_string(&asm_path).unwrap_or_default();
         return Err(TestError::Mismatch {
             expected,
             actual,
+            expected_stdout: expected_stdout.to_string(),
+            actual_stdout: actual_stdout.into_owned(),
             asm: asm_content,
         }.into());
     }
@@ -0,0 +1,77 @@
+/* Minimal SysY runtime library, linked into both the reference and the
+ * yasysyc build of every test case */
+#include <stdarg.h>
+#include <stdio.h>
+#include "sylib.h"
+
+int getint(void) {
+  int a = 0;
+  scanf("%d", &a);
+  return a;
+}
+
+int getch(void) {
+  return getchar();
+}
+
+/* For scanf, %f reads any form of floating-point number, hexadecimal too */
+float getfloat(void) {
+  float a = 0;
+  scanf("%f", &a);
+  return a;
+}
+
+int getarray(int a[]) {
+  int n = getint();
+  for (int i = 0; i < n; i++) {
+    a[i] = getint();
+  }
+  return n;
+}
+
+int getfarray(float a[]) {
+  int n = getint();
+  for (int i = 0; i < n; i++) {
+    a[i] = getfloat();
+  }
+  return n;
+}
+
+void putint(int a) {
+  printf("%d", a);
+}
+
+void putch(int a) {
+  putchar(a);
+}
+
+void putfloat(float a) {
+  printf("%a", a);
+}
+
+void putarray(int n, int a[]) {
+  printf("%d:", n);
+  for (int i = 0; i < n; i++) {
+    printf(" %d", a[i]);
+  }
+  putchar('\n');
+}
+
+void putfarray(int n, float a[]) {
+  printf("%d:", n);
+  for (int i = 0; i < n; i++) {
+    printf(" %a", a[i]);
+  }
+  putchar('\n');
+}
+
+void putf(char a[], ...) {
+  va_list args;
+  va_start(args, a);
+  vprintf(a, args);
+  va_end(args);
+}
+
+void starttime(void) {}
+
+void stoptime(void) {}
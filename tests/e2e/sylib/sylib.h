@@ -0,0 +1,19 @@
+/* SysY runtime library, declared for the GCC reference build */
+#ifndef SYLIB_H
+#define SYLIB_H
+
+int getint(void);
+int getch(void);
+float getfloat(void);
+int getarray(int a[]);
+int getfarray(float a[]);
+void putint(int a);
+void putch(int a);
+void putfloat(float a);
+void putarray(int n, int a[]);
+void putfarray(int n, float a[]);
+void putf(char a[], ...);
+void starttime(void);
+void stoptime(void);
+
+#endif
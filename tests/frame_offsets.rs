@@ -0,0 +1,111 @@
+//! Generated programs with progressively larger stack frames, checking that
+//! every immediate in the emitted assembly fits its 12-bit encoding, however
+//! far the frame grows. Needs no RISC-V toolchain: only the compiler runs.
+
+use std::process::Command;
+
+use yasysyc::backend::asm::tokenize;
+
+/// Frame sizes to try, in locals or array elements
+const SIZES: &[usize] = &[1, 100, 600, 5000];
+
+/// A function with `n` scalar locals, all live until the end, that also
+/// calls with arguments on the stack
+fn scalar_locals(n: usize) -> String {
+    let mut src = format!("int scalars_{n}(int x) {{\n");
+    for i in 0..n {
+        src += &format!("  int v{i} = x + {i};\n");
+    }
+    src += "  x = many(x, 1, 2, 3, 4, 5, 6, 7, 8, 9);\n";
+    let sum: Vec<String> = (0..n).map(|i| format!("v{i}")).collect();
+    src += &format!("  return x + {};\n}}\n", sum.join(" + "));
+    src
+}
+
+/// A function with an `n`-element local array, followed by a scalar local
+/// and reading arguments passed on the stack
+fn array_local(n: usize) -> String {
+    format!(
+        "int array_{n}(int p0, int p1, int p2, int p3, int p4, int p5, int p6, int p7, int p8, int p9) {{
+  int a[{n}];
+  int i = 0;
+  while (i < {n}) {{
+    a[i] = i + p9;
+    i = i + 1;
+  }}
+  int last = a[{last}] + p8;
+  putint(last);
+  return a[p0] + last;
+}}
+",
+        last = n - 1,
+    )
+}
+
+fn program() -> String {
+    let mut src = String::from(
+        "int many(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+  return a + j;
+}
+",
+    );
+    let mut calls = Vec::new();
+    for &n in SIZES {
+        src += &scalar_locals(n);
+        src += &array_local(n);
+        calls.push(format!("scalars_{n}(1) + array_{n}(0, 1, 2, 3, 4, 5, 6, 7, 8, 9)"));
+    }
+    src += &format!("int main() {{\n  return {};\n}}\n", calls.join(" + "));
+    src
+}
+
+/// Immediates of `line` that must fit in 12 bits
+fn immediates(line: &str) -> Vec<i32> {
+    let tokens = tokenize(line);
+    let index = match tokens.first() {
+        Some(&("lw" | "sw" | "ld" | "sd")) => 2,
+        Some(&("addi" | "andi" | "ori" | "xori" | "slti" | "sltiu")) => 3,
+        _ => return Vec::new(),
+    };
+    tokens.get(index).and_then(|token| token.parse().ok()).into_iter().collect()
+}
+
+fn check(flags: &[&str]) {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("frames.c");
+    std::fs::write(&source, program()).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_yasysyc"))
+        .arg("--riscv")
+        .args(flags)
+        .arg(&source)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "yasysyc {:?} failed:\n{}",
+        flags,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let asm = String::from_utf8(output.stdout).unwrap();
+    let bad: Vec<&str> = asm
+        .lines()
+        .filter(|line| immediates(line).iter().any(|imm| !(-2048..2048).contains(imm)))
+        .collect();
+    assert!(bad.is_empty(), "yasysyc {:?} emitted unencodable immediates:\n{}", flags, bad.join("\n"));
+}
+
+#[test]
+fn offsets_fit_at_o0() {
+    check(&["-O0"]);
+}
+
+#[test]
+fn offsets_fit_at_o1() {
+    check(&["-O1"]);
+}
+
+#[test]
+fn offsets_fit_with_64_bit_ints() {
+    check(&["-O1", "--int-width=64"]);
+}
@@ -0,0 +1,40 @@
+//! `--int-width=64` rejects the runtime functions that read or write
+//! 4-byte `int`s through pointers, which the 8-byte layout would garble.
+
+use std::process::Command;
+
+/// Compile `source` with `--riscv --int-width=64`, returning whether it
+/// succeeded and what it printed to stderr
+fn compile(source: &str) -> (bool, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("wide.c");
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_yasysyc"))
+        .args(["--riscv", "--int-width=64"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    (output.status.success(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+#[test]
+fn narrow_runtime_functions_are_rejected() {
+    let calls = [
+        ("getarray", "int a[4]; int main() { return getarray(a); }"),
+        ("getfarray", "float a[4]; int main() { return getfarray(a); }"),
+        ("putarray", "int a[4]; int main() { putarray(4, a); return 0; }"),
+        ("putfarray", "float a[4]; int main() { putfarray(4, a); return 0; }"),
+        ("putf", "int main() { putf(\"%d\\n\", 1); return 0; }"),
+    ];
+    for (name, source) in calls {
+        let (success, stderr) = compile(source);
+        assert!(!success, "{} was accepted", name);
+        assert!(stderr.contains(&format!("{} cannot be called with --int-width=64", name)), "{}", stderr);
+    }
+}
+
+#[test]
+fn scalar_runtime_functions_are_accepted() {
+    let (success, stderr) = compile("int main() { putint(getint()); putch(10); return 0; }");
+    assert!(success, "{}", stderr);
+}
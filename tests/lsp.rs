@@ -0,0 +1,179 @@
+//! The language server run in-process on a scripted session: the messages an
+//! editor sends when opening and editing a file, and what the server answers.
+
+use std::io::Cursor;
+
+use yasysyc::lsp::json::Json;
+use yasysyc::lsp::{self, Options};
+
+const SOURCE: &str = "const int N = 4;
+int total;
+int add(int x, int y) {
+  int sum = x + y;
+  return sum + N;
+}
+int main() {
+  total = add(1, 2);
+  return total;
+}
+";
+
+fn message(body: &Json) -> String {
+    let body = body.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+fn request(id: usize, method: &str, params: Json) -> String {
+    let fields: [(&str, Json); 4] =
+        [("jsonrpc", "2.0".into()), ("id", id.into()), ("method", method.into()), ("params", params)];
+    message(&Json::object(fields))
+}
+
+fn notification(method: &str, params: Json) -> String {
+    message(&Json::object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)]))
+}
+
+fn document(uri: &str) -> Json {
+    Json::object([("textDocument", Json::object([("uri", uri.into())]))])
+}
+
+/// Run a session and return the messages the server sent
+fn serve(session: &[String], options: Options) -> Vec<Json> {
+    let mut output = Vec::new();
+    lsp::serve(&mut Cursor::new(session.concat()), &mut output, options).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let mut messages = Vec::new();
+    let mut rest = output.as_str();
+    while let Some((header, tail)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header.strip_prefix("Content-Length: ").unwrap().parse().unwrap();
+        messages.push(Json::parse(&tail[..length]).unwrap());
+        rest = &tail[length..];
+    }
+    messages
+}
+
+fn open(uri: &str, text: &str) -> String {
+    let item = Json::object([
+        ("uri", uri.into()),
+        ("languageId", "c".into()),
+        ("version", 1usize.into()),
+        ("text", text.into()),
+    ]);
+    notification("textDocument/didOpen", Json::object([("textDocument", item)]))
+}
+
+/// The `result` of the response to request `id`
+fn result(messages: &[Json], id: usize) -> &Json {
+    let response = messages.iter().find(|message| message.get("id") == Some(&Json::from(id))).unwrap();
+    response.get("result").unwrap()
+}
+
+fn number(json: &Json) -> usize {
+    match json {
+        Json::Number(value) => *value as usize,
+        _ => panic!("{} is not a number", json),
+    }
+}
+
+#[test]
+fn json_round_trips() {
+    let text = r#"{"a":[1,-2.5,true,null],"b":"tab\there \"quoted\" é😀","c":{}}"#;
+    let json = Json::parse(text).unwrap();
+    assert_eq!(json.get("b").and_then(Json::as_str), Some("tab\there \"quoted\" é😀"));
+    assert_eq!(Json::parse(&json.to_string()).unwrap(), json);
+    assert!(Json::parse("{\"a\":}").is_err());
+}
+
+#[test]
+fn diagnostics_are_published_on_open_and_change() {
+    let uri = "file:///tmp/shadow.c";
+    let changed = Json::object([
+        ("textDocument", Json::object([("uri", uri.into()), ("version", 2usize.into())])),
+        ("contentChanges", vec![Json::object([("text", "int main() { return 0; }".into())])].into()),
+    ]);
+    let session = [
+        request(1, "initialize", Json::object([])),
+        open(uri, "int main() {\n  int x = 1;\n  { int x = 2; }\n  return y;\n}\n"),
+        notification("textDocument/didChange", changed),
+        request(2, "shutdown", Json::Null),
+        notification("exit", Json::Null),
+    ];
+    let mut options = Options::default();
+    assert!(options.lints.set("shadow"));
+    let messages = serve(&session, options);
+
+    let capabilities = result(&messages, 1).get("capabilities").unwrap();
+    assert_eq!(capabilities.get("documentSymbolProvider"), Some(&Json::Bool(true)));
+
+    let published: Vec<&Json> = messages
+        .iter()
+        .filter(|message| message.get("method").and_then(Json::as_str) == Some("textDocument/publishDiagnostics"))
+        .map(|message| message.get("params").unwrap().get("diagnostics").unwrap())
+        .collect();
+    assert_eq!(published.len(), 2);
+    let diagnostics = published[0].as_array().unwrap();
+    // The undeclared `y` is an error; the shadowing `x` a warning
+    let severities: Vec<usize> = diagnostics.iter().map(|diag| number(diag.get("severity").unwrap())).collect();
+    assert!(severities.contains(&1) && severities.contains(&2), "{}", published[0]);
+    let undeclared = diagnostics.iter().find(|diag| number(diag.get("severity").unwrap()) == 1).unwrap();
+    let start = undeclared.get("range").unwrap().get("start").unwrap();
+    assert_eq!((number(start.get("line").unwrap()), number(start.get("character").unwrap())), (3, 9));
+    assert!(published[1].as_array().unwrap().is_empty(), "{}", published[1]);
+}
+
+#[test]
+fn semantic_tokens_tell_names_apart() {
+    let uri = "file:///tmp/add.c";
+    let session = [
+        open(uri, SOURCE),
+        request(1, "textDocument/semanticTokens/full", document(uri)),
+        notification("exit", Json::Null),
+    ];
+    let messages = serve(&session, Options::default());
+    let data: Vec<usize> = result(&messages, 1).get("data").unwrap().as_array().unwrap().iter().map(number).collect();
+
+    // Decode the relative positions back into (line, column, length, type, modifiers)
+    let mut tokens = Vec::new();
+    let (mut line, mut col) = (0, 0);
+    for token in data.chunks(5) {
+        col = if token[0] == 0 { col + token[1] } else { token[1] };
+        line += token[0];
+        tokens.push((line, col, token[2], token[3], token[4]));
+    }
+    // `N` is a readonly variable where it is declared and used
+    assert_eq!(tokens[0], (0, 10, 1, 1, 0b11));
+    assert!(tokens.contains(&(4, 15, 1, 1, 0b10)), "{:?}", tokens);
+    // `add` is a function, `x` a parameter, `sum` a variable
+    assert!(tokens.contains(&(2, 4, 3, 0, 0b01)), "{:?}", tokens);
+    assert!(tokens.contains(&(7, 10, 3, 0, 0)), "{:?}", tokens);
+    assert!(tokens.contains(&(3, 12, 1, 2, 0)), "{:?}", tokens);
+    assert!(tokens.contains(&(4, 9, 3, 1, 0)), "{:?}", tokens);
+}
+
+#[test]
+fn document_symbols_list_the_file_scope() {
+    let uri = "file:///tmp/add.c";
+    let session = [
+        open(uri, SOURCE),
+        request(1, "textDocument/documentSymbol", document(uri)),
+        request(2, "textDocument/hover", document(uri)),
+        notification("exit", Json::Null),
+    ];
+    let messages = serve(&session, Options::default());
+    let symbols = result(&messages, 1).as_array().unwrap();
+    let names: Vec<(&str, usize)> = symbols
+        .iter()
+        .map(|symbol| (symbol.get("name").and_then(Json::as_str).unwrap(), number(symbol.get("kind").unwrap())))
+        .collect();
+    assert_eq!(names, [("N", 14), ("total", 13), ("add", 12), ("main", 12)]);
+
+    // A function's range is its header, selecting its name
+    let add = &symbols[2];
+    let selection = add.get("selectionRange").unwrap().get("start").unwrap();
+    assert_eq!((number(selection.get("line").unwrap()), number(selection.get("character").unwrap())), (2, 4));
+    let end = add.get("range").unwrap().get("end").unwrap();
+    assert_eq!((number(end.get("line").unwrap()), number(end.get("character").unwrap())), (2, 21));
+
+    let unknown = messages.iter().find(|message| message.get("id") == Some(&Json::from(2usize))).unwrap();
+    assert!(unknown.get("error").is_some(), "{}", unknown);
+}
@@ -0,0 +1,66 @@
+//! `// yasysyc: optimize(0)` keeps a function out of the `-O1` pipeline,
+//! while the functions around it are still optimized.
+
+use std::process::Command;
+
+const SOURCE: &str = "int pick(int c) {
+  int x;
+  if (c) x = 1; else x = 2;
+  return x;
+}
+
+// Keep this one as emitted
+// yasysyc: optimize(0)
+int pick_unoptimized(int c) {
+  int x;
+  if (c) x = 1; else x = 2;
+  return x;
+}
+
+// yasysyc: optimize(0)
+
+int pick_after_blank(int c) {
+  int x;
+  if (c) x = 1; else x = 2;
+  return x;
+}
+
+int main() {
+  return pick(1) + pick_unoptimized(0) + pick_after_blank(1);
+}
+";
+
+/// The Koopa IR of each function, compiled at `level`
+fn functions(level: &str) -> Vec<String> {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("pragma.c");
+    std::fs::write(&source, SOURCE).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_yasysyc"))
+        .args(["--koopa", level])
+        .arg(&source)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let ir = String::from_utf8(output.stdout).unwrap();
+    format!("\n{}", ir).split("\nfun ").skip(1).map(str::to_string).collect()
+}
+
+fn function<'a>(functions: &'a [String], name: &str) -> &'a str {
+    functions.iter().find(|func| func.starts_with(&format!("@{}(", name))).unwrap()
+}
+
+#[test]
+fn pragma_excludes_function() {
+    let o0 = functions("-O0");
+    let o1 = functions("-O1");
+    assert_eq!(function(&o0, "pick_unoptimized"), function(&o1, "pick_unoptimized"));
+    assert_ne!(function(&o0, "pick"), function(&o1, "pick"));
+}
+
+#[test]
+fn pragma_must_be_directly_above() {
+    // A blank line separates the pragma from the function
+    let o0 = functions("-O0");
+    let o1 = functions("-O1");
+    assert_ne!(function(&o0, "pick_after_blank"), function(&o1, "pick_after_blank"));
+}
@@ -0,0 +1,46 @@
+//! Tests of the peephole rules: each feeds an instruction sequence through
+//! the rules and checks the rewritten sequence.
+
+use yasysyc::backend::peephole::{rewrite_text, RULES};
+
+fn check(input: &str, expected: &str) {
+    let expected: Vec<&str> = expected.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    assert_eq!(rewrite_text(input, RULES), expected.join("\n"));
+}
+
+#[test]
+fn self_move_is_removed() {
+    check("mv t0, t0\nli t1, 1", "li t1, 1");
+}
+
+#[test]
+fn add_zero_becomes_move() {
+    check("addi t0, t0, 0\naddi a0, t1, 0", "mv a0, t1");
+}
+
+#[test]
+fn reload_of_stored_value_is_removed() {
+    check(
+        "sw t0, 8(sp)
+         lw t0, 8(sp)
+         add t2, t0, t1",
+        "sw t0, 8(sp)
+         add t2, t0, t1",
+    );
+}
+
+#[test]
+fn load_of_stored_value_is_forwarded() {
+    check(
+        "sw t2, 12(sp)
+         lw t0, 12(sp)",
+        "sw t2, 12(sp)
+         mv t0, t2",
+    );
+}
+
+#[test]
+fn different_slots_are_left_alone() {
+    let asm = "sw t0, 4(sp)\nlw t1, 8(sp)\nlw t2, 4(t0)";
+    check(asm, asm);
+}
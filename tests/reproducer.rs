@@ -0,0 +1,158 @@
+//! Crash reproducers: the pass manager keeps the IR each pass got, the
+//! reproducer records it with the stages left to run, and `--from-koopa`
+//! replays it without the source.
+
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process::Command;
+
+use koopa::back::KoopaGenerator;
+use koopa::front::Driver;
+use koopa::ir::Program;
+use yasysyc::ast::ExprArena;
+use yasysyc::ext::Extensions;
+use yasysyc::ice::{self, Header};
+use yasysyc::lexer::Lexer;
+use yasysyc::opt::{self, FunctionPass, Pass, PassManager, const_fold, cse};
+use yasysyc::sysy::CompUnitParser;
+
+const SOURCE: &str = "int g = 3;
+int square(int x) {
+  return x * x;
+}
+int main() {
+  int a[4] = {1, 2, 3, 4};
+  int i = 0;
+  int sum = 2 * 8;
+  while (i < 4) {
+    sum = sum + square(a[i]) + g;
+    i = i + 1;
+  }
+  return sum;
+}
+";
+
+const FLOAT_SOURCE: &str = "float scale(float x, int n) {
+  return x * n;
+}
+int main() {
+  return scale(1.5, 4);
+}
+";
+
+fn lower(source: &str) -> Program {
+    CompUnitParser::new().parse(&mut ExprArena::default(), Lexer::new(source, &Extensions::default())).unwrap().emit()
+}
+
+fn print(program: &Program) -> String {
+    let mut ir = Vec::new();
+    KoopaGenerator::new(&mut ir).generate_on(program).unwrap();
+    String::from_utf8(ir).unwrap()
+}
+
+/// A pass that crashes on any program
+struct Explode;
+
+impl Pass for Explode {
+    fn name(&self) -> &'static str {
+        "explode"
+    }
+
+    fn run(&mut self, _: &mut Program, _: &HashSet<String>) {
+        panic!("explode");
+    }
+}
+
+fn yasysyc(args: &[&str], input: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_yasysyc")).args(args).arg(input).output().unwrap();
+    assert!(output.status.success(), "yasysyc {:?} failed:\n{}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn snapshot_holds_the_ir_the_crashing_pass_got() {
+    let mut passes = PassManager::new();
+    passes.register(FunctionPass { name: "const-fold", run: const_fold::run }, true);
+    passes.register(Explode, true);
+    passes.register(FunctionPass { name: "select", run: opt::select::run }, false);
+    passes.register(FunctionPass { name: "cse", run: cse::run }, true);
+    passes.keep_snapshots();
+
+    let mut program = lower(SOURCE);
+    let crashed = panic::catch_unwind(AssertUnwindSafe(|| passes.run(&mut program, &HashSet::new())));
+    assert!(crashed.is_err());
+
+    let mut folded = lower(SOURCE);
+    folded.funcs_mut().values_mut().for_each(const_fold::run);
+    let snapshot = passes.snapshot().unwrap();
+    assert_eq!(snapshot.remaining, ["explode", "cse"]);
+    assert_eq!(snapshot.ir, print(&folded));
+    Driver::from(snapshot.ir.clone()).generate_program().unwrap();
+}
+
+#[test]
+fn snapshot_after_all_passes_leaves_nothing_to_run() {
+    let mut passes = opt::pipeline(1);
+    passes.keep_snapshots();
+    let mut program = lower(SOURCE);
+    passes.run(&mut program, &HashSet::new());
+
+    let snapshot = passes.snapshot().unwrap();
+    assert!(snapshot.remaining.is_empty());
+    assert_eq!(snapshot.ir, print(&program));
+}
+
+#[test]
+fn reproducer_header_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let unoptimized = HashSet::from(["square".to_string(), "main".to_string()]);
+    let floats = HashMap::from([("square".to_string(), "f->f".parse().unwrap())]);
+    let path = ice::write_reproducer(dir.path(), &print(&lower(SOURCE)), &["cse", "codegen"], &unoptimized, &floats)
+        .unwrap();
+    let text = std::fs::read_to_string(path).unwrap();
+
+    let header = ice::read_header(&text).unwrap();
+    assert_eq!(
+        header,
+        Header {
+            pipeline: vec!["cse".into(), "codegen".into()],
+            unoptimized: vec!["main".into(), "square".into()],
+            floats: vec![("square".into(), "f->f".parse().unwrap())],
+        }
+    );
+    assert_eq!(ice::read_header(&print(&lower(SOURCE))), None);
+}
+
+#[test]
+fn replaying_a_reproducer_runs_its_pipeline() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("loop.c");
+    std::fs::write(&source, SOURCE).unwrap();
+
+    // What a crash in the first -O1 pass would leave behind
+    let lowered = yasysyc(&["--koopa", "-O0"], &source);
+    let mut stages = opt::pipeline(1).enabled();
+    stages.push("codegen");
+    let reproducer = ice::write_reproducer(dir.path(), &lowered, &stages, &HashSet::new(), &HashMap::new()).unwrap();
+
+    let replayed = yasysyc(&["--from-koopa", "--riscv"], &reproducer);
+    assert_eq!(replayed, yasysyc(&["-O1", "--riscv"], &source));
+}
+
+#[test]
+fn replaying_a_reproducer_keeps_the_float_calling_convention() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("scale.c");
+    std::fs::write(&source, FLOAT_SOURCE).unwrap();
+
+    // The IR holds floats as ints; only the header says `x` and the result
+    // of `scale` go in fa0
+    let lowered = yasysyc(&["--koopa", "-O0"], &source);
+    let floats = HashMap::from([("scale".to_string(), "fi->f".parse().unwrap())]);
+    let reproducer = ice::write_reproducer(dir.path(), &lowered, &["codegen"], &HashSet::new(), &floats).unwrap();
+
+    let replayed = yasysyc(&["--from-koopa", "--riscv"], &reproducer);
+    assert!(replayed.contains("fmv.x.w t0, fa0"), "{}", replayed);
+    assert_eq!(replayed, yasysyc(&["-O0", "--riscv"], &source));
+}